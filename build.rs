@@ -1,15 +1,89 @@
 use std::{env, fs};
-use std::path::{Path};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
 fn main() {
     println!("cargo:rerun-if-changed=resources");
+    println!("cargo:rerun-if-env-changed=GLSLC");
+    println!("cargo:rerun-if-env-changed=VULKAN_SDK");
     let resource_dir = Path::new("resources");
+    compile_shaders(&resource_dir.join("shaders"));
 
     let manifest_dir_string = env::var("CARGO_MANIFEST_DIR").unwrap();
     let build_type = env::var("PROFILE").unwrap();
     let out_path = Path::new(&manifest_dir_string).join("target").join(build_type);
-    iterate_copy(&resource_dir, &out_path);
+    iterate_copy(resource_dir, &out_path);
+}
+
+/// Compiles every `<source> -o <output>` line of `compile.bat` whose `.spv` is missing or older
+/// than its source or any `.glsl` include, so the shaders never go stale against the sources.
+/// Without glslc a stale `.spv` is only warned about, but a missing one fails the build.
+///
+/// The `.spv` files are written next to their sources in `resources/shaders` rather than to
+/// `OUT_DIR`: `ShaderPaths::default` loads them from there at runtime, and `iterate_copy`
+/// ships them with the rest of `resources`. They're ignored by git.
+fn compile_shaders(shader_dir: &Path) {
+    let script = fs::read_to_string(shader_dir.join("compile.bat")).unwrap();
+    let includes_modified = fs::read_dir(shader_dir).unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "glsl"))
+        .filter_map(|e| modified(&e.path()))
+        .max();
+
+    let glslc = find_glslc();
+    for line in script.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [_, source, "-o", output] = words[..] else {
+            continue;
+        };
+
+        let source_path = shader_dir.join(source);
+        let output_path = shader_dir.join(output);
+        let newest_input = modified(&source_path).max(includes_modified);
+        let up_to_date = match (modified(&output_path), newest_input) {
+            (Some(built), Some(input)) => built >= input,
+            (built, _) => built.is_some(),
+        };
+        if up_to_date {
+            continue;
+        }
+
+        let Some(glslc) = &glslc else {
+            assert!(
+                output_path.exists(),
+                "{} is missing and glslc wasn't found; install the Vulkan SDK, put glslc on the \
+                 PATH or set GLSLC to it",
+                output
+            );
+            println!("cargo:warning={} is stale; install glslc or set GLSLC", output);
+            continue;
+        };
+        let status = Command::new(glslc)
+            .current_dir(shader_dir)
+            .args([source, "-o", output])
+            .status()
+            .expect("Unable to run glslc");
+        assert!(status.success(), "glslc failed to compile {}", source);
+    }
+}
+
+/// `GLSLC`, the Vulkan SDK's glslc, or glslc on the `PATH`, whichever is found first.
+fn find_glslc() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("GLSLC") {
+        return Some(PathBuf::from(path));
+    }
+    let name = if cfg!(windows) { "glslc.exe" } else { "glslc" };
+    let sdk = env::var_os("VULKAN_SDK").map(|sdk| Path::new(&sdk).join("Bin").join(name));
+    let path = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).map(|dir| dir.join(name)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    sdk.into_iter().chain(path).find(|candidate| candidate.is_file())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
 }
 
 fn iterate_copy(in_path: &Path, out_path: &Path) {
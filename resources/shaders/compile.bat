@@ -1,7 +1,24 @@
-C:\VulkanSDK\1.3.290.0\Bin/glslc.exe shader.vert -o vert.spv
-C:\VulkanSDK\1.3.290.0\Bin/glslc.exe shader.frag -o frag.spv
-C:\VulkanSDK\1.3.290.0\Bin/glslc.exe shadow.vert -o shadow.spv
-C:\VulkanSDK\1.3.290.0\Bin/glslc.exe lighting.frag -o lighting.spv
-C:\VulkanSDK\1.3.290.0\Bin/glslc.exe quad.vert -o quad.spv
+glslc shader.vert -o vert.spv
+glslc shader_multiview.vert -o vert_multiview.spv
+glslc shader_instanced.vert -o vert_instanced.spv
+glslc shader_instanced_multiview.vert -o vert_instanced_multiview.spv
+glslc shader.frag -o frag.spv
+glslc shadow.vert -o shadow.spv
+glslc shadow_caster.vert -o shadow_caster.spv
+glslc lighting.frag -o lighting.spv
+glslc quad.vert -o quad.spv
+glslc linear_depth.frag -o linear_depth.spv
+glslc ssao.frag -o ssao.spv
+glslc ssao_blur.frag -o ssao_blur.spv
+glslc ssr.frag -o ssr.spv
+glslc esm.comp -o esm.spv
+glslc bloom.comp -o bloom.spv
+glslc tonemap.comp -o tonemap.spv
+glslc luminance_histogram.comp -o luminance_histogram.spv
+glslc exposure_average.comp -o exposure_average.spv
+glslc present.frag -o present.spv
+glslc fxaa.frag -o fxaa.spv
+glslc skin.comp -o skin.spv
+glslc cull.comp -o cull.spv
 
 pause
\ No newline at end of file
@@ -0,0 +1,9 @@
+// Which end of the G-buffer depth range is the far plane, see RenderConfig::reverse_z in
+// config.rs. Include after the shader's own specialization constants, constant_id 3 is taken.
+
+layout(constant_id = 3) const bool REVERSE_Z = false;
+
+// Whether nothing was drawn at this depth, only the clear value left
+bool isFarPlane(float depth) {
+    return REVERSE_Z ? depth <= 0.0 : depth >= 1.0;
+}
@@ -0,0 +1,147 @@
+#version 450
+
+// The tonemap image or the draw image, whichever the swapchain would be written from
+layout(set = 0, binding = 0) uniform sampler2D sourceImage;
+layout(set = 0, binding = 1) uniform Fxaa {
+    float edgeThreshold;
+    uint searchSteps;
+} fxaa;
+
+// Whether sampling sourceImage gives linear color rather than sRGB encoded color
+layout(constant_id = 0) const bool LINEAR_INPUT = false;
+// Whether the swapchain is UNORM with an sRGB color space, so the encode happens here
+layout(constant_id = 1) const bool ENCODE_SRGB = false;
+
+// Keeps dark areas, where the relative threshold gets tiny, from counting as edges
+const float EDGE_THRESHOLD_MIN = 0.0312;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 fragColor;
+
+vec3 linearToSrgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, greaterThan(color, vec3(0.0031308)));
+}
+
+// Perceptual luma, edges are detected on what the eye sees
+float luma(vec2 uv) {
+    vec3 color = clamp(textureLod(sourceImage, uv, 0.0).rgb, 0.0, 1.0);
+    float value = dot(color, vec3(0.299, 0.587, 0.114));
+    return LINEAR_INPUT ? sqrt(value) : value;
+}
+
+vec4 fxaaColor() {
+    vec2 texel = 1.0 / vec2(textureSize(sourceImage, 0));
+    vec2 uv = fragTexCoord;
+
+    float center = luma(uv);
+    float north = luma(uv + vec2(0.0, -texel.y));
+    float south = luma(uv + vec2(0.0, texel.y));
+    float west = luma(uv + vec2(-texel.x, 0.0));
+    float east = luma(uv + vec2(texel.x, 0.0));
+
+    float lumaMin = min(center, min(min(north, south), min(west, east)));
+    float lumaMax = max(center, max(max(north, south), max(west, east)));
+    float range = lumaMax - lumaMin;
+    if (range < max(EDGE_THRESHOLD_MIN, lumaMax * fxaa.edgeThreshold)) {
+        return textureLod(sourceImage, uv, 0.0);
+    }
+
+    float northWest = luma(uv + vec2(-texel.x, -texel.y));
+    float northEast = luma(uv + vec2(texel.x, -texel.y));
+    float southWest = luma(uv + vec2(-texel.x, texel.y));
+    float southEast = luma(uv + vec2(texel.x, texel.y));
+
+    // Horizontal edges change most across rows
+    float horizontal = abs(northWest + southWest - 2.0 * west)
+        + 2.0 * abs(north + south - 2.0 * center)
+        + abs(northEast + southEast - 2.0 * east);
+    float vertical = abs(northWest + northEast - 2.0 * north)
+        + 2.0 * abs(west + east - 2.0 * center)
+        + abs(southWest + southEast - 2.0 * south);
+    bool isHorizontal = horizontal >= vertical;
+
+    // Which side of the pixel the edge is on
+    float negative = isHorizontal ? north : west;
+    float positive = isHorizontal ? south : east;
+    float gradientNegative = abs(negative - center);
+    float gradientPositive = abs(positive - center);
+    float stepLength = isHorizontal ? texel.y : texel.x;
+    float edgeLuma;
+    float gradient;
+    if (gradientNegative >= gradientPositive) {
+        stepLength = -stepLength;
+        edgeLuma = 0.5 * (negative + center);
+        gradient = gradientNegative;
+    } else {
+        edgeLuma = 0.5 * (positive + center);
+        gradient = gradientPositive;
+    }
+
+    // Walk along the edge, half a texel over, until the luma leaves the edge's
+    vec2 edgeUv = uv;
+    vec2 along;
+    if (isHorizontal) {
+        edgeUv.y += stepLength * 0.5;
+        along = vec2(texel.x, 0.0);
+    } else {
+        edgeUv.x += stepLength * 0.5;
+        along = vec2(0.0, texel.y);
+    }
+    float scaledGradient = gradient * 0.25;
+
+    vec2 uvNegative = edgeUv - along;
+    vec2 uvPositive = edgeUv + along;
+    float deltaNegative = luma(uvNegative) - edgeLuma;
+    float deltaPositive = luma(uvPositive) - edgeLuma;
+    bool doneNegative = abs(deltaNegative) >= scaledGradient;
+    bool donePositive = abs(deltaPositive) >= scaledGradient;
+    for (uint i = 1; i < fxaa.searchSteps && !(doneNegative && donePositive); i++) {
+        if (!doneNegative) {
+            uvNegative -= along;
+            deltaNegative = luma(uvNegative) - edgeLuma;
+            doneNegative = abs(deltaNegative) >= scaledGradient;
+        }
+        if (!donePositive) {
+            uvPositive += along;
+            deltaPositive = luma(uvPositive) - edgeLuma;
+            donePositive = abs(deltaPositive) >= scaledGradient;
+        }
+    }
+
+    float distanceNegative = isHorizontal ? uv.x - uvNegative.x : uv.y - uvNegative.y;
+    float distancePositive = isHorizontal ? uvPositive.x - uv.x : uvPositive.y - uv.y;
+    bool negativeCloser = distanceNegative < distancePositive;
+    float closest = min(distanceNegative, distancePositive);
+    float edgeLength = distanceNegative + distancePositive;
+
+    // Only blend when the end we're closer to bends away from this pixel's side
+    bool centerBelow = center - edgeLuma < 0.0;
+    bool endBelow = (negativeCloser ? deltaNegative : deltaPositive) < 0.0;
+    float edgeOffset = endBelow != centerBelow ? 0.5 - closest / edgeLength : 0.0;
+
+    // Subpixel aliasing, e.g. single pixel features, from the 3x3 neighborhood average
+    float average = (2.0 * (north + south + west + east) + northWest + northEast + southWest + southEast) / 12.0;
+    float subpixel = clamp(abs(average - center) / range, 0.0, 1.0);
+    subpixel = smoothstep(0.0, 1.0, subpixel);
+    float subpixelOffset = subpixel * subpixel * 0.75;
+
+    float offset = max(edgeOffset, subpixelOffset);
+    vec2 finalUv = uv;
+    if (isHorizontal) {
+        finalUv.y += offset * stepLength;
+    } else {
+        finalUv.x += offset * stepLength;
+    }
+    return textureLod(sourceImage, finalUv, 0.0);
+}
+
+void main() {
+    vec4 color = fxaaColor();
+    if (ENCODE_SRGB) {
+        color.rgb = linearToSrgb(clamp(color.rgb, 0.0, 1.0));
+    }
+    fragColor = color;
+}
@@ -1,43 +1,271 @@
 #version 450
 
+#extension GL_GOOGLE_include_directive: require
+
 // G-buffer samplers
 layout(set = 0, binding = 1) uniform sampler2D albedoTexture;  // Albedo texture
 layout(set = 0, binding = 2) uniform sampler2D normalTexture;  // Normal texture
 layout(set = 0, binding = 3) uniform sampler2D depthTexture;   // Depth texture
+layout(set = 0, binding = 4) uniform sampler2DArrayShadow shadowMap; // Layer per cascade, compare
+layout(set = 0, binding = 6) uniform sampler2D esmMap;         // exp(c * light depth), ESM only
+layout(set = 0, binding = 8) uniform sampler2DArrayShadow casterShadowMaps; // Layer per caster
+layout(set = 0, binding = 9) uniform sampler2D aoTexture;      // Blurred SSAO, SSAO_ENABLED only
+
+layout(set = 0, binding = 5) uniform Camera {
+    mat4 view;
+    mat4 proj;
+    mat4 lightViewProj;
+    mat4 eyeViewProj[2];
+    mat4 casterViewProj[8]; // Indexed by Light.shadowLayer
+    vec4 position; // World space eye, w unused
+    mat4 cascadeViewProj[4]; // Indexed by shadowMap layer
+} camera;
 
 // Lighting uniform (ambient and directional light)
 layout(std140, set = 0, binding = 0) uniform Lighting {
     vec4 lightDirection;  // Direction of the directional light
     vec4 lightColor;      // Color of the directional light
     vec4 ambiantLight; // Offset 68 (padding to 80 bytes)
+    vec4 shadowParams; // x: technique (0 = PCF, 1 = ESM, 2 = Poisson PCF), y: ESM exponent, z: depth bias
+    vec4 pcfParams;    // x: Poisson radius in texels, y: per-pixel rotation
 } lighting;
 
+struct Light {
+    uint kind;       // 0 = directional, 1 = point, 2 = spot
+    uint castsShadow;
+    int shadowLayer; // Layer of casterShadowMaps, -1 without a shadow
+    vec4 position;   // World space, unused by directional lights
+    vec4 direction;  // Direction the light travels, unused by point lights
+    vec4 color;      // w: intensity
+    vec4 params;     // x: range, y: cos inner cone angle, z: cos outer cone angle
+};
+
+// Extra lights on top of the directional light above, shadowed when they got a layer
+layout(std430, set = 0, binding = 7) readonly buffer Lights {
+    uint lightCount;
+    Light lights[];
+} lightList;
+
+// Poisson PCF tap count, at most the size of POISSON_DISK
+layout(constant_id = 0) const int PCF_SAMPLES = 16;
+// Whether aoTexture holds ambient occlusion
+layout(constant_id = 2) const bool SSAO_ENABLED = false;
+
+#include "normal_encoding.glsl"
+#include "depth_range.glsl"
+
+const float PI = 3.14159265;
+
+const vec2 POISSON_DISK[16] = vec2[](
+    vec2(-0.94201624, -0.39906216),
+    vec2( 0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870),
+    vec2( 0.34495938,  0.29387760),
+    vec2(-0.91588581,  0.45771432),
+    vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543,  0.27676845),
+    vec2( 0.97484398,  0.75648379),
+    vec2( 0.44323325, -0.97511554),
+    vec2( 0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023),
+    vec2( 0.79197514,  0.19090188),
+    vec2(-0.24188840,  0.99706507),
+    vec2(-0.81409955,  0.91437590),
+    vec2( 0.19984126,  0.78641367),
+    vec2( 0.14383161, -0.14100790)
+);
+
+// Interleaved gradient noise, stable per pixel
+float pixelNoise(vec2 pixel) {
+    return fract(52.9829189 * fract(dot(pixel, vec2(0.06711056, 0.00583715))));
+}
+
+float poissonPcf(vec2 shadowUv, float cascade, float receiver) {
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMap, 0).xy);
+    float angle = lighting.pcfParams.y > 0.5 ? pixelNoise(gl_FragCoord.xy) * 6.28318530 : 0.0;
+    mat2 rotation = mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+
+    float visibility = 0.0;
+    for (int i = 0; i < PCF_SAMPLES; i++) {
+        vec2 offset = rotation * POISSON_DISK[i] * lighting.pcfParams.x * texelSize;
+        visibility += texture(shadowMap, vec4(shadowUv + offset, cascade, receiver));
+    }
+
+    return visibility / float(PCF_SAMPLES);
+}
+
 layout(location = 0) in vec2 fragTexCoord;  // Texture coordinates
 
 layout(location = 0) out vec4 fragColor;  // Final fragment color
 
+vec3 worldPosition(vec2 uv, float depth) {
+    vec4 worldPos = inverse(camera.proj * camera.view) * vec4(uv * 2.0 - 1.0, depth, 1.0);
+    return worldPos.xyz / worldPos.w;
+}
+
+float shadowVisibility(vec2 uv) {
+    float depth = texture(depthTexture, uv).r;
+    if (isFarPlane(depth)) {
+        return 1.0;
+    }
+
+    vec3 worldPos = worldPosition(uv, depth);
+
+    // ESM prefilters a single map, fitted like the first cascade
+    if (lighting.shadowParams.x > 0.5 && lighting.shadowParams.x < 1.5) {
+        vec4 lightClip = camera.lightViewProj * vec4(worldPos, 1.0);
+        vec3 lightCoord = lightClip.xyz / lightClip.w;
+        vec2 shadowUv = lightCoord.xy * 0.5 + 0.5;
+        if (any(lessThan(shadowUv, vec2(0.0))) || any(greaterThan(shadowUv, vec2(1.0))) || lightCoord.z > 1.0) {
+            return 1.0;
+        }
+        float esm = texture(esmMap, shadowUv).r;
+        return clamp(exp(-lighting.shadowParams.y * lightCoord.z) * esm, 0.0, 1.0);
+    }
+
+    // Cascades get farther out with each layer, the first one containing the point wins
+    int cascadeCount = textureSize(shadowMap, 0).z;
+    for (int cascade = 0; cascade < cascadeCount; cascade++) {
+        vec4 lightClip = camera.cascadeViewProj[cascade] * vec4(worldPos, 1.0);
+        vec3 lightCoord = lightClip.xyz / lightClip.w;
+        vec2 shadowUv = lightCoord.xy * 0.5 + 0.5;
+        if (any(lessThan(shadowUv, vec2(0.0))) || any(greaterThan(shadowUv, vec2(1.0))) || lightCoord.z > 1.0) {
+            continue;
+        }
+
+        float receiver = lightCoord.z - lighting.shadowParams.z;
+        if (lighting.shadowParams.x > 1.5) {
+            return poissonPcf(shadowUv, float(cascade), receiver);
+        }
+        return texture(shadowMap, vec4(shadowUv, float(cascade), receiver));
+    }
+
+    // Outside every cascade counts as lit
+    return 1.0;
+}
+
+// Hardware PCF against a caster layer, biased and clipped like the main light
+float casterVisibility(int layer, vec3 worldPos) {
+    vec4 lightClip = camera.casterViewProj[layer] * vec4(worldPos, 1.0);
+    vec3 lightCoord = lightClip.xyz / lightClip.w;
+    vec2 shadowUv = lightCoord.xy * 0.5 + 0.5;
+
+    if (lightClip.w <= 0.0 || any(lessThan(shadowUv, vec2(0.0))) || any(greaterThan(shadowUv, vec2(1.0))) || lightCoord.z > 1.0) {
+        return 1.0;
+    }
+
+    return texture(casterShadowMaps, vec4(shadowUv, float(layer), lightCoord.z - lighting.shadowParams.z));
+}
+
+// Smooth falloff reaching zero at the light's range
+float rangeAttenuation(float distance, float range) {
+    float window = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+struct Surface {
+    vec3 worldPos;
+    vec3 normal;
+    vec3 view;     // Towards the camera
+    vec3 albedo;
+    float metallic;
+    float roughness;
+};
+
+// Cook-Torrance with GGX, Smith-Schlick and Schlick's Fresnel for light arriving along
+// lightDir. Scaled by PI so a white diffuse surface facing the light gets the radiance
+// itself, the units the lights and the ambient term have always used.
+vec3 brdf(Surface surface, vec3 lightDir, vec3 radiance) {
+    float nDotL = max(dot(surface.normal, lightDir), 0.0);
+    if (nDotL <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 halfway = normalize(lightDir + surface.view);
+    float nDotV = max(dot(surface.normal, surface.view), 1e-4);
+    float nDotH = max(dot(surface.normal, halfway), 0.0);
+
+    float alpha = surface.roughness * surface.roughness;
+    float alpha2 = alpha * alpha;
+    float denom = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+    float distribution = alpha2 / (PI * denom * denom);
+
+    float k = (surface.roughness + 1.0) * (surface.roughness + 1.0) / 8.0;
+    float geometry = (nDotV / (nDotV * (1.0 - k) + k)) * (nDotL / (nDotL * (1.0 - k) + k));
+
+    vec3 f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+    vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(halfway, surface.view), 0.0), 5.0);
+
+    vec3 specular = distribution * geometry * fresnel / (4.0 * nDotV * nDotL + 1e-4);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - surface.metallic) * surface.albedo;
+
+    return (diffuse + specular * PI) * radiance * nDotL;
+}
+
+vec3 listLighting(Surface surface) {
+    vec3 result = vec3(0.0);
+    for (uint i = 0; i < lightList.lightCount; i++) {
+        Light light = lightList.lights[i];
+        vec3 radiance = light.color.rgb * light.color.w;
+        if (light.shadowLayer >= 0) {
+            radiance *= casterVisibility(light.shadowLayer, surface.worldPos);
+        }
+
+        if (light.kind == 0) {
+            result += brdf(surface, -normalize(light.direction.xyz), radiance);
+            continue;
+        }
+
+        vec3 toLight = light.position.xyz - surface.worldPos;
+        float distance = length(toLight);
+        vec3 lightDir = toLight / max(distance, 1e-4);
+        float attenuation = rangeAttenuation(distance, light.params.x);
+
+        if (light.kind == 2) {
+            float cosAngle = dot(-lightDir, normalize(light.direction.xyz));
+            attenuation *= smoothstep(light.params.z, light.params.y, cosAngle);
+        }
+
+        result += brdf(surface, lightDir, radiance * attenuation);
+    }
+
+    return result;
+}
+
 void main() {
-    // Sample the G-buffer textures
-    vec3 albedo = texture(albedoTexture, fragTexCoord).rgb;
-    vec3 normal = vec3(0.0, 0.0, 1.0);
-    // Compute lighting
+    // Sample the G-buffer textures, metallic in the albedo's alpha
+    vec4 albedo = texture(albedoTexture, fragTexCoord);
+    float depth = texture(depthTexture, fragTexCoord).r;
+    // Nothing was drawn, keep the clear color
+    if (isFarPlane(depth)) {
+        fragColor = vec4(albedo.rgb, 1.0);
+        return;
+    }
+    vec4 normalRoughness = decodeNormal(texture(normalTexture, fragTexCoord));
+
+    Surface surface;
+    surface.worldPos = worldPosition(fragTexCoord, depth);
+    surface.normal = normalRoughness.xyz;
+    surface.view = normalize(camera.position.xyz - surface.worldPos);
+    surface.albedo = albedo.rgb;
+    surface.metallic = clamp(albedo.a, 0.0, 1.0);
+    // Fully smooth surfaces would turn the GGX highlight into a singularity
+    surface.roughness = clamp(normalRoughness.w, 0.04, 1.0);
 
     // Ambient lighting
     vec3 ambient = lighting.ambiantLight.rgb * lighting.ambiantLight.w;
+    if (SSAO_ENABLED) {
+        ambient *= texture(aoTexture, fragTexCoord).r;
+    }
 
     // Directional lighting
     vec3 lightDir = normalize(lighting.lightDirection.rgb);
-    float diff = max(dot(normal, -lightDir), 0.0); // Negative for correct direction
-
-    // Diffuse lighting
-    vec3 diffuse = diff * lighting.lightColor.rgb * lighting.lightColor.w;
-
-    // Combine ambient and diffuse lighting
-    vec3 lightingResult = ambient + diffuse;
+    vec3 radiance = lighting.lightColor.rgb * lighting.lightColor.w * shadowVisibility(fragTexCoord);
 
-    // Apply lighting to the albedo color
-    vec3 finalColor = albedo * lightingResult;
+    // Combine ambient, the directional light and the light list
+    vec3 finalColor = ambient * surface.albedo
+        + brdf(surface, -lightDir, radiance) // Negative for correct direction
+        + listLighting(surface);
 
     // Output the final color
     fragColor = vec4(finalColor, 1.0);
-}
\ No newline at end of file
+}
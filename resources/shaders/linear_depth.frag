@@ -0,0 +1,22 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(set = 0, binding = 1) uniform sampler2D depthTexture;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out float outLinearDepth;
+
+void main() {
+    float depth = texelFetch(depthTexture, ivec2(gl_FragCoord.xy), 0).r;
+
+    // Invert the projection's z/w mapping: clip.z = proj[2][2] * z + proj[3][2], clip.w = proj[2][3] * z
+    float viewZ = ubo.proj[3][2] / (depth * ubo.proj[2][3] - ubo.proj[2][2]);
+
+    // View space looks down -z, so flip to get a positive distance
+    outLinearDepth = -viewZ;
+}
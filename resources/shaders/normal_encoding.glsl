@@ -0,0 +1,64 @@
+// How the G-buffer normal attachment stores normals, see NormalEncoding in config.rs.
+// Include after the shader's own specialization constants, constant_id 1 is taken.
+
+const uint NORMAL_RGBA16_SNORM = 0;
+const uint NORMAL_OCT_RG16 = 1;
+const uint NORMAL_OCT_RGB8 = 2;
+const uint NORMAL_RGB10A2 = 3;
+
+layout(constant_id = 1) const uint NORMAL_ENCODING = NORMAL_RGBA16_SNORM;
+
+// MaterialUbo's default roughness, also what encodings without room for roughness read
+// back.
+const float DEFAULT_ROUGHNESS = 0.3;
+
+vec2 signNotZero(vec2 v) {
+    return vec2(v.x >= 0.0 ? 1.0 : -1.0, v.y >= 0.0 ? 1.0 : -1.0);
+}
+
+// Unit vector onto the [-1, 1] square, the lower hemisphere folded over the corners
+vec2 octEncode(vec3 n) {
+    vec2 p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+    return n.z <= 0.0 ? (1.0 - abs(p.yx)) * signNotZero(p) : p;
+}
+
+vec3 octDecode(vec2 e) {
+    vec3 n = vec3(e, 1.0 - abs(e.x) - abs(e.y));
+    if (n.z < 0.0) {
+        n.xy = (1.0 - abs(n.yx)) * signNotZero(n.xy);
+    }
+    return normalize(n);
+}
+
+// World space normal and roughness as the normal attachment stores them
+vec4 encodeNormal(vec3 normal, float roughness) {
+    if (NORMAL_ENCODING == NORMAL_OCT_RG16) {
+        return vec4(octEncode(normal), 0.0, 0.0);
+    }
+    if (NORMAL_ENCODING == NORMAL_OCT_RGB8) {
+        // 12 bits per octahedral component, split over three UNORM bytes
+        uvec2 q = uvec2(round((octEncode(normal) * 0.5 + 0.5) * 4095.0));
+        uvec3 bytes = uvec3(q.x >> 4, ((q.x & 15u) << 4) | (q.y >> 8), q.y & 255u);
+        return vec4(vec3(bytes) / 255.0, roughness);
+    }
+    if (NORMAL_ENCODING == NORMAL_RGB10A2) {
+        return vec4(normal * 0.5 + 0.5, roughness);
+    }
+    return vec4(normal, roughness);
+}
+
+// World space normal in xyz and roughness in w from a normal attachment texel
+vec4 decodeNormal(vec4 texel) {
+    if (NORMAL_ENCODING == NORMAL_OCT_RG16) {
+        return vec4(octDecode(texel.xy), DEFAULT_ROUGHNESS);
+    }
+    if (NORMAL_ENCODING == NORMAL_OCT_RGB8) {
+        uvec3 bytes = uvec3(round(texel.xyz * 255.0));
+        uvec2 q = uvec2((bytes.x << 4) | (bytes.y >> 4), ((bytes.y & 15u) << 8) | bytes.z);
+        return vec4(octDecode(vec2(q) / 4095.0 * 2.0 - 1.0), texel.w);
+    }
+    if (NORMAL_ENCODING == NORMAL_RGB10A2) {
+        return vec4(normalize(texel.xyz * 2.0 - 1.0), texel.w);
+    }
+    return vec4(normalize(texel.xyz), texel.w);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+// Only used for non-sRGB swapchains, sRGB ones get a plain blit
+layout(set = 0, binding = 0) uniform sampler2D drawImage;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 fragColor;
+
+vec3 linearToSrgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, greaterThan(color, vec3(0.0031308)));
+}
+
+void main() {
+    vec4 color = texture(drawImage, fragTexCoord);
+    fragColor = vec4(linearToSrgb(clamp(color.rgb, 0.0, 1.0)), color.a);
+}
@@ -2,15 +2,53 @@
 
 
 #extension GL_ARB_separate_shader_objects: enable
+#extension GL_GOOGLE_include_directive: require
+
+#include "normal_encoding.glsl"
 
 layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outNormal;
 layout(location = 0) in vec3 fragColor;
 
 layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in vec3 fragWorldPos;
+layout(location = 3) in vec3 fragNormal;
+layout(location = 4) in vec4 fragTangent;
+
+// MAX_TEXTURES slots, only the registered ones are bound
+layout(set = 2, binding = 0) uniform sampler2D textures[256];
+
+// ModelDynamicUbo, the vertex stage only reads the model and normal matrices
+layout(set = 0, binding = 1) uniform UboInstance {
+    mat4 model;
+    mat4 normalModel;
+    vec4 baseColor;
+    vec4 emissive; // Not written, the G-buffer has no channel left for it
+    vec4 materialParams; // x: metallic, y: roughness
+} uboInstance;
 
-layout(binding = 2) uniform sampler2D texSampler;
+layout(push_constant) uniform Material {
+    uint textureIndex;
+    // Tangent space normal map, NO_TEXTURE for flat normals
+    uint normalTextureIndex;
+} material;
+
+const uint NO_TEXTURE = 0xFFFFFFFFu;
 
 void main() {
-    outColor = texture(texSampler, fragTexCoord);
+    // Metallic rides in the albedo's alpha
+    vec3 baseColor = texture(textures[material.textureIndex], fragTexCoord).rgb * uboInstance.baseColor.rgb;
+    outColor = vec4(baseColor, uboInstance.materialParams.x);
     //outColor = vec4(fragColor, 1.0);
+
+    // Flat world-space normal from screen-space derivatives
+    vec3 normal = normalize(cross(dFdx(fragWorldPos), dFdy(fragWorldPos)));
+    if (material.normalTextureIndex != NO_TEXTURE && dot(fragTangent.xyz, fragTangent.xyz) > 0.0) {
+        vec3 vertexNormal = normalize(fragNormal);
+        vec3 tangent = normalize(fragTangent.xyz - vertexNormal * dot(vertexNormal, fragTangent.xyz));
+        vec3 bitangent = cross(vertexNormal, tangent) * fragTangent.w;
+        vec3 mapped = texture(textures[material.normalTextureIndex], fragTexCoord).xyz * 2.0 - 1.0;
+        normal = normalize(mat3(tangent, bitangent, vertexNormal) * mapped);
+    }
+    outNormal = encodeNormal(normal, uboInstance.materialParams.y);
 }
\ No newline at end of file
@@ -8,20 +8,35 @@ layout(binding = 0) uniform UniformBufferObject {
 
 layout(binding = 1) uniform UboInstance {
     mat4 model;
+    mat4 normalModel;
 } uboInstance;
 
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inColor;
 layout(location = 2) in vec2 inTexCoord;
+// Locations 5 to 8 are the instance matrix of shader_instanced.vert
+layout(location = 9) in vec3 inNormal;
+layout(location = 10) in vec4 inTangent;
 
 layout(location = 0) out vec3 fragColor;
 layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out vec3 fragWorldPos;
+layout(location = 3) out vec3 fragNormal;
+layout(location = 4) out vec4 fragTangent;
+// Invariant so the depth prepass, which runs this shader without a fragment stage, computes
+// exactly the depth the G-buffer pass tests EQUAL against
 out gl_PerVertex {
-    vec4 gl_Position;
+    invariant vec4 gl_Position;
 };
 
 void main() {
-    gl_Position = ubo.proj * ubo.view * uboInstance.model * vec4(inPosition, 1.0);
+    vec4 worldPos = uboInstance.model * vec4(inPosition, 1.0);
+    gl_Position = ubo.proj * ubo.view * worldPos;
     fragColor = inColor;
     fragTexCoord = inTexCoord;
+    fragWorldPos = worldPos.xyz;
+
+    // World space, the fragment shader renormalizes them
+    fragNormal = mat3(uboInstance.normalModel) * inNormal;
+    fragTangent = vec4(mat3(uboInstance.model) * inTangent.xyz, inTangent.w);
 }
\ No newline at end of file
@@ -0,0 +1,45 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_EXT_multiview : enable
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    mat4 lightViewProj;
+    mat4 eyeViewProj[2];
+} ubo;
+
+layout(binding = 1) uniform UboInstance {
+    mat4 model;
+    mat4 normalModel;
+} uboInstance;
+
+layout(location = 0) in vec3 inPosition;
+layout(location = 1) in vec3 inColor;
+layout(location = 2) in vec2 inTexCoord;
+// Locations 5 to 8 are the instance matrix of shader_instanced.vert
+layout(location = 9) in vec3 inNormal;
+layout(location = 10) in vec4 inTangent;
+
+layout(location = 0) out vec3 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out vec3 fragWorldPos;
+layout(location = 3) out vec3 fragNormal;
+layout(location = 4) out vec4 fragTangent;
+// Invariant so the depth prepass, which runs this shader without a fragment stage, computes
+// exactly the depth the G-buffer pass tests EQUAL against
+out gl_PerVertex {
+    invariant vec4 gl_Position;
+};
+
+void main() {
+    vec4 worldPos = uboInstance.model * vec4(inPosition, 1.0);
+    gl_Position = ubo.eyeViewProj[gl_ViewIndex] * worldPos;
+    fragColor = inColor;
+    fragTexCoord = inTexCoord;
+    fragWorldPos = worldPos.xyz;
+
+    // World space, the fragment shader renormalizes them
+    fragNormal = mat3(uboInstance.normalModel) * inNormal;
+    fragTangent = vec4(mat3(uboInstance.model) * inTangent.xyz, inTangent.w);
+}
\ No newline at end of file
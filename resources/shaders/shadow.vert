@@ -5,17 +5,23 @@ layout (location = 0) in vec3 inPos;
 layout(binding = 0) uniform UniformBufferObject {
     mat4 view;
     mat4 proj;
-    mat4 ff;
+    mat4 lightViewProj;
+    mat4 eyeViewProj[2];
+    mat4 casterViewProj[8];
+    vec4 position;
+    mat4 cascadeViewProj[4];
 } ubo;
 
-layout(binding = 2) uniform UboInstance {
+layout(binding = 1) uniform UboInstance {
     mat4 model;
 } uboInstance;
 
-layout(location = 1) in vec3 inColor;
-layout(location = 2) in vec2 inTexCoord;
+// Shadow map cascade being rendered
+layout(push_constant) uniform Cascade {
+    uint index;
+} cascade;
 
 void main()
 {
-    gl_Position = ubo.proj * ubo.view * uboInstance.model * vec4(inPos, 1.0);
-}
\ No newline at end of file
+    gl_Position = ubo.cascadeViewProj[cascade.index] * uboInstance.model * vec4(inPos, 1.0);
+}
@@ -0,0 +1,25 @@
+#version 450
+
+layout (location = 0) in vec3 inPos;
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    mat4 lightViewProj;
+    mat4 eyeViewProj[2];
+    mat4 casterViewProj[8];
+} ubo;
+
+layout(binding = 1) uniform UboInstance {
+    mat4 model;
+} uboInstance;
+
+// Shadow caster layer being rendered
+layout(push_constant) uniform Caster {
+    uint layer;
+} caster;
+
+void main()
+{
+    gl_Position = ubo.casterViewProj[caster.layer] * uboInstance.model * vec4(inPos, 1.0);
+}
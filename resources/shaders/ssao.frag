@@ -0,0 +1,74 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive: require
+
+#include "normal_encoding.glsl"
+#include "depth_range.glsl"
+
+const int MAX_SSAO_SAMPLES = 64;
+const float NOISE_SIZE = 4.0;
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(std140, set = 0, binding = 1) uniform Ssao {
+    vec4 kernel[MAX_SSAO_SAMPLES]; // Hemisphere around +z, in units of the radius
+    vec4 params;                   // x: sample count, y: radius, z: bias
+} ssao;
+
+layout(set = 0, binding = 2) uniform sampler2D normalTexture;  // World-space normal, see normal_encoding.glsl
+layout(set = 0, binding = 3) uniform sampler2D depthTexture;
+layout(set = 0, binding = 4) uniform sampler2D noiseTexture;   // Rotations around the normal, tiled
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out float outOcclusion;
+
+vec3 viewPositionAt(vec2 uv, mat4 invProj) {
+    float depth = textureLod(depthTexture, uv, 0.0).r;
+    vec4 viewPos = invProj * vec4(uv * 2.0 - 1.0, depth, 1.0);
+    return viewPos.xyz / viewPos.w;
+}
+
+void main() {
+    float depth = textureLod(depthTexture, fragTexCoord, 0.0).r;
+    // Nothing to occlude the sky
+    if (isFarPlane(depth)) {
+        outOcclusion = 1.0;
+        return;
+    }
+
+    mat4 invProj = inverse(ubo.proj);
+    vec3 viewPos = viewPositionAt(fragTexCoord, invProj);
+    vec3 normal = normalize(mat3(ubo.view) * decodeNormal(textureLod(normalTexture, fragTexCoord, 0.0)).xyz);
+
+    // Gram-Schmidt a random tangent against the normal, so each pixel in the noise tile
+    // turns the kernel differently
+    vec2 noiseScale = vec2(textureSize(depthTexture, 0)) / NOISE_SIZE;
+    vec3 randomVec = vec3(textureLod(noiseTexture, fragTexCoord * noiseScale, 0.0).xy * 2.0 - 1.0, 0.0);
+    vec3 tangent = normalize(randomVec - normal * dot(randomVec, normal));
+    vec3 bitangent = cross(normal, tangent);
+    mat3 tbn = mat3(tangent, bitangent, normal);
+
+    int samples = int(ssao.params.x);
+    float radius = ssao.params.y;
+    float bias = ssao.params.z;
+
+    float occlusion = 0.0;
+    for (int i = 0; i < samples; i++) {
+        vec3 samplePos = viewPos + tbn * ssao.kernel[i].xyz * radius;
+
+        vec4 clip = ubo.proj * vec4(samplePos, 1.0);
+        vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+        float sceneZ = viewPositionAt(uv, invProj).z;
+
+        // Occluders further than the radius from the surface fade out instead of
+        // darkening silhouettes
+        float rangeCheck = smoothstep(0.0, 1.0, radius / max(abs(viewPos.z - sceneZ), 0.0001));
+        occlusion += (sceneZ >= samplePos.z + bias ? 1.0 : 0.0) * rangeCheck;
+    }
+
+    outOcclusion = 1.0 - occlusion / float(max(samples, 1));
+}
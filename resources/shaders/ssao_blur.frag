@@ -0,0 +1,20 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform sampler2D aoTexture; // Raw occlusion from ssao.frag
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out float outOcclusion;
+
+void main() {
+    // 4x4 box, the size of the noise tile, so its pattern averages out
+    vec2 texelSize = 1.0 / vec2(textureSize(aoTexture, 0));
+    float result = 0.0;
+    for (int x = -2; x < 2; x++) {
+        for (int y = -2; y < 2; y++) {
+            vec2 offset = vec2(x, y) * texelSize;
+            result += textureLod(aoTexture, fragTexCoord + offset, 0.0).r;
+        }
+    }
+    outOcclusion = result / 16.0;
+}
@@ -0,0 +1,83 @@
+#version 450
+
+#extension GL_GOOGLE_include_directive: require
+
+#include "normal_encoding.glsl"
+#include "depth_range.glsl"
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(std140, set = 0, binding = 1) uniform Ssr {
+    uint maxSteps;
+    float thickness;
+    float maxRoughness;
+    float intensity;
+} ssr;
+
+layout(set = 0, binding = 2) uniform sampler2D litTexture;
+layout(set = 0, binding = 3) uniform sampler2D normalTexture;  // World-space normal and roughness, see normal_encoding.glsl
+layout(set = 0, binding = 4) uniform sampler2D depthTexture;
+
+layout(location = 0) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 fragColor;
+
+vec3 viewPositionAt(vec2 uv, mat4 invProj) {
+    float depth = textureLod(depthTexture, uv, 0.0).r;
+    vec4 viewPos = invProj * vec4(uv * 2.0 - 1.0, depth, 1.0);
+    return viewPos.xyz / viewPos.w;
+}
+
+void main() {
+    vec4 lit = textureLod(litTexture, fragTexCoord, 0.0);
+    vec4 normalRoughness = decodeNormal(textureLod(normalTexture, fragTexCoord, 0.0));
+    float depth = textureLod(depthTexture, fragTexCoord, 0.0).r;
+    float roughness = normalRoughness.a;
+
+    // Sky or too rough to reflect
+    if (isFarPlane(depth) || roughness > ssr.maxRoughness) {
+        fragColor = lit;
+        return;
+    }
+
+    mat4 invProj = inverse(ubo.proj);
+    vec3 viewPos = viewPositionAt(fragTexCoord, invProj);
+    vec3 normal = normalize(mat3(ubo.view) * normalRoughness.xyz);
+    vec3 viewDir = normalize(viewPos);
+    vec3 reflected = normalize(reflect(viewDir, normal));
+
+    // March as far as the surface is from the camera, split into maxSteps
+    float stepLength = max(-viewPos.z, 1.0) / float(ssr.maxSteps);
+
+    vec3 hitColor = vec3(0.0);
+    float hit = 0.0;
+    for (uint i = 1; i <= ssr.maxSteps; i++) {
+        vec3 rayPos = viewPos + reflected * stepLength * float(i);
+
+        vec4 clip = ubo.proj * vec4(rayPos, 1.0);
+        vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+        if (clip.w <= 0.0 || any(lessThan(uv, vec2(0.0))) || any(greaterThan(uv, vec2(1.0)))) {
+            break;
+        }
+
+        vec3 scenePos = viewPositionAt(uv, invProj);
+        float behind = scenePos.z - rayPos.z;
+        if (behind > 0.0 && behind < ssr.thickness) {
+            // Fade out towards the screen edges where the ray leaves known data
+            vec2 edge = smoothstep(0.0, 0.1, uv) * (1.0 - smoothstep(0.9, 1.0, uv));
+            hitColor = textureLod(litTexture, uv, 0.0).rgb;
+            hit = edge.x * edge.y;
+            break;
+        }
+    }
+
+    // No environment map to fall back to on a miss, so a miss keeps the lit color
+    float fresnel = 0.04 + 0.96 * pow(1.0 - max(dot(-viewDir, normal), 0.0), 5.0);
+    float glossiness = 1.0 - roughness / max(ssr.maxRoughness, 0.0001);
+    float weight = clamp(hit * fresnel * glossiness * ssr.intensity, 0.0, 1.0);
+
+    fragColor = vec4(mix(lit.rgb, hitColor, weight), lit.a);
+}
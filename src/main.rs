@@ -1,18 +1,50 @@
 use new::terrain::generator::{generate_mesh, new_terrain};
+use new::vulkan_render::config::{RenderConfig, ValidationConfig};
 use new::vulkan_render::scene::{Mesh, SceneNode};
 use new::vulkan_render::vulkan_backend::VulkanBackend;
 use std::cell::RefCell;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::Instant;
-use winit::event::{DeviceEvent, DeviceId, WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, ElementState, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 use winit::{application::ApplicationHandler, dpi::LogicalSize};
 
 const WINDOW_TITLE: &str = "Vulkan Test";
 const WINDOW_WIDTH: u32 = 800;
 const WINDOW_HEIGHT: u32 = 600;
+
+/// Prints `log` records to stderr, the renderer and validation layer log through it.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// `file` in `resources/<directory>` under the crate's manifest directory, like
+/// `ShaderPaths::default`, independent of the working directory.
+fn resource_path(directory: &str, file: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("resources")
+        .join(directory)
+        .join(file)
+}
+
 struct AppWindow {
     window: Option<winit::window::Window>,
     vulkan_app: Option<VulkanBackend>,
@@ -24,16 +56,16 @@ struct AppWindow {
 impl Default for AppWindow {
     fn default() -> Self {
         let scene_root = SceneNode::new(
-            ".\\resources\\models\\test.obj",
-            ".\\resources\\textures\\texture.png",
+            resource_path("models", "test.obj"),
+            resource_path("textures", "texture.png"),
         );
         //scene_root.borrow_mut().add_child("E:\\rust\\new\\src\\models\\test2.obj");
         let terrain = new_terrain(123, 150);
         let mesh = generate_mesh(terrain);
         SceneNode::add_child(
             scene_root.clone(),
-            ".\\resources\\models\\test2.obj",
-            ".\\resources\\textures\\texture.png",
+            resource_path("models", "test2.obj"),
+            resource_path("textures", "texture.png"),
         );
         SceneNode::update(scene_root.clone());
         Self {
@@ -58,9 +90,15 @@ impl ApplicationHandler for AppWindow {
                 self.window.as_ref().unwrap(),
                 self.scene.clone(),
                 self.terrain.clone(),
+                RenderConfig {
+                    validation: cfg!(debug_assertions).then(ValidationConfig::default),
+                    ..RenderConfig::default()
+                },
             )
             .expect(""),
         );
+        // Setup and the pipeline warm-up aren't part of the first frame's time
+        self.last_frame_time = Instant::now();
     }
 
     // Handle window event
@@ -71,7 +109,12 @@ impl ApplicationHandler for AppWindow {
         event: winit::event::WindowEvent,
     ) {
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::CloseRequested => {
+                if let Some(app) = self.vulkan_app.as_mut() {
+                    app.destroy();
+                }
+                event_loop.exit()
+            }
             WindowEvent::RedrawRequested => match self.vulkan_app {
                 Some(ref mut app) => {
                     let time_elapsed = self.last_frame_time.elapsed();
@@ -80,7 +123,15 @@ impl ApplicationHandler for AppWindow {
                     print!("\r{}", delta_time);
                     std::io::stdout().flush().unwrap();
                     app.camera.update(delta_time);
+                    if app.poll_shader_changes() {
+                        if let Err(error) = app.reload_pipelines() {
+                            log::warn!("Shader reload failed: {}", error);
+                        }
+                    }
                     app.draw_frame(delta_time);
+                    if app.is_device_lost() {
+                        app.recreate_device(self.scene.clone(), self.terrain.clone());
+                    }
                     let window = &self.window.as_ref().unwrap();
                     Window::request_redraw(window);
                 }
@@ -103,13 +154,28 @@ impl ApplicationHandler for AppWindow {
                     .camera
                     .process_cursor_moved(delta.0 as f32, delta.1 as f32);
             }
-            DeviceEvent::Key(input) => vulkan_app.camera.process_keyboard_event(input),
+            DeviceEvent::Key(input) => {
+                // F2 switches between a low and high shadow resolution
+                if input.state == ElementState::Pressed
+                    && input.physical_key == PhysicalKey::Code(KeyCode::F2)
+                {
+                    let resolution = if vulkan_app.shadow_resolution() > 1024 {
+                        1024
+                    } else {
+                        4096
+                    };
+                    vulkan_app.set_shadow_resolution(resolution);
+                }
+                vulkan_app.camera.process_keyboard_event(input)
+            }
             _ => {}
         }
     }
 }
 
 fn main() {
+    log::set_logger(&LOGGER).expect("Unable to install logger");
+    log::set_max_level(log::LevelFilter::Info);
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
     let mut app_window = AppWindow::default();
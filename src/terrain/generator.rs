@@ -9,7 +9,6 @@ const BASE_HEIGHT: u32 = 15;
 pub fn new_terrain(seed: u32, size: u32) -> Vec<Vec<Vec<u32>>> {
     let fbm_simplex = Fbm::<Simplex>::new(seed).set_octaves(5).set_frequency(0.01);
     let mut out = vec![vec![vec![0; size as usize]; size as usize]; size as usize];
-    size as usize;
     for x in 0..size {
         for y in 0..50 {
             for z in 0..size {
@@ -33,6 +32,7 @@ pub fn new_terrain(seed: u32, size: u32) -> Vec<Vec<Vec<u32>>> {
     out
 }
 
+#[allow(clippy::collapsible_match)]
 pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
     let mut vertices = vec![];
     let mut indices = vec![];
@@ -40,7 +40,7 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
     for x in 1..terrain_data.len() - 1 {
         for y in 0..terrain_data[x].len() - 1 {
             for z in 1..terrain_data[x][y].len() - 1 {
-                if terrain_data[x][y][z] <= 0 {
+                if terrain_data[x][y][z] == 0 {
                     continue;
                 }
 
@@ -51,30 +51,33 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                             if terrain_data[x][y + 1][z] == 0 || y + 1 == terrain_data.len() {
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 255.0, 0.0),
                                     tex_coord: vec2(0.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 255.0, 0.0),
                                     tex_coord: vec2(1.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 255.0, 0.0),
                                     tex_coord: vec2(1.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
@@ -84,12 +87,13 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                                     ),
                                     color: vec3(0.0, 255.0, 0.0),
                                     tex_coord: vec2(0.0, 1.0),
+                                    ..Default::default()
                                 });
 
                                 indices.push(2 + element_index);
                                 indices.push(1 + element_index);
-                                indices.push(0 + element_index);
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
+                                indices.push(element_index);
                                 indices.push(3 + element_index);
                                 indices.push(2 + element_index);
 
@@ -101,48 +105,52 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                             if y as i32 - 1 == -1 || terrain_data[x][y - 1][z] == 0 {
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(255.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(255.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(255.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(255.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 0.0),
+                                    ..Default::default()
                                 });
 
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 indices.push(1 + element_index);
                                 indices.push(2 + element_index);
 
                                 indices.push(2 + element_index);
                                 indices.push(3 + element_index);
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
 
                                 element_index += 4;
                             }
@@ -152,48 +160,52 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                             if terrain_data[x - 1][y][z] == 0 {
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 0.0),
+                                    ..Default::default()
                                 });
 
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 indices.push(1 + element_index);
                                 indices.push(2 + element_index);
 
                                 indices.push(2 + element_index);
                                 indices.push(3 + element_index);
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 element_index += 4;
                             }
                         }
@@ -203,28 +215,31 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
@@ -234,13 +249,14 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 0.0),
+                                    ..Default::default()
                                 });
 
                                 indices.push(2 + element_index);
                                 indices.push(1 + element_index);
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
 
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 indices.push(3 + element_index);
                                 indices.push(2 + element_index);
                                 element_index += 4;
@@ -250,46 +266,50 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                             if terrain_data[x][y][z - 1] == 0 {
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 0.0),
+                                    ..Default::default()
                                 });
 
                                 indices.push(2 + element_index);
                                 indices.push(1 + element_index);
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
 
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 indices.push(3 + element_index);
                                 indices.push(2 + element_index);
                                 element_index += 4;
@@ -299,30 +319,33 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                             if terrain_data[x][y][z + 1] == 0 {
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 0.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
-                                        (-1 * VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(0.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
                                         (VOXEL_SIZE_HALF + (x as i32 * VOXEL_SIZE)) as f32,
-                                        (-1 * VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
+                                        (-VOXEL_SIZE_HALF + (y as i32 * VOXEL_SIZE)) as f32,
                                         (VOXEL_SIZE_HALF + (z as i32 * VOXEL_SIZE)) as f32,
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 1.0),
+                                    ..Default::default()
                                 });
                                 vertices.push(Vertex {
                                     pos: vec3(
@@ -332,15 +355,16 @@ pub fn generate_mesh(terrain_data: Vec<Vec<Vec<u32>>>) -> Mesh {
                                     ),
                                     color: vec3(0.0, 0.0, 0.0),
                                     tex_coord: vec2(1.0, 0.0),
+                                    ..Default::default()
                                 });
 
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 indices.push(1 + element_index);
                                 indices.push(2 + element_index);
 
                                 indices.push(2 + element_index);
                                 indices.push(3 + element_index);
-                                indices.push(0 + element_index);
+                                indices.push(element_index);
                                 element_index += 4;
                             }
                         }
@@ -0,0 +1,236 @@
+//! Mip chain and descriptor sets of the bloom pass, see `RenderConfig::bloom`. Each frame
+//! has its own, sized after its draw image.
+
+use crate::vulkan_render::buffer::AllocatedBuffer;
+use crate::vulkan_render::config::{BloomConfig, SamplerConfig};
+use crate::vulkan_render::constants::MAX_BLOOM_MIPS;
+use crate::vulkan_render::descriptor::DescriptorManager;
+use crate::vulkan_render::device::DeviceInfo;
+use crate::vulkan_render::image_util::AllocatedImage;
+use crate::vulkan_render::structs::BloomUbo;
+use crate::vulkan_render::utils;
+use ash::vk::{BufferUsageFlags, DescriptorSet, ImageAspectFlags, MemoryPropertyFlags};
+use ash::{vk, Device, Instance};
+use std::mem;
+
+/// HDR like the draw image, sampled by the next step and written as a storage image.
+const MIP_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Smallest width or height a mip is made for, smaller ones only blur a few pixels further.
+const MIN_MIP_SIZE: u32 = 8;
+
+/// What `bloom.comp` does in a step, pushed as its only push constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BloomMode {
+    /// Downsamples the draw image into mip 0, keeping only what's above the threshold.
+    Threshold = 0,
+    Downsample = 1,
+    /// Upsamples the next smaller mip and adds it to the destination mip.
+    Upsample = 2,
+    /// Upsamples mip 0 and adds it, scaled by the intensity, into the draw image.
+    Composite = 3,
+}
+
+/// One dispatch, covering `extent` of its destination.
+pub struct BloomStep {
+    pub mode: BloomMode,
+    pub descriptor_set: DescriptorSet,
+    pub extent: vk::Extent2D,
+}
+
+/// Levels below a half resolution mip 0 until the smaller side reaches `MIN_MIP_SIZE`,
+/// at least 1 and at most `MAX_BLOOM_MIPS`.
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    let half = (width.min(height) / 2).max(1);
+    let levels = (half / MIN_MIP_SIZE).max(1).ilog2() + 1;
+    levels.min(MAX_BLOOM_MIPS as u32)
+}
+
+pub struct BloomTargets {
+    /// Half the draw image's size, halving with every further level.
+    pub mips: Vec<AllocatedImage>,
+    /// `BloomUbo` every step reads.
+    pub buffer: AllocatedBuffer,
+    /// Linear and clamped, so each tap averages four texels.
+    pub sampler: vk::Sampler,
+    /// `BLOOM_SET_COUNT` sets, the first `steps().len()` of them written.
+    pub descriptor_sets: Vec<DescriptorSet>,
+    draw_extent: vk::Extent2D,
+}
+
+impl BloomTargets {
+    pub fn new(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        descriptor_manager: &DescriptorManager,
+        draw_image: &AllocatedImage,
+        config: BloomConfig,
+    ) -> Self {
+        let mut buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            mem::size_of::<BloomUbo>() as u64,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        buffer.update_buffer(device_info, &[Self::bloom_ubo(config)]);
+
+        let sampler =
+            utils::create_texture_sampler(device_info, instance, &SamplerConfig::screen(), 1);
+
+        let mut targets = Self {
+            mips: vec![],
+            buffer,
+            sampler,
+            descriptor_sets: descriptor_manager
+                .create_bloom_descriptor_sets(&device_info.logical_device),
+            draw_extent: vk::Extent2D::default(),
+        };
+        targets.resize(device_info, instance, descriptor_manager, draw_image);
+        targets
+    }
+
+    /// Recreates the mip chain for a new draw image and points the sets at it. Nothing may
+    /// still be using the old mips.
+    pub fn resize(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        descriptor_manager: &DescriptorManager,
+        draw_image: &AllocatedImage,
+    ) {
+        for mip in self.mips.drain(..) {
+            mip.destroy_image(&device_info.logical_device);
+        }
+
+        let extent = draw_image.image_extent;
+        self.draw_extent = vk::Extent2D {
+            width: extent.width,
+            height: extent.height,
+        };
+        let (mut width, mut height) = ((extent.width / 2).max(1), (extent.height / 2).max(1));
+        for _ in 0..mip_count(extent.width, extent.height) {
+            self.mips.push(AllocatedImage::new(
+                device_info,
+                instance,
+                width,
+                height,
+                MIP_FORMAT,
+                ImageAspectFlags::COLOR,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            ));
+            width = (width / 2).max(1);
+            height = (height / 2).max(1);
+        }
+
+        for ((source, destination), &descriptor_set) in self
+            .step_views(draw_image.image_view)
+            .into_iter()
+            .zip(self.descriptor_sets.iter())
+        {
+            descriptor_manager.update_bloom_descriptor_set(
+                device_info,
+                &source,
+                &self.sampler,
+                &destination,
+                &self.buffer,
+                descriptor_set,
+            );
+        }
+    }
+
+    /// Source and destination view of every step, in `steps` order.
+    fn step_views(&self, draw_view: vk::ImageView) -> Vec<(vk::ImageView, vk::ImageView)> {
+        let views = self
+            .mips
+            .iter()
+            .map(|mip| mip.image_view)
+            .collect::<Vec<_>>();
+        let mut steps = vec![(draw_view, views[0])];
+        steps.extend(views.windows(2).map(|pair| (pair[0], pair[1])));
+        steps.extend(views.windows(2).rev().map(|pair| (pair[1], pair[0])));
+        steps.push((views[0], draw_view));
+        steps
+    }
+
+    /// The threshold, downsamples to the smallest mip, upsamples back to mip 0 and the add
+    /// into the draw image. Each step reads what the one before wrote.
+    pub fn steps(&self) -> Vec<BloomStep> {
+        let extents = self
+            .mips
+            .iter()
+            .map(|mip| vk::Extent2D {
+                width: mip.image_extent.width,
+                height: mip.image_extent.height,
+            })
+            .collect::<Vec<_>>();
+        let last = extents.len() - 1;
+
+        let mut modes_and_extents = vec![(BloomMode::Threshold, extents[0])];
+        modes_and_extents.extend((1..=last).map(|level| (BloomMode::Downsample, extents[level])));
+        modes_and_extents.extend(
+            (0..last)
+                .rev()
+                .map(|level| (BloomMode::Upsample, extents[level])),
+        );
+        modes_and_extents.push((BloomMode::Composite, self.draw_extent));
+        modes_and_extents
+            .into_iter()
+            .zip(self.descriptor_sets.iter())
+            .map(|((mode, extent), &descriptor_set)| BloomStep {
+                mode,
+                descriptor_set,
+                extent,
+            })
+            .collect()
+    }
+
+    pub fn set_config(&mut self, device_info: &DeviceInfo, config: BloomConfig) {
+        self.buffer
+            .update_buffer(device_info, &[Self::bloom_ubo(config)]);
+    }
+
+    fn bloom_ubo(config: BloomConfig) -> BloomUbo {
+        BloomUbo {
+            threshold: config.threshold,
+            knee: config.knee,
+            intensity: config.intensity,
+            _padding: 0.0,
+        }
+    }
+
+    /// Nothing may still be using the targets. The descriptor sets go back with the pool.
+    pub fn destroy(self, device: &Device) {
+        unsafe { device.destroy_sampler(self.sampler, None) };
+        self.buffer.destroy_buffer(device);
+        for mip in self.mips {
+            mip.destroy_image(device);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_count_stops_before_mips_get_smaller_than_the_minimum() {
+        assert_eq!(mip_count(32, 32), 2);
+        assert_eq!(mip_count(1920, 1080), 7);
+        assert_eq!(mip_count(1080, 1920), 7);
+    }
+
+    #[test]
+    fn mip_count_keeps_one_level_for_tiny_images() {
+        assert_eq!(mip_count(1, 1), 1);
+        assert_eq!(mip_count(16, 4096), 1);
+    }
+
+    #[test]
+    fn mip_count_is_capped() {
+        assert_eq!(mip_count(3840, 2160), MAX_BLOOM_MIPS as u32);
+        assert_eq!(mip_count(7680, 4320), MAX_BLOOM_MIPS as u32);
+    }
+}
@@ -1,18 +1,30 @@
 use super::{device, utils};
 use crate::vulkan_render::device::DeviceInfo;
-use ash::vk::{BufferUsageFlags, DeviceMemory, DeviceSize, MappedMemoryRange, MemoryPropertyFlags};
+use crate::vulkan_render::upload::UploadBatch;
+use ash::vk::{BufferUsageFlags, DeviceMemory, DeviceSize, MemoryPropertyFlags};
 use ash::{vk, Instance};
 use core::panic;
 use std::ffi::c_void;
-use std::slice;
+use std::{mem, ptr, slice};
 
 pub struct AllocatedBuffer {
     pub buffer: vk::Buffer,
     pub buffer_memory: DeviceMemory,
+    /// Mapped for the buffer's whole life when HOST_VISIBLE, null otherwise.
     pub mapped_buffer: *mut c_void,
+    pub size: DeviceSize,
+    /// Whether the memory lacks HOST_COHERENT, so writes need a `flush` before the device
+    /// sees them.
+    pub needs_flush: bool,
 }
 
+// The mapping is only written through `&mut self` and the buffer owns its memory, so it can be
+// moved to and read from other threads, like the ones recording G-buffer draws.
+unsafe impl Send for AllocatedBuffer {}
+unsafe impl Sync for AllocatedBuffer {}
+
 impl AllocatedBuffer {
+    /// Maps HOST_VISIBLE memory once, `update_buffer` writes through that mapping.
     pub fn new(
         device_info: &DeviceInfo,
         instance: &Instance,
@@ -27,35 +39,108 @@ impl AllocatedBuffer {
             usage,
             memory_property_flags,
         );
-        let mapped_buffer = unsafe {
-            device_info
-                .logical_device
-                .map_memory(buffer_memory, 0, buffer_size, vk::MemoryMapFlags::empty())
-                .expect("failed to map memory")
+        let host_visible = memory_property_flags.contains(MemoryPropertyFlags::HOST_VISIBLE);
+        let mapped_buffer = if host_visible {
+            unsafe {
+                device_info
+                    .logical_device
+                    .map_memory(buffer_memory, 0, buffer_size, vk::MemoryMapFlags::empty())
+                    .expect("failed to map memory")
+            }
+        } else {
+            ptr::null_mut()
         };
 
         AllocatedBuffer {
             buffer,
             buffer_memory,
             mapped_buffer,
+            size: buffer_size,
+            needs_flush: host_visible
+                && !memory_property_flags.contains(MemoryPropertyFlags::HOST_COHERENT),
         }
     }
 
-    pub fn update_buffer<T>(&mut self, data: &[T]) {
-        let current_mapped_memory = self.mapped_buffer as *mut T;
+    /// DEVICE_LOCAL buffer holding `data`, copied in through a temporary staging buffer in a
+    /// one-time submit that's waited on, see `UploadBatch`. `usage` gets TRANSFER_DST added.
+    /// Uploading several at once is better done through one `UploadBatch`.
+    pub fn new_device_local_with_staging<T>(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        data: &[T],
+        usage: BufferUsageFlags,
+    ) -> Self {
+        let mut batch = UploadBatch::new(device_info, instance);
+        let buffer = batch.buffer(data, usage);
+        batch.submit();
+        buffer
+    }
 
-        unsafe { current_mapped_memory.copy_from_nonoverlapping(data.as_ptr(), data.len()) };
+    /// Frees the buffer and its memory, which also unmaps it.
+    pub fn destroy_buffer(self, logical_device: &ash::Device) {
+        unsafe {
+            logical_device.destroy_buffer(self.buffer, None);
+            logical_device.free_memory(self.buffer_memory, None);
+        }
     }
 
-    pub fn flush_mapped_memory_ranges(
+    /// Writes `data` to the start of the mapping, flushing it when the memory isn't coherent.
+    pub fn update_buffer<T>(&mut self, device_info: &DeviceInfo, data: &[T]) {
+        self.update_buffer_at(device_info, 0, data);
+    }
+
+    /// Writes `data` `offset` bytes into the mapping, like `update_buffer`. Panics unless it
+    /// fits in the buffer and `offset` is aligned for `T`.
+    pub fn update_buffer_at<T>(
         &mut self,
-        device: &ash::Device,
-        mapped_memory_range: MappedMemoryRange,
+        device_info: &DeviceInfo,
+        offset: DeviceSize,
+        data: &[T],
     ) {
+        let size = mem::size_of_val(data) as DeviceSize;
+        assert!(
+            offset + size <= self.size,
+            "write past the end of the buffer"
+        );
+        assert_eq!(
+            offset % mem::align_of::<T>() as DeviceSize,
+            0,
+            "misaligned buffer write"
+        );
+
+        unsafe {
+            let current_mapped_memory =
+                (self.mapped_buffer as *mut u8).add(offset as usize) as *mut T;
+            current_mapped_memory.copy_from_nonoverlapping(data.as_ptr(), data.len());
+        }
+        self.flush(device_info, offset, size);
+    }
+
+    /// Makes host writes to `size` bytes from `offset` visible to the device, widened to
+    /// `non_coherent_atom_size`. Does nothing for coherent memory.
+    pub fn flush(&self, device_info: &DeviceInfo, offset: DeviceSize, size: DeviceSize) {
+        if !self.needs_flush || size == 0 {
+            return;
+        }
+        let atom_size = device_info.non_coherent_atom_size;
+        let start = offset / atom_size * atom_size;
+        let end = (offset + size).next_multiple_of(atom_size);
+        // Past the buffer's last atom only the rest of the allocation is left to cover
+        let size = if end >= self.size {
+            vk::WHOLE_SIZE
+        } else {
+            end - start
+        };
+
+        let range = vk::MappedMemoryRange::default()
+            .memory(self.buffer_memory)
+            .offset(start)
+            .size(size);
         unsafe {
-            device
-                .flush_mapped_memory_ranges(&[mapped_memory_range])
-                .expect("d");
+            device_info
+                .logical_device
+                .flush_mapped_memory_ranges(&[range])
+                .expect("failed to flush mapped memory range");
         }
     }
 
@@ -180,7 +265,7 @@ impl BufferInfo {
         size: u64,
         device_info: &device::DeviceInfo,
     ) {
-        let command_buffer = Self::begin_single_time_command(&device_info);
+        let command_buffer = Self::begin_single_time_command(device_info);
 
         let copy_region = vk::BufferCopy {
             src_offset: 0,
@@ -201,10 +286,18 @@ impl BufferInfo {
     }
 
     pub fn begin_single_time_command(device_info: &DeviceInfo) -> vk::CommandBuffer {
+        Self::begin_single_time_command_in(device_info, device_info.command_pool)
+    }
+
+    /// `begin_single_time_command` from another queue family's `command_pool`.
+    pub fn begin_single_time_command_in(
+        device_info: &DeviceInfo,
+        command_pool: vk::CommandPool,
+    ) -> vk::CommandBuffer {
         let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
             .level(vk::CommandBufferLevel::PRIMARY)
             .command_buffer_count(1)
-            .command_pool(device_info.command_pool);
+            .command_pool(command_pool);
 
         let command_buffer = unsafe {
             device_info
@@ -227,6 +320,22 @@ impl BufferInfo {
     }
 
     pub fn end_single_time_command(device_info: &DeviceInfo, command_buffer: vk::CommandBuffer) {
+        Self::end_single_time_command_on(
+            device_info,
+            device_info.queue_info.graphics_queue,
+            device_info.command_pool,
+            command_buffer,
+        );
+    }
+
+    /// `end_single_time_command` of a command buffer from `begin_single_time_command_in`,
+    /// submitted to a queue of `command_pool`'s family.
+    pub fn end_single_time_command_on(
+        device_info: &DeviceInfo,
+        queue: vk::Queue,
+        command_pool: vk::CommandPool,
+        command_buffer: vk::CommandBuffer,
+    ) {
         unsafe {
             device_info
                 .logical_device
@@ -239,19 +348,15 @@ impl BufferInfo {
         unsafe {
             device_info
                 .logical_device
-                .queue_submit(
-                    device_info.queue_info.graphics_queue,
-                    &[submit_info],
-                    vk::Fence::null(),
-                )
+                .queue_submit(queue, &[submit_info], vk::Fence::null())
                 .expect("Failed to submit queue!");
             device_info
                 .logical_device
-                .queue_wait_idle(device_info.queue_info.graphics_queue)
+                .queue_wait_idle(queue)
                 .expect("Failed to wait on queue!");
             device_info
                 .logical_device
-                .free_command_buffers(device_info.command_pool, &[command_buffer]);
+                .free_command_buffers(command_pool, &[command_buffer]);
         }
     }
 
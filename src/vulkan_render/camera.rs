@@ -66,13 +66,7 @@ impl Camera {
     pub fn process_cursor_moved(&mut self, mouse_x: f32, mouse_y: f32) {
         self.yaw += mouse_x / 200.0;
         self.pitch -= mouse_y / 200.0;
-
-        if self.pitch > 1.5 {
-            self.pitch = 1.5;
-        }
-        if self.pitch < -1.5 {
-            self.pitch = -1.5;
-        }
+        self.pitch = self.pitch.clamp(-1.5, 1.5);
     }
 
     pub fn get_view_matrix(&self) -> Mat4 {
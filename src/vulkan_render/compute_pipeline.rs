@@ -0,0 +1,93 @@
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+
+use super::config::ShaderPaths;
+use super::graphics_pipeline::PipelineInfo;
+use super::structs::PipelineError;
+use ash::vk;
+
+pub struct ComputePipelineInfo {
+    pub pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+    /// The shader name it was created from.
+    pub name: String,
+    /// Time spent in `create_compute_pipelines`, shader module creation excluded.
+    pub creation_time: Duration,
+}
+
+impl ComputePipelineInfo {
+    /// Builds the layout from `set_layouts` and `push_constant_ranges` and the pipeline from
+    /// the single compute shader `shader_name`. Nothing is left behind on failure.
+    pub fn new(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange],
+        shader_name: &str,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<ComputePipelineInfo, PipelineError> {
+        let compute_shader_code = PipelineInfo::read_shader_file(shader_paths, shader_name)
+            .map_err(PipelineError::ShaderReadError)?;
+        let compute_shader_module =
+            PipelineInfo::create_shader_module(&compute_shader_code, logical_device)?;
+
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(set_layouts)
+            .push_constant_ranges(push_constant_ranges);
+
+        let pipeline_layout =
+            unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }
+                .map_err(|result| {
+                    unsafe { logical_device.destroy_shader_module(compute_shader_module, None) };
+                    PipelineError::PipelineCreation(result)
+                })?;
+
+        let name = shader_name.to_string();
+        let shader_name = CString::new("main").unwrap();
+
+        let stage_create_info = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(compute_shader_module)
+            .name(&shader_name);
+
+        let pipeline_create_info = vk::ComputePipelineCreateInfo::default()
+            .stage(stage_create_info)
+            .layout(pipeline_layout);
+
+        let start = Instant::now();
+        let compute_pipelines = unsafe {
+            logical_device.create_compute_pipelines(pipeline_cache, &[pipeline_create_info], None)
+        };
+        let creation_time = start.elapsed();
+
+        unsafe {
+            logical_device.destroy_shader_module(compute_shader_module, None);
+        };
+
+        let compute_pipelines = compute_pipelines.map_err(|(pipelines, result)| {
+            unsafe {
+                for pipeline in pipelines {
+                    logical_device.destroy_pipeline(pipeline, None);
+                }
+                logical_device.destroy_pipeline_layout(pipeline_layout, None);
+            }
+            PipelineError::PipelineCreation(result)
+        })?;
+        log::info!("Created pipeline {} in {:?}", name, creation_time);
+
+        Ok(Self {
+            pipeline: compute_pipelines[0],
+            pipeline_layout,
+            name,
+            creation_time,
+        })
+    }
+
+    /// Destroys the pipeline and its layout. Nothing may still be using them.
+    pub fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+}
@@ -0,0 +1,769 @@
+use super::structs::{LightingUbo, UnsupportedFormatsError};
+use ash::vk;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+pub struct RenderConfig {
+    /// Write linearized view-space depth into an R32_SFLOAT image after the gbuffer pass.
+    pub linear_depth: bool,
+    /// Screen-space reflections over the lit image, disabled when `None`.
+    pub ssr: Option<SsrConfig>,
+    /// Screen-space ambient occlusion from the G-buffer's depth and normals, rendered at half
+    /// resolution and blurred, darkening the lighting pass's ambient term. Off when `None`.
+    pub ssao: Option<SsaoConfig>,
+    /// Tonemap the draw image into an 8-bit sRGB image in a compute pass, which the swapchain
+    /// is then blitted from. Needs an R16G16B16A16_SFLOAT draw image the device can use as a
+    /// storage image, without one the draw image is presented as is, as it is when `None`.
+    pub tonemap: Option<TonemapConfig>,
+    /// Bloom added into the HDR draw image before tonemapping, from a thresholded and blurred
+    /// mip chain. Needs the same storage draw image as `tonemap`. Off when `None`.
+    pub bloom: Option<BloomConfig>,
+    /// FXAA on the final image as it's drawn into the swapchain, replacing the blit or
+    /// present pass. Off when `None`.
+    pub fxaa: Option<FxaaConfig>,
+    /// How the lighting pass filters the directional light's shadow map.
+    pub shadow_technique: ShadowTechnique,
+    /// Main light, ambient and shadow parameters to start with, `LightingUbo::new` shining
+    /// along (-1, -1, -1) when `None`. The shadow parameters must match `shadow_technique`.
+    pub lighting: Option<LightingUbo>,
+    /// Main light shadow map and caster map sizes, cascades and depth bias, filtered as
+    /// `shadow_technique` says.
+    pub shadow: ShadowConfig,
+    pub draw_image: DrawImagePolicy,
+    /// Filter of the final blit when the draw image and swapchain sizes differ.
+    pub present_filter: BlitFilter,
+    /// Bytes of readback buffer preallocated per frame in flight, grown on demand.
+    pub readback_size: u64,
+    /// Frames recorded ahead of the GPU, `MAX_FRAMES_IN_FLIGHT` when `None` and at least 1.
+    /// Each one has its own copy of the G-buffer and most other per-frame resources.
+    pub frames_in_flight: Option<usize>,
+    /// How long `VulkanBackend::render_frame` waits on a frame's fence before treating the
+    /// GPU as hung, forever when `None`.
+    pub frame_timeout: Option<Duration>,
+    /// Count frustum visible meshes in a compute pass, see `VulkanBackend::frame_stats`.
+    pub cull_stats: bool,
+    /// Issue the G-buffer and overlay draws from commands the cull pass writes, skipping
+    /// meshes outside the camera frustum without reading anything back. Runs the cull pass
+    /// even without `cull_stats`. Each mesh keeps its own buffers, so each is one indirect
+    /// draw: `cmd_draw_indexed_indirect_count` with a GPU written count of 0 or 1 when the
+    /// device has `drawIndirectCount`, and `cmd_draw_indexed_indirect` of one command the
+    /// cull pass gives 0 instances otherwise. Frozen frames keep culling against the frustum
+    /// they were recorded with.
+    pub indirect_draws: bool,
+    /// Give the G-buffer pipeline a second vertex binding of per-instance `InstanceData`, so
+    /// `VulkanBackend::set_mesh_instances` can draw a mesh many times in one indexed draw.
+    /// Instanced meshes skip the cull pass, and shadow and overlay passes draw them once.
+    pub instancing: bool,
+    /// Skip the G-buffer and overlay draws of meshes whose world bounding sphere or box is
+    /// outside the camera's `Frustum`, tested on the CPU while recording. Has no effect on
+    /// draws the cull pass issues with `indirect_draws`, or on instanced meshes. Frozen frames
+    /// keep the meshes they were recorded with.
+    pub cpu_culling: bool,
+    /// Fill the G-buffer depth in a depth-only pass first, so the G-buffer pass shades each
+    /// pixel once, testing EQUAL. Worth it with expensive fragment shading and a lot of
+    /// overdraw, a loss for simple scenes. Toggled at runtime through
+    /// `VulkanBackend::set_depth_prepass`.
+    pub depth_prepass: bool,
+    /// Draw the G-buffer pass's triangles as lines, for debugging geometry. Needs the
+    /// `fillModeNonSolid` device feature. Takes the place of the depth prepass while on.
+    /// Toggled at runtime through `VulkanBackend::set_wireframe`. Off when `None`.
+    pub wireframe: Option<WireframeConfig>,
+    /// Reverse-Z: the projection maps the near plane to depth 1.0 and the far plane to 0.0,
+    /// the G-buffer depth is cleared to 0.0 and tested GREATER. Floats are densest near 0.0,
+    /// so with a `D32_SFLOAT` depth this spreads the precision over the whole view distance
+    /// instead of spending it all close to the camera. Overlay pipeline descriptions have to
+    /// use the flipped compare op themselves. Shadow maps keep the standard range, see
+    /// `ShadowDepthBias`.
+    pub reverse_z: bool,
+    /// Threads the G-buffer draws are split between, each recording a secondary command
+    /// buffer of its own that the frame's command buffer executes. The threads are created
+    /// with the `FrameManager` and kept for its lifetime. Secondaries inherit no dynamic
+    /// state from the frame's command buffer, so each sets its own viewport, scissor and line
+    /// width. 0 and 1 record the draws on the frame's command buffer. Frozen frames do too,
+    /// since the secondaries are rerecorded every frame. Only worth it with many meshes.
+    pub gbuffer_threads: usize,
+    /// Write GPU timestamps between passes, see `VulkanBackend::pass_timings`. Ignored when
+    /// the graphics queue can't write timestamps.
+    pub gpu_timings: bool,
+    /// Distance between the eyes in world units with the `multiview` feature. The G-buffer,
+    /// depth and draw images get one layer per eye; later passes read the left eye.
+    pub eye_separation: f32,
+    /// Samples per pixel of the G-buffer pass, clamped to the highest count the device
+    /// supports for color and depth attachments. The multisampled attachments are resolved
+    /// into the single-sample G-buffer the later passes read: colors and normals are
+    /// averaged, depth takes sample 0. Single-sampled when `None`.
+    pub msaa_samples: Option<vk::SampleCountFlags>,
+    /// How the G-buffer stores normals, trading precision for bandwidth.
+    pub normal_encoding: NormalEncoding,
+    /// Formats of the other G-buffer attachments and the draw image.
+    pub gbuffer_formats: GBufferFormats,
+    /// Record classic barriers and submits even on devices supporting synchronization2.
+    pub disable_synchronization2: bool,
+    /// Filtering and addressing of the mesh texture and G-buffer samplers. The shadow
+    /// samplers always clamp to a white border without anisotropy.
+    pub samplers: SamplerConfigs,
+    /// Largest width or height of an uploaded texture. Bigger ones are downscaled on load,
+    /// keeping their aspect ratio.
+    pub max_texture_size: Option<u32>,
+    /// Where the built-in shaders and shaders named in `PipelineDescription`s are loaded from.
+    pub shader_paths: ShaderPaths,
+    /// File the pipeline cache is loaded from at startup and saved to by
+    /// `VulkanBackend::destroy`. A cache from another driver or device is ignored. Pipelines
+    /// still share an in-memory cache when `None`.
+    pub pipeline_cache_path: Option<PathBuf>,
+    /// Path to a compiled `.spv` fragment shader replacing the built-in lighting model.
+    /// It runs after the fullscreen `quad` vertex shader and can read, all in set 0:
+    ///
+    /// - binding 0: `Lighting` uniform (light direction, color, ambient, shadow params)
+    /// - binding 1, 2, 3: albedo, normal and depth `sampler2D`s of the G-buffer. Albedo alpha
+    ///   is the `MaterialUbo` metallic, normals and roughness are encoded as
+    ///   `RenderConfig::normal_encoding` says, `decodeNormal` from `normal_encoding.glsl`
+    ///   undoes it
+    /// - binding 4: light depth `sampler2DShadow`
+    /// - binding 5: `Camera` uniform (view, proj, lightViewProj, eyeViewProj[2],
+    ///   casterViewProj[8])
+    /// - binding 6: ESM `sampler2D`, only meaningful with `ShadowTechnique::Esm`
+    /// - binding 7: `Lights` storage buffer, a `uint` count then `Light`s from offset 16
+    /// - binding 8: shadow caster `sampler2DArrayShadow`, the layer is `Light::shadow_layer`
+    /// - binding 9: ambient occlusion `sampler2D`, only meaningful with `RenderConfig::ssao`
+    ///
+    /// Input location 0 is the `vec2` screen UV and output location 0 is the lit
+    /// `vec4` color, written to an image of `GBufferFormats::draw`. Specialization constant 0
+    /// carries the Poisson PCF tap count, 1 the `NormalEncoding` and 2 whether binding 9 holds
+    /// ambient occlusion.
+    pub lighting_shader: Option<String>,
+    /// Enables `VK_LAYER_KHRONOS_validation` when it's installed, logging its messages
+    /// through the `log` crate under the `vulkan` target. Off when `None`.
+    pub validation: Option<ValidationConfig>,
+}
+
+/// Where compiled shaders are read from. Names ending in `.spv` are paths used as given,
+/// other names are looked up in `overrides` and then as `<name>.spv` in `directory`.
+#[derive(Clone, Debug)]
+pub struct ShaderPaths {
+    /// `resources/shaders` under the crate's manifest directory by default, independent of
+    /// the working directory.
+    pub directory: PathBuf,
+    /// Explicit files for shader names, like `"vert"` or `"frag"` of the G-buffer pass.
+    pub overrides: HashMap<String, PathBuf>,
+}
+
+impl Default for ShaderPaths {
+    fn default() -> Self {
+        Self::new(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("resources")
+                .join("shaders"),
+        )
+    }
+}
+
+impl ShaderPaths {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Loads the shader named `name` from `path` instead of the directory.
+    pub fn with_shader(mut self, name: &str, path: impl Into<PathBuf>) -> Self {
+        self.overrides.insert(name.to_string(), path.into());
+        self
+    }
+
+    /// The file `shader` is read from.
+    pub fn resolve(&self, shader: &str) -> PathBuf {
+        if shader.ends_with(".spv") {
+            return PathBuf::from(shader);
+        }
+        match self.overrides.get(shader) {
+            Some(path) => path.clone(),
+            None => self.directory.join(format!("{}.spv", shader)),
+        }
+    }
+}
+
+/// How one sampler filters and addresses its image.
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerConfig {
+    pub min_filter: vk::Filter,
+    pub mag_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    /// Used for u, v and w.
+    pub address_mode: vk::SamplerAddressMode,
+    /// Clamped to the device's maxSamplerAnisotropy, no anisotropic filtering when `None`.
+    pub max_anisotropy: Option<f32>,
+}
+
+impl SamplerConfig {
+    /// Linear, repeating and 16x anisotropic, for textures mapped onto surfaces.
+    pub fn texture() -> Self {
+        Self {
+            min_filter: vk::Filter::LINEAR,
+            mag_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            max_anisotropy: Some(16.0),
+        }
+    }
+
+    /// Linear and clamped to the edge without anisotropy, for screen-space images read at
+    /// about one texel per pixel.
+    pub fn screen() -> Self {
+        Self {
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            max_anisotropy: None,
+            ..Self::texture()
+        }
+    }
+}
+
+/// One `SamplerConfig` per sampler the renderer creates for its own images.
+#[derive(Clone, Copy, Debug)]
+pub struct SamplerConfigs {
+    /// The mesh texture.
+    pub texture: SamplerConfig,
+    pub albedo: SamplerConfig,
+    pub normal: SamplerConfig,
+    /// Also reads depth in the linear depth and SSR passes.
+    pub depth: SamplerConfig,
+    /// The lit image, read by the present pass.
+    pub draw: SamplerConfig,
+}
+
+impl Default for SamplerConfigs {
+    fn default() -> Self {
+        Self {
+            texture: SamplerConfig::texture(),
+            albedo: SamplerConfig::texture(),
+            normal: SamplerConfig::texture(),
+            depth: SamplerConfig::screen(),
+            draw: SamplerConfig::texture(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum BlitFilter {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+impl BlitFilter {
+    pub fn filter(self) -> vk::Filter {
+        match self {
+            Self::Nearest => vk::Filter::NEAREST,
+            Self::Linear => vk::Filter::LINEAR,
+        }
+    }
+}
+
+/// Format of the G-buffer normal attachment. The shaders branch on it through
+/// `normal_encoding.glsl`, which gets it as specialization constant 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalEncoding {
+    /// World space xyz and roughness in R16G16B16A16_SNORM, 8 bytes per pixel.
+    #[default]
+    Rgba16Snorm,
+    /// Octahedral in R16G16_SNORM, 4 bytes per pixel with nearly the same precision. Has no
+    /// room for roughness, so every surface reads back the default 0.3.
+    OctRg16,
+    /// Octahedral at 12 bits per component packed into the RGB of R8G8B8A8_UNORM, roughness
+    /// in alpha. 4 bytes per pixel, normals are off by up to about 0.1 degrees.
+    OctRgb8,
+    /// World space xyz remapped to [0, 1] in A2B10G10R10_UNORM_PACK32, 4 bytes per pixel.
+    /// Roughness only gets the 2 alpha bits.
+    Rgb10A2,
+}
+
+impl NormalEncoding {
+    pub fn format(self) -> vk::Format {
+        match self {
+            Self::Rgba16Snorm => vk::Format::R16G16B16A16_SNORM,
+            Self::OctRg16 => vk::Format::R16G16_SNORM,
+            Self::OctRgb8 => vk::Format::R8G8B8A8_UNORM,
+            Self::Rgb10A2 => vk::Format::A2B10G10R10_UNORM_PACK32,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            Self::Rgba16Snorm => 8,
+            Self::OctRg16 | Self::OctRgb8 | Self::Rgb10A2 => 4,
+        }
+    }
+
+    /// The `NORMAL_ENCODING` value the shaders compare against.
+    pub fn spec_constant(self) -> u32 {
+        self as u32
+    }
+}
+
+/// Tried in order by `GBufferFormats::with_fallbacks` when a chosen format isn't supported.
+const ALBEDO_FALLBACKS: [vk::Format; 2] =
+    [vk::Format::R16G16B16A16_SFLOAT, vk::Format::R8G8B8A8_UNORM];
+const DEPTH_FALLBACKS: [vk::Format; 3] = [
+    vk::Format::D32_SFLOAT,
+    vk::Format::X8_D24_UNORM_PACK32,
+    vk::Format::D16_UNORM,
+];
+const DRAW_FALLBACKS: [vk::Format; 3] = [
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::Format::B10G11R11_UFLOAT_PACK32,
+    vk::Format::R8G8B8A8_UNORM,
+];
+
+/// Formats of the attachments the G-buffer and lighting passes render into. Normals follow
+/// `RenderConfig::normal_encoding`, since the shaders have to decode them. Overlay
+/// `PipelineDescription`s render into the draw and depth images, so they must use `draw`
+/// and `depth` too.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GBufferFormats {
+    /// Linear color, so UNORM rather than SRGB. Alpha holds the metallic factor.
+    pub albedo: vk::Format,
+    /// A depth-only format: D32_SFLOAT, X8_D24_UNORM_PACK32 or D16_UNORM.
+    pub depth: vk::Format,
+    /// The lit image SSR and overlays draw over and the swapchain is blitted from.
+    pub draw: vk::Format,
+}
+
+impl Default for GBufferFormats {
+    fn default() -> Self {
+        Self {
+            albedo: vk::Format::R16G16B16A16_SFLOAT,
+            depth: vk::Format::D32_SFLOAT,
+            draw: vk::Format::R16G16B16A16_SFLOAT,
+        }
+    }
+}
+
+/// Which of the images `GBufferFormats` and `NormalEncoding` pick formats for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GBufferAttachment {
+    Albedo,
+    Normal,
+    Depth,
+    Draw,
+}
+
+impl GBufferAttachment {
+    /// Optimal tiling features the attachment's format needs.
+    fn required_features(self) -> vk::FormatFeatureFlags {
+        let usage = match self {
+            Self::Depth => vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+            Self::Draw => {
+                vk::FormatFeatureFlags::COLOR_ATTACHMENT | vk::FormatFeatureFlags::BLIT_SRC
+            }
+            Self::Albedo | Self::Normal => vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+        };
+        usage | vk::FormatFeatureFlags::SAMPLED_IMAGE
+    }
+}
+
+impl GBufferFormats {
+    /// 4 bytes per pixel albedo for bandwidth limited devices, pair with
+    /// `NormalEncoding::Rgb10A2` or `OctRgb8`.
+    pub fn compact() -> Self {
+        Self {
+            albedo: vk::Format::R8G8B8A8_UNORM,
+            ..Self::default()
+        }
+    }
+
+    /// Swaps each format `physical_device` can't use for the first supported one of a
+    /// fixed list, keeping it when none is. Whatever is left unsupported `validate` reports.
+    pub fn with_fallbacks(
+        self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        let pick = |attachment, format, fallbacks: &[vk::Format]| {
+            if Self::supports(instance, physical_device, attachment, format) {
+                return format;
+            }
+            let fallback = fallbacks
+                .iter()
+                .copied()
+                .find(|&fallback| Self::supports(instance, physical_device, attachment, fallback));
+            match fallback {
+                Some(fallback) => {
+                    log::warn!(
+                        "{:?} format {:?} unsupported, using {:?}",
+                        attachment, format, fallback
+                    );
+                    fallback
+                }
+                None => format,
+            }
+        };
+
+        Self {
+            albedo: pick(GBufferAttachment::Albedo, self.albedo, &ALBEDO_FALLBACKS),
+            depth: pick(GBufferAttachment::Depth, self.depth, &DEPTH_FALLBACKS),
+            draw: pick(GBufferAttachment::Draw, self.draw, &DRAW_FALLBACKS),
+        }
+    }
+
+    /// Checks that the attachments can be rendered to and sampled with optimal tiling, the
+    /// draw image also blitted from, listing every one that can't.
+    pub fn validate(
+        &self,
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        normal_encoding: NormalEncoding,
+    ) -> Result<(), UnsupportedFormatsError> {
+        let formats = [
+            (GBufferAttachment::Albedo, self.albedo),
+            (GBufferAttachment::Normal, normal_encoding.format()),
+            (GBufferAttachment::Depth, self.depth),
+            (GBufferAttachment::Draw, self.draw),
+        ]
+        .into_iter()
+        .filter(|&(attachment, format)| {
+            !Self::supports(instance, physical_device, attachment, format)
+        })
+        .collect::<Vec<_>>();
+
+        if formats.is_empty() {
+            Ok(())
+        } else {
+            Err(UnsupportedFormatsError { formats })
+        }
+    }
+
+    fn supports(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        attachment: GBufferAttachment,
+        format: vk::Format,
+    ) -> bool {
+        // Barriers and views on the depth image only cover the depth aspect
+        if attachment == GBufferAttachment::Depth && !DEPTH_FALLBACKS.contains(&format) {
+            return false;
+        }
+        let features = unsafe {
+            instance
+                .get_physical_device_format_properties(physical_device, format)
+                .optimal_tiling_features
+        };
+        features.contains(attachment.required_features())
+    }
+}
+
+/// What the draw image holds when a frame starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DrawImagePolicy {
+    /// Undefined contents, one draw image per frame in flight.
+    #[default]
+    Discard,
+    /// The previous frame's final image, for history reads like TAA. All frames share one
+    /// draw image, so consecutive frames serialize on it.
+    Preserve,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowConfig {
+    /// Allocate and render the main light's shadow map. Without it the main light is
+    /// unshadowed and a 1x1 stand-in is bound in its place, saving a `resolution` sized map
+    /// (and ESM image) per frame in flight. Caster maps are unaffected.
+    pub enabled: bool,
+    /// Faces culled while rendering the shadow map, independent of the gbuffer pass.
+    pub cull: ShadowCull,
+    /// Shadow map width and height, clamped to the device's maxImageDimension2D.
+    pub resolution: u32,
+    /// Lights made `Light::with_shadow` that get a shadow map of their own, at most
+    /// `MAX_SHADOW_CASTERS`. Each takes a `resolution` sized D32_SFLOAT layer per frame in
+    /// flight and a shadow pass every frame, so keep it small, like 4.
+    pub max_shadow_casters: u32,
+    /// Layers the main light's shadow map is split into along the view, at most
+    /// `MAX_SHADOW_CASCADES`. 1 fits a single map around the whole scene. ESM prefilters a
+    /// single map, so `ShadowTechnique::Esm` always uses 1.
+    pub cascades: u32,
+    /// Blend of the cascade split distances from uniform (0.0) to logarithmic (1.0), see
+    /// `utils::cascade_splits`.
+    pub cascade_split_lambda: f32,
+    /// Rasterizer depth bias of the shadow passes, changeable at runtime through
+    /// `VulkanBackend::set_shadow_depth_bias`.
+    pub depth_bias: ShadowDepthBias,
+}
+
+/// `cmd_set_depth_bias` factors for shadow map rendering: depth is pushed away from the
+/// light by `constant_factor` depth units plus `slope_factor` times the polygon's depth
+/// slope, so surfaces facing the light don't shadow themselves. Shadow maps use the standard
+/// depth range even with `RenderConfig::reverse_z`, so the factors stay positive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowDepthBias {
+    pub constant_factor: f32,
+    pub slope_factor: f32,
+}
+
+impl Default for ShadowDepthBias {
+    fn default() -> Self {
+        Self {
+            constant_factor: 1.25,
+            slope_factor: 1.75,
+        }
+    }
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cull: ShadowCull::default(),
+            resolution: 2048,
+            max_shadow_casters: 0,
+            cascades: 1,
+            cascade_split_lambda: 0.75,
+            depth_bias: ShadowDepthBias::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ShadowCull {
+    /// Only back faces cast, less acne but thin geometry can leak light.
+    #[default]
+    Front,
+    /// Only front faces cast, prone to acne and peter-panning.
+    Back,
+    None,
+}
+
+impl ShadowCull {
+    pub fn cull_mode(self) -> vk::CullModeFlags {
+        match self {
+            ShadowCull::Front => vk::CullModeFlags::FRONT,
+            ShadowCull::Back => vk::CullModeFlags::BACK,
+            ShadowCull::None => vk::CullModeFlags::NONE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SsrConfig {
+    pub max_steps: u32,
+    /// View-space depth range behind a surface that still counts as a hit.
+    pub thickness: f32,
+    /// Surfaces rougher than this get no reflection.
+    pub max_roughness: f32,
+    /// Scales the reflection's blend weight, 0 turns reflections off without dropping the
+    /// pass.
+    pub intensity: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ValidationConfig {
+    /// Makes `VulkanBackend::render_frame` return `RenderError::Validation` once the layer
+    /// reported an ERROR message, so CI runs fail on them.
+    pub fail_on_error: bool,
+}
+
+impl Default for SsrConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: 64,
+            thickness: 0.5,
+            max_roughness: 0.6,
+            intensity: 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SsaoConfig {
+    /// Hemisphere samples per pixel, clamped to 1..=`MAX_SSAO_SAMPLES`.
+    pub samples: u32,
+    /// View-space radius of the hemisphere. Occluders farther away than it fade out.
+    pub radius: f32,
+    /// View-space depth a sample has to be behind the surface by to occlude, against
+    /// flat surfaces occluding themselves.
+    pub bias: f32,
+}
+
+impl Default for SsaoConfig {
+    fn default() -> Self {
+        Self {
+            samples: 16,
+            radius: 0.5,
+            bias: 0.025,
+        }
+    }
+}
+
+/// Changeable at runtime through `VulkanBackend::set_tonemap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TonemapConfig {
+    pub operator: TonemapOperator,
+    /// Linear color is scaled by it before the curve. With `auto_exposure` it scales the
+    /// automatic exposure instead, as exposure compensation.
+    pub exposure: f32,
+    /// Expose for the scene's brightness rather than a fixed `exposure`. Fixed when `None`.
+    pub auto_exposure: Option<AutoExposureConfig>,
+}
+
+impl Default for TonemapConfig {
+    fn default() -> Self {
+        Self {
+            operator: TonemapOperator::default(),
+            exposure: 1.0,
+            auto_exposure: None,
+        }
+    }
+}
+
+/// Exposure following the draw image's brightness. A compute pass counts the pixels in a
+/// histogram of log2 luminance between `min_ev` and `max_ev`, a second one averages it,
+/// ignoring black pixels, and eases the adapted luminance towards that average. The tonemap
+/// pass then exposes the adapted luminance as middle grey, 0.18.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoExposureConfig {
+    /// log2 of the darkest average luminance exposed for, darker scenes stay dark.
+    pub min_ev: f32,
+    /// log2 of the brightest average luminance exposed for, above `min_ev`.
+    pub max_ev: f32,
+    /// How fast the adapted luminance follows, per second. It closes `1 - exp(-speed * dt)`
+    /// of the gap every frame, so 1.0 covers about two thirds of a change in a second.
+    pub adaptation_speed: f32,
+}
+
+impl Default for AutoExposureConfig {
+    fn default() -> Self {
+        Self {
+            min_ev: -8.0,
+            max_ev: 8.0,
+            adaptation_speed: 1.5,
+        }
+    }
+}
+
+/// Changeable at runtime through `VulkanBackend::set_bloom`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomConfig {
+    /// Brightness, the largest linear color component, from which on pixels bloom.
+    pub threshold: f32,
+    /// Width of the soft ramp below `threshold`, 0 for a hard cut.
+    pub knee: f32,
+    /// Scale of the blurred light added back into the draw image.
+    pub intensity: f32,
+}
+
+impl Default for BloomConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.1,
+        }
+    }
+}
+
+/// Changeable at runtime through `VulkanBackend::set_wireframe`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WireframeConfig {
+    /// In pixels, clamped to the device's `lineWidthRange`. Widths other than 1.0 need the
+    /// `wideLines` device feature.
+    pub line_width: f32,
+}
+
+impl Default for WireframeConfig {
+    fn default() -> Self {
+        Self { line_width: 1.0 }
+    }
+}
+
+/// Changeable at runtime through `VulkanBackend::set_fxaa`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FxaaConfig {
+    pub quality: FxaaQuality,
+    /// Local contrast in luma, relative to the brightest neighbor, below which a pixel isn't
+    /// treated as an edge. Lower smooths more edges and more texture detail.
+    pub edge_threshold: f32,
+}
+
+impl Default for FxaaConfig {
+    fn default() -> Self {
+        Self {
+            quality: FxaaQuality::default(),
+            edge_threshold: 0.125,
+        }
+    }
+}
+
+/// How far FXAA searches along an edge for its ends, longer searches smooth shallower
+/// edges.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FxaaQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl FxaaQuality {
+    /// Steps taken in each direction along an edge.
+    pub fn search_steps(self) -> u32 {
+        match self {
+            Self::Low => 4,
+            Self::Medium => 8,
+            Self::High => 12,
+        }
+    }
+}
+
+/// Curve the tonemap pass squeezes HDR color into 0..1 with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TonemapOperator {
+    /// `c / (1 + c)` per channel, never clips but washes out bright colors.
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve, more contrast and a soft shoulder that
+    /// clips past about 7.
+    #[default]
+    Aces,
+}
+
+impl TonemapOperator {
+    /// What `tonemap.comp` branches on.
+    pub fn shader_value(self) -> u32 {
+        match self {
+            Self::Reinhard => 0,
+            Self::Aces => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ShadowTechnique {
+    /// Hardware depth comparison on the shadow map.
+    #[default]
+    Pcf,
+    /// Exponential shadow map, stores `exp(c * depth)` in a prefiltered R32_SFLOAT image.
+    /// Larger `c` darkens contact shadows but overflows f32 past roughly 88.
+    Esm { c: f32 },
+    /// Hardware comparison taps at Poisson disk offsets, softer penumbras than `Pcf`.
+    PoissonPcf(PcfConfig),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PcfConfig {
+    /// Taps per pixel, clamped to the 16 entries of the shader's Poisson disk.
+    pub samples: u32,
+    /// Kernel radius in shadow map texels.
+    pub radius: f32,
+    /// Rotate the disk per pixel to trade banding for noise.
+    pub rotate: bool,
+}
+
+impl Default for PcfConfig {
+    fn default() -> Self {
+        Self {
+            samples: 16,
+            radius: 1.5,
+            rotate: true,
+        }
+    }
+}
@@ -1,3 +1,23 @@
 pub const WINDOW_WIDTH: u32 = 800;
 pub const WINDOW_HEIGHT: u32 = 600;
 pub const MAX_FRAMES_IN_FLIGHT: u32 = 3;
+/// Views rendered per frame, 2 for stereo with the `multiview` feature
+pub const VIEW_COUNT: u32 = if cfg!(feature = "multiview") { 2 } else { 1 };
+/// Upper bound of `ShadowConfig::max_shadow_casters`, the size of `CameraMvpUbo::caster_view_proj`
+pub const MAX_SHADOW_CASTERS: usize = 8;
+/// Upper bound of `ShadowConfig::cascades`, the matrices `FrameData::update_shadow_cascades` takes
+pub const MAX_SHADOW_CASCADES: usize = 4;
+/// Slots of the gbuffer pass's texture array, see `DescriptorManager::register_texture`
+pub const MAX_TEXTURES: usize = 256;
+/// Texture array slot of the scene texture, the default `GPUMeshData::texture_index`
+pub const SCENE_TEXTURE: u32 = 0;
+/// Roughness of `MaterialUbo::default`, `DEFAULT_ROUGHNESS` in normal_encoding.glsl
+pub const DEFAULT_ROUGHNESS: f32 = 0.3;
+/// Normal map slot pushed for meshes without one, `NO_TEXTURE` in shader.frag
+pub const NO_TEXTURE: u32 = u32::MAX;
+/// Shadow test depth bias `LightingUbo::new` starts with
+pub const SHADOW_DEPTH_BIAS: f32 = 0.002;
+/// Upper bound of `SsaoConfig::samples`, the size of `SsaoUbo::kernel`
+pub const MAX_SSAO_SAMPLES: usize = 64;
+/// Most levels of the bloom mip chain, see `bloom::mip_count`
+pub const MAX_BLOOM_MIPS: usize = 8;
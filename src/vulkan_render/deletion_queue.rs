@@ -0,0 +1,36 @@
+use crate::vulkan_render::buffer::AllocatedBuffer;
+use crate::vulkan_render::structs::{Skin, Texture};
+use ash::vk;
+
+/// A resource replaced while frames in flight may still read it.
+pub enum Retired {
+    Buffer(AllocatedBuffer),
+    Texture(Texture),
+    Skin(Skin),
+    CommandBuffer(vk::CommandBuffer),
+}
+
+/// Holds retired resources until the frames that could use them have finished. Follows the
+/// frame ring like `SemaphorePool`; `FrameManager` destroys what `take` returns.
+pub struct DeletionQueue {
+    retired: Vec<Vec<Retired>>,
+}
+
+impl DeletionQueue {
+    pub fn new(frame_count: usize) -> Self {
+        Self {
+            retired: (0..frame_count).map(|_| vec![]).collect(),
+        }
+    }
+
+    /// Queues `resource` behind the fence of `last_submitted`, the frame recorded last. That
+    /// fence also covers every submit before it.
+    pub fn push(&mut self, last_submitted: usize, resource: Retired) {
+        self.retired[last_submitted].push(resource);
+    }
+
+    /// What was queued behind `frame`'s fence. Call after waiting on it.
+    pub fn take(&mut self, frame: usize) -> Vec<Retired> {
+        std::mem::take(&mut self.retired[frame])
+    }
+}
@@ -1,27 +1,81 @@
 use crate::vulkan_render::buffer::AllocatedBuffer;
+use crate::vulkan_render::constants::{MAX_BLOOM_MIPS, MAX_TEXTURES};
 use crate::vulkan_render::device::DeviceInfo;
-use crate::vulkan_render::structs::{CameraMvpUbo, LightingUbo};
+use crate::vulkan_render::exposure::ExposureBuffers;
+use crate::vulkan_render::image_util::AllocatedImage;
+use crate::vulkan_render::structs::{
+    BloomUbo, FrameStats, FxaaUbo, GlobalsUbo, LightingUbo, PostProcessUbo, SsaoUbo, SsrUbo,
+    CAMERA_BUFFER_SIZE,
+};
 use ash::vk::{
     DescriptorPool, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSet,
     DescriptorSetAllocateInfo, DescriptorSetLayout, DescriptorSetLayoutBinding,
     DescriptorSetLayoutCreateFlags, DescriptorSetLayoutCreateInfo, DescriptorType, ImageView,
+    Sampler,
 };
 use ash::{vk, Device};
 use std::{mem, slice};
 
 /// Uniform buffer count: 1 for camera, 1 for lighting
-const GLOBAL_UNIFORM_BUFFER_COUNT: usize = 10;
+const GLOBAL_UNIFORM_BUFFER_COUNT: usize = 11;
 
 /// Dynamic buffer count: 1 for model matrix
 const GLOBAL_DYNAMIC_UNIFORM_BUFFER_COUNT: usize = 10;
 
-/// Global image sampler count: 4 for albedo, normal, depth, shadow-map
-const GLOBAL_IMAGE_SAMPLER_COUNT: usize = 10;
+/// Global image sampler count: 8 for albedo, normal, depth, shadow-map, esm, shadow casters,
+/// ambient occlusion, draw image, 4 for the ssao pass's normal, depth, noise and blur input
+const GLOBAL_IMAGE_SAMPLER_COUNT: usize = 20;
+
+/// Storage image count: 1 for the esm map, 2 for the tonemap pass's draw and output images
+const GLOBAL_STORAGE_IMAGE_COUNT: usize = 10;
+
+/// Storage buffer count: 4 for the cull pass bounds, stats, draws and draw counts, 1 for the
+/// light list, 2 for the tonemap pass's adapted luminance and histogram
+const GLOBAL_STORAGE_BUFFER_COUNT: usize = 7;
+
+/// Descriptor sets per frame: gbuffer, lighting, linear depth, ssao, ssao blur, ssr, esm,
+/// tonemap, present, fxaa, cull, globals
+const GLOBAL_SET_COUNT: usize = 12;
+
+/// Skinned meshes the skin pool has room for, each with one set per frame
+const MAX_SKINNED_MESHES: usize = 16;
+
+/// Storage buffers per skin set: source vertices, joints, skinned vertices
+const SKIN_STORAGE_BUFFER_COUNT: usize = 3;
+
+/// Bloom sets per frame: the threshold, a downsample and an upsample per further mip, and
+/// the add into the draw image
+pub const BLOOM_SET_COUNT: usize = 2 * MAX_BLOOM_MIPS;
 
 pub struct DescriptorManager {
     pub global_pool: DescriptorPool,
     pub global_gbuffer_layout: DescriptorSetLayout,
     pub global_lighting_layout: DescriptorSetLayout,
+    pub global_linear_depth_layout: DescriptorSetLayout,
+    pub global_ssao_layout: DescriptorSetLayout,
+    pub global_ssao_blur_layout: DescriptorSetLayout,
+    pub global_ssr_layout: DescriptorSetLayout,
+    pub global_esm_layout: DescriptorSetLayout,
+    pub global_tonemap_layout: DescriptorSetLayout,
+    pub global_present_layout: DescriptorSetLayout,
+    pub global_fxaa_layout: DescriptorSetLayout,
+    pub global_cull_layout: DescriptorSetLayout,
+    /// `GlobalsUbo`, set 1 of every pipeline layout.
+    pub globals_layout: DescriptorSetLayout,
+    pub skin_pool: DescriptorPool,
+    pub skin_layout: DescriptorSetLayout,
+    /// `BLOOM_SET_COUNT` sets per frame, each binding a source, a destination and the
+    /// `BloomUbo`.
+    pub bloom_pool: DescriptorPool,
+    pub bloom_layout: DescriptorSetLayout,
+    /// Update-after-bind pool of the per-frame texture array sets.
+    pub texture_pool: DescriptorPool,
+    /// `MAX_TEXTURES` partially bound combined image samplers, set 2 of the gbuffer pipeline.
+    pub texture_layout: DescriptorSetLayout,
+    /// What each texture array slot samples, indexed by `register_texture`'s result.
+    textures: Vec<(ImageView, Sampler)>,
+    /// Bumped whenever `textures` changes, so frames know to rewrite their array.
+    texture_generation: u64,
 }
 
 impl DescriptorManager {
@@ -29,14 +83,107 @@ impl DescriptorManager {
         let global_pool = Self::create_global_pool(device, max_frames);
         let global_gbuffer_layout = Self::create_global_gbuffer_layout(device);
         let global_lighting_layout = Self::create_global_lighting_layout(device);
+        let global_linear_depth_layout = Self::create_global_linear_depth_layout(device);
+        let global_ssao_layout = Self::create_global_ssao_layout(device);
+        let global_ssao_blur_layout = Self::create_global_ssao_blur_layout(device);
+        let global_ssr_layout = Self::create_global_ssr_layout(device);
+        let global_esm_layout = Self::create_global_esm_layout(device);
+        let global_tonemap_layout = Self::create_global_tonemap_layout(device);
+        let global_present_layout = Self::create_global_present_layout(device);
+        let global_fxaa_layout = Self::create_global_fxaa_layout(device);
+        let global_cull_layout = Self::create_global_cull_layout(device);
+        let globals_layout = Self::create_globals_layout(device);
+        let skin_pool = Self::create_skin_pool(device, max_frames);
+        let skin_layout = Self::create_skin_layout(device);
+        let bloom_pool = Self::create_bloom_pool(device, max_frames);
+        let bloom_layout = Self::create_bloom_layout(device);
+        let texture_pool = Self::create_texture_pool(device, max_frames);
+        let texture_layout = Self::create_texture_layout(device);
 
         Self {
             global_pool,
             global_gbuffer_layout,
             global_lighting_layout,
+            global_linear_depth_layout,
+            global_ssao_layout,
+            global_ssao_blur_layout,
+            global_ssr_layout,
+            global_esm_layout,
+            global_tonemap_layout,
+            global_present_layout,
+            global_fxaa_layout,
+            global_cull_layout,
+            globals_layout,
+            skin_pool,
+            skin_layout,
+            bloom_pool,
+            bloom_layout,
+            texture_pool,
+            texture_layout,
+            textures: vec![],
+            texture_generation: 0,
+        }
+    }
+
+    /// Destroys the pools, which frees every set allocated from them, and the layouts.
+    pub fn destroy(&self, device: &Device) {
+        let pools = [
+            self.global_pool,
+            self.skin_pool,
+            self.bloom_pool,
+            self.texture_pool,
+        ];
+        let layouts = [
+            self.global_gbuffer_layout,
+            self.global_lighting_layout,
+            self.global_linear_depth_layout,
+            self.global_ssao_layout,
+            self.global_ssao_blur_layout,
+            self.global_ssr_layout,
+            self.global_esm_layout,
+            self.global_tonemap_layout,
+            self.global_present_layout,
+            self.global_fxaa_layout,
+            self.global_cull_layout,
+            self.globals_layout,
+            self.skin_layout,
+            self.bloom_layout,
+            self.texture_layout,
+        ];
+        unsafe {
+            for pool in pools {
+                device.destroy_descriptor_pool(pool, None);
+            }
+            for layout in layouts {
+                device.destroy_descriptor_set_layout(layout, None);
+            }
         }
     }
 
+    /// Adds a texture to the gbuffer pass's texture array and returns the slot meshes pick it
+    /// with, see `GPUMeshData::texture_index`. Frames write it into their array once they're
+    /// idle, `image_view` and `sampler` must outlive every frame sampling it.
+    pub fn register_texture(&mut self, image_view: ImageView, sampler: Sampler) -> u32 {
+        assert!(
+            self.textures.len() < MAX_TEXTURES,
+            "texture array is full, at most {} textures",
+            MAX_TEXTURES
+        );
+        self.textures.push((image_view, sampler));
+        self.texture_generation += 1;
+        (self.textures.len() - 1) as u32
+    }
+
+    /// Points an already registered slot at another texture.
+    pub fn replace_texture(&mut self, index: u32, image_view: ImageView, sampler: Sampler) {
+        self.textures[index as usize] = (image_view, sampler);
+        self.texture_generation += 1;
+    }
+
+    pub fn texture_generation(&self) -> u64 {
+        self.texture_generation
+    }
+
     pub fn create_gbuffer_descriptor_set(&self, device: &Device) -> DescriptorSet {
         let binding = [self.global_gbuffer_layout];
         let allocate_info = DescriptorSetAllocateInfo::default()
@@ -63,6 +210,245 @@ impl DescriptorManager {
         }
     }
 
+    pub fn create_linear_depth_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_linear_depth_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_ssao_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_ssao_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_ssao_blur_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_ssao_blur_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_ssr_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_ssr_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_esm_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_esm_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_tonemap_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_tonemap_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_present_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_present_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_fxaa_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_fxaa_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_cull_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.global_cull_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_globals_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.globals_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.global_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn create_texture_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.texture_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.texture_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    /// `pass_layout` at set 0 followed by the globals layout, for a pipeline layout.
+    pub fn with_globals(&self, pass_layout: DescriptorSetLayout) -> [DescriptorSetLayout; 2] {
+        [pass_layout, self.globals_layout]
+    }
+
+    pub fn create_skin_descriptor_set(&self, device: &Device) -> DescriptorSet {
+        let binding = [self.skin_layout];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.skin_pool)
+            .set_layouts(&binding);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create descriptor set layout")[0]
+        }
+    }
+
+    pub fn free_skin_descriptor_sets(&self, device: &Device, descriptor_sets: &[DescriptorSet]) {
+        unsafe {
+            device
+                .free_descriptor_sets(self.skin_pool, descriptor_sets)
+                .expect("failed to free skin descriptor sets")
+        }
+    }
+
+    pub fn create_bloom_descriptor_sets(&self, device: &Device) -> Vec<DescriptorSet> {
+        let layouts = [self.bloom_layout; BLOOM_SET_COUNT];
+        let allocate_info = DescriptorSetAllocateInfo::default()
+            .descriptor_pool(self.bloom_pool)
+            .set_layouts(&layouts);
+
+        unsafe {
+            device
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("failed to create bloom descriptor sets")
+        }
+    }
+
+    /// A bloom step sampling `source_image_view` and writing `destination_image_view`, both
+    /// in GENERAL.
+    pub fn update_bloom_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        source_image_view: &ImageView,
+        sampler: &vk::Sampler,
+        destination_image_view: &ImageView,
+        bloom_buffer: &AllocatedBuffer,
+        descriptor_set: DescriptorSet,
+    ) {
+        let source_info = [vk::DescriptorImageInfo::default()
+            .image_view(*source_image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .sampler(*sampler)];
+
+        let destination_info = [vk::DescriptorImageInfo::default()
+            .image_view(*destination_image_view)
+            .image_layout(vk::ImageLayout::GENERAL)];
+
+        let bloom_info = vk::DescriptorBufferInfo::default()
+            .buffer(bloom_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<BloomUbo>() as u64);
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(source_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .image_info(destination_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&bloom_info)),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn update_gbuffer_descriptor_set(
         &self,
         device_info: &DeviceInfo,
@@ -76,7 +462,7 @@ impl DescriptorManager {
         let buffer_info = vk::DescriptorBufferInfo::default()
             .buffer(camera_mvp_buffer.buffer)
             .offset(0)
-            .range(mem::size_of::<CameraMvpUbo>() as u64);
+            .range(CAMERA_BUFFER_SIZE as u64);
 
         let dynamic_buffer_info = vk::DescriptorBufferInfo::default()
             .buffer(dynamic_model_buffer.buffer)
@@ -88,9 +474,7 @@ impl DescriptorManager {
             .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .sampler(*texture_sampler)];
 
-        let mut write_descriptor_sets = vec![];
-
-        write_descriptor_sets.push(
+        let write_descriptor_sets = [
             vk::WriteDescriptorSet::default()
                 .dst_set(descriptor_set)
                 .dst_binding(0)
@@ -98,9 +482,6 @@ impl DescriptorManager {
                 .descriptor_type(DescriptorType::UNIFORM_BUFFER)
                 .descriptor_count(1)
                 .buffer_info(slice::from_ref(&buffer_info)),
-        );
-
-        write_descriptor_sets.push(
             vk::WriteDescriptorSet::default()
                 .dst_set(descriptor_set)
                 .dst_binding(1)
@@ -108,9 +489,6 @@ impl DescriptorManager {
                 .descriptor_type(DescriptorType::UNIFORM_BUFFER_DYNAMIC)
                 .descriptor_count(1)
                 .buffer_info(slice::from_ref(&dynamic_buffer_info)),
-        );
-
-        write_descriptor_sets.push(
             vk::WriteDescriptorSet::default()
                 .dst_set(descriptor_set)
                 .dst_binding(2)
@@ -118,9 +496,9 @@ impl DescriptorManager {
                 .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .descriptor_count(1)
                 .image_info(image_info.as_slice()),
-        );
+        ];
 
-        println!("Write descriptor sets");
+        log::debug!("Write descriptor sets");
         unsafe {
             device_info
                 .logical_device
@@ -128,6 +506,43 @@ impl DescriptorManager {
         }
     }
 
+    /// Writes every registered texture into `descriptor_set`'s array. Slots past them stay
+    /// unbound, which the partially bound array allows as long as no mesh picks them.
+    pub fn update_texture_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        descriptor_set: DescriptorSet,
+    ) {
+        if self.textures.is_empty() {
+            return;
+        }
+        let image_infos = self
+            .textures
+            .iter()
+            .map(|&(image_view, sampler)| {
+                vk::DescriptorImageInfo::default()
+                    .image_view(image_view)
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .sampler(sampler)
+            })
+            .collect::<Vec<_>>();
+
+        let write_descriptor_set = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(image_infos.len() as u32)
+            .image_info(&image_infos);
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(slice::from_ref(&write_descriptor_set), &[]);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn update_lighting_descriptor_set(
         &self,
         device_info: &DeviceInfo,
@@ -136,8 +551,9 @@ impl DescriptorManager {
         albedo_sampler: &vk::Sampler,
         normal_image_view: &ImageView,
         normal_sampler: &vk::Sampler,
-        depth_image_view: &ImageView,
+        depth_image: &AllocatedImage,
         depth_sampler: &vk::Sampler,
+        camera_mvp_buffer: &AllocatedBuffer,
         descriptor_set: DescriptorSet,
     ) {
         let buffer_info = vk::DescriptorBufferInfo::default()
@@ -145,6 +561,11 @@ impl DescriptorManager {
             .offset(0)
             .range(mem::size_of::<LightingUbo>() as u64);
 
+        let camera_info = vk::DescriptorBufferInfo::default()
+            .buffer(camera_mvp_buffer.buffer)
+            .offset(0)
+            .range(CAMERA_BUFFER_SIZE as u64);
+
         let albedo_info = [vk::DescriptorImageInfo::default()
             .image_view(*albedo_image_view)
             .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
@@ -155,9 +576,11 @@ impl DescriptorManager {
             .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .sampler(*normal_sampler)];
 
+        // Depth is sampled in the layout `depth_write_to_read_only` leaves it in, and only its
+        // depth aspect may be sampled should it gain a stencil
         let depth_info = [vk::DescriptorImageInfo::default()
-            .image_view(*depth_image_view)
-            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(depth_image.sampled_view())
+            .image_layout(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
             .sampler(*depth_sampler)];
 
         let mut write_descriptor_sets = vec![];
@@ -202,6 +625,16 @@ impl DescriptorManager {
                 .image_info(depth_info.as_slice()),
         );
 
+        write_descriptor_sets.push(
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(5)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&camera_info)),
+        );
+
         unsafe {
             device_info
                 .logical_device
@@ -209,59 +642,1134 @@ impl DescriptorManager {
         }
     }
 
-    fn create_global_pool(device: &Device, max_frames: usize) -> DescriptorPool {
-        let pool_sizes = [
-            DescriptorPoolSize::default()
-                .descriptor_count((max_frames * GLOBAL_UNIFORM_BUFFER_COUNT) as u32)
-                .ty(DescriptorType::UNIFORM_BUFFER),
-            DescriptorPoolSize::default()
-                .descriptor_count((max_frames * GLOBAL_DYNAMIC_UNIFORM_BUFFER_COUNT) as u32)
-                .ty(DescriptorType::UNIFORM_BUFFER_DYNAMIC),
-            DescriptorPoolSize::default()
-                .descriptor_count((max_frames * GLOBAL_IMAGE_SAMPLER_COUNT) as u32)
-                .ty(DescriptorType::COMBINED_IMAGE_SAMPLER),
-        ];
+    /// Shadow bindings of the lighting set, rewritten whenever the shadow map is recreated.
+    /// The caster maps share the shadow map's compare sampler.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_lighting_shadow_descriptors(
+        &self,
+        device_info: &DeviceInfo,
+        shadow_map_image_view: &ImageView,
+        shadow_map_sampler: &vk::Sampler,
+        esm_image_view: &ImageView,
+        esm_sampler: &vk::Sampler,
+        caster_array_view: &ImageView,
+        descriptor_set: DescriptorSet,
+    ) {
+        let shadow_map_info = [vk::DescriptorImageInfo::default()
+            .image_view(*shadow_map_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*shadow_map_sampler)];
 
-        let create_info = DescriptorPoolCreateInfo::default()
+        let caster_info = [vk::DescriptorImageInfo::default()
+            .image_view(*caster_array_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*shadow_map_sampler)];
+
+        let esm_info = [vk::DescriptorImageInfo::default()
+            .image_view(*esm_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*esm_sampler)];
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(4)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(shadow_map_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(6)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(esm_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(8)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(caster_info.as_slice()),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    pub fn update_lighting_light_descriptor(
+        &self,
+        device_info: &DeviceInfo,
+        light_buffer: &AllocatedBuffer,
+        descriptor_set: DescriptorSet,
+    ) {
+        let buffer_info = vk::DescriptorBufferInfo::default()
+            .buffer(light_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let write_descriptor_set = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(7)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .buffer_info(slice::from_ref(&buffer_info));
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(slice::from_ref(&write_descriptor_set), &[]);
+        }
+    }
+
+    /// Ambient occlusion binding of the lighting set, rewritten whenever the AO image is
+    /// recreated. Without SSAO any sampled image will do, the shader never reads it.
+    pub fn update_lighting_ao_descriptor(
+        &self,
+        device_info: &DeviceInfo,
+        ao_image_view: &ImageView,
+        ao_sampler: &vk::Sampler,
+        descriptor_set: DescriptorSet,
+    ) {
+        let ao_info = [vk::DescriptorImageInfo::default()
+            .image_view(*ao_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*ao_sampler)];
+
+        let write_descriptor_set = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(9)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .image_info(ao_info.as_slice());
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(slice::from_ref(&write_descriptor_set), &[]);
+        }
+    }
+
+    pub fn update_linear_depth_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        camera_mvp_buffer: &AllocatedBuffer,
+        depth_image_view: &ImageView,
+        depth_sampler: &vk::Sampler,
+        descriptor_set: DescriptorSet,
+    ) {
+        let buffer_info = vk::DescriptorBufferInfo::default()
+            .buffer(camera_mvp_buffer.buffer)
+            .offset(0)
+            .range(CAMERA_BUFFER_SIZE as u64);
+
+        let depth_info = [vk::DescriptorImageInfo::default()
+            .image_view(*depth_image_view)
+            .image_layout(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
+            .sampler(*depth_sampler)];
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&buffer_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(depth_info.as_slice()),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    /// `noise_sampler` must repeat, the noise is tiled over the screen.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_ssao_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        camera_mvp_buffer: &AllocatedBuffer,
+        ssao_buffer: &AllocatedBuffer,
+        normal_image_view: &ImageView,
+        depth_image_view: &ImageView,
+        sampler: &vk::Sampler,
+        noise_image_view: &ImageView,
+        noise_sampler: &vk::Sampler,
+        descriptor_set: DescriptorSet,
+    ) {
+        let camera_info = vk::DescriptorBufferInfo::default()
+            .buffer(camera_mvp_buffer.buffer)
+            .offset(0)
+            .range(CAMERA_BUFFER_SIZE as u64);
+
+        let ssao_info = vk::DescriptorBufferInfo::default()
+            .buffer(ssao_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<SsaoUbo>() as u64);
+
+        let normal_info = [vk::DescriptorImageInfo::default()
+            .image_view(*normal_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
+
+        let depth_info = [vk::DescriptorImageInfo::default()
+            .image_view(*depth_image_view)
+            .image_layout(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
+
+        let noise_info = [vk::DescriptorImageInfo::default()
+            .image_view(*noise_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*noise_sampler)];
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&camera_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&ssao_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(normal_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(3)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(depth_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(4)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(noise_info.as_slice()),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    pub fn update_ssao_blur_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        ao_image_view: &ImageView,
+        ao_sampler: &vk::Sampler,
+        descriptor_set: DescriptorSet,
+    ) {
+        let ao_info = [vk::DescriptorImageInfo::default()
+            .image_view(*ao_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*ao_sampler)];
+
+        let write_descriptor_set = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .image_info(ao_info.as_slice());
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(slice::from_ref(&write_descriptor_set), &[]);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_ssr_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        camera_mvp_buffer: &AllocatedBuffer,
+        ssr_buffer: &AllocatedBuffer,
+        lit_image_view: &ImageView,
+        normal_image_view: &ImageView,
+        depth_image_view: &ImageView,
+        sampler: &vk::Sampler,
+        descriptor_set: DescriptorSet,
+    ) {
+        let camera_info = vk::DescriptorBufferInfo::default()
+            .buffer(camera_mvp_buffer.buffer)
+            .offset(0)
+            .range(CAMERA_BUFFER_SIZE as u64);
+
+        let ssr_info = vk::DescriptorBufferInfo::default()
+            .buffer(ssr_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<SsrUbo>() as u64);
+
+        let lit_info = [vk::DescriptorImageInfo::default()
+            .image_view(*lit_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
+
+        let normal_info = [vk::DescriptorImageInfo::default()
+            .image_view(*normal_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
+
+        let depth_info = [vk::DescriptorImageInfo::default()
+            .image_view(*depth_image_view)
+            .image_layout(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&camera_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&ssr_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(lit_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(3)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(normal_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(4)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(depth_info.as_slice()),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    pub fn update_esm_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        shadow_map_image_view: &ImageView,
+        shadow_map_sampler: &vk::Sampler,
+        esm_image_view: &ImageView,
+        descriptor_set: DescriptorSet,
+    ) {
+        let shadow_map_info = [vk::DescriptorImageInfo::default()
+            .image_view(*shadow_map_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*shadow_map_sampler)];
+
+        let esm_info = [vk::DescriptorImageInfo::default()
+            .image_view(*esm_image_view)
+            .image_layout(vk::ImageLayout::GENERAL)];
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(shadow_map_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .image_info(esm_info.as_slice()),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    /// Both images are written in GENERAL. `output_image_view` is the output's storage view,
+    /// see `AllocatedImage::storage_view`. The auto-exposure passes use the same set.
+    pub fn update_tonemap_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        draw_image_view: &ImageView,
+        output_image_view: &ImageView,
+        post_process_buffer: &AllocatedBuffer,
+        exposure: &ExposureBuffers,
+        descriptor_set: DescriptorSet,
+    ) {
+        let draw_info = [vk::DescriptorImageInfo::default()
+            .image_view(*draw_image_view)
+            .image_layout(vk::ImageLayout::GENERAL)];
+
+        let output_info = [vk::DescriptorImageInfo::default()
+            .image_view(*output_image_view)
+            .image_layout(vk::ImageLayout::GENERAL)];
+
+        let post_process_info = vk::DescriptorBufferInfo::default()
+            .buffer(post_process_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<PostProcessUbo>() as u64);
+
+        let luminance_info = vk::DescriptorBufferInfo::default()
+            .buffer(exposure.luminance.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let histogram_info = vk::DescriptorBufferInfo::default()
+            .buffer(exposure.histogram.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .image_info(draw_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .image_info(output_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&post_process_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(3)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&luminance_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(4)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&histogram_info)),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    pub fn update_present_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        draw_image_view: &ImageView,
+        draw_sampler: &vk::Sampler,
+        descriptor_set: DescriptorSet,
+    ) {
+        let draw_info = [vk::DescriptorImageInfo::default()
+            .image_view(*draw_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*draw_sampler)];
+
+        let write_descriptor_sets = [vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .image_info(draw_info.as_slice())];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    /// `source_image_view` is the tonemap image's or the draw image's, whichever the
+    /// swapchain would otherwise be written from.
+    pub fn update_fxaa_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        source_image_view: &ImageView,
+        sampler: &vk::Sampler,
+        fxaa_buffer: &AllocatedBuffer,
+        descriptor_set: DescriptorSet,
+    ) {
+        let source_info = [vk::DescriptorImageInfo::default()
+            .image_view(*source_image_view)
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .sampler(*sampler)];
+
+        let fxaa_info = vk::DescriptorBufferInfo::default()
+            .buffer(fxaa_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<FxaaUbo>() as u64);
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .image_info(source_info.as_slice()),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&fxaa_info)),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    pub fn update_cull_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        bounds_buffer: &AllocatedBuffer,
+        stats_buffer: &AllocatedBuffer,
+        draw_buffer: &AllocatedBuffer,
+        draw_count_buffer: &AllocatedBuffer,
+        descriptor_set: DescriptorSet,
+    ) {
+        let bounds_info = vk::DescriptorBufferInfo::default()
+            .buffer(bounds_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let stats_info = vk::DescriptorBufferInfo::default()
+            .buffer(stats_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<FrameStats>() as u64);
+
+        let draw_info = vk::DescriptorBufferInfo::default()
+            .buffer(draw_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let draw_count_info = vk::DescriptorBufferInfo::default()
+            .buffer(draw_count_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&bounds_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&stats_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&draw_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(3)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&draw_count_info)),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    pub fn update_globals_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        globals_buffer: &AllocatedBuffer,
+        descriptor_set: DescriptorSet,
+    ) {
+        let buffer_info = vk::DescriptorBufferInfo::default()
+            .buffer(globals_buffer.buffer)
+            .offset(0)
+            .range(mem::size_of::<GlobalsUbo>() as u64);
+
+        let write_descriptor_set = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .buffer_info(slice::from_ref(&buffer_info));
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(slice::from_ref(&write_descriptor_set), &[]);
+        }
+    }
+
+    pub fn update_skin_descriptor_set(
+        &self,
+        device_info: &DeviceInfo,
+        source_vertex_buffer: &AllocatedBuffer,
+        joint_buffer: &AllocatedBuffer,
+        skinned_vertex_buffer: &AllocatedBuffer,
+        descriptor_set: DescriptorSet,
+    ) {
+        let source_info = vk::DescriptorBufferInfo::default()
+            .buffer(source_vertex_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let joint_info = vk::DescriptorBufferInfo::default()
+            .buffer(joint_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let skinned_info = vk::DescriptorBufferInfo::default()
+            .buffer(skinned_vertex_buffer.buffer)
+            .offset(0)
+            .range(vk::WHOLE_SIZE);
+
+        let write_descriptor_sets = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&source_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&joint_info)),
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(slice::from_ref(&skinned_info)),
+        ];
+
+        unsafe {
+            device_info
+                .logical_device
+                .update_descriptor_sets(&write_descriptor_sets, &[]);
+        }
+    }
+
+    fn create_skin_pool(device: &Device, max_frames: usize) -> DescriptorPool {
+        let pool_sizes = [DescriptorPoolSize::default()
+            .descriptor_count((max_frames * MAX_SKINNED_MESHES * SKIN_STORAGE_BUFFER_COUNT) as u32)
+            .ty(DescriptorType::STORAGE_BUFFER)];
+
+        // Skins are recreated when they outgrow their joint buffers
+        let create_info = DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets((max_frames * MAX_SKINNED_MESHES) as u32)
+            .flags(vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET);
+
+        unsafe {
+            device
+                .create_descriptor_pool(&create_info, None)
+                .expect("Failed to create skin descriptor pool")
+        }
+    }
+
+    fn create_bloom_pool(device: &Device, max_frames: usize) -> DescriptorPool {
+        let set_count = (max_frames * BLOOM_SET_COUNT) as u32;
+        let pool_sizes = [
+            DescriptorPoolSize::default()
+                .descriptor_count(set_count)
+                .ty(DescriptorType::COMBINED_IMAGE_SAMPLER),
+            DescriptorPoolSize::default()
+                .descriptor_count(set_count)
+                .ty(DescriptorType::STORAGE_IMAGE),
+            DescriptorPoolSize::default()
+                .descriptor_count(set_count)
+                .ty(DescriptorType::UNIFORM_BUFFER),
+        ];
+
+        // Allocated once per frame and rewritten on resize
+        let create_info = DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(set_count);
+
+        unsafe {
+            device
+                .create_descriptor_pool(&create_info, None)
+                .expect("Failed to create bloom descriptor pool")
+        }
+    }
+
+    fn create_texture_pool(device: &Device, max_frames: usize) -> DescriptorPool {
+        let pool_sizes = [DescriptorPoolSize::default()
+            .descriptor_count((max_frames * MAX_TEXTURES) as u32)
+            .ty(DescriptorType::COMBINED_IMAGE_SAMPLER)];
+
+        let create_info = DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(max_frames as u32)
+            .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND);
+
+        unsafe {
+            device
+                .create_descriptor_pool(&create_info, None)
+                .expect("Failed to create texture descriptor pool")
+        }
+    }
+
+    fn create_global_pool(device: &Device, max_frames: usize) -> DescriptorPool {
+        let pool_sizes = [
+            DescriptorPoolSize::default()
+                .descriptor_count((max_frames * GLOBAL_UNIFORM_BUFFER_COUNT) as u32)
+                .ty(DescriptorType::UNIFORM_BUFFER),
+            DescriptorPoolSize::default()
+                .descriptor_count((max_frames * GLOBAL_DYNAMIC_UNIFORM_BUFFER_COUNT) as u32)
+                .ty(DescriptorType::UNIFORM_BUFFER_DYNAMIC),
+            DescriptorPoolSize::default()
+                .descriptor_count((max_frames * GLOBAL_IMAGE_SAMPLER_COUNT) as u32)
+                .ty(DescriptorType::COMBINED_IMAGE_SAMPLER),
+            DescriptorPoolSize::default()
+                .descriptor_count((max_frames * GLOBAL_STORAGE_IMAGE_COUNT) as u32)
+                .ty(DescriptorType::STORAGE_IMAGE),
+            DescriptorPoolSize::default()
+                .descriptor_count((max_frames * GLOBAL_STORAGE_BUFFER_COUNT) as u32)
+                .ty(DescriptorType::STORAGE_BUFFER),
+        ];
+
+        let create_info = DescriptorPoolCreateInfo::default()
             .pool_sizes(&pool_sizes)
-            .max_sets(max_frames as u32 * 2);
+            .max_sets((max_frames * GLOBAL_SET_COUNT) as u32);
+
+        unsafe {
+            device
+                .create_descriptor_pool(&create_info, None)
+                .expect("Failed to create global descriptor pool")
+        }
+    }
+
+    fn create_global_lighting_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Lighting Data
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Albedo Texture
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Normal Texture
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Depth Texture
+            DescriptorSetLayoutBinding::default()
+                .binding(3)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Shadow map
+            DescriptorSetLayoutBinding::default()
+                .binding(4)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Camera Data
+            DescriptorSetLayoutBinding::default()
+                .binding(5)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // ESM map
+            DescriptorSetLayoutBinding::default()
+                .binding(6)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Point, spot and extra directional lights
+            DescriptorSetLayoutBinding::default()
+                .binding(7)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Shadow caster maps
+            DescriptorSetLayoutBinding::default()
+                .binding(8)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Ambient occlusion
+            DescriptorSetLayoutBinding::default()
+                .binding(9)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global lighting descriptor set")
+        }
+    }
+
+    fn create_global_linear_depth_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Camera Data
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Depth Texture
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global linear depth descriptor set")
+        }
+    }
+
+    fn create_global_ssao_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Camera Data
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Kernel and parameters
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Normal Texture
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Depth Texture
+            DescriptorSetLayoutBinding::default()
+                .binding(3)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Rotation noise
+            DescriptorSetLayoutBinding::default()
+                .binding(4)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
 
         unsafe {
             device
-                .create_descriptor_pool(&create_info, None)
-                .expect("Failed to create global descriptor pool")
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global ssao descriptor set")
         }
     }
 
-    fn create_global_lighting_layout(device: &Device) -> DescriptorSetLayout {
+    fn create_global_ssao_blur_layout(device: &Device) -> DescriptorSetLayout {
         let bindings = [
-            // Lighting Data
+            // Raw ambient occlusion
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global ssao blur descriptor set")
+        }
+    }
+
+    fn create_global_ssr_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Camera Data
             DescriptorSetLayoutBinding::default()
                 .binding(0)
                 .descriptor_type(DescriptorType::UNIFORM_BUFFER)
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT),
-            // Albedo Texture
+            // SSR parameters
             DescriptorSetLayoutBinding::default()
                 .binding(1)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Lit color
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
                 .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT),
             // Normal Texture
             DescriptorSetLayoutBinding::default()
-                .binding(2)
+                .binding(3)
                 .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT),
             // Depth Texture
             DescriptorSetLayoutBinding::default()
-                .binding(3)
+                .binding(4)
                 .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global ssr descriptor set")
+        }
+    }
+
+    fn create_global_esm_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
             // Shadow map
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // ESM map
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global esm descriptor set")
+        }
+    }
+
+    fn create_global_tonemap_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // HDR draw image
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Tonemapped output
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Exposure and operator
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Adapted luminance, shared by every frame
+            DescriptorSetLayoutBinding::default()
+                .binding(3)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Luminance histogram, shared by every frame
             DescriptorSetLayoutBinding::default()
                 .binding(4)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global tonemap descriptor set")
+        }
+    }
+
+    fn create_global_cull_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // World space mesh bounding spheres
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Frame stats counters
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Indexed indirect draw commands, one per mesh
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Draw counts, one per mesh
+            DescriptorSetLayoutBinding::default()
+                .binding(3)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global cull descriptor set")
+        }
+    }
+
+    fn create_globals_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Time, resolution and jitter
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::ALL),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create globals descriptor set")
+        }
+    }
+
+    fn create_global_present_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Draw image
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
                 .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::FRAGMENT),
@@ -274,7 +1782,125 @@ impl DescriptorManager {
         unsafe {
             device
                 .create_descriptor_set_layout(&create_info, None)
-                .expect("Failed to create global lighting descriptor set")
+                .expect("Failed to create global present descriptor set")
+        }
+    }
+
+    fn create_global_fxaa_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Final image
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            // Edge threshold and search steps
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create global fxaa descriptor set")
+        }
+    }
+
+    fn create_bloom_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Sampled source mip, or the draw image for the threshold
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Destination mip, or the draw image for the final add
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Threshold, knee and intensity
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create bloom descriptor set")
+        }
+    }
+
+    fn create_skin_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [
+            // Source vertices
+            DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Joint matrices
+            DescriptorSetLayoutBinding::default()
+                .binding(1)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            // Skinned vertices
+            DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+        ];
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::empty());
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create skin descriptor set")
+        }
+    }
+
+    /// Update-after-bind, so new slots can be written with the set still bound in frozen
+    /// recordings.
+    fn create_texture_layout(device: &Device) -> DescriptorSetLayout {
+        let bindings = [DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(MAX_TEXTURES as u32)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)];
+        let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND];
+        let mut binding_flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags);
+
+        let create_info = DescriptorSetLayoutCreateInfo::default()
+            .bindings(&bindings)
+            .flags(DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .push_next(&mut binding_flags_info);
+
+        unsafe {
+            device
+                .create_descriptor_set_layout(&create_info, None)
+                .expect("Failed to create texture descriptor set")
         }
     }
 
@@ -286,14 +1912,13 @@ impl DescriptorManager {
                 .descriptor_type(DescriptorType::UNIFORM_BUFFER)
                 .descriptor_count(1)
                 .stage_flags(vk::ShaderStageFlags::VERTEX),
-            // Model offset Texture
+            // Model matrix and material of each mesh, `ModelDynamicUbo`
             DescriptorSetLayoutBinding::default()
                 .binding(1)
                 .descriptor_type(DescriptorType::UNIFORM_BUFFER_DYNAMIC)
                 .descriptor_count(1)
-                .stage_flags(vk::ShaderStageFlags::VERTEX),
-            // TODO: Create Material system
-            // Texture
+                .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT),
+            // Scene texture for overlays, the gbuffer pass samples the texture array instead
             DescriptorSetLayoutBinding::default()
                 .binding(2)
                 .descriptor_type(DescriptorType::COMBINED_IMAGE_SAMPLER)
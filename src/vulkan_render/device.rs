@@ -2,35 +2,104 @@ use std::{collections::HashSet, ffi::CStr};
 
 use ash::vk;
 
+use super::constants::VIEW_COUNT;
+use super::raw_handles::RawDeviceHandles;
 use super::surface::SurfaceInfo;
 
-const DEVICE_EXTENSIONS: [&CStr; 3] = [
-    vk::KHR_SWAPCHAIN_NAME,
-    vk::KHR_SYNCHRONIZATION2_NAME,
-    vk::KHR_DYNAMIC_RENDERING_NAME,
-];
+// synchronization2 is core in 1.3 and only enabled when the device reports it, see `sync`
+const DEVICE_EXTENSIONS: [&CStr; 2] = [vk::KHR_SWAPCHAIN_NAME, vk::KHR_DYNAMIC_RENDERING_NAME];
 
 pub struct DeviceInfo {
     pub _physical_device: vk::PhysicalDevice,
     pub logical_device: ash::Device,
     pub queue_info: QueueInfo,
+    /// Graphics queue pool with RESET_COMMAND_BUFFER, so each frame's buffer is reset on its
+    /// own. Only the thread rendering frames may record from it.
     pub command_pool: vk::CommandPool,
+    /// Queue without graphics that staging uploads are copied on, `None` when the device has
+    /// none and they go through the graphics queue.
+    pub transfer: Option<TransferQueue>,
     pub swapchain_support_details: SwapChainSupportDetails,
     pub min_ubo_alignment: u64,
+    /// Granularity of flushes of memory without HOST_COHERENT, see `AllocatedBuffer::flush`.
+    pub non_coherent_atom_size: u64,
+    /// Whether the `2` barriers and submits are enabled. `sync` falls back to the classic
+    /// commands when they aren't.
+    pub synchronization2: bool,
+    /// Whether `cmd_draw_indexed_indirect_count` can be used, see `RenderConfig::indirect_draws`.
+    pub draw_indirect_count: bool,
+    /// Whether pipelines can rasterize LINE and POINT polygons, see `RenderConfig::wireframe`.
+    pub fill_mode_non_solid: bool,
+    /// Whether lines can be wider than 1.0, up to `line_width_range[1]`.
+    pub wide_lines: bool,
+    pub line_width_range: [f32; 2],
+    /// Nanoseconds per timestamp tick.
+    pub timestamp_period: f32,
+    /// Meaningful bits of the graphics queue's timestamps, 0 when it can't write any.
+    pub timestamp_valid_bits: u32,
+    /// Loader `utils::set_debug_name` goes through.
+    #[cfg(feature = "debug-names")]
+    pub debug_utils: ash::ext::debug_utils::Device,
 }
 
 impl DeviceInfo {
-    pub fn new(instance: &ash::Instance, surface_info: &SurfaceInfo) -> DeviceInfo {
+    /// Destroys the command pools, the transfer queue's included, and the device. Everything
+    /// created from it must already be destroyed.
+    pub fn destroy(&self) {
+        unsafe {
+            self.logical_device
+                .destroy_command_pool(self.command_pool, None);
+            if let Some(transfer) = &self.transfer {
+                self.logical_device
+                    .destroy_command_pool(transfer.command_pool, None);
+            }
+            self.logical_device.destroy_device(None);
+        }
+    }
+
+    /// Raw device and queue handles, see `raw_handles` for the contract.
+    pub fn raw_handles<'a>(&'a self, instance: &'a ash::Instance) -> RawDeviceHandles<'a> {
+        RawDeviceHandles {
+            instance,
+            device: &self.logical_device,
+            physical_device: self._physical_device,
+            graphics_queue: self.queue_info.graphics_queue,
+            graphics_queue_index: self.queue_info.graphics_queue_index,
+            present_queue: self.queue_info.present_queue,
+            present_queue_index: self.queue_info.present_queue_index,
+            command_pool: self.command_pool,
+        }
+    }
+
+    /// `allow_synchronization2` false keeps synchronization2 disabled even when supported.
+    pub fn new(
+        instance: &ash::Instance,
+        surface_info: &SurfaceInfo,
+        allow_synchronization2: bool,
+    ) -> DeviceInfo {
         let physical_device = Self::pick_physical_device(instance, surface_info);
+        let synchronization2 =
+            allow_synchronization2 && Self::supports_synchronization2(instance, physical_device);
+        if !synchronization2 {
+            log::info!("synchronization2 disabled, using classic barriers and submits.");
+        }
+        let draw_indirect_count = Self::supports_draw_indirect_count(instance, physical_device);
+        // Only the debug wireframe needs these, so devices without them are still picked
+        let supported_features = unsafe { instance.get_physical_device_features(physical_device) };
+        let fill_mode_non_solid = supported_features.fill_mode_non_solid == vk::TRUE;
+        let wide_lines = supported_features.wide_lines == vk::TRUE;
         let swapchain_support_details =
             Self::query_swap_chain_support(physical_device, surface_info);
         // We can safely unwrap because
         let queue_indices =
             Self::find_queue_family(instance, physical_device, surface_info).unwrap();
 
+        let transfer_queue_index = Self::find_transfer_queue_family(instance, physical_device);
+
         let mut unique_queue_families = HashSet::new();
         unique_queue_families.insert(queue_indices.graphics_queue_index);
         unique_queue_families.insert(queue_indices.present_queue_index);
+        unique_queue_families.extend(transfer_queue_index);
 
         let queue_priorities = [1.0_f32];
         let mut queue_create_infos = vec![];
@@ -43,16 +112,30 @@ impl DeviceInfo {
             queue_create_infos.push(queue_create_info);
         }
 
-        let physical_device_features =
-            vk::PhysicalDeviceFeatures::default().sampler_anisotropy(true);
+        let physical_device_features = vk::PhysicalDeviceFeatures::default()
+            .sampler_anisotropy(true)
+            .shader_sampled_image_array_dynamic_indexing(true)
+            .fill_mode_non_solid(fill_mode_non_solid)
+            .wide_lines(wide_lines);
 
         let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default()
             .dynamic_rendering(true)
-            .synchronization2(true);
+            .synchronization2(synchronization2);
+
+        // The gbuffer pass's texture array, see `DescriptorManager::register_texture`
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default()
+            .draw_indirect_count(draw_indirect_count)
+            .descriptor_binding_partially_bound(true)
+            .descriptor_binding_sampled_image_update_after_bind(true);
+
+        let mut vulkan_11_features =
+            vk::PhysicalDeviceVulkan11Features::default().multiview(VIEW_COUNT > 1);
 
         let binding = DEVICE_EXTENSIONS.map(|name| name.as_ptr());
         let create_info = vk::DeviceCreateInfo::default()
             .push_next(&mut vulkan_13_features)
+            .push_next(&mut vulkan_12_features)
+            .push_next(&mut vulkan_11_features)
             .queue_create_infos(queue_create_infos.as_slice())
             .enabled_features(&physical_device_features)
             .enabled_extension_names(binding.as_slice());
@@ -68,12 +151,30 @@ impl DeviceInfo {
         let present_queue =
             unsafe { logical_device.get_device_queue(queue_indices.present_queue_index, 0) };
 
-        let command_pool = Self::create_command_pool(&logical_device, &queue_indices);
+        let command_pool =
+            Self::create_command_pool(&logical_device, queue_indices.graphics_queue_index);
+        let transfer = transfer_queue_index.map(|queue_index| TransferQueue {
+            queue_index,
+            queue: unsafe { logical_device.get_device_queue(queue_index, 0) },
+            command_pool: Self::create_command_pool(&logical_device, queue_index),
+        });
 
-        let min_ubo_alignment = unsafe {
+        let (min_ubo_alignment, non_coherent_atom_size, timestamp_period, line_width_range) = unsafe {
             let xc = instance.get_physical_device_properties(physical_device);
-            xc.limits.min_uniform_buffer_offset_alignment as u64
+            (
+                xc.limits.min_uniform_buffer_offset_alignment,
+                xc.limits.non_coherent_atom_size,
+                xc.limits.timestamp_period,
+                xc.limits.line_width_range,
+            )
+        };
+        let timestamp_valid_bits = unsafe {
+            instance.get_physical_device_queue_family_properties(physical_device)
+                [queue_indices.graphics_queue_index as usize]
+                .timestamp_valid_bits
         };
+        #[cfg(feature = "debug-names")]
+        let debug_utils = ash::ext::debug_utils::Device::new(instance, &logical_device);
 
         Self {
             logical_device,
@@ -86,7 +187,18 @@ impl DeviceInfo {
             },
             swapchain_support_details,
             command_pool,
+            transfer,
             min_ubo_alignment,
+            non_coherent_atom_size,
+            synchronization2,
+            draw_indirect_count,
+            fill_mode_non_solid,
+            wide_lines,
+            line_width_range,
+            timestamp_period,
+            timestamp_valid_bits,
+            #[cfg(feature = "debug-names")]
+            debug_utils,
         }
     }
 
@@ -105,7 +217,7 @@ impl DeviceInfo {
                 .expect("Failed to enumerate Physical Devices!")
         };
 
-        println!(
+        log::info!(
             "{} devices (GPU) found with vulkan support.",
             physical_devices.len()
         );
@@ -132,6 +244,70 @@ impl DeviceInfo {
         }
     }
 
+    fn supports_synchronization2(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> bool {
+        let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default();
+        let mut features =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_13_features);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+        vulkan_13_features.synchronization2 == vk::TRUE
+    }
+
+    fn supports_draw_indirect_count(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> bool {
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default();
+        let mut features =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_12_features);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+        vulkan_12_features.draw_indirect_count == vk::TRUE
+    }
+
+    /// `PhysicalDeviceVulkan13Features` may only be queried on devices that support 1.3,
+    /// which every feature check after this one relies on.
+    fn supports_vulkan_1_3(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> bool {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+
+        properties.api_version >= vk::API_VERSION_1_3
+    }
+
+    /// Every pass records with `cmd_begin_rendering`, there are no render pass objects to
+    /// fall back to.
+    fn supports_dynamic_rendering(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> bool {
+        let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default();
+        let mut features =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_13_features);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+        vulkan_13_features.dynamic_rendering == vk::TRUE
+    }
+
+    /// Whether the gbuffer pass's partially bound, update-after-bind texture array works.
+    fn supports_texture_array(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> bool {
+        let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default();
+        let mut features =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut vulkan_12_features);
+        unsafe { instance.get_physical_device_features2(physical_device, &mut features) };
+
+        features
+            .features
+            .shader_sampled_image_array_dynamic_indexing
+            == vk::TRUE
+            && vulkan_12_features.descriptor_binding_partially_bound == vk::TRUE
+            && vulkan_12_features.descriptor_binding_sampled_image_update_after_bind == vk::TRUE
+    }
+
     fn is_physical_device_suitable(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
@@ -147,7 +323,12 @@ impl DeviceInfo {
                 && !swapchain_support_details.present_modes.is_empty();
         }
 
-        indices.is_some() && extensions_supported && swapchain_adequate
+        indices.is_some()
+            && extensions_supported
+            && swapchain_adequate
+            && Self::supports_vulkan_1_3(instance, physical_device)
+            && Self::supports_dynamic_rendering(instance, physical_device)
+            && Self::supports_texture_array(instance, physical_device)
     }
 
     fn find_queue_family(
@@ -194,6 +375,26 @@ impl DeviceInfo {
         })
     }
 
+    /// A transfer-only family first, then one with compute but without graphics.
+    fn find_transfer_queue_family(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Option<u32> {
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        let find = |excluded: vk::QueueFlags| {
+            queue_families.iter().position(|family| {
+                family.queue_count > 0
+                    && family.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                    && !family.queue_flags.intersects(excluded)
+            })
+        };
+
+        find(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+            .or_else(|| find(vk::QueueFlags::GRAPHICS))
+            .map(|index| index as u32)
+    }
+
     fn check_device_extension_support(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
@@ -244,10 +445,10 @@ impl DeviceInfo {
 
     fn create_command_pool(
         logical_device: &ash::Device,
-        queue_family_indeces: &QueueFamiliyIndices,
+        queue_family_index: u32,
     ) -> ash::vk::CommandPool {
         let command_pool_create_info = ash::vk::CommandPoolCreateInfo::default()
-            .queue_family_index(queue_family_indeces.graphics_queue_index)
+            .queue_family_index(queue_family_index)
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
 
         unsafe {
@@ -265,6 +466,13 @@ pub struct QueueInfo {
     pub present_queue: vk::Queue,
 }
 
+/// See `DeviceInfo::transfer`.
+pub struct TransferQueue {
+    pub queue_index: u32,
+    pub queue: vk::Queue,
+    pub command_pool: vk::CommandPool,
+}
+
 #[derive(Default)]
 struct QueueFamiliyIndices {
     graphics_queue_index: u32,
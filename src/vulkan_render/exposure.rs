@@ -0,0 +1,46 @@
+//! Histogram and adapted luminance of the auto-exposure passes, see
+//! `TonemapConfig::auto_exposure`. Shared by every frame, so the adaptation carries over from
+//! one frame to the next.
+
+use crate::vulkan_render::buffer::AllocatedBuffer;
+use crate::vulkan_render::device::DeviceInfo;
+use ash::vk::BufferUsageFlags;
+use ash::{Device, Instance};
+
+/// Log luminance bins of `luminance_histogram.comp`, bin 0 counting the black pixels. One
+/// per invocation of the 16x16 workgroups.
+pub const HISTOGRAM_BINS: usize = 256;
+
+pub struct ExposureBuffers {
+    /// `HISTOGRAM_BINS` pixel counts, which `exposure_average.comp` clears again after
+    /// reading them.
+    pub histogram: AllocatedBuffer,
+    /// The adapted luminance, a single `f32`. 0.0 until the first average, which it then
+    /// takes over without smoothing.
+    pub luminance: AllocatedBuffer,
+}
+
+impl ExposureBuffers {
+    pub fn new(device_info: &DeviceInfo, instance: &Instance) -> Self {
+        Self {
+            histogram: AllocatedBuffer::new_device_local_with_staging(
+                device_info,
+                instance,
+                &[0u32; HISTOGRAM_BINS],
+                BufferUsageFlags::STORAGE_BUFFER,
+            ),
+            luminance: AllocatedBuffer::new_device_local_with_staging(
+                device_info,
+                instance,
+                &[0.0f32],
+                BufferUsageFlags::STORAGE_BUFFER,
+            ),
+        }
+    }
+
+    /// Nothing may still be using the buffers.
+    pub fn destroy(self, device: &Device) {
+        self.histogram.destroy_buffer(device);
+        self.luminance.destroy_buffer(device);
+    }
+}
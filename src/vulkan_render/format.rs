@@ -0,0 +1,296 @@
+use ash::vk;
+
+/// Size of one texel block. Uncompressed formats are 1x1 blocks, BC formats 4x4.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub block_width: u32,
+    pub block_height: u32,
+    pub bytes_per_block: u32,
+}
+
+impl BlockInfo {
+    const fn texel(bytes: u32) -> Self {
+        Self {
+            block_width: 1,
+            block_height: 1,
+            bytes_per_block: bytes,
+        }
+    }
+
+    const fn block_4x4(bytes: u32) -> Self {
+        Self {
+            block_width: 4,
+            block_height: 4,
+            bytes_per_block: bytes,
+        }
+    }
+
+    /// Number of blocks covering `width` x `height` texels, partial blocks included.
+    pub fn block_count(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            width.div_ceil(self.block_width),
+            height.div_ceil(self.block_height),
+        )
+    }
+
+    /// Tightly packed bytes of one row of blocks.
+    pub fn row_pitch(&self, width: u32) -> vk::DeviceSize {
+        width.div_ceil(self.block_width) as vk::DeviceSize * self.bytes_per_block as vk::DeviceSize
+    }
+
+    /// Tightly packed bytes of a `width` x `height` image.
+    pub fn image_size(&self, width: u32, height: u32) -> vk::DeviceSize {
+        self.row_pitch(width) * height.div_ceil(self.block_height) as vk::DeviceSize
+    }
+}
+
+/// Block layout of the formats the crate uploads or allocates. Panics on any other format.
+pub fn texel_block_info(format: vk::Format) -> BlockInfo {
+    match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB => BlockInfo::texel(1),
+        vk::Format::R8G8_UNORM | vk::Format::R8G8_SRGB | vk::Format::D16_UNORM => {
+            BlockInfo::texel(2)
+        }
+        vk::Format::R8G8B8_SRGB | vk::Format::B8G8R8_SRGB => BlockInfo::texel(3),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A8B8G8R8_SRGB_PACK32
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SNORM
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SFLOAT
+        | vk::Format::D32_SFLOAT => BlockInfo::texel(4),
+        vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SNORM
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R32G32_SFLOAT => BlockInfo::texel(8),
+        vk::Format::R32G32B32_SFLOAT => BlockInfo::texel(12),
+        vk::Format::R32G32B32A32_UINT | vk::Format::R32G32B32A32_SFLOAT => BlockInfo::texel(16),
+        vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => BlockInfo::block_4x4(16),
+        _ => panic!("no texel block info for {:?}", format),
+    }
+}
+
+/// Tightly packed `width` x `height` texels of a draw image format as sRGB encoded RGBA8,
+/// alpha kept linear. Values are clamped to 0..1 first, nothing is tonemapped. Panics on
+/// formats other than those in the draw format fallbacks.
+pub fn linear_to_srgb_rgba8(format: vk::Format, bytes: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let info = texel_block_info(format);
+    let row_pitch = info.row_pitch(width) as usize;
+    let texel_size = info.bytes_per_block as usize;
+    assert!(
+        bytes.len() >= row_pitch * height as usize,
+        "{} bytes can't hold a {}x{} {:?} image",
+        bytes.len(),
+        width,
+        height,
+        format
+    );
+
+    let mut rgba8 = Vec::with_capacity(width as usize * height as usize * 4);
+    for row in bytes.chunks_exact(row_pitch).take(height as usize) {
+        for texel in row.chunks_exact(texel_size) {
+            let [r, g, b, a] = decode_linear(format, texel);
+            rgba8.extend([srgb_encode(r), srgb_encode(g), srgb_encode(b), unorm8(a)]);
+        }
+    }
+    rgba8
+}
+
+fn decode_linear(format: vk::Format, texel: &[u8]) -> [f32; 4] {
+    match format {
+        vk::Format::R16G16B16A16_SFLOAT => {
+            [0, 1, 2, 3].map(|i| half_to_f32(u16::from_le_bytes([texel[i * 2], texel[i * 2 + 1]])))
+        }
+        vk::Format::B10G11R11_UFLOAT_PACK32 => {
+            let packed = u32::from_le_bytes([texel[0], texel[1], texel[2], texel[3]]);
+            [
+                unsigned_float(packed & 0x7ff, 6),
+                unsigned_float((packed >> 11) & 0x7ff, 6),
+                unsigned_float(packed >> 22, 5),
+                1.0,
+            ]
+        }
+        vk::Format::R8G8B8A8_UNORM => [0, 1, 2, 3].map(|i| texel[i] as f32 / 255.0),
+        _ => panic!("no linear decode for {:?}", format),
+    }
+}
+
+/// IEEE 754 half precision, subnormals included.
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// The 11 and 10 bit floats of B10G11R11, a 5 bit exponent and no sign.
+fn unsigned_float(bits: u32, mantissa_bits: u32) -> f32 {
+    let exponent = (bits >> mantissa_bits) as i32;
+    let mantissa = (bits & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => mantissa * 2f32.powi(-14),
+        0x1f => f32::INFINITY,
+        _ => (1.0 + mantissa) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn srgb_encode(linear: f32) -> u8 {
+    let linear = if linear.is_nan() {
+        0.0
+    } else {
+        linear.clamp(0.0, 1.0)
+    };
+    let encoded = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    unorm8(encoded)
+}
+
+fn unorm8(value: f32) -> u8 {
+    let value = if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    };
+    (value * 255.0).round() as u8
+}
+
+/// Extent of mip `level`, never smaller than one texel.
+pub fn mip_extent(extent: vk::Extent2D, level: u32) -> vk::Extent2D {
+    vk::Extent2D {
+        width: (extent.width >> level).max(1),
+        height: (extent.height >> level).max(1),
+    }
+}
+
+/// Mips in a full chain for `extent`, down to 1x1.
+pub fn mip_count(extent: vk::Extent2D) -> u32 {
+    u32::BITS - extent.width.max(extent.height).max(1).leading_zeros()
+}
+
+/// Copy regions for mips `0..mip_levels` packed back to back in one staging buffer, and the
+/// buffer size they need. Each mip starts at a multiple of both 4 and the block size, as
+/// `vkCmdCopyBufferToImage` requires.
+pub fn buffer_image_copies(
+    format: vk::Format,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    aspect_mask: vk::ImageAspectFlags,
+) -> (Vec<vk::BufferImageCopy>, vk::DeviceSize) {
+    let info = texel_block_info(format);
+    let alignment = lcm(4, info.bytes_per_block as vk::DeviceSize);
+
+    let mut offset = 0;
+    let regions = (0..mip_levels)
+        .map(|level| {
+            let mip = mip_extent(extent, level);
+            let region = vk::BufferImageCopy::default()
+                .buffer_offset(offset)
+                .buffer_row_length(0)
+                .buffer_image_height(0)
+                .image_subresource(
+                    vk::ImageSubresourceLayers::default()
+                        .aspect_mask(aspect_mask)
+                        .mip_level(level)
+                        .base_array_layer(0)
+                        .layer_count(1),
+                )
+                .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+                .image_extent(vk::Extent3D {
+                    width: mip.width,
+                    height: mip.height,
+                    depth: 1,
+                });
+
+            offset += info
+                .image_size(mip.width, mip.height)
+                .next_multiple_of(alignment);
+            region
+        })
+        .collect();
+
+    (regions, offset)
+}
+
+fn lcm(a: vk::DeviceSize, b: vk::DeviceSize) -> vk::DeviceSize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_to_f32_decodes_normals_subnormals_and_specials() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0x3800), 0.5);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x03ff), 1023.0 * 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(half_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(half_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn rgba16f_is_encoded_clamped_with_linear_alpha() {
+        let halves: [u16; 8] = [
+            0x3c00, 0x3800, 0x0000, 0x3800, 0x4000, 0xbc00, 0x7e00, 0x3c00,
+        ];
+        let bytes = halves
+            .iter()
+            .flat_map(|half| half.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let rgba8 = linear_to_srgb_rgba8(vk::Format::R16G16B16A16_SFLOAT, &bytes, 2, 1);
+
+        assert_eq!(rgba8, [255, 188, 0, 128, 255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn b10g11r11_white_stays_white() {
+        let one_11 = 15 << 6;
+        let one_10 = 15 << 5;
+        let packed: u32 = one_11 | (one_11 << 11) | (one_10 << 22);
+
+        let rgba8 = linear_to_srgb_rgba8(
+            vk::Format::B10G11R11_UFLOAT_PACK32,
+            &packed.to_le_bytes(),
+            1,
+            1,
+        );
+
+        assert_eq!(rgba8, [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn rgba8_rows_are_encoded_in_order() {
+        let bytes = [0, 128, 255, 51, 255, 0, 0, 255];
+
+        let rgba8 = linear_to_srgb_rgba8(vk::Format::R8G8B8A8_UNORM, &bytes, 1, 2);
+
+        assert_eq!(rgba8, [0, 188, 255, 51, 255, 0, 0, 255]);
+    }
+}
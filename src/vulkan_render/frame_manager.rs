@@ -1,118 +1,1157 @@
-use crate::vulkan_render::buffer::AllocatedBuffer;
-use crate::vulkan_render::constants::MAX_FRAMES_IN_FLIGHT;
+use crate::vulkan_render::bloom::{BloomMode, BloomTargets};
+use crate::vulkan_render::buffer::{AllocatedBuffer, BufferInfo};
+use crate::vulkan_render::compute_pipeline::ComputePipelineInfo;
+use crate::vulkan_render::config::{
+    BloomConfig, DrawImagePolicy, FxaaConfig, GBufferFormats, NormalEncoding, RenderConfig,
+    SamplerConfigs, ShadowConfig, ShadowDepthBias, ShadowTechnique, SsrConfig, TonemapConfig,
+    WireframeConfig,
+};
+use crate::vulkan_render::constants::{
+    MAX_SHADOW_CASCADES, MAX_SHADOW_CASTERS, SCENE_TEXTURE, VIEW_COUNT,
+};
+use crate::vulkan_render::deletion_queue::{DeletionQueue, Retired};
 use crate::vulkan_render::descriptor::DescriptorManager;
 use crate::vulkan_render::device::DeviceInfo;
-use crate::vulkan_render::graphics_pipeline::PipelineInfo;
+use crate::vulkan_render::exposure::ExposureBuffers;
+use crate::vulkan_render::format;
+use crate::vulkan_render::graphics_pipeline::{GBufferSettings, PipelineInfo};
 use crate::vulkan_render::image_util::AllocatedImage;
-use crate::vulkan_render::structs::{CameraMvpUbo, LightingUbo, ModelDynamicUbo};
+use crate::vulkan_render::pipeline_cache::PipelineCacheManager;
+use crate::vulkan_render::pipeline_description::PipelineDescription;
+use crate::vulkan_render::present;
+use crate::vulkan_render::raw_handles::RawFrameHandles;
+use crate::vulkan_render::readback_pool::ReadbackPool;
+use crate::vulkan_render::semaphore_pool::SemaphorePool;
+use crate::vulkan_render::worker_pool::WorkerPool;
+use crate::vulkan_render::ssao::SsaoResources;
+use crate::vulkan_render::structs::{
+    CameraMvpUbo, ClearValues, FrameStats, FrameWaitError, FxaaUbo, GlobalsUbo, GpuPass,
+    InstanceData, Light, LightListHeader, LightingUbo, ModelDynamicUbo, MsaaTargets,
+    PipelineCreationReport, PipelineCreationTime, PipelineError, PostProcessUbo, ShadowCasterMaps,
+    Skin, SsrUbo, UnsupportedFormatsError, Vertex, CAMERA_BUFFER_SIZE,
+};
+use crate::vulkan_render::sync;
 use ash::vk::{
     BufferUsageFlags, DescriptorSet, Extent2D, Format, ImageAspectFlags, ImageView,
-    MemoryPropertyFlags, Sampler,
+    MemoryPropertyFlags, Rect2D, Sampler,
 };
 use ash::{vk, Instance};
-use glm::{normalize, vec3, vec3_to_vec4, vec4};
-use std::mem;
+use glm::{normalize, vec3};
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, io, iter, mem, ptr, slice};
 use crate::vulkan_render::utils;
 use crate::vulkan_render::utils::get_buffer_alignment;
 
+const ESM_SHADER: &str = "esm";
+const TONEMAP_SHADER: &str = "tonemap";
+const HISTOGRAM_SHADER: &str = "luminance_histogram";
+const EXPOSURE_SHADER: &str = "exposure_average";
+const BLOOM_SHADER: &str = "bloom";
+const SKIN_SHADER: &str = "skin";
+const CULL_SHADER: &str = "cull";
+/// Six frustum planes and the mesh count
+const CULL_PUSH_CONSTANT_SIZE: u32 = 6 * 16 + 4;
+/// Capacity of each frame's light list
+const MAX_LIGHTS: usize = 64;
+/// Size of the Poisson disk in lighting.frag
+const POISSON_DISK_SIZE: u32 = 16;
+/// How often `poll_shader_changes` looks at the shader files.
+const SHADER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 #[allow(dead_code)]
 pub struct FrameData {
     pub render_semaphore: vk::Semaphore,
-    pub swapchain_semaphore: vk::Semaphore,
     pub render_fence: vk::Fence,
     pub command_buffer: vk::CommandBuffer,
 
     pub camera_mvp_buffer: AllocatedBuffer,
     pub model_dynamic_buffer: AllocatedBuffer,
     pub lighting_buffer: AllocatedBuffer,
+    pub globals_buffer: AllocatedBuffer,
+    /// `LightListHeader` followed by up to `MAX_LIGHTS` `Light`s.
+    pub light_buffer: AllocatedBuffer,
 
     pub descriptor_gbuffer_set: DescriptorSet,
     pub descriptor_lighting_set: DescriptorSet,
+    pub descriptor_linear_depth_set: Option<DescriptorSet>,
+    pub descriptor_ssao_set: Option<DescriptorSet>,
+    pub descriptor_ssao_blur_set: Option<DescriptorSet>,
+    pub descriptor_ssr_set: Option<DescriptorSet>,
+    pub descriptor_esm_set: Option<DescriptorSet>,
+    pub descriptor_tonemap_set: Option<DescriptorSet>,
+    pub descriptor_present_set: Option<DescriptorSet>,
+    pub descriptor_fxaa_set: Option<DescriptorSet>,
+    pub descriptor_globals_set: DescriptorSet,
+    /// The gbuffer pass's texture array, set 2 of its pipeline layout.
+    pub descriptor_texture_set: DescriptorSet,
+    /// `DescriptorManager::texture_generation` the texture array was written at.
+    texture_generation: u64,
 
     pub albedo_image: AllocatedImage,
-    pub albedo_sampler: Sampler,
-
     pub normal_image: AllocatedImage,
-    pub normal_sampler: Sampler,
-
     pub depth_image: AllocatedImage,
-    pub depth_sampler: Sampler,
 
-    pub shadow_map_image: AllocatedImage,
-    pub shadow_map_sampler: Sampler,
+    /// What the G-buffer pass renders into with MSAA, resolving into the images above.
+    pub msaa_targets: Option<MsaaTargets>,
+
+    /// The main light's shadow map, one layer per cascade, `None` without
+    /// `ShadowConfig::enabled`.
+    pub shadow_map: Option<ShadowCasterMaps>,
+
+    /// Prefiltered exp(c * depth) of the shadow map, only with `ShadowTechnique::Esm`.
+    pub esm_image: Option<AllocatedImage>,
+
+    /// Shadow maps of the lights besides the main one, sampled through
+    /// `FrameSamplers::shadow_map`.
+    pub shadow_caster_maps: ShadowCasterMaps,
 
     pub draw_image: AllocatedImage,
+
+    /// Linearized view-space depth, written after the gbuffer pass when enabled.
+    pub linear_depth_image: Option<AllocatedImage>,
+
+    /// Half resolution raw occlusion and its blurred copy the lighting pass samples, only
+    /// with `RenderConfig::ssao`.
+    pub ssao_image: Option<AllocatedImage>,
+    pub ssao_blur_image: Option<AllocatedImage>,
+
+    /// Lit color with reflections applied, copied back into draw_image when SSR is enabled.
+    pub ssr_image: Option<AllocatedImage>,
+    pub ssr_buffer: Option<AllocatedBuffer>,
+
+    /// sRGB encoded 8-bit copy of draw_image the swapchain is blitted from, only with
+    /// `RenderConfig::tonemap`.
+    pub tonemap_image: Option<AllocatedImage>,
+    /// `PostProcessUbo` of the tonemap pass.
+    pub tonemap_buffer: Option<AllocatedBuffer>,
+    /// Mip chain of the bloom pass, only with `RenderConfig::bloom`.
+    pub bloom: Option<BloomTargets>,
+    /// `FxaaUbo` of the FXAA pass, only with `RenderConfig::fxaa`.
+    pub fxaa_buffer: Option<AllocatedBuffer>,
+
+    /// World space bounding spheres and the counters of the cull pass, when enabled.
+    pub cull_bounds_buffer: Option<AllocatedBuffer>,
+    pub cull_stats_buffer: Option<AllocatedBuffer>,
+    /// One `vk::DrawIndexedIndirectCommand` and one `u32` draw count per mesh, the cull pass
+    /// writes the instance and draw counts.
+    pub cull_draw_buffer: Option<AllocatedBuffer>,
+    pub cull_draw_count_buffer: Option<AllocatedBuffer>,
+    pub descriptor_cull_set: Option<DescriptorSet>,
+
+    /// `GpuPass::TIMESTAMP_COUNT` timestamps, with `RenderConfig::gpu_timings`.
+    pub timestamp_pool: Option<vk::QueryPool>,
+    /// One per `RenderConfig::gbuffer_threads`, empty when the G-buffer pass is recorded
+    /// inline.
+    pub gbuffer_secondaries: Vec<SecondaryCommandBuffer>,
+
+    /// Meshes the model and cull buffers hold.
+    pub mesh_capacity: usize,
+    /// `FrameManager::set_scene` calls this frame's resources reflect.
+    scene_generation: u64,
+    /// `draw_image` belongs to every frame with `DrawImagePolicy::Preserve`, so the
+    /// `FrameManager` destroys it instead.
+    shares_draw_image: bool,
+    destroyed: bool,
 }
 
 impl FrameData {
-    pub fn update_camera_mvp_buffer(&mut self, mvp: CameraMvpUbo) {
-        self.camera_mvp_buffer.update_buffer(&[mvp]);
+    /// Raw command buffer and attachment handles, see `raw_handles` for the contract.
+    pub fn raw_handles(&self) -> RawFrameHandles {
+        RawFrameHandles {
+            command_buffer: self.command_buffer,
+            draw_image: self.draw_image.image,
+            draw_image_view: self.draw_image.image_view,
+            depth_image: self.depth_image.image,
+            depth_image_view: self.depth_image.image_view,
+            albedo_image: self.albedo_image.image,
+            albedo_image_view: self.albedo_image.image_view,
+            normal_image: self.normal_image.image,
+            normal_image_view: self.normal_image.image_view,
+        }
+    }
+
+    /// Names the frame's images, buffers and sync objects `frame<index>.<field>`,
+    /// see `utils::set_debug_name`. Images recreated since need naming again.
+    pub fn set_debug_names(&self, device_info: &DeviceInfo, index: usize) {
+        if !cfg!(feature = "debug-names") {
+            return;
+        }
+        let name = |field: &str| format!("frame{}.{}", index, field);
+
+        utils::set_debug_name(
+            device_info,
+            self.render_semaphore,
+            &name("render_semaphore"),
+        );
+        utils::set_debug_name(device_info, self.render_fence, &name("render_fence"));
+        utils::set_debug_name(device_info, self.command_buffer, &name("command_buffer"));
+
+        let buffers = [
+            ("camera_mvp_buffer", Some(&self.camera_mvp_buffer)),
+            ("model_dynamic_buffer", Some(&self.model_dynamic_buffer)),
+            ("lighting_buffer", Some(&self.lighting_buffer)),
+            ("globals_buffer", Some(&self.globals_buffer)),
+            ("light_buffer", Some(&self.light_buffer)),
+            ("ssr_buffer", self.ssr_buffer.as_ref()),
+            ("tonemap_buffer", self.tonemap_buffer.as_ref()),
+            ("fxaa_buffer", self.fxaa_buffer.as_ref()),
+            ("cull_bounds_buffer", self.cull_bounds_buffer.as_ref()),
+            ("cull_stats_buffer", self.cull_stats_buffer.as_ref()),
+            ("cull_draw_buffer", self.cull_draw_buffer.as_ref()),
+            (
+                "cull_draw_count_buffer",
+                self.cull_draw_count_buffer.as_ref(),
+            ),
+        ];
+        for (field, buffer) in buffers {
+            if let Some(buffer) = buffer {
+                utils::set_debug_name(device_info, buffer.buffer, &name(field));
+            }
+        }
+
+        let images = [
+            ("albedo", Some(&self.albedo_image)),
+            ("normal", Some(&self.normal_image)),
+            ("depth", Some(&self.depth_image)),
+            (
+                "shadow_map",
+                self.shadow_map.as_ref().map(|maps| &maps.image),
+            ),
+            ("shadow_caster_maps", Some(&self.shadow_caster_maps.image)),
+            ("esm", self.esm_image.as_ref()),
+            ("linear_depth", self.linear_depth_image.as_ref()),
+            ("ssao", self.ssao_image.as_ref()),
+            ("ssao_blur", self.ssao_blur_image.as_ref()),
+            ("ssr", self.ssr_image.as_ref()),
+            ("tonemap", self.tonemap_image.as_ref()),
+            (
+                "msaa_albedo",
+                self.msaa_targets.as_ref().map(|targets| &targets.albedo),
+            ),
+            (
+                "msaa_normal",
+                self.msaa_targets.as_ref().map(|targets| &targets.normal),
+            ),
+            (
+                "msaa_depth",
+                self.msaa_targets.as_ref().map(|targets| &targets.depth),
+            ),
+        ];
+        for (field, image) in images {
+            if let Some(image) = image {
+                utils::set_debug_name(device_info, image.image, &name(field));
+                utils::set_debug_name(device_info, image.image_view, &name(field));
+            }
+        }
+        if let Some(bloom) = self.bloom.as_ref() {
+            utils::set_debug_name(device_info, bloom.buffer.buffer, &name("bloom_buffer"));
+            for (level, mip) in bloom.mips.iter().enumerate() {
+                let mip_name = name(&format!("bloom_mip{}", level));
+                utils::set_debug_name(device_info, mip.image, &mip_name);
+                utils::set_debug_name(device_info, mip.image_view, &mip_name);
+            }
+        }
+        // Every frame's draw_image is the same one with DrawImagePolicy::Preserve
+        let draw_name = if self.shares_draw_image {
+            "draw".to_string()
+        } else {
+            name("draw")
+        };
+        utils::set_debug_name(device_info, self.draw_image.image, &draw_name);
+        utils::set_debug_name(device_info, self.draw_image.image_view, &draw_name);
+    }
+
+    /// Resets the timestamp pool, before the first `write_timestamp` of a recording.
+    pub fn reset_timestamps(&self, device_info: &DeviceInfo, command_buffer: vk::CommandBuffer) {
+        if let Some(pool) = self.timestamp_pool {
+            unsafe {
+                device_info.logical_device.cmd_reset_query_pool(
+                    command_buffer,
+                    pool,
+                    0,
+                    GpuPass::TIMESTAMP_COUNT,
+                )
+            };
+        }
+    }
+
+    /// Writes timestamp `index` once the commands before it reach `stage`. Does nothing
+    /// without a timestamp pool.
+    pub fn write_timestamp(
+        &self,
+        device_info: &DeviceInfo,
+        command_buffer: vk::CommandBuffer,
+        stage: vk::PipelineStageFlags2,
+        index: u32,
+    ) {
+        if let Some(pool) = self.timestamp_pool {
+            sync::write_timestamp(device_info, command_buffer, stage, pool, index);
+        }
+    }
+
+    /// Resets `command_buffer` and begins recording it again, which its pool allows with
+    /// RESET_COMMAND_BUFFER, see `DeviceInfo::command_pool`. Panics unless `render_fence` is
+    /// signaled, so a buffer still in flight is never reset: wait on it first and only reset
+    /// the fence once recording is done.
+    pub fn begin_command_buffer(&self, device: &ash::Device) {
+        let finished = unsafe { device.get_fence_status(self.render_fence) }
+            .expect("failed to query render fence");
+        assert!(
+            finished,
+            "command buffer reset while its last submit is in flight"
+        );
+
+        unsafe {
+            device
+                .reset_command_buffer(self.command_buffer, vk::CommandBufferResetFlags::empty())
+                .expect("Unable to reset command buffer");
+            device
+                .begin_command_buffer(self.command_buffer, &vk::CommandBufferBeginInfo::default())
+                .expect("failed to begin command buffer");
+        }
+    }
+
+    pub fn end_command_buffer(&self, device: &ash::Device) {
+        unsafe {
+            device
+                .end_command_buffer(self.command_buffer)
+                .expect("failed to end command buffer");
+        }
+    }
+
+    /// Milliseconds between consecutive timestamps, one per `GpuPass::ALL`. Empty without a
+    /// timestamp pool or before the frame's first submit has finished, so only call it after
+    /// waiting on `render_fence`.
+    pub fn resolve_timings(&self, device_info: &DeviceInfo) -> Vec<f32> {
+        let Some(pool) = self.timestamp_pool else {
+            return vec![];
+        };
+        let mut timestamps = [0u64; GpuPass::TIMESTAMP_COUNT as usize];
+        let result = unsafe {
+            device_info.logical_device.get_query_pool_results(
+                pool,
+                0,
+                &mut timestamps,
+                vk::QueryResultFlags::TYPE_64,
+            )
+        };
+        if result.is_err() {
+            return vec![];
+        }
+
+        let mask = u64::MAX >> (64 - device_info.timestamp_valid_bits.min(64));
+        timestamps
+            .windows(2)
+            .map(|pair| {
+                let ticks = pair[1].wrapping_sub(pair[0]) & mask;
+                (ticks as f64 * device_info.timestamp_period as f64 / 1_000_000.0) as f32
+            })
+            .collect()
+    }
+
+    /// Destroys the frame's sync objects, buffers and images. The device must be idle; the
+    /// command buffer and descriptor sets go with their pools, the samplers with the
+    /// `FrameManager`. Later calls do nothing.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        if self.destroyed {
+            return;
+        }
+        self.destroyed = true;
+
+        unsafe {
+            device.destroy_semaphore(self.render_semaphore, None);
+            device.destroy_fence(self.render_fence, None);
+            if let Some(pool) = self.timestamp_pool {
+                device.destroy_query_pool(pool, None);
+            }
+            for secondary in self.gbuffer_secondaries.drain(..) {
+                device.destroy_command_pool(secondary.pool, None);
+            }
+
+            let buffers = [
+                &self.camera_mvp_buffer,
+                &self.model_dynamic_buffer,
+                &self.lighting_buffer,
+                &self.globals_buffer,
+                &self.light_buffer,
+            ];
+            let optional_buffers = [
+                &self.ssr_buffer,
+                &self.tonemap_buffer,
+                &self.fxaa_buffer,
+                &self.cull_bounds_buffer,
+                &self.cull_stats_buffer,
+                &self.cull_draw_buffer,
+                &self.cull_draw_count_buffer,
+            ];
+            for buffer in buffers
+                .into_iter()
+                .chain(optional_buffers.into_iter().flatten())
+            {
+                device.destroy_buffer(buffer.buffer, None);
+                device.free_memory(buffer.buffer_memory, None);
+            }
+        }
+
+        let images = [&self.albedo_image, &self.normal_image, &self.depth_image];
+        let optional_images = [
+            &self.esm_image,
+            &self.linear_depth_image,
+            &self.ssao_image,
+            &self.ssao_blur_image,
+            &self.ssr_image,
+            &self.tonemap_image,
+        ];
+        for image in images
+            .into_iter()
+            .chain(optional_images.into_iter().flatten())
+        {
+            image.clone().destroy_image(device);
+        }
+        if let Some(shadow_map) = self.shadow_map.take() {
+            shadow_map.destroy(device);
+        }
+        self.shadow_caster_maps.clone().destroy(device);
+        if let Some(msaa_targets) = self.msaa_targets.take() {
+            msaa_targets.destroy(device);
+        }
+        if let Some(bloom) = self.bloom.take() {
+            bloom.destroy(device);
+        }
+        if !self.shares_draw_image {
+            self.draw_image.clone().destroy_image(device);
+        }
+    }
+
+    pub fn update_camera_mvp_buffer(&mut self, device_info: &DeviceInfo, mvp: CameraMvpUbo) {
+        self.camera_mvp_buffer.update_buffer(device_info, &[mvp]);
+    }
+
+    /// View-projection of each shadow cascade, stored after the `CameraMvpUbo`. Matrices past
+    /// the shadow map's layers are never read.
+    pub fn update_shadow_cascades(
+        &mut self,
+        device_info: &DeviceInfo,
+        matrices: [Matrix4<f32>; MAX_SHADOW_CASCADES],
+    ) {
+        self.camera_mvp_buffer.update_buffer_at(
+            device_info,
+            mem::size_of::<CameraMvpUbo>() as u64,
+            &matrices,
+        );
+    }
+
+    /// Writes mesh `i`'s entry at `i * alignment`, the dynamic offset its draws bind.
+    pub fn update_model_dynamic_buffer(
+        &mut self,
+        device_info: &DeviceInfo,
+        models: &[ModelDynamicUbo],
+        alignment: u64,
+    ) {
+        let mapped = self.model_dynamic_buffer.mapped_buffer as *mut u8;
+        for (i, model) in models.iter().enumerate() {
+            unsafe {
+                (mapped.add(i * alignment as usize) as *mut ModelDynamicUbo).write(*model);
+            }
+        }
+        self.model_dynamic_buffer
+            .flush(device_info, 0, models.len() as u64 * alignment);
+    }
+
+    pub fn update_globals_buffer(&mut self, device_info: &DeviceInfo, globals: GlobalsUbo) {
+        self.globals_buffer.update_buffer(device_info, &[globals]);
+    }
+
+    pub fn update_light_buffer(&mut self, device_info: &DeviceInfo, lights: &[Light]) {
+        let header = LightListHeader {
+            count: lights.len() as u32,
+            _pad: [0; 3],
+        };
+        self.light_buffer.update_buffer(device_info, &[header]);
+        unsafe {
+            let lights_ptr = (self.light_buffer.mapped_buffer as *mut u8)
+                .add(mem::size_of::<LightListHeader>()) as *mut Light;
+            lights_ptr.copy_from_nonoverlapping(lights.as_ptr(), lights.len());
+        }
+        self.light_buffer.flush(
+            device_info,
+            mem::size_of::<LightListHeader>() as u64,
+            mem::size_of_val(lights) as u64,
+        );
+    }
+
+    pub fn update_lighting_buffer(&mut self, device_info: &DeviceInfo, mvp: LightingUbo) {
+        self.lighting_buffer.update_buffer(device_info, &[mvp]);
+    }
+
+    /// Restricts following draws in `command_buffer` to `rect`, which must lie within
+    /// `render_extent`. The scissor persists until it's set again, including across passes.
+    pub fn set_scissor(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        rect: Rect2D,
+        render_extent: Extent2D,
+    ) {
+        let within_extent = rect.offset.x >= 0
+            && rect.offset.y >= 0
+            && rect.offset.x as u64 + rect.extent.width as u64 <= render_extent.width as u64
+            && rect.offset.y as u64 + rect.extent.height as u64 <= render_extent.height as u64;
+        assert!(
+            within_extent,
+            "scissor {:?} is outside the render extent {:?}",
+            rect, render_extent
+        );
+
+        unsafe {
+            device.cmd_set_scissor(command_buffer, 0, &[rect]);
+        }
+    }
+
+    /// Sets a viewport in `command_buffer` over `render_extent` whose depth range is
+    /// `min_depth..max_depth`, both within 0..=1. The projection still maps depth to 0..1,
+    /// which the viewport then squeezes into the range, so a mesh drawn in 0..0.1 ends up in
+    /// front of the world drawn in 0.1..1. Passes reconstructing positions from depth assume
+    /// the full range.
+    pub fn set_depth_range(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        min_depth: f32,
+        max_depth: f32,
+        render_extent: Extent2D,
+    ) {
+        assert!(
+            (0.0..=1.0).contains(&min_depth) && (0.0..=1.0).contains(&max_depth),
+            "depth range {}..{} is outside 0..=1",
+            min_depth,
+            max_depth
+        );
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: render_extent.width as f32,
+            height: render_extent.height as f32,
+            min_depth,
+            max_depth,
+        };
+        unsafe {
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+        }
+    }
+}
+
+/// A secondary command buffer with a pool of its own, since pools can't be used from several
+/// threads at once. Each worker thread records one while the others record theirs.
+pub struct SecondaryCommandBuffer {
+    pub pool: vk::CommandPool,
+    pub command_buffer: vk::CommandBuffer,
+}
+
+impl SecondaryCommandBuffer {
+    fn new(device_info: &DeviceInfo) -> Self {
+        let device = &device_info.logical_device;
+        let pool_create_info = vk::CommandPoolCreateInfo::default()
+            .queue_family_index(device_info.queue_info.graphics_queue_index)
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT);
+        let pool = unsafe { device.create_command_pool(&pool_create_info, None) }
+            .expect("Unable to create secondary command pool");
+
+        let alloc_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::SECONDARY)
+            .command_buffer_count(1);
+        let command_buffer = unsafe { device.allocate_command_buffers(&alloc_info) }
+            .expect("failed to allocate secondary command buffer")[0];
+
+        Self {
+            pool,
+            command_buffer,
+        }
+    }
+
+    /// Resets the pool and begins continuing the dynamic rendering `rendering` describes.
+    /// Like `FrameData::begin_command_buffer`, only once the frame's fence has signaled.
+    pub fn begin(
+        &self,
+        device: &ash::Device,
+        rendering: &mut vk::CommandBufferInheritanceRenderingInfo,
+    ) {
+        let inheritance_info = vk::CommandBufferInheritanceInfo::default().push_next(rendering);
+        let begin_info = vk::CommandBufferBeginInfo::default()
+            .flags(
+                vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE
+                    | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            )
+            .inheritance_info(&inheritance_info);
+
+        unsafe {
+            device
+                .reset_command_pool(self.pool, vk::CommandPoolResetFlags::empty())
+                .expect("Unable to reset secondary command pool");
+            device
+                .begin_command_buffer(self.command_buffer, &begin_info)
+                .expect("failed to begin secondary command buffer");
+        }
+    }
+
+    pub fn end(&self, device: &ash::Device) {
+        unsafe {
+            device
+                .end_command_buffer(self.command_buffer)
+                .expect("failed to end secondary command buffer");
+        }
+    }
+}
+
+/// Samplers of the renderer's own images. Every frame's descriptors use the same ones, they
+/// don't depend on the images' size.
+#[derive(Clone, Copy)]
+pub struct FrameSamplers {
+    pub albedo: Sampler,
+    pub normal: Sampler,
+    /// Also reads depth in the linear depth, SSAO and SSR passes.
+    pub depth: Sampler,
+    /// Compares against the shadow maps, for the lighting pass's sampler2DShadow.
+    pub shadow_map: Sampler,
+    /// Plain reads of the prefiltered shadow map, only with the ESM pipeline.
+    pub esm: Option<Sampler>,
+    /// The lit image, read by the present and FXAA passes.
+    pub draw: Sampler,
+}
+
+impl FrameSamplers {
+    fn new(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        configs: &SamplerConfigs,
+        esm: bool,
+    ) -> Self {
+        Self {
+            albedo: utils::create_texture_sampler(device_info, instance, &configs.albedo, 1),
+            normal: utils::create_texture_sampler(device_info, instance, &configs.normal, 1),
+            depth: utils::create_texture_sampler(device_info, instance, &configs.depth, 1),
+            shadow_map: utils::create_shadow_sampler(
+                device_info,
+                Some(vk::CompareOp::LESS_OR_EQUAL),
+            ),
+            esm: esm.then(|| utils::create_shadow_sampler(device_info, None)),
+            draw: utils::create_texture_sampler(device_info, instance, &configs.draw, 1),
+        }
     }
 
-    pub fn update_model_dynamic_buffer(&mut self, mvp: Vec<ModelDynamicUbo>) {
-        self.model_dynamic_buffer.update_buffer(&mvp);
+    fn named(&self) -> impl Iterator<Item = (&'static str, Sampler)> {
+        [
+            ("albedo_sampler", self.albedo),
+            ("normal_sampler", self.normal),
+            ("depth_sampler", self.depth),
+            ("shadow_map_sampler", self.shadow_map),
+            ("draw_sampler", self.draw),
+        ]
+        .into_iter()
+        .chain(self.esm.map(|esm| ("esm_sampler", esm)))
     }
 
-    #[allow(dead_code)]
-    pub fn update_lighting_buffer(&mut self, mvp: LightingUbo) {
-        self.lighting_buffer.update_buffer(&[mvp]);
+    /// The ESM binding's sampler. Without ESM the binding is unused and gets the depth
+    /// sampler, like it gets the shadow map in place of the ESM image.
+    fn esm_or_stand_in(&self) -> &Sampler {
+        self.esm.as_ref().unwrap_or(&self.depth)
     }
+
+    /// Nothing may still read through them.
+    fn destroy(&self, device: &ash::Device) {
+        for (_, sampler) in self.named() {
+            unsafe { device.destroy_sampler(sampler, None) };
+        }
+    }
+}
+
+/// A frame's cull pass buffers, see the `cull_` fields of `FrameData`.
+struct CullBuffers {
+    bounds: AllocatedBuffer,
+    stats: AllocatedBuffer,
+    draws: AllocatedBuffer,
+    draw_counts: AllocatedBuffer,
 }
 
 pub struct FrameManager {
     frames: Vec<FrameData>,
     current_frame: usize,
     frame_count: usize,
-    _descriptor_manager: DescriptorManager,
+    descriptor_manager: DescriptorManager,
     pub gbuffer_pipeline: PipelineInfo,
     pub lighting_pipeline: PipelineInfo,
-    pub model_ubo_alignment: u64
+    pub linear_depth_pipeline: Option<PipelineInfo>,
+    /// Occlusion into `FrameData::ssao_image` and its blur into `ssao_blur_image`.
+    pub ssao_pipeline: Option<PipelineInfo>,
+    pub ssao_blur_pipeline: Option<PipelineInfo>,
+    /// Kernel, noise and samplers shared by every frame's SSAO pass.
+    ssao: Option<SsaoResources>,
+    /// Shared by every frame's descriptors.
+    pub samplers: FrameSamplers,
+    /// 1x1 map cleared to 1.0 the lighting descriptors read instead of the shadow map without
+    /// `ShadowConfig::enabled`.
+    shadow_fallback: Option<ShadowCasterMaps>,
+    pub ssr_pipeline: Option<PipelineInfo>,
+    pub shadow_pipeline: PipelineInfo,
+    /// Renders one layer of `FrameData::shadow_caster_maps` per draw, `None` without casters.
+    pub shadow_caster_pipeline: Option<PipelineInfo>,
+    pub esm_pipeline: Option<ComputePipelineInfo>,
+    /// Tonemaps draw_image into `FrameData::tonemap_image`, see `RenderConfig::tonemap`.
+    pub tonemap_pipeline: Option<ComputePipelineInfo>,
+    /// Counts draw_image's pixels into `ExposureBuffers::histogram`, with the tonemap set.
+    pub histogram_pipeline: Option<ComputePipelineInfo>,
+    /// Averages the histogram into `ExposureBuffers::luminance`, a single workgroup.
+    pub exposure_pipeline: Option<ComputePipelineInfo>,
+    /// Shared by every frame's tonemap set, there whenever the tonemap pass is.
+    exposure: Option<ExposureBuffers>,
+    /// Every step of `FrameData::bloom`, picked by a `BloomMode` push constant.
+    pub bloom_pipeline: Option<ComputePipelineInfo>,
+    pub cull_pipeline: Option<ComputePipelineInfo>,
+    /// Whether the G-buffer and overlay passes draw from `FrameData::cull_draw_buffer`.
+    pub indirect_draws: bool,
+    /// Whether the G-buffer pipeline reads `InstanceData` at binding 1, see
+    /// `RenderConfig::instancing`.
+    pub instancing: bool,
+    /// `RenderConfig::cpu_culling`.
+    pub cpu_culling: bool,
+    /// Whether the G-buffer pass draws after a depth prepass, see `set_depth_prepass`.
+    pub depth_prepass: bool,
+    /// Depth-only G-buffer geometry, created with the first `set_depth_prepass(true)`.
+    pub depth_prepass_pipeline: Option<PipelineInfo>,
+    /// `gbuffer_pipeline` testing EQUAL against the prepass depth, created along with it.
+    pub gbuffer_depth_equal_pipeline: Option<PipelineInfo>,
+    /// Whether the G-buffer pass draws lines instead, see `set_wireframe`.
+    pub wireframe: Option<WireframeConfig>,
+    /// `gbuffer_pipeline` drawing lines, created with the first `set_wireframe(Some(_))`.
+    pub gbuffer_wireframe_pipeline: Option<PipelineInfo>,
+    /// A single identity `InstanceData`, bound for meshes without instances of their own.
+    pub identity_instance_buffer: Option<AllocatedBuffer>,
+    /// Created with the first skin, meshes without joints never dispatch it.
+    pub skin_pipeline: Option<ComputePipelineInfo>,
+    /// Fullscreen sRGB encode into the swapchain, `None` when a plain blit is correct, the
+    /// tonemap pass already encoded or the FXAA pass writes the swapchain.
+    pub present_pipeline: Option<PipelineInfo>,
+    /// FXAA from the tonemap or draw image into the swapchain, see `RenderConfig::fxaa`.
+    pub fxaa_pipeline: Option<PipelineInfo>,
+    /// Drawn over the lit image, testing against the gbuffer depth without writing it.
+    pub overlay_pipelines: Vec<PipelineInfo>,
+    pub shadow_technique: ShadowTechnique,
+    pub draw_image_policy: DrawImagePolicy,
+    /// Filter of the final blit into the swapchain.
+    pub present_filter: vk::Filter,
+    /// Distance between the two eyes of the multiview G-buffer pass.
+    pub eye_separation: f32,
+    /// Samples per pixel of the G-buffer pass, TYPE_1 without MSAA.
+    pub msaa_samples: vk::SampleCountFlags,
+    /// Every pipeline is created through it.
+    pipeline_cache: PipelineCacheManager,
+    /// Layout the last frame left its draw image in, what the next frame starts from with
+    /// `DrawImagePolicy::Preserve` and `read_draw_image` reads it in.
+    pub preserved_draw_image_layout: vk::ImageLayout,
+    /// What the G-buffer, draw and depth attachments are cleared to, see
+    /// `VulkanBackend::set_clear_values`.
+    pub clear_values: ClearValues,
+    pub light_direction: Vector3<f32>,
+    /// The latest lighting, which `set_lighting_all` writes to every frame's lighting buffer
+    /// and `set_lighting` to the current one's.
+    lighting: LightingUbo,
+    /// Uploaded to the current frame's light buffer every frame.
+    lights: Vec<Light>,
+    pub model_ubo_alignment: u64,
+    pub semaphore_pool: SemaphorePool,
+    /// Record each frame's `gbuffer_secondaries`, one thread per secondary.
+    pub gbuffer_workers: WorkerPool,
+    pub readback_pool: ReadbackPool,
+    deletion_queue: DeletionQueue,
+    /// Command buffers kept while frozen, replayed for the same frame and swapchain image.
+    frozen: Option<HashMap<(usize, u32), vk::CommandBuffer>>,
+    /// Written into each frame's gbuffer set, the view changes with `set_scene`. Destroyed
+    /// along with the frame manager.
+    texture_sampler: Sampler,
+    texture_image_view: ImageView,
+    scene_generation: u64,
+    globals: GlobalsUbo,
+    /// Size of the frame images, from `new` or `recreate_images`. Kept for `recreate_device`.
+    extent: Extent2D,
+    swapchain_format: vk::SurfaceFormatKHR,
+    mesh_count: usize,
+    config: RenderConfig,
+    /// `current_shader_times` as of the last pipeline load, see `poll_shader_changes`.
+    shader_times: Vec<Option<SystemTime>>,
+    last_shader_poll: Instant,
+    /// `FrameData::resolve_timings` of the frame last waited on.
+    pass_timings: Vec<f32>,
 }
 
 impl FrameManager {
+    /// `max_frames` is clamped to at least 1. Unsupported `RenderConfig::gbuffer_formats` are
+    /// swapped for fallbacks first, see `GBufferFormats::with_fallbacks`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device_info: &DeviceInfo,
         instance: &Instance,
         max_frames: usize,
         extent2d: Extent2D,
+        swapchain_format: vk::SurfaceFormatKHR,
         mesh_count: usize,
         texture_sampler: &Sampler,
         texture_image_view: &ImageView,
-    ) -> Self {
+        config: &RenderConfig,
+    ) -> Result<Self, UnsupportedFormatsError> {
+        let mut config = config.clone();
+        config.gbuffer_formats = config
+            .gbuffer_formats
+            .with_fallbacks(instance, device_info._physical_device);
+        config.gbuffer_formats.validate(
+            instance,
+            device_info._physical_device,
+            config.normal_encoding,
+        )?;
+        let config = &config;
+
+        let max_frames = max_frames.max(1);
         let image_width = extent2d.width;
         let image_height = extent2d.height;
-        let command_buffers = Self::create_command_buffers(device_info);
-        let descriptor_manager = DescriptorManager::new(&device_info.logical_device, max_frames);
+        let command_buffers = Self::create_command_buffers(device_info, max_frames);
+        let mut descriptor_manager =
+            DescriptorManager::new(&device_info.logical_device, max_frames);
+        let scene_texture =
+            descriptor_manager.register_texture(*texture_image_view, *texture_sampler);
+        debug_assert_eq!(scene_texture, SCENE_TEXTURE);
 
         let mut frame_data = vec![];
-        let pipeline = PipelineInfo::new_gbuffer_pipeline(
-            &device_info.logical_device,
-            &descriptor_manager.global_gbuffer_layout,
-        );
-        let lighting_pipeline = PipelineInfo::new_lighing_pipeline(
+        let pipeline_cache =
+            PipelineCacheManager::new(device_info, instance, config.pipeline_cache_path.as_deref());
+        let msaa_samples = Self::clamp_msaa_samples(device_info, instance, config.msaa_samples);
+        let pipeline = Self::create_gbuffer_pipeline(
+            device_info,
+            &descriptor_manager,
+            config,
+            msaa_samples,
+            pipeline_cache.cache,
+        )
+        .expect("Unable to create gbuffer pipeline");
+        let lighting_pipeline = Self::create_lighting_pipeline(
+            device_info,
+            &descriptor_manager,
+            config,
+            pipeline_cache.cache,
+        )
+        .expect("Unable to create lighting pipeline");
+        let linear_depth_pipeline = config.linear_depth.then(|| {
+            PipelineInfo::new_linear_depth_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_linear_depth_layout),
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create linear depth pipeline")
+        });
+        let ssao_pipeline = config.ssao.map(|_| {
+            PipelineInfo::new_ssao_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_ssao_layout),
+                config.normal_encoding,
+                config.reverse_z,
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create SSAO pipeline")
+        });
+        let ssao_blur_pipeline = config.ssao.map(|_| {
+            PipelineInfo::new_ssao_blur_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_ssao_blur_layout),
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create SSAO blur pipeline")
+        });
+        let ssao = config
+            .ssao
+            .map(|ssao_config| SsaoResources::new(device_info, instance, ssao_config));
+        let ssr_pipeline = config.ssr.map(|_| {
+            PipelineInfo::new_ssr_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_ssr_layout),
+                config.gbuffer_formats.draw,
+                config.normal_encoding,
+                config.reverse_z,
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create SSR pipeline")
+        });
+
+        let shadow_pipeline = PipelineInfo::new_shadow_pipeline(
             &device_info.logical_device,
-            &descriptor_manager.global_lighting_layout,
-        );
+            &descriptor_manager.with_globals(descriptor_manager.global_gbuffer_layout),
+            config.shadow.cull.cull_mode(),
+            &config.shader_paths,
+            pipeline_cache.cache,
+        )
+        .expect("Unable to create shadow pipeline");
+        let shadow_caster_pipeline = (config.shadow.max_shadow_casters > 0).then(|| {
+            PipelineInfo::new_shadow_caster_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_gbuffer_layout),
+                config.shadow.cull.cull_mode(),
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create shadow caster pipeline")
+        });
+        let esm_pipeline = match config.shadow_technique {
+            ShadowTechnique::Esm { .. } if config.shadow.enabled => {
+                let push_constant_range = vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(mem::size_of::<f32>() as u32);
+
+                Some(
+                    ComputePipelineInfo::new(
+                        &device_info.logical_device,
+                        &descriptor_manager.with_globals(descriptor_manager.global_esm_layout),
+                        &[push_constant_range],
+                        ESM_SHADER,
+                        &config.shader_paths,
+                        pipeline_cache.cache,
+                    )
+                    .expect("Unable to create ESM pipeline"),
+                )
+            }
+            _ => None,
+        };
+
+        let cull_pipeline = (config.cull_stats || config.indirect_draws).then(|| {
+            let push_constant_range = vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(CULL_PUSH_CONSTANT_SIZE);
+
+            ComputePipelineInfo::new(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_cull_layout),
+                &[push_constant_range],
+                CULL_SHADER,
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create cull pipeline")
+        });
+
+        let tonemap_supported = config.gbuffer_formats.draw == Format::R16G16B16A16_SFLOAT
+            && !Self::storage_usage(device_info, instance, config.gbuffer_formats.draw).is_empty();
+        if config.tonemap.is_some() && !tonemap_supported {
+            log::warn!(
+                "Tonemapping needs an R16G16B16A16_SFLOAT storage draw image, not {:?}",
+                config.gbuffer_formats.draw
+            );
+        }
+        if config.bloom.is_some() && !tonemap_supported {
+            log::warn!(
+                "Bloom needs an R16G16B16A16_SFLOAT storage draw image, not {:?}",
+                config.gbuffer_formats.draw
+            );
+        }
+        let bloom_pipeline = config.bloom.filter(|_| tonemap_supported).map(|_| {
+            ComputePipelineInfo::new(
+                &device_info.logical_device,
+                &[
+                    descriptor_manager.bloom_layout,
+                    descriptor_manager.globals_layout,
+                ],
+                &[vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                    .offset(0)
+                    .size(mem::size_of::<u32>() as u32)],
+                BLOOM_SHADER,
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create bloom pipeline")
+        });
+        let tonemap_pipeline = config.tonemap.filter(|_| tonemap_supported).map(|_| {
+            ComputePipelineInfo::new(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_tonemap_layout),
+                &[],
+                TONEMAP_SHADER,
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create tonemap pipeline")
+        });
+        // Made along with the tonemap pass, so `set_tonemap_all` can turn auto exposure on
+        let [histogram_pipeline, exposure_pipeline] =
+            [HISTOGRAM_SHADER, EXPOSURE_SHADER].map(|shader| {
+                tonemap_pipeline.as_ref().map(|_| {
+                    ComputePipelineInfo::new(
+                        &device_info.logical_device,
+                        &descriptor_manager.with_globals(descriptor_manager.global_tonemap_layout),
+                        &[],
+                        shader,
+                        &config.shader_paths,
+                        pipeline_cache.cache,
+                    )
+                    .expect("Unable to create auto-exposure pipeline")
+                })
+            });
+        let exposure = tonemap_pipeline
+            .as_ref()
+            .map(|_| ExposureBuffers::new(device_info, instance));
+
+        // Tonemapped images are already in the swapchain's encoding, and sampling an sRGB
+        // tonemap image gives the linear values an sRGB swapchain encodes again
+        let fxaa_pipeline = config.fxaa.map(|_| {
+            PipelineInfo::new_fxaa_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_fxaa_layout),
+                swapchain_format.format,
+                tonemap_pipeline.is_none() || present::is_srgb_format(swapchain_format.format),
+                tonemap_pipeline.is_none() && present::needs_manual_srgb_encode(swapchain_format),
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create FXAA pipeline")
+        });
+
+        let present_pipeline = (tonemap_pipeline.is_none()
+            && fxaa_pipeline.is_none()
+            && present::needs_manual_srgb_encode(swapchain_format))
+        .then(|| {
+            PipelineInfo::new_present_pipeline(
+                &device_info.logical_device,
+                &descriptor_manager.with_globals(descriptor_manager.global_present_layout),
+                swapchain_format.format,
+                &config.shader_paths,
+                pipeline_cache.cache,
+            )
+            .expect("Unable to create present pipeline")
+        });
+
+        let identity_instance_buffer = config.instancing.then(|| {
+            AllocatedBuffer::new_device_local_with_staging(
+                device_info,
+                instance,
+                &[InstanceData {
+                    model: Matrix4::identity(),
+                }],
+                BufferUsageFlags::VERTEX_BUFFER,
+            )
+        });
+
+        let shadow_resolution =
+            Self::clamp_shadow_resolution(device_info, instance, config.shadow.resolution);
+        let cascades = Self::cascade_count(&config.shadow, config.shadow_technique) as u32;
+
+        let lighting = config.lighting.unwrap_or_else(|| {
+            LightingUbo::new(normalize(&vec3(-1.0, -1.0, -1.0)), config.shadow_technique)
+        });
+        let light_direction = lighting.light_direction.xyz();
 
         let model_ubo_alignment = get_buffer_alignment::<ModelDynamicUbo>(device_info);
 
-        for frame in 0..max_frames {
-            let command_buffer = command_buffers[frame];
-            let (swapchain_semaphore, render_semaphore, render_fence) =
+        let draw_format = config.gbuffer_formats.draw;
+        let shared_draw_image = (config.draw_image == DrawImagePolicy::Preserve).then(|| {
+            Self::create_draw_image(
+                device_info,
+                instance,
+                image_width,
+                image_height,
+                draw_format,
+            )
+        });
+
+        let samplers = FrameSamplers::new(
+            device_info,
+            instance,
+            &config.samplers,
+            esm_pipeline.is_some(),
+        );
+        let shadow_fallback =
+            (!config.shadow.enabled).then(|| Self::create_shadow_fallback(device_info, instance));
+
+        for command_buffer in command_buffers {
+            let (render_semaphore, render_fence) =
                 Self::create_sync_objects(&device_info.logical_device);
+            let timestamp_pool = (config.gpu_timings && device_info.timestamp_valid_bits > 0)
+                .then(|| Self::create_timestamp_pool(device_info));
 
             let camera_mvp_buffer = Self::create_camera_mvp_buffer(device_info, instance);
             let model_dynamic_buffer =
                 Self::create_model_dynamic_uniform_buffer(device_info, instance, mesh_count, model_ubo_alignment);
-            let lighting_buffer = Self::create_lighting_buffer(device_info, instance);
+            let lighting_buffer = Self::create_lighting_buffer(device_info, instance, lighting);
+            let globals_buffer = Self::create_globals_buffer(device_info, instance);
+            let light_buffer = Self::create_light_buffer(device_info, instance);
+            let globals_descriptor_set =
+                descriptor_manager.create_globals_descriptor_set(&device_info.logical_device);
+            descriptor_manager.update_globals_descriptor_set(
+                device_info,
+                &globals_buffer,
+                globals_descriptor_set,
+            );
+
+            let (albedo_image, normal_image, depth_image) =
+                Self::create_images(device_info, instance, image_width, image_height, config);
+            let msaa_targets = (msaa_samples != vk::SampleCountFlags::TYPE_1).then(|| {
+                Self::create_msaa_targets(device_info, instance, extent2d, msaa_samples, config)
+            });
+            let draw_image = shared_draw_image.clone().unwrap_or_else(|| {
+                Self::create_draw_image(
+                    device_info,
+                    instance,
+                    image_width,
+                    image_height,
+                    draw_format,
+                )
+            });
+            let shadow_map = config.shadow.enabled.then(|| {
+                Self::create_shadow_map(device_info, instance, shadow_resolution, cascades)
+            });
+            let sampled_shadow_map = shadow_map
+                .as_ref()
+                .or(shadow_fallback.as_ref())
+                .expect("no shadow map or stand-in");
+            let shadow_caster_maps = Self::create_shadow_caster_maps(
+                device_info,
+                instance,
+                shadow_resolution,
+                config.shadow.max_shadow_casters,
+            );
+
+            let present_descriptor_set = present_pipeline.as_ref().map(|_| {
+                let descriptor_set =
+                    descriptor_manager.create_present_descriptor_set(&device_info.logical_device);
+                descriptor_manager.update_present_descriptor_set(
+                    device_info,
+                    &draw_image.image_view,
+                    &samplers.draw,
+                    descriptor_set,
+                );
+                descriptor_set
+            });
 
-            let (albedo_image, normal_image, depth_image, shadow_map_image, draw_image) =
-                Self::create_images(device_info, instance, image_width, image_height);
+            let esm_image = esm_pipeline
+                .as_ref()
+                .map(|_| Self::create_esm_image(device_info, instance, shadow_resolution));
 
-            let albedo_sampler = utils::create_texture_sampler(device_info, instance);
-            let normal_sampler = utils::create_texture_sampler(device_info, instance);
-            let depth_sampler = utils::create_texture_sampler(device_info, instance);
-            let shadow_map_sampler = utils::create_texture_sampler(device_info, instance);
+            let esm_descriptor_set = esm_image.as_ref().map(|esm_image| {
+                let descriptor_set =
+                    descriptor_manager.create_esm_descriptor_set(&device_info.logical_device);
+                descriptor_manager.update_esm_descriptor_set(
+                    device_info,
+                    &sampled_shadow_map.layer_views[0],
+                    samplers.esm_or_stand_in(),
+                    &esm_image.image_view,
+                    descriptor_set,
+                );
+                descriptor_set
+            });
 
             let gbuffer_descriptor_set =
                 descriptor_manager.create_gbuffer_descriptor_set(&device_info.logical_device);
@@ -125,6 +1164,9 @@ impl FrameManager {
                 texture_sampler,
                 gbuffer_descriptor_set,
             );
+            let texture_descriptor_set =
+                descriptor_manager.create_texture_descriptor_set(&device_info.logical_device);
+            descriptor_manager.update_texture_descriptor_set(device_info, texture_descriptor_set);
 
             let lighting_descriptor_set =
                 descriptor_manager.create_lighting_descriptor_set(&device_info.logical_device);
@@ -132,103 +1174,2493 @@ impl FrameManager {
                 device_info,
                 &lighting_buffer,
                 &albedo_image.image_view,
-                &albedo_sampler,
+                &samplers.albedo,
                 &normal_image.image_view,
-                &normal_sampler,
-                &depth_image.image_view,
-                &depth_sampler,
+                &samplers.normal,
+                &depth_image,
+                &samplers.depth,
+                &camera_mvp_buffer,
+                lighting_descriptor_set,
+            );
+            descriptor_manager.update_lighting_shadow_descriptors(
+                device_info,
+                &sampled_shadow_map.array_view,
+                &samplers.shadow_map,
+                // The shadow map stands in for the unused ESM binding without ESM
+                esm_image
+                    .as_ref()
+                    .map_or(&sampled_shadow_map.layer_views[0], |esm_image| {
+                        &esm_image.image_view
+                    }),
+                samplers.esm_or_stand_in(),
+                &shadow_caster_maps.array_view,
+                lighting_descriptor_set,
+            );
+            descriptor_manager.update_lighting_light_descriptor(
+                device_info,
+                &light_buffer,
+                lighting_descriptor_set,
+            );
+
+            let linear_depth_image = config.linear_depth.then(|| {
+                Self::create_linear_depth_image(device_info, instance, image_width, image_height)
+            });
+
+            let linear_depth_descriptor_set = linear_depth_image.as_ref().map(|_| {
+                let descriptor_set = descriptor_manager
+                    .create_linear_depth_descriptor_set(&device_info.logical_device);
+                descriptor_manager.update_linear_depth_descriptor_set(
+                    device_info,
+                    &camera_mvp_buffer,
+                    &depth_image.sampled_view(),
+                    &samplers.depth,
+                    descriptor_set,
+                );
+                descriptor_set
+            });
+
+            let ssao_image = ssao
+                .as_ref()
+                .map(|_| Self::create_ssao_image(device_info, instance, extent2d));
+            let ssao_blur_image = ssao
+                .as_ref()
+                .map(|_| Self::create_ssao_image(device_info, instance, extent2d));
+            let ssao_descriptor_set = ssao.as_ref().map(|ssao| {
+                let descriptor_set =
+                    descriptor_manager.create_ssao_descriptor_set(&device_info.logical_device);
+                descriptor_manager.update_ssao_descriptor_set(
+                    device_info,
+                    &camera_mvp_buffer,
+                    &ssao.kernel_buffer,
+                    &normal_image.image_view,
+                    &depth_image.sampled_view(),
+                    &samplers.depth,
+                    &ssao.noise_image.image_view,
+                    &ssao.noise_sampler,
+                    descriptor_set,
+                );
+                descriptor_set
+            });
+            let ssao_blur_descriptor_set =
+                ssao.as_ref()
+                    .zip(ssao_image.as_ref())
+                    .map(|(ssao, ssao_image)| {
+                        let descriptor_set = descriptor_manager
+                            .create_ssao_blur_descriptor_set(&device_info.logical_device);
+                        descriptor_manager.update_ssao_blur_descriptor_set(
+                            device_info,
+                            &ssao_image.image_view,
+                            &ssao.ao_sampler,
+                            descriptor_set,
+                        );
+                        descriptor_set
+                    });
+            // Albedo stands in for the unused AO binding without SSAO
+            let (ao_image_view, ao_sampler) = ssao.as_ref().zip(ssao_blur_image.as_ref()).map_or(
+                (albedo_image.image_view, samplers.albedo),
+                |(ssao, ssao_blur_image)| (ssao_blur_image.image_view, ssao.ao_sampler),
+            );
+            descriptor_manager.update_lighting_ao_descriptor(
+                device_info,
+                &ao_image_view,
+                &ao_sampler,
                 lighting_descriptor_set,
             );
 
+            let ssr_image = config.ssr.map(|_| {
+                Self::create_ssr_image(
+                    device_info,
+                    instance,
+                    image_width,
+                    image_height,
+                    draw_format,
+                )
+            });
+            let ssr_buffer = config
+                .ssr
+                .map(|ssr_config| Self::create_ssr_buffer(device_info, instance, ssr_config));
+
+            let ssr_descriptor_set = match (&ssr_image, &ssr_buffer) {
+                (Some(_), Some(ssr_buffer)) => {
+                    let descriptor_set =
+                        descriptor_manager.create_ssr_descriptor_set(&device_info.logical_device);
+                    descriptor_manager.update_ssr_descriptor_set(
+                        device_info,
+                        &camera_mvp_buffer,
+                        ssr_buffer,
+                        &draw_image.image_view,
+                        &normal_image.image_view,
+                        &depth_image.sampled_view(),
+                        &samplers.depth,
+                        descriptor_set,
+                    );
+                    Some(descriptor_set)
+                }
+                _ => None,
+            };
+
+            let tonemap_image = tonemap_pipeline.as_ref().map(|_| {
+                Self::create_tonemap_image(
+                    device_info,
+                    instance,
+                    image_width,
+                    image_height,
+                    swapchain_format,
+                )
+            });
+            let tonemap_buffer = tonemap_pipeline
+                .as_ref()
+                .zip(config.tonemap)
+                .map(|(_, tonemap)| Self::create_tonemap_buffer(device_info, instance, tonemap));
+            let tonemap_descriptor_set = match (&tonemap_image, &tonemap_buffer, &exposure) {
+                (Some(tonemap_image), Some(tonemap_buffer), Some(exposure)) => {
+                    let descriptor_set = descriptor_manager
+                        .create_tonemap_descriptor_set(&device_info.logical_device);
+                    descriptor_manager.update_tonemap_descriptor_set(
+                        device_info,
+                        &draw_image.image_view,
+                        &tonemap_image
+                            .storage_view
+                            .expect("tonemap image has a storage view"),
+                        tonemap_buffer,
+                        exposure,
+                        descriptor_set,
+                    );
+                    Some(descriptor_set)
+                }
+                _ => None,
+            };
+
+            let bloom = bloom_pipeline.as_ref().zip(config.bloom).map(|(_, bloom)| {
+                BloomTargets::new(
+                    device_info,
+                    instance,
+                    &descriptor_manager,
+                    &draw_image,
+                    bloom,
+                )
+            });
+
+            let fxaa_buffer = fxaa_pipeline
+                .as_ref()
+                .zip(config.fxaa)
+                .map(|(_, fxaa)| Self::create_fxaa_buffer(device_info, instance, fxaa));
+            let fxaa_descriptor_set = fxaa_buffer.as_ref().map(|fxaa_buffer| {
+                let descriptor_set =
+                    descriptor_manager.create_fxaa_descriptor_set(&device_info.logical_device);
+                descriptor_manager.update_fxaa_descriptor_set(
+                    device_info,
+                    &tonemap_image.as_ref().unwrap_or(&draw_image).image_view,
+                    &samplers.draw,
+                    fxaa_buffer,
+                    descriptor_set,
+                );
+                descriptor_set
+            });
+
+            let cull_buffers = cull_pipeline
+                .is_some()
+                .then(|| Self::create_cull_buffers(device_info, instance, mesh_count));
+            let cull_descriptor_set = cull_buffers.as_ref().map(|buffers| {
+                let descriptor_set =
+                    descriptor_manager.create_cull_descriptor_set(&device_info.logical_device);
+                descriptor_manager.update_cull_descriptor_set(
+                    device_info,
+                    &buffers.bounds,
+                    &buffers.stats,
+                    &buffers.draws,
+                    &buffers.draw_counts,
+                    descriptor_set,
+                );
+                descriptor_set
+            });
+            let (cull_bounds_buffer, cull_stats_buffer, cull_draw_buffer, cull_draw_count_buffer) =
+                match cull_buffers {
+                    Some(buffers) => (
+                        Some(buffers.bounds),
+                        Some(buffers.stats),
+                        Some(buffers.draws),
+                        Some(buffers.draw_counts),
+                    ),
+                    None => (None, None, None, None),
+                };
+
             frame_data.push(FrameData {
                 render_semaphore,
-                swapchain_semaphore,
                 render_fence,
                 command_buffer,
                 camera_mvp_buffer,
                 model_dynamic_buffer,
                 lighting_buffer,
+                globals_buffer,
+                light_buffer,
                 descriptor_gbuffer_set: gbuffer_descriptor_set,
                 descriptor_lighting_set: lighting_descriptor_set,
+                descriptor_linear_depth_set: linear_depth_descriptor_set,
+                descriptor_ssao_set: ssao_descriptor_set,
+                descriptor_ssao_blur_set: ssao_blur_descriptor_set,
+                descriptor_ssr_set: ssr_descriptor_set,
+                descriptor_esm_set: esm_descriptor_set,
+                descriptor_tonemap_set: tonemap_descriptor_set,
+                descriptor_present_set: present_descriptor_set,
+                descriptor_fxaa_set: fxaa_descriptor_set,
+                descriptor_globals_set: globals_descriptor_set,
+                descriptor_texture_set: texture_descriptor_set,
+                texture_generation: descriptor_manager.texture_generation(),
                 albedo_image,
-                albedo_sampler,
                 normal_image,
-                normal_sampler,
                 depth_image,
-                depth_sampler,
-                shadow_map_image,
-                shadow_map_sampler,
+                msaa_targets,
+                shadow_map,
+                esm_image,
+                shadow_caster_maps,
                 draw_image,
+                linear_depth_image,
+                ssao_image,
+                ssao_blur_image,
+                ssr_image,
+                ssr_buffer,
+                tonemap_image,
+                tonemap_buffer,
+                bloom,
+                fxaa_buffer,
+                cull_bounds_buffer,
+                cull_stats_buffer,
+                cull_draw_buffer,
+                cull_draw_count_buffer,
+                descriptor_cull_set: cull_descriptor_set,
+                timestamp_pool,
+                gbuffer_secondaries: (0..config.gbuffer_threads)
+                    .filter(|_| config.gbuffer_threads > 1)
+                    .map(|_| SecondaryCommandBuffer::new(device_info))
+                    .collect(),
+                mesh_capacity: mesh_count,
+                scene_generation: 0,
+                shares_draw_image: shared_draw_image.is_some(),
+                destroyed: false,
             });
         }
 
-        Self {
-            _descriptor_manager: descriptor_manager,
+        let mut frame_manager = Self {
+            descriptor_manager,
             frames: frame_data,
             current_frame: 0,
             frame_count: max_frames,
             gbuffer_pipeline: pipeline,
             lighting_pipeline,
-            model_ubo_alignment
+            linear_depth_pipeline,
+            ssao_pipeline,
+            ssao_blur_pipeline,
+            ssao,
+            samplers,
+            shadow_fallback,
+            ssr_pipeline,
+            shadow_pipeline,
+            shadow_caster_pipeline,
+            esm_pipeline,
+            tonemap_pipeline,
+            histogram_pipeline,
+            exposure_pipeline,
+            exposure,
+            bloom_pipeline,
+            cull_pipeline,
+            indirect_draws: config.indirect_draws,
+            instancing: config.instancing,
+            cpu_culling: config.cpu_culling,
+            depth_prepass: false,
+            depth_prepass_pipeline: None,
+            gbuffer_depth_equal_pipeline: None,
+            wireframe: None,
+            gbuffer_wireframe_pipeline: None,
+            identity_instance_buffer,
+            skin_pipeline: None,
+            present_pipeline,
+            fxaa_pipeline,
+            overlay_pipelines: vec![],
+            shadow_technique: config.shadow_technique,
+            draw_image_policy: config.draw_image,
+            present_filter: config.present_filter.filter(),
+            eye_separation: config.eye_separation,
+            msaa_samples,
+            pipeline_cache,
+            preserved_draw_image_layout: vk::ImageLayout::UNDEFINED,
+            clear_values: ClearValues::new(config.reverse_z),
+            light_direction,
+            lighting,
+            lights: vec![],
+            model_ubo_alignment,
+            semaphore_pool: SemaphorePool::new(max_frames),
+            gbuffer_workers: WorkerPool::new(if config.gbuffer_threads > 1 {
+                config.gbuffer_threads
+            } else {
+                0
+            }),
+            readback_pool: ReadbackPool::new(
+                device_info,
+                instance,
+                max_frames,
+                config.readback_size,
+            ),
+            deletion_queue: DeletionQueue::new(max_frames),
+            frozen: None,
+            texture_sampler: *texture_sampler,
+            texture_image_view: *texture_image_view,
+            scene_generation: 0,
+            globals: GlobalsUbo::default(),
+            extent: extent2d,
+            swapchain_format,
+            mesh_count,
+            config: config.clone(),
+            shader_times: vec![],
+            last_shader_poll: Instant::now(),
+            pass_timings: vec![],
+        };
+        frame_manager.shader_times = frame_manager.current_shader_times();
+        frame_manager.set_debug_names(device_info);
+        if config.depth_prepass {
+            frame_manager
+                .set_depth_prepass(device_info, true)
+                .expect("Unable to create depth prepass pipelines");
         }
+        if config.wireframe.is_some() {
+            frame_manager
+                .set_wireframe(device_info, config.wireframe)
+                .expect("Unable to create wireframe pipeline");
+        }
+        Ok(frame_manager)
     }
 
-    pub fn advance_frame(&mut self) {
-        self.current_frame = (self.current_frame + 1) % self.frame_count;
-    }
-
-    pub fn get_current_frame(&self) -> &FrameData {
-        &self.frames[self.current_frame]
+    fn create_gbuffer_pipeline(
+        device_info: &DeviceInfo,
+        descriptor_manager: &DescriptorManager,
+        config: &RenderConfig,
+        msaa_samples: vk::SampleCountFlags,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        let [gbuffer_layout, globals_layout] =
+            descriptor_manager.with_globals(descriptor_manager.global_gbuffer_layout);
+        PipelineInfo::new_gbuffer_pipeline(
+            &device_info.logical_device,
+            &[
+                gbuffer_layout,
+                globals_layout,
+                descriptor_manager.texture_layout,
+            ],
+            GBufferSettings::new(config, msaa_samples),
+            false,
+            &config.shader_paths,
+            pipeline_cache,
+        )
     }
 
-    pub fn get_mut_current_frame(&mut self) -> &mut FrameData {
-        self.frames.get_mut(self.current_frame).unwrap()
+    /// The depth prepass pipeline and the `depth_equal` G-buffer one drawn after it.
+    fn create_depth_prepass_pipelines(
+        device_info: &DeviceInfo,
+        descriptor_manager: &DescriptorManager,
+        config: &RenderConfig,
+        msaa_samples: vk::SampleCountFlags,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<(PipelineInfo, PipelineInfo), PipelineError> {
+        let [gbuffer_layout, globals_layout] =
+            descriptor_manager.with_globals(descriptor_manager.global_gbuffer_layout);
+        PipelineInfo::new_depth_prepass_pipelines(
+            &device_info.logical_device,
+            &[gbuffer_layout, globals_layout],
+            &[
+                gbuffer_layout,
+                globals_layout,
+                descriptor_manager.texture_layout,
+            ],
+            GBufferSettings::new(config, msaa_samples),
+            &config.shader_paths,
+            pipeline_cache,
+        )
     }
 
-    fn create_command_buffers(device_info: &DeviceInfo) -> Vec<vk::CommandBuffer> {
-        let command_buffer_alloc_info = vk::CommandBufferAllocateInfo::default()
-            .command_pool(device_info.command_pool)
-            .level(vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(MAX_FRAMES_IN_FLIGHT);
+    /// Turns the depth prepass on or off, see `RenderConfig::depth_prepass`, creating its
+    /// pipelines the first time it's turned on. Frozen frames are rerecorded.
+    pub fn set_depth_prepass(
+        &mut self,
+        device_info: &DeviceInfo,
+        enabled: bool,
+    ) -> Result<(), PipelineError> {
+        if enabled && self.depth_prepass_pipeline.is_none() {
+            let (prepass_pipeline, depth_equal_pipeline) = Self::create_depth_prepass_pipelines(
+                device_info,
+                &self.descriptor_manager,
+                &self.config,
+                self.msaa_samples,
+                self.pipeline_cache.cache,
+            )?;
 
-        unsafe {
-            device_info
-                .logical_device
-                .allocate_command_buffers(&command_buffer_alloc_info)
-                .expect("failed to allocate command buffer")
+            self.depth_prepass_pipeline = Some(prepass_pipeline);
+            self.gbuffer_depth_equal_pipeline = Some(depth_equal_pipeline);
         }
+
+        self.depth_prepass = enabled;
+        self.config.depth_prepass = enabled;
+        self.invalidate_frozen();
+        Ok(())
     }
 
-    fn create_camera_mvp_buffer(device_info: &DeviceInfo, instance: &Instance) -> AllocatedBuffer {
-        let buffer_size = mem::size_of::<CameraMvpUbo>() as u64;
-        AllocatedBuffer::new(
-            device_info,
-            instance,
-            buffer_size,
-            BufferUsageFlags::UNIFORM_BUFFER,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+    fn create_wireframe_pipeline(
+        device_info: &DeviceInfo,
+        descriptor_manager: &DescriptorManager,
+        config: &RenderConfig,
+        msaa_samples: vk::SampleCountFlags,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        let [gbuffer_layout, globals_layout] =
+            descriptor_manager.with_globals(descriptor_manager.global_gbuffer_layout);
+        PipelineInfo::new_gbuffer_wireframe_pipeline(
+            &device_info.logical_device,
+            &[
+                gbuffer_layout,
+                globals_layout,
+                descriptor_manager.texture_layout,
+            ],
+            GBufferSettings::new(config, msaa_samples),
+            &config.shader_paths,
+            pipeline_cache,
         )
     }
 
-    fn create_lighting_buffer(device_info: &DeviceInfo, instance: &Instance) -> AllocatedBuffer {
-        let buffer_size = mem::size_of::<LightingUbo>() as u64;
-        let mut buffer = AllocatedBuffer::new(
-            device_info,
-            instance,
-            buffer_size,
+    /// Turns the wireframe G-buffer pass on or off, see `RenderConfig::wireframe`, creating
+    /// its pipeline the first time it's turned on. Fails without the `fillModeNonSolid`
+    /// feature, or the `wideLines` one for a line width other than 1.0, leaving the pass as
+    /// it was. Frozen frames are rerecorded.
+    pub fn set_wireframe(
+        &mut self,
+        device_info: &DeviceInfo,
+        wireframe: Option<WireframeConfig>,
+    ) -> Result<(), PipelineError> {
+        if let Some(config) = wireframe {
+            if !device_info.fill_mode_non_solid {
+                return Err(PipelineError::MissingFeature("fillModeNonSolid"));
+            }
+            if config.line_width != 1.0 && !device_info.wide_lines {
+                return Err(PipelineError::MissingFeature("wideLines"));
+            }
+        }
+        if wireframe.is_some() && self.gbuffer_wireframe_pipeline.is_none() {
+            self.gbuffer_wireframe_pipeline = Some(Self::create_wireframe_pipeline(
+                device_info,
+                &self.descriptor_manager,
+                &self.config,
+                self.msaa_samples,
+                self.pipeline_cache.cache,
+            )?);
+        }
+
+        self.wireframe = wireframe;
+        self.config.wireframe = wireframe;
+        self.invalidate_frozen();
+        Ok(())
+    }
+
+    /// Width the wireframe's lines are drawn with, within the device's range, `None` while
+    /// the G-buffer pass draws triangles.
+    pub fn wireframe_line_width(&self, device_info: &DeviceInfo) -> Option<f32> {
+        let [min, max] = device_info.line_width_range;
+        self.wireframe
+            .map(|wireframe| wireframe.line_width.clamp(min, max))
+    }
+
+    fn create_lighting_pipeline(
+        device_info: &DeviceInfo,
+        descriptor_manager: &DescriptorManager,
+        config: &RenderConfig,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        let pcf_samples = match config.shadow_technique {
+            ShadowTechnique::PoissonPcf(pcf_config) => {
+                pcf_config.samples.clamp(1, POISSON_DISK_SIZE)
+            }
+            _ => 1,
+        };
+        PipelineInfo::new_lighing_pipeline(
+            &device_info.logical_device,
+            &descriptor_manager.with_globals(descriptor_manager.global_lighting_layout),
+            pcf_samples,
+            config.lighting_shader.as_deref(),
+            config.gbuffer_formats.draw,
+            config.normal_encoding,
+            config.ssao.is_some(),
+            config.reverse_z,
+            &config.shader_paths,
+            pipeline_cache,
+        )
+    }
+
+    /// Rebuilds the gbuffer, depth prepass, wireframe and lighting pipelines from their shader
+    /// files, once every frame's render fence has signaled, and destroys the old ones. Frozen
+    /// frames are rerecorded. On error the old pipelines stay in use, and
+    /// `poll_shader_changes` doesn't report the same files again until they're edited.
+    pub fn reload_pipelines(&mut self, device_info: &DeviceInfo) -> Result<(), PipelineError> {
+        let device = &device_info.logical_device;
+        self.wait_for_render_fences(device_info);
+
+        // Times are taken first so edits made while compiling are picked up by the next poll,
+        // and kept on failure so a broken shader isn't retried until it changes
+        self.shader_times = self.current_shader_times();
+        let gbuffer_pipeline = Self::create_gbuffer_pipeline(
+            device_info,
+            &self.descriptor_manager,
+            &self.config,
+            self.msaa_samples,
+            self.pipeline_cache.cache,
+        )?;
+        let lighting_pipeline = Self::create_lighting_pipeline(
+            device_info,
+            &self.descriptor_manager,
+            &self.config,
+            self.pipeline_cache.cache,
+        )
+        .inspect_err(|_| gbuffer_pipeline.destroy(device))?;
+        // The depth prepass pipelines share the G-buffer shaders
+        let prepass_pipelines = self
+            .depth_prepass_pipeline
+            .is_some()
+            .then(|| {
+                Self::create_depth_prepass_pipelines(
+                    device_info,
+                    &self.descriptor_manager,
+                    &self.config,
+                    self.msaa_samples,
+                    self.pipeline_cache.cache,
+                )
+            })
+            .transpose()
+            .inspect_err(|_| {
+                gbuffer_pipeline.destroy(device);
+                lighting_pipeline.destroy(device);
+            })?;
+        let wireframe_pipeline = self
+            .gbuffer_wireframe_pipeline
+            .is_some()
+            .then(|| {
+                Self::create_wireframe_pipeline(
+                    device_info,
+                    &self.descriptor_manager,
+                    &self.config,
+                    self.msaa_samples,
+                    self.pipeline_cache.cache,
+                )
+            })
+            .transpose()
+            .inspect_err(|_| {
+                gbuffer_pipeline.destroy(device);
+                lighting_pipeline.destroy(device);
+                for pipeline in prepass_pipelines.iter().flat_map(|(a, b)| [a, b]) {
+                    pipeline.destroy(device);
+                }
+            })?;
+
+        mem::replace(&mut self.gbuffer_pipeline, gbuffer_pipeline).destroy(device);
+        mem::replace(&mut self.lighting_pipeline, lighting_pipeline).destroy(device);
+        if let Some((prepass_pipeline, depth_equal_pipeline)) = prepass_pipelines {
+            for old_pipeline in [
+                self.depth_prepass_pipeline.replace(prepass_pipeline),
+                self.gbuffer_depth_equal_pipeline
+                    .replace(depth_equal_pipeline),
+            ]
+            .into_iter()
+            .flatten()
+            {
+                old_pipeline.destroy(device);
+            }
+        }
+        if let Some(wireframe_pipeline) = wireframe_pipeline {
+            if let Some(old_pipeline) = self.gbuffer_wireframe_pipeline.replace(wireframe_pipeline)
+            {
+                old_pipeline.destroy(device);
+            }
+        }
+        self.invalidate_frozen();
+        Ok(())
+    }
+
+    /// Whether a shader file of the gbuffer or lighting pipeline changed since it was last
+    /// loaded, so `reload_pipelines` would pick up something new. Checks the files at most
+    /// every `SHADER_POLL_INTERVAL`, returning false in between.
+    pub fn poll_shader_changes(&mut self) -> bool {
+        if self.last_shader_poll.elapsed() < SHADER_POLL_INTERVAL {
+            return false;
+        }
+        self.last_shader_poll = Instant::now();
+        self.current_shader_times() != self.shader_times
+    }
+
+    /// Modification times of the gbuffer and lighting shader files, `None` for unreadable ones.
+    fn current_shader_times(&self) -> Vec<Option<SystemTime>> {
+        [&self.gbuffer_pipeline, &self.lighting_pipeline]
+            .into_iter()
+            .flat_map(|pipeline| {
+                let description = pipeline.describe();
+                iter::once(&description.vertex_shader).chain(&description.fragment_shader)
+            })
+            .map(|shader| {
+                fs::metadata(self.config.shader_paths.resolve(shader))
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Rebuilds every per-frame resource, pipeline and descriptor set on a fresh `device_info`,
+    /// e.g. after `ERROR_DEVICE_LOST`. The old handles must already be gone through `destroy`,
+    /// on the old device. The current shadow resolution, overlay pipelines, globals and
+    /// clear values carry over.
+    pub fn recreate_device(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        texture_sampler: &Sampler,
+        texture_image_view: &ImageView,
+    ) {
+        let overlay_descriptions = self
+            .overlay_pipelines
+            .iter()
+            .map(|pipeline| pipeline.describe().clone())
+            .collect::<Vec<_>>();
+        let globals = self.globals;
+        let lighting = self.lighting;
+        let lights = mem::take(&mut self.lights);
+        let clear_values = self.clear_values;
+
+        *self = Self::new(
+            device_info,
+            instance,
+            self.frame_count,
+            self.extent,
+            self.swapchain_format,
+            self.mesh_count,
+            texture_sampler,
+            texture_image_view,
+            &self.config.clone(),
+        )
+        .expect("G-buffer formats unsupported by the new device");
+        self.globals = globals;
+        self.lights = lights;
+        self.clear_values = clear_values;
+        self.set_lighting_all(device_info, lighting);
+
+        for description in overlay_descriptions {
+            self.add_overlay_pipeline(device_info, description)
+                .expect("Unable to recreate overlay pipeline");
+        }
+    }
+
+    /// Recreates every frame's shadow maps (and ESM image) at `resolution` and re-points
+    /// the descriptors that read them. Waits for the device to go idle first.
+    pub fn set_shadow_resolution(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        resolution: u32,
+    ) {
+        let resolution = Self::clamp_shadow_resolution(device_info, instance, resolution);
+        self.config.shadow.resolution = resolution;
+
+        unsafe {
+            device_info
+                .logical_device
+                .device_wait_idle()
+                .expect("failed to wait for device idle");
+        }
+        self.invalidate_frozen();
+
+        let cascades = self.shadow_cascade_count() as u32;
+        for frame in self.frames.iter_mut() {
+            if let Some(old_shadow_map) = frame.shadow_map.take() {
+                old_shadow_map.destroy(&device_info.logical_device);
+                frame.shadow_map = Some(Self::create_shadow_map(
+                    device_info,
+                    instance,
+                    resolution,
+                    cascades,
+                ));
+            }
+
+            let shadow_caster_maps = Self::create_shadow_caster_maps(
+                device_info,
+                instance,
+                resolution,
+                self.config.shadow.max_shadow_casters,
+            );
+            mem::replace(&mut frame.shadow_caster_maps, shadow_caster_maps)
+                .destroy(&device_info.logical_device);
+
+            if let Some(esm_image) = frame.esm_image.take() {
+                esm_image.destroy_image(&device_info.logical_device);
+                frame.esm_image = Some(Self::create_esm_image(device_info, instance, resolution));
+            }
+
+            let sampled_shadow_map = frame
+                .shadow_map
+                .as_ref()
+                .or(self.shadow_fallback.as_ref())
+                .expect("no shadow map or stand-in");
+            if let (Some(esm_image), Some(descriptor_set)) =
+                (frame.esm_image.as_ref(), frame.descriptor_esm_set)
+            {
+                self.descriptor_manager.update_esm_descriptor_set(
+                    device_info,
+                    &sampled_shadow_map.layer_views[0],
+                    self.samplers.esm_or_stand_in(),
+                    &esm_image.image_view,
+                    descriptor_set,
+                );
+            }
+
+            self.descriptor_manager.update_lighting_shadow_descriptors(
+                device_info,
+                &sampled_shadow_map.array_view,
+                &self.samplers.shadow_map,
+                frame
+                    .esm_image
+                    .as_ref()
+                    .map_or(&sampled_shadow_map.layer_views[0], |esm_image| {
+                        &esm_image.image_view
+                    }),
+                self.samplers.esm_or_stand_in(),
+                &frame.shadow_caster_maps.array_view,
+                frame.descriptor_lighting_set,
+            );
+        }
+        self.set_debug_names(device_info);
+    }
+
+    /// Recreates every frame's G-buffer, draw, linear depth, SSAO, SSR and bloom images at
+    /// `new_extent` and re-points the descriptors that read them. The samplers don't depend on
+    /// the size and the shadow maps keep their resolution. Waits for the device to go idle first.
+    pub fn recreate_images(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        new_extent: Extent2D,
+    ) {
+        let device = &device_info.logical_device;
+        unsafe {
+            device
+                .device_wait_idle()
+                .expect("failed to wait for device idle");
+        }
+        self.invalidate_frozen();
+
+        let (width, height) = (new_extent.width, new_extent.height);
+        self.extent = new_extent;
+
+        let draw_format = self.config.gbuffer_formats.draw;
+        let shared_draw_image = (self.config.draw_image == DrawImagePolicy::Preserve)
+            .then(|| Self::create_draw_image(device_info, instance, width, height, draw_format));
+        if let Some(old_draw_image) = self
+            .frames
+            .first()
+            .filter(|frame| frame.shares_draw_image)
+            .map(|frame| frame.draw_image.clone())
+        {
+            old_draw_image.destroy_image(device);
+        }
+        self.preserved_draw_image_layout = vk::ImageLayout::UNDEFINED;
+
+        for frame in self.frames.iter_mut() {
+            let (albedo_image, normal_image, depth_image) =
+                Self::create_images(device_info, instance, width, height, &self.config);
+            mem::replace(&mut frame.albedo_image, albedo_image).destroy_image(device);
+            mem::replace(&mut frame.normal_image, normal_image).destroy_image(device);
+            mem::replace(&mut frame.depth_image, depth_image).destroy_image(device);
+            if let Some(msaa_targets) = frame.msaa_targets.take() {
+                msaa_targets.destroy(device);
+                frame.msaa_targets = Some(Self::create_msaa_targets(
+                    device_info,
+                    instance,
+                    new_extent,
+                    self.msaa_samples,
+                    &self.config,
+                ));
+            }
+
+            let draw_image = shared_draw_image.clone().unwrap_or_else(|| {
+                Self::create_draw_image(device_info, instance, width, height, draw_format)
+            });
+            let old_draw_image = mem::replace(&mut frame.draw_image, draw_image);
+            if !frame.shares_draw_image {
+                old_draw_image.destroy_image(device);
+            }
+            frame.shares_draw_image = shared_draw_image.is_some();
+            if let Some(bloom) = frame.bloom.as_mut() {
+                bloom.resize(
+                    device_info,
+                    instance,
+                    &self.descriptor_manager,
+                    &frame.draw_image,
+                );
+            }
+
+            if let Some(linear_depth_image) = frame.linear_depth_image.take() {
+                linear_depth_image.destroy_image(device);
+                frame.linear_depth_image = Some(Self::create_linear_depth_image(
+                    device_info,
+                    instance,
+                    width,
+                    height,
+                ));
+            }
+            if let Some(ssao_image) = frame.ssao_image.take() {
+                ssao_image.destroy_image(device);
+                frame.ssao_image = Some(Self::create_ssao_image(device_info, instance, new_extent));
+            }
+            if let Some(ssao_blur_image) = frame.ssao_blur_image.take() {
+                ssao_blur_image.destroy_image(device);
+                frame.ssao_blur_image =
+                    Some(Self::create_ssao_image(device_info, instance, new_extent));
+            }
+            if let Some(ssr_image) = frame.ssr_image.take() {
+                ssr_image.destroy_image(device);
+                frame.ssr_image = Some(Self::create_ssr_image(
+                    device_info,
+                    instance,
+                    width,
+                    height,
+                    draw_format,
+                ));
+            }
+            if let Some(tonemap_image) = frame.tonemap_image.take() {
+                tonemap_image.destroy_image(device);
+                frame.tonemap_image = Some(Self::create_tonemap_image(
+                    device_info,
+                    instance,
+                    width,
+                    height,
+                    self.swapchain_format,
+                ));
+            }
+
+            self.descriptor_manager.update_lighting_descriptor_set(
+                device_info,
+                &frame.lighting_buffer,
+                &frame.albedo_image.image_view,
+                &self.samplers.albedo,
+                &frame.normal_image.image_view,
+                &self.samplers.normal,
+                &frame.depth_image,
+                &self.samplers.depth,
+                &frame.camera_mvp_buffer,
+                frame.descriptor_lighting_set,
+            );
+            let (ao_image_view, ao_sampler) = self
+                .ssao
+                .as_ref()
+                .zip(frame.ssao_blur_image.as_ref())
+                .map_or(
+                    (frame.albedo_image.image_view, self.samplers.albedo),
+                    |(ssao, image)| (image.image_view, ssao.ao_sampler),
+                );
+            self.descriptor_manager.update_lighting_ao_descriptor(
+                device_info,
+                &ao_image_view,
+                &ao_sampler,
+                frame.descriptor_lighting_set,
+            );
+
+            if let Some(descriptor_set) = frame.descriptor_linear_depth_set {
+                self.descriptor_manager.update_linear_depth_descriptor_set(
+                    device_info,
+                    &frame.camera_mvp_buffer,
+                    &frame.depth_image.sampled_view(),
+                    &self.samplers.depth,
+                    descriptor_set,
+                );
+            }
+
+            if let (Some(ssao), Some(descriptor_set)) =
+                (self.ssao.as_ref(), frame.descriptor_ssao_set)
+            {
+                self.descriptor_manager.update_ssao_descriptor_set(
+                    device_info,
+                    &frame.camera_mvp_buffer,
+                    &ssao.kernel_buffer,
+                    &frame.normal_image.image_view,
+                    &frame.depth_image.sampled_view(),
+                    &self.samplers.depth,
+                    &ssao.noise_image.image_view,
+                    &ssao.noise_sampler,
+                    descriptor_set,
+                );
+            }
+            if let (Some(ssao), Some(ssao_image), Some(descriptor_set)) = (
+                self.ssao.as_ref(),
+                frame.ssao_image.as_ref(),
+                frame.descriptor_ssao_blur_set,
+            ) {
+                self.descriptor_manager.update_ssao_blur_descriptor_set(
+                    device_info,
+                    &ssao_image.image_view,
+                    &ssao.ao_sampler,
+                    descriptor_set,
+                );
+            }
+
+            if let (Some(ssr_buffer), Some(descriptor_set)) =
+                (frame.ssr_buffer.as_ref(), frame.descriptor_ssr_set)
+            {
+                self.descriptor_manager.update_ssr_descriptor_set(
+                    device_info,
+                    &frame.camera_mvp_buffer,
+                    ssr_buffer,
+                    &frame.draw_image.image_view,
+                    &frame.normal_image.image_view,
+                    &frame.depth_image.sampled_view(),
+                    &self.samplers.depth,
+                    descriptor_set,
+                );
+            }
+
+            if let (
+                Some(tonemap_image),
+                Some(tonemap_buffer),
+                Some(exposure),
+                Some(descriptor_set),
+            ) = (
+                frame.tonemap_image.as_ref(),
+                frame.tonemap_buffer.as_ref(),
+                self.exposure.as_ref(),
+                frame.descriptor_tonemap_set,
+            ) {
+                self.descriptor_manager.update_tonemap_descriptor_set(
+                    device_info,
+                    &frame.draw_image.image_view,
+                    &tonemap_image
+                        .storage_view
+                        .expect("tonemap image has a storage view"),
+                    tonemap_buffer,
+                    exposure,
+                    descriptor_set,
+                );
+            }
+
+            if let Some(descriptor_set) = frame.descriptor_present_set {
+                self.descriptor_manager.update_present_descriptor_set(
+                    device_info,
+                    &frame.draw_image.image_view,
+                    &self.samplers.draw,
+                    descriptor_set,
+                );
+            }
+
+            if let (Some(fxaa_buffer), Some(descriptor_set)) =
+                (frame.fxaa_buffer.as_ref(), frame.descriptor_fxaa_set)
+            {
+                self.descriptor_manager.update_fxaa_descriptor_set(
+                    device_info,
+                    &frame
+                        .tonemap_image
+                        .as_ref()
+                        .unwrap_or(&frame.draw_image)
+                        .image_view,
+                    &self.samplers.draw,
+                    fxaa_buffer,
+                    descriptor_set,
+                );
+            }
+        }
+
+        self.set_debug_names(device_info);
+    }
+
+    /// Binds every pipeline and records an empty draw or dispatch against frame 0's resources
+    /// in a throwaway submit, so the driver finishes deferred compilation before the first
+    /// frame. Also touches each frame's mapped buffers. Waits for the device to go idle.
+    pub fn warm_up(&self, device_info: &DeviceInfo, instance: &Instance) {
+        let device = &device_info.logical_device;
+        let frame = &self.frames[0];
+
+        for frame in self.frames.iter() {
+            let buffers = [
+                Some(&frame.camera_mvp_buffer),
+                Some(&frame.model_dynamic_buffer),
+                Some(&frame.lighting_buffer),
+                Some(&frame.globals_buffer),
+                Some(&frame.light_buffer),
+                frame.ssr_buffer.as_ref(),
+            ];
+            for buffer in buffers.into_iter().flatten() {
+                unsafe { ptr::read_volatile(buffer.mapped_buffer as *const u8) };
+            }
+        }
+
+        // Draws with vertex input need a bound vertex buffer, even for zero vertices, the
+        // instanced G-buffer pipeline one at binding 1 too
+        let dummy_vertex_buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            mem::size_of::<Vertex>() as u64,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        let command_buffer = BufferInfo::begin_single_time_command(device_info);
+
+        let msaa_targets = frame.msaa_targets.as_ref();
+        let color_images = [
+            Some(&frame.albedo_image),
+            Some(&frame.normal_image),
+            Some(&frame.draw_image),
+            frame.linear_depth_image.as_ref(),
+            frame.ssao_image.as_ref(),
+            frame.ssao_blur_image.as_ref(),
+            frame.ssr_image.as_ref(),
+            frame.esm_image.as_ref(),
+            frame.tonemap_image.as_ref(),
+            msaa_targets.map(|targets| &targets.albedo),
+            msaa_targets.map(|targets| &targets.normal),
+        ];
+        for image in color_images.into_iter().flatten() {
+            image.transition_layout(
+                device_info,
+                &command_buffer,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::GENERAL,
+            );
+        }
+        let depth_images = [
+            Some(&frame.depth_image),
+            frame.shadow_map.as_ref().map(|maps| &maps.image),
+            Some(&frame.shadow_caster_maps.image),
+            msaa_targets.map(|targets| &targets.depth),
+        ];
+        for image in depth_images.into_iter().flatten() {
+            image.transition_layout(
+                device_info,
+                &command_buffer,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            );
+        }
+
+        unsafe {
+            device.cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[dummy_vertex_buffer.buffer, dummy_vertex_buffer.buffer],
+                &[0, 0],
+            );
+        }
+
+        let gbuffer_set = frame.descriptor_gbuffer_set;
+        let (albedo_image, normal_image, depth_image) = match msaa_targets {
+            Some(targets) => (&targets.albedo, &targets.normal, &targets.depth),
+            None => (&frame.albedo_image, &frame.normal_image, &frame.depth_image),
+        };
+        self.warm_up_graphics_pipeline(
+            device,
+            command_buffer,
+            &self.gbuffer_pipeline,
+            (gbuffer_set, &[0]),
+            &[albedo_image, normal_image],
+            Some(depth_image),
+        );
+        if let Some(pipeline) = self.gbuffer_depth_equal_pipeline.as_ref() {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (gbuffer_set, &[0]),
+                &[albedo_image, normal_image],
+                Some(depth_image),
+            );
+        }
+        if let Some(pipeline) = self.gbuffer_wireframe_pipeline.as_ref() {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (gbuffer_set, &[0]),
+                &[albedo_image, normal_image],
+                Some(depth_image),
+            );
+        }
+        if let Some(pipeline) = self.depth_prepass_pipeline.as_ref() {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (gbuffer_set, &[0]),
+                &[],
+                Some(depth_image),
+            );
+        }
+        if let Some(shadow_map) = frame.shadow_map.as_ref() {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                &self.shadow_pipeline,
+                (gbuffer_set, &[0]),
+                &[],
+                Some(&shadow_map.image),
+            );
+        }
+        if let Some(pipeline) = self.shadow_caster_pipeline.as_ref() {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (gbuffer_set, &[0]),
+                &[],
+                Some(&frame.shadow_caster_maps.image),
+            );
+        }
+        self.warm_up_graphics_pipeline(
+            device,
+            command_buffer,
+            &self.lighting_pipeline,
+            (frame.descriptor_lighting_set, &[]),
+            &[&frame.draw_image],
+            None,
+        );
+        if let (Some(pipeline), Some(set), Some(image)) = (
+            self.linear_depth_pipeline.as_ref(),
+            frame.descriptor_linear_depth_set,
+            frame.linear_depth_image.as_ref(),
+        ) {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (set, &[]),
+                &[image],
+                None,
+            );
+        }
+        if let (Some(pipeline), Some(set), Some(image)) = (
+            self.ssao_pipeline.as_ref(),
+            frame.descriptor_ssao_set,
+            frame.ssao_image.as_ref(),
+        ) {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (set, &[]),
+                &[image],
+                None,
+            );
+        }
+        if let (Some(pipeline), Some(set), Some(image)) = (
+            self.ssao_blur_pipeline.as_ref(),
+            frame.descriptor_ssao_blur_set,
+            frame.ssao_blur_image.as_ref(),
+        ) {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (set, &[]),
+                &[image],
+                None,
+            );
+        }
+        if let (Some(pipeline), Some(set), Some(image)) = (
+            self.ssr_pipeline.as_ref(),
+            frame.descriptor_ssr_set,
+            frame.ssr_image.as_ref(),
+        ) {
+            self.warm_up_graphics_pipeline(
+                device,
+                command_buffer,
+                pipeline,
+                (set, &[]),
+                &[image],
+                None,
+            );
+        }
+
+        // The present and FXAA passes render into the swapchain, which isn't acquired yet, so
+        // they're only bound
+        for pipeline in [self.present_pipeline.as_ref(), self.fxaa_pipeline.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            unsafe {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.pipeline(),
+                );
+            }
+        }
+
+        if let (Some(pipeline), Some(set)) = (self.esm_pipeline.as_ref(), frame.descriptor_esm_set)
+        {
+            unsafe {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.pipeline,
+                );
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.pipeline_layout,
+                    0,
+                    &[set, frame.descriptor_globals_set],
+                    &[],
+                );
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline.pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &0f32.to_ne_bytes(),
+                );
+                device.cmd_dispatch(command_buffer, 0, 0, 0);
+            }
+        }
+
+        let tonemap_pipelines = [
+            self.tonemap_pipeline.as_ref(),
+            self.histogram_pipeline.as_ref(),
+            self.exposure_pipeline.as_ref(),
+        ];
+        if let Some(set) = frame.descriptor_tonemap_set {
+            for pipeline in tonemap_pipelines.into_iter().flatten() {
+                unsafe {
+                    device.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::COMPUTE,
+                        pipeline.pipeline,
+                    );
+                    device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::COMPUTE,
+                        pipeline.pipeline_layout,
+                        0,
+                        &[set, frame.descriptor_globals_set],
+                        &[],
+                    );
+                    device.cmd_dispatch(command_buffer, 0, 0, 0);
+                }
+            }
+        }
+
+        if let (Some(pipeline), Some(bloom)) = (self.bloom_pipeline.as_ref(), frame.bloom.as_ref())
+        {
+            unsafe {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.pipeline,
+                );
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.pipeline_layout,
+                    0,
+                    &[bloom.descriptor_sets[0], frame.descriptor_globals_set],
+                    &[],
+                );
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline.pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &(BloomMode::Threshold as u32).to_ne_bytes(),
+                );
+                device.cmd_dispatch(command_buffer, 0, 0, 0);
+            }
+        }
+
+        BufferInfo::end_single_time_command(device_info, command_buffer);
+
+        unsafe {
+            device
+                .device_wait_idle()
+                .expect("failed to wait for device idle");
+            device.destroy_buffer(dummy_vertex_buffer.buffer, None);
+            device.free_memory(dummy_vertex_buffer.buffer_memory, None);
+        }
+    }
+
+    /// Records a zero-vertex draw with `pipeline` into the given attachments.
+    fn warm_up_graphics_pipeline(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        pipeline: &PipelineInfo,
+        (descriptor_set, dynamic_offsets): (DescriptorSet, &[u32]),
+        color_images: &[&AllocatedImage],
+        depth_image: Option<&AllocatedImage>,
+    ) {
+        let view_mask = pipeline.describe().view_mask;
+        // Multiview pipelines render every layer, others only the first
+        let attachment_view = |image: &AllocatedImage| {
+            if view_mask != 0 {
+                image.render_view()
+            } else {
+                image.image_view
+            }
+        };
+
+        let color_attachments = color_images
+            .iter()
+            .map(|image| {
+                vk::RenderingAttachmentInfo::default()
+                    .image_view(attachment_view(image))
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .load_op(vk::AttachmentLoadOp::DONT_CARE)
+                    .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            })
+            .collect::<Vec<_>>();
+        let depth_attachment = depth_image.map(|image| {
+            vk::RenderingAttachmentInfo::default()
+                .image_view(attachment_view(image))
+                .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::DONT_CARE)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+        });
+
+        let extent = color_images
+            .first()
+            .or(depth_image.as_ref())
+            .expect("warm up draw needs an attachment")
+            .image_extent;
+        let extent = Extent2D {
+            width: extent.width,
+            height: extent.height,
+        };
+
+        let mut rendering_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent,
+            })
+            .layer_count(1)
+            .view_mask(view_mask)
+            .color_attachments(&color_attachments);
+        if let Some(depth_attachment) = depth_attachment.as_ref() {
+            rendering_info = rendering_info.depth_attachment(depth_attachment);
+        }
+
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let dynamic_states = &pipeline.describe().dynamic_states;
+        let depth_bias_dynamic = dynamic_states.contains(&vk::DynamicState::DEPTH_BIAS.as_raw());
+        let line_width_dynamic = dynamic_states.contains(&vk::DynamicState::LINE_WIDTH.as_raw());
+        let push_constant_ranges = pipeline.describe().push_constant_ranges();
+
+        unsafe {
+            device.cmd_begin_rendering(command_buffer, &rendering_info);
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+            device.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, self.frames[0].descriptor_globals_set],
+                dynamic_offsets,
+            );
+            device.cmd_set_viewport(command_buffer, 0, &[viewport]);
+            device.cmd_set_scissor(
+                command_buffer,
+                0,
+                &[Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent,
+                }],
+            );
+            if depth_bias_dynamic {
+                device.cmd_set_depth_bias(command_buffer, 0.0, 0.0, 0.0);
+            }
+            if line_width_dynamic {
+                device.cmd_set_line_width(command_buffer, 1.0);
+            }
+            // The texture array is set 2 of the G-buffer pipelines only
+            let gbuffer_layouts = [
+                Some(&self.gbuffer_pipeline),
+                self.gbuffer_depth_equal_pipeline.as_ref(),
+                self.gbuffer_wireframe_pipeline.as_ref(),
+            ]
+            .map(|gbuffer| gbuffer.map(|gbuffer| gbuffer.pipeline_layout));
+            if gbuffer_layouts.contains(&Some(pipeline.pipeline_layout)) {
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.pipeline_layout,
+                    2,
+                    &[self.frames[0].descriptor_texture_set],
+                    &[],
+                );
+            }
+            for range in push_constant_ranges {
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline.pipeline_layout,
+                    range.stage_flags,
+                    range.offset,
+                    &vec![0u8; range.size as usize],
+                );
+            }
+            device.cmd_draw(command_buffer, 0, 1, 0, 0);
+            device.cmd_end_rendering(command_buffer);
+        }
+    }
+
+    /// Builds a pipeline drawn over the lit image with the gbuffer's mesh bindings. It has to
+    /// depth test without writing, since the depth image is read-only by then, with a compare
+    /// op for the depth range `RenderConfig::reverse_z` picks.
+    pub fn add_overlay_pipeline(
+        &mut self,
+        device_info: &DeviceInfo,
+        description: PipelineDescription,
+    ) -> Result<(), PipelineError> {
+        assert!(
+            description.depth.as_ref().is_some_and(|depth| !depth.write),
+            "overlay pipeline {} must depth test without writing",
+            description.name
+        );
+
+        let pipeline = PipelineInfo::from_description(
+            &device_info.logical_device,
+            &self
+                .descriptor_manager
+                .with_globals(self.descriptor_manager.global_gbuffer_layout),
+            description,
+            &self.config.shader_paths,
+            self.pipeline_cache.cache,
+        )?;
+        self.overlay_pipelines.push(pipeline);
+        self.invalidate_frozen();
+        Ok(())
+    }
+
+    pub fn describe_pipelines(&self) -> Vec<PipelineDescription> {
+        self.graphics_pipelines()
+            .map(|pipeline| pipeline.describe().clone())
+            .collect()
+    }
+
+    /// Waits for the device to go idle, or to be lost, and destroys every frame's resources,
+    /// see `FrameData::destroy`, along with the pipelines, descriptors, retired resources and
+    /// pools. Then saves and destroys the pipeline cache. Nothing may be rendered afterwards.
+    pub fn destroy(&mut self, device_info: &DeviceInfo) {
+        let device = &device_info.logical_device;
+        match unsafe { device.device_wait_idle() } {
+            Ok(()) | Err(vk::Result::ERROR_DEVICE_LOST) => {}
+            Err(error) => panic!("failed to wait for device idle: {}", error),
+        }
+
+        for frame in 0..self.frame_count {
+            for resource in self.deletion_queue.take(frame) {
+                self.destroy_retired(device_info, resource);
+            }
+        }
+
+        let shared_draw_image = self
+            .frames
+            .first()
+            .filter(|frame| frame.shares_draw_image && !frame.destroyed)
+            .map(|frame| frame.draw_image.clone());
+        for frame in self.frames.iter_mut() {
+            frame.destroy(device);
+        }
+        if let Some(draw_image) = shared_draw_image {
+            draw_image.destroy_image(device);
+        }
+        if let Some(ssao) = self.ssao.take() {
+            ssao.destroy(device);
+        }
+        if let Some(exposure) = self.exposure.take() {
+            exposure.destroy(device);
+        }
+        self.samplers.destroy(device);
+        if let Some(shadow_fallback) = self.shadow_fallback.take() {
+            shadow_fallback.destroy(device);
+        }
+        if let Some(buffer) = self.identity_instance_buffer.take() {
+            buffer.destroy_buffer(device);
+        }
+        self.readback_pool.destroy(device_info);
+        self.semaphore_pool.destroy(device);
+
+        for pipeline in self.graphics_pipelines() {
+            pipeline.destroy(device);
+        }
+        for pipeline in self.compute_pipelines() {
+            pipeline.destroy(device);
+        }
+        self.descriptor_manager.destroy(device);
+        unsafe { device.destroy_sampler(self.texture_sampler, None) };
+
+        if let Err(error) = self.pipeline_cache.save(device) {
+            log::warn!("Unable to save pipeline cache: {}", error);
+        }
+        self.pipeline_cache.destroy(device);
+    }
+
+    /// Writes the pipeline cache to `RenderConfig::pipeline_cache_path`, if set.
+    pub fn save_pipeline_cache(&self, device_info: &DeviceInfo) -> io::Result<()> {
+        self.pipeline_cache.save(&device_info.logical_device)
+    }
+
+    pub fn normal_encoding(&self) -> NormalEncoding {
+        self.config.normal_encoding
+    }
+
+    /// `RenderConfig::gbuffer_formats` after fallbacks.
+    pub fn gbuffer_formats(&self) -> GBufferFormats {
+        self.config.gbuffer_formats
+    }
+
+    /// Creation times of the graphics pipelines, then the compute ones. The skin pipeline is
+    /// only there once the first skin was created.
+    pub fn pipeline_creation_report(&self) -> PipelineCreationReport {
+        let graphics = self
+            .graphics_pipelines()
+            .map(|pipeline| PipelineCreationTime {
+                name: pipeline.describe().name.clone(),
+                duration: pipeline.creation_time,
+                batch_size: pipeline.batch_size,
+            });
+        let compute = self
+            .compute_pipelines()
+            .map(|pipeline| PipelineCreationTime {
+                name: pipeline.name.clone(),
+                duration: pipeline.creation_time,
+                batch_size: 1,
+            });
+
+        PipelineCreationReport {
+            pipelines: graphics.chain(compute).collect(),
+            cache_loaded_size: self.pipeline_cache.loaded_size,
+            cache_creation_time: self.pipeline_cache.creation_time,
+        }
+    }
+
+    fn graphics_pipelines(&self) -> impl Iterator<Item = &PipelineInfo> {
+        [
+            Some(&self.gbuffer_pipeline),
+            self.depth_prepass_pipeline.as_ref(),
+            self.gbuffer_depth_equal_pipeline.as_ref(),
+            self.gbuffer_wireframe_pipeline.as_ref(),
+            Some(&self.shadow_pipeline),
+            self.shadow_caster_pipeline.as_ref(),
+            Some(&self.lighting_pipeline),
+            self.linear_depth_pipeline.as_ref(),
+            self.ssao_pipeline.as_ref(),
+            self.ssao_blur_pipeline.as_ref(),
+            self.ssr_pipeline.as_ref(),
+            self.present_pipeline.as_ref(),
+            self.fxaa_pipeline.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(self.overlay_pipelines.iter())
+    }
+
+    fn compute_pipelines(&self) -> impl Iterator<Item = &ComputePipelineInfo> {
+        [
+            self.esm_pipeline.as_ref(),
+            self.tonemap_pipeline.as_ref(),
+            self.histogram_pipeline.as_ref(),
+            self.exposure_pipeline.as_ref(),
+            self.bloom_pipeline.as_ref(),
+            self.cull_pipeline.as_ref(),
+            self.skin_pipeline.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Builds the per-frame joint buffers, skinned vertex buffers and descriptor sets for a
+    /// mesh, with room for `joint_capacity` joints.
+    pub fn create_skin(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        vertex_buffer: &AllocatedBuffer,
+        vertex_count: u32,
+        joint_capacity: usize,
+    ) -> Skin {
+        if self.skin_pipeline.is_none() {
+            let push_constant_range = vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(mem::size_of::<u32>() as u32);
+
+            self.skin_pipeline = Some(
+                ComputePipelineInfo::new(
+                    &device_info.logical_device,
+                    &self
+                        .descriptor_manager
+                        .with_globals(self.descriptor_manager.skin_layout),
+                    &[push_constant_range],
+                    SKIN_SHADER,
+                    &self.config.shader_paths,
+                    self.pipeline_cache.cache,
+                )
+                .expect("Unable to create skin pipeline"),
+            );
+        }
+
+        let joint_capacity = joint_capacity.max(1);
+        let vertex_buffer_size = (vertex_count as usize * mem::size_of::<Vertex>()) as u64;
+
+        let mut joint_buffers = vec![];
+        let mut skinned_vertex_buffers = vec![];
+        let mut descriptor_sets = vec![];
+        for _ in 0..self.frame_count {
+            let joint_buffer = AllocatedBuffer::new(
+                device_info,
+                instance,
+                (joint_capacity * mem::size_of::<Matrix4<f32>>()) as u64,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+
+            let skinned_vertex_buffer = AllocatedBuffer::new(
+                device_info,
+                instance,
+                vertex_buffer_size,
+                vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            );
+
+            let descriptor_set = self
+                .descriptor_manager
+                .create_skin_descriptor_set(&device_info.logical_device);
+            self.descriptor_manager.update_skin_descriptor_set(
+                device_info,
+                vertex_buffer,
+                &joint_buffer,
+                &skinned_vertex_buffer,
+                descriptor_set,
+            );
+
+            joint_buffers.push(joint_buffer);
+            skinned_vertex_buffers.push(skinned_vertex_buffer);
+            descriptor_sets.push(descriptor_set);
+        }
+
+        Skin {
+            joint_matrices: vec![],
+            joint_capacity,
+            joint_buffers,
+            skinned_vertex_buffers,
+            descriptor_sets,
+        }
+    }
+
+    /// Frees a skin's buffers and descriptor sets. The GPU must no longer be using them.
+    pub fn destroy_skin(&self, device_info: &DeviceInfo, skin: Skin) {
+        self.descriptor_manager
+            .free_skin_descriptor_sets(&device_info.logical_device, &skin.descriptor_sets);
+
+        for buffer in skin
+            .joint_buffers
+            .iter()
+            .chain(&skin.skinned_vertex_buffers)
+        {
+            unsafe {
+                device_info
+                    .logical_device
+                    .destroy_buffer(buffer.buffer, None);
+                device_info
+                    .logical_device
+                    .free_memory(buffer.buffer_memory, None);
+            }
+        }
+    }
+
+    /// Restarts `GlobalsUbo::time` at `time` seconds, from the next `advance` on.
+    pub fn set_time(&mut self, time: f32) {
+        self.globals.time = time;
+    }
+
+    pub fn set_jitter(&mut self, jitter: Vector2<f32>) {
+        self.globals.jitter = jitter;
+    }
+
+    /// Steps the globals by `delta_time` seconds and writes them to the current frame's buffer.
+    /// Call once per frame, after its fence has been waited on.
+    pub fn advance(&mut self, device_info: &DeviceInfo, delta_time: f32) {
+        self.globals.time += delta_time;
+        self.globals.delta_time = delta_time;
+        self.globals.resolution = Vector2::new(self.extent.width as f32, self.extent.height as f32);
+
+        let globals = self.globals;
+        self.globals.frame_index = self.globals.frame_index.wrapping_add(1);
+        self.get_mut_current_frame()
+            .update_globals_buffer(device_info, globals);
+    }
+
+    /// Appends `light` to the light list, returning its index.
+    pub fn add_light(&mut self, light: Light) -> usize {
+        assert!(
+            self.lights.len() < MAX_LIGHTS,
+            "light list is full, at most {} lights",
+            MAX_LIGHTS
+        );
+        let shadow_casters = self.shadow_caster_count();
+        self.lights.push(light);
+        self.invalidate_frozen_if_casters_changed(shadow_casters);
+        self.lights.len() - 1
+    }
+
+    pub fn set_light_casts_shadow(&mut self, index: usize, casts_shadow: bool) {
+        let shadow_casters = self.shadow_caster_count();
+        self.lights[index].casts_shadow = casts_shadow as u32;
+        self.invalidate_frozen_if_casters_changed(shadow_casters);
+    }
+
+    /// Replaces the whole light list, uploaded from the next frame on.
+    pub fn set_lights(&mut self, lights: &[Light]) {
+        assert!(
+            lights.len() <= MAX_LIGHTS,
+            "{} lights don't fit the light list, at most {}",
+            lights.len(),
+            MAX_LIGHTS
+        );
+        let shadow_casters = self.shadow_caster_count();
+        self.lights = lights.to_vec();
+        self.invalidate_frozen_if_casters_changed(shadow_casters);
+    }
+
+    /// The shadow caster pass records one draw per caster.
+    fn invalidate_frozen_if_casters_changed(&mut self, shadow_casters: usize) {
+        if self.shadow_caster_count() != shadow_casters {
+            self.invalidate_frozen();
+        }
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    /// Switches to a scene of `mesh_count` meshes, sampling `texture_image_view` when given,
+    /// lit by `lights`. The texture replaces slot `SCENE_TEXTURE` of the texture array.
+    /// Frames pick it up in `sync_scene`.
+    pub fn set_scene(
+        &mut self,
+        mesh_count: usize,
+        texture_image_view: Option<ImageView>,
+        lights: Vec<Light>,
+    ) {
+        assert!(
+            lights.len() <= MAX_LIGHTS,
+            "{} lights don't fit the light list, at most {}",
+            lights.len(),
+            MAX_LIGHTS
+        );
+        self.mesh_count = mesh_count;
+        if let Some(texture_image_view) = texture_image_view {
+            self.texture_image_view = texture_image_view;
+            self.descriptor_manager.replace_texture(
+                SCENE_TEXTURE,
+                texture_image_view,
+                self.texture_sampler,
+            );
+        }
+        self.lights = lights;
+        self.scene_generation += 1;
+        self.invalidate_frozen();
+    }
+
+    /// Adds a texture meshes can sample through `GPUMeshData::texture_index`, see
+    /// `DescriptorManager::register_texture`. Registrations don't survive `recreate_device`.
+    pub fn register_texture(&mut self, image_view: ImageView, sampler: Sampler) -> u32 {
+        self.descriptor_manager
+            .register_texture(image_view, sampler)
+    }
+
+    /// Mipmapped sampler of the scene texture, shared by `VulkanBackend::add_texture`'s.
+    pub fn texture_sampler(&self) -> Sampler {
+        self.texture_sampler
+    }
+
+    /// Destroys `resource` once every frame submitted so far has finished.
+    pub fn retire(&mut self, resource: Retired) {
+        let last_submitted = (self.current_frame + self.frame_count - 1) % self.frame_count;
+        self.deletion_queue.push(last_submitted, resource);
+    }
+
+    fn destroy_retired(&self, device_info: &DeviceInfo, resource: Retired) {
+        let device = &device_info.logical_device;
+        match resource {
+            Retired::Buffer(buffer) => buffer.destroy_buffer(device),
+            Retired::Texture(texture) => texture.destroy(device),
+            Retired::Skin(skin) => self.destroy_skin(device_info, skin),
+            Retired::CommandBuffer(command_buffer) => unsafe {
+                device.free_command_buffers(device_info.command_pool, &[command_buffer]);
+            },
+        }
+    }
+
+    /// Destroys what was retired behind the current frame's fence and brings the frame up to
+    /// the last `set_scene`: its model and cull bounds buffers grow to the mesh count and its
+    /// gbuffer and cull sets are rewritten. Call after waiting on the frame's fence.
+    pub fn sync_scene(&mut self, device_info: &DeviceInfo, instance: &Instance) {
+        for resource in self.deletion_queue.take(self.current_frame) {
+            self.destroy_retired(device_info, resource);
+        }
+
+        let frame = &mut self.frames[self.current_frame];
+        if frame.texture_generation != self.descriptor_manager.texture_generation() {
+            self.descriptor_manager
+                .update_texture_descriptor_set(device_info, frame.descriptor_texture_set);
+            frame.texture_generation = self.descriptor_manager.texture_generation();
+        }
+        if frame.scene_generation == self.scene_generation {
+            return;
+        }
+        frame.scene_generation = self.scene_generation;
+
+        if self.mesh_count > frame.mesh_capacity {
+            Self::grow_mesh_buffers(
+                device_info,
+                instance,
+                frame,
+                self.current_frame,
+                self.mesh_count,
+                self.model_ubo_alignment,
+            );
+        }
+        self.write_scene_descriptor_sets(device_info, self.current_frame);
+    }
+
+    /// Grows every frame's model and cull bounds buffers to hold at least `mesh_count`
+    /// meshes, rounded up to a power of two, and rewrites the sets reading them. Does
+    /// nothing when they already fit, otherwise waits for the device to go idle first and
+    /// drops the recordings kept while frozen.
+    pub fn ensure_model_capacity(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        mesh_count: usize,
+    ) {
+        if self
+            .frames
+            .iter()
+            .all(|frame| frame.mesh_capacity >= mesh_count)
+        {
+            return;
+        }
+
+        unsafe {
+            device_info
+                .logical_device
+                .device_wait_idle()
+                .expect("failed to wait for device idle");
+        }
+        // Kept recordings bind the old buffers
+        self.invalidate_frozen();
+
+        for index in 0..self.frames.len() {
+            let frame = &mut self.frames[index];
+            if frame.mesh_capacity >= mesh_count {
+                continue;
+            }
+            Self::grow_mesh_buffers(
+                device_info,
+                instance,
+                frame,
+                index,
+                mesh_count,
+                self.model_ubo_alignment,
+            );
+            self.write_scene_descriptor_sets(device_info, index);
+        }
+    }
+
+    /// Replaces `frame`'s model and cull buffers with ones for `mesh_count.next_power_of_two()`
+    /// meshes. The frame must be idle.
+    fn grow_mesh_buffers(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        frame: &mut FrameData,
+        index: usize,
+        mesh_count: usize,
+        model_ubo_alignment: u64,
+    ) {
+        let device = &device_info.logical_device;
+        let capacity = mesh_count.next_power_of_two();
+
+        let model_dynamic_buffer = Self::create_model_dynamic_uniform_buffer(
+            device_info,
+            instance,
+            capacity,
+            model_ubo_alignment,
+        );
+        mem::replace(&mut frame.model_dynamic_buffer, model_dynamic_buffer).destroy_buffer(device);
+
+        if let (Some(bounds_buffer), Some(draw_buffer), Some(draw_count_buffer)) = (
+            frame.cull_bounds_buffer.as_mut(),
+            frame.cull_draw_buffer.as_mut(),
+            frame.cull_draw_count_buffer.as_mut(),
+        ) {
+            let buffers = Self::create_cull_buffers(device_info, instance, capacity);
+            buffers.stats.destroy_buffer(device);
+            mem::replace(bounds_buffer, buffers.bounds).destroy_buffer(device);
+            mem::replace(draw_buffer, buffers.draws).destroy_buffer(device);
+            mem::replace(draw_count_buffer, buffers.draw_counts).destroy_buffer(device);
+        }
+        frame.mesh_capacity = capacity;
+        frame.set_debug_names(device_info, index);
+    }
+
+    /// Points frame `index`'s gbuffer and cull sets at its current buffers and the scene
+    /// texture.
+    fn write_scene_descriptor_sets(&self, device_info: &DeviceInfo, index: usize) {
+        self.debug_assert_unrecorded(index);
+        let frame = &self.frames[index];
+        self.descriptor_manager.update_gbuffer_descriptor_set(
+            device_info,
+            &frame.camera_mvp_buffer,
+            &frame.model_dynamic_buffer,
+            self.model_ubo_alignment,
+            &self.texture_image_view,
+            &self.texture_sampler,
+            frame.descriptor_gbuffer_set,
+        );
+        if let (
+            Some(bounds_buffer),
+            Some(stats_buffer),
+            Some(draw_buffer),
+            Some(draw_count_buffer),
+            Some(descriptor_set),
+        ) = (
+            frame.cull_bounds_buffer.as_ref(),
+            frame.cull_stats_buffer.as_ref(),
+            frame.cull_draw_buffer.as_ref(),
+            frame.cull_draw_count_buffer.as_ref(),
+            frame.descriptor_cull_set,
+        ) {
+            self.descriptor_manager.update_cull_descriptor_set(
+                device_info,
+                bounds_buffer,
+                stats_buffer,
+                draw_buffer,
+                draw_count_buffer,
+                descriptor_set,
+            );
+        }
+    }
+
+    /// Stops recording frames: each frame's next recording per swapchain image is kept and
+    /// submitted again instead of recording anew. Only what shaders read from buffers, like
+    /// the camera and model matrices, keeps changing. Work recorded through `raw_handles`
+    /// after the first recording isn't part of the replays.
+    pub fn freeze(&mut self) {
+        if self.frozen.is_none() {
+            self.frozen = Some(HashMap::new());
+        }
+    }
+
+    /// Goes back to recording every frame.
+    pub fn unfreeze(&mut self) {
+        self.invalidate_frozen();
+        self.frozen = None;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+
+    /// Drops the kept recordings, so frames record again while staying frozen. Anything that
+    /// changes recorded handles, like rebuilding a buffer, set or pipeline, or what the
+    /// recording draws, must call it first, e.g. on resize or a new scene.
+    pub fn invalidate_frozen(&mut self) {
+        let Some(recorded) = self.frozen.as_mut() else {
+            return;
+        };
+        let command_buffers = recorded.drain().map(|(_, command_buffer)| command_buffer);
+        for command_buffer in command_buffers.collect::<Vec<_>>() {
+            self.retire(Retired::CommandBuffer(command_buffer));
+        }
+    }
+
+    /// Panics in debug builds when a kept recording of frame `index` would replay the sets or
+    /// buffers about to be rewritten, i.e. `invalidate_frozen` was missed.
+    fn debug_assert_unrecorded(&self, index: usize) {
+        debug_assert!(
+            self.frozen
+                .as_ref()
+                .is_none_or(|recorded| recorded.keys().all(|&(frame, _)| frame != index)),
+            "frame {} is rewritten while a frozen recording still uses it",
+            index
+        );
+    }
+
+    /// The current frame's kept recording for `image_index`, while frozen.
+    pub fn frozen_command_buffer(&self, image_index: u32) -> Option<vk::CommandBuffer> {
+        self.frozen
+            .as_ref()?
+            .get(&(self.current_frame, image_index))
+            .copied()
+    }
+
+    /// Keeps the current frame's just recorded command buffer for `image_index` and gives the
+    /// frame a new one to record into.
+    pub fn keep_frozen(&mut self, device_info: &DeviceInfo, image_index: u32) {
+        let Some(recorded) = self.frozen.as_mut() else {
+            return;
+        };
+        let frame = &mut self.frames[self.current_frame];
+        let command_buffer = mem::replace(
+            &mut frame.command_buffer,
+            Self::allocate_command_buffer(device_info),
+        );
+        recorded.insert((self.current_frame, image_index), command_buffer);
+    }
+
+    /// Writes the light list to the current frame's buffer, after its fence has been waited on.
+    /// Lights wanting a shadow get the caster layers in order, until they run out.
+    pub fn upload_lights(&mut self, device_info: &DeviceInfo) {
+        let mut layers = 0..self.shadow_caster_count() as i32;
+        let lights = self
+            .lights
+            .iter()
+            .map(|&light| Light {
+                shadow_layer: if light.wants_shadow_layer() {
+                    layers.next().unwrap_or(-1)
+                } else {
+                    -1
+                },
+                ..light
+            })
+            .collect::<Vec<_>>();
+
+        let frame = &mut self.frames[self.current_frame];
+        frame.update_light_buffer(device_info, &lights);
+    }
+
+    /// `RenderConfig::reverse_z`, whether the G-buffer depth runs from 1.0 at the near plane
+    /// to 0.0 at the far one.
+    pub fn reverse_z(&self) -> bool {
+        self.config.reverse_z
+    }
+
+    /// `ShadowConfig::enabled`, whether the frames have a main light shadow map.
+    pub fn shadows_enabled(&self) -> bool {
+        self.config.shadow.enabled
+    }
+
+    /// Shadow map width and height, also when there's no main light shadow map.
+    pub fn shadow_resolution(&self) -> u32 {
+        self.get_current_frame()
+            .shadow_caster_maps
+            .image
+            .image_extent
+            .width
+    }
+
+    /// Layers of each frame's main shadow map, and shadow passes recorded for them.
+    pub fn shadow_cascade_count(&self) -> usize {
+        Self::cascade_count(&self.config.shadow, self.shadow_technique)
+    }
+
+    pub fn shadow_depth_bias(&self) -> ShadowDepthBias {
+        self.config.shadow.depth_bias
+    }
+
+    /// Takes effect in frames recorded from now on.
+    pub fn set_shadow_depth_bias(&mut self, depth_bias: ShadowDepthBias) {
+        self.config.shadow.depth_bias = depth_bias;
+        self.invalidate_frozen();
+    }
+
+    /// Blend of uniform and logarithmic cascade splits, see `utils::cascade_splits`.
+    pub fn cascade_split_lambda(&self) -> f32 {
+        self.config.shadow.cascade_split_lambda
+    }
+
+    /// Layers each frame's shadow caster maps have, and shadow passes recorded for them.
+    pub fn shadow_caster_count(&self) -> usize {
+        (self.config.shadow.max_shadow_casters as usize).min(MAX_SHADOW_CASTERS)
+    }
+
+    /// The lights `upload_lights` gives a caster layer, in layer order.
+    pub fn shadow_casters(&self) -> impl Iterator<Item = &Light> {
+        self.lights
+            .iter()
+            .filter(|light| light.wants_shadow_layer())
+            .take(self.shadow_caster_count())
+    }
+
+    /// Runs `f` on every frame, for writes that have to reach all frames' buffers at once.
+    /// Waits for every frame in flight first, so none of them reads a buffer while it's
+    /// written.
+    pub fn for_each_frame_mut(&mut self, device_info: &DeviceInfo, f: impl FnMut(&mut FrameData)) {
+        self.wait_for_render_fences(device_info);
+        self.frames.iter_mut().for_each(f);
+    }
+
+    /// Blocks until every frame's last submit has finished.
+    fn wait_for_render_fences(&self, device_info: &DeviceInfo) {
+        let fences = self
+            .frames
+            .iter()
+            .map(|frame| frame.render_fence)
+            .collect::<Vec<_>>();
+        unsafe {
+            device_info
+                .logical_device
+                .wait_for_fences(&fences, true, u64::MAX)
+                .expect("failed to wait for render fences");
+        }
+    }
+
+    pub fn lighting(&self) -> LightingUbo {
+        self.lighting
+    }
+
+    /// Writes `lighting` to every frame's lighting buffer, so the change shows up on the next
+    /// frame instead of one frame at a time. Waits for the frames in flight, see
+    /// `for_each_frame_mut`. Its direction also moves the shadow frustum.
+    pub fn set_lighting_all(&mut self, device_info: &DeviceInfo, lighting: LightingUbo) {
+        self.lighting = lighting;
+        self.light_direction = lighting.light_direction.xyz();
+        self.for_each_frame_mut(device_info, |frame| {
+            frame.update_lighting_buffer(device_info, lighting)
+        });
+    }
+
+    /// Writes `lighting` to the current frame's lighting buffer only, which must no longer be
+    /// in flight. The other frames pick it up as they're recorded, see `FrameInputs::lighting`.
+    pub fn set_lighting(&mut self, device_info: &DeviceInfo, lighting: LightingUbo) {
+        self.lighting = lighting;
+        self.light_direction = lighting.light_direction.xyz();
+        self.get_mut_current_frame()
+            .update_lighting_buffer(device_info, lighting);
+    }
+
+    /// The histogram and average pipelines when the tonemap pass runs with auto exposure.
+    pub fn auto_exposure_pipelines(&self) -> Option<(&ComputePipelineInfo, &ComputePipelineInfo)> {
+        self.config
+            .tonemap
+            .and_then(|tonemap| tonemap.auto_exposure)
+            .and(self.histogram_pipeline.as_ref())
+            .zip(self.exposure_pipeline.as_ref())
+    }
+
+    /// `RenderConfig::tonemap` as last set, whether or not the tonemap pass runs.
+    pub fn tonemap(&self) -> Option<TonemapConfig> {
+        self.config.tonemap
+    }
+
+    /// `RenderConfig::bloom` as last set, whether or not the bloom pass runs.
+    pub fn bloom(&self) -> Option<BloomConfig> {
+        self.config.bloom
+    }
+
+    /// Writes `bloom` to every frame's bloom buffer, like `set_tonemap_all`. Only remembered
+    /// when the bloom pass doesn't run.
+    pub fn set_bloom_all(&mut self, device_info: &DeviceInfo, bloom: BloomConfig) {
+        self.config.bloom = Some(bloom);
+        self.for_each_frame_mut(device_info, |frame| {
+            if let Some(targets) = frame.bloom.as_mut() {
+                targets.set_config(device_info, bloom);
+            }
+        });
+    }
+
+    /// `RenderConfig::fxaa` as last set, whether or not the FXAA pass runs.
+    pub fn fxaa(&self) -> Option<FxaaConfig> {
+        self.config.fxaa
+    }
+
+    /// Writes `tonemap` to every frame's tonemap buffer, like `set_lighting_all`. Only
+    /// remembered when the tonemap pass doesn't run. Frozen frames are rerecorded when auto
+    /// exposure is turned on or off, which adds or removes its passes.
+    pub fn set_tonemap_all(&mut self, device_info: &DeviceInfo, tonemap: TonemapConfig) {
+        let auto_exposure = |config: Option<TonemapConfig>| {
+            config.is_some_and(|tonemap| tonemap.auto_exposure.is_some())
+        };
+        if auto_exposure(self.config.tonemap) != auto_exposure(Some(tonemap)) {
+            self.invalidate_frozen();
+        }
+        self.config.tonemap = Some(tonemap);
+        self.for_each_frame_mut(device_info, |frame| {
+            if let Some(buffer) = frame.tonemap_buffer.as_mut() {
+                buffer.update_buffer(device_info, &[Self::post_process_ubo(tonemap)]);
+            }
+        });
+    }
+
+    /// Writes `fxaa` to every frame's FXAA buffer, like `set_tonemap_all`. Only remembered
+    /// when the FXAA pass doesn't run.
+    pub fn set_fxaa_all(&mut self, device_info: &DeviceInfo, fxaa: FxaaConfig) {
+        self.config.fxaa = Some(fxaa);
+        self.for_each_frame_mut(device_info, |frame| {
+            if let Some(buffer) = frame.fxaa_buffer.as_mut() {
+                buffer.update_buffer(device_info, &[Self::fxaa_ubo(fxaa)]);
+            }
+        });
+    }
+
+    /// Reads the current frame's timestamps from its last submit. Call once its render fence
+    /// has signaled, before recording it again.
+    pub fn resolve_pass_timings(&mut self, device_info: &DeviceInfo) {
+        self.pass_timings = self.get_current_frame().resolve_timings(device_info);
+    }
+
+    /// Milliseconds per `GpuPass::ALL` of the frame `frame_count` frames back, empty without
+    /// `RenderConfig::gpu_timings`.
+    pub fn pass_timings(&self) -> &[f32] {
+        &self.pass_timings
+    }
+
+    /// `FrameData::set_debug_names` of every frame, after any of their resources changed,
+    /// and the shared samplers.
+    fn set_debug_names(&self, device_info: &DeviceInfo) {
+        for (index, frame) in self.frames.iter().enumerate() {
+            frame.set_debug_names(device_info, index);
+        }
+        if cfg!(feature = "debug-names") {
+            for (name, sampler) in self.samplers.named() {
+                utils::set_debug_name(device_info, sampler, name);
+            }
+            if let Some(shadow_fallback) = self.shadow_fallback.as_ref() {
+                utils::set_debug_name(device_info, shadow_fallback.image.image, "shadow_fallback");
+            }
+        }
+    }
+
+    /// Frames in flight, how many of each per-frame resource there are.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    pub fn current_frame_index(&self) -> usize {
+        self.current_frame
+    }
+
+    pub fn advance_frame(&mut self) {
+        self.current_frame = (self.current_frame + 1) % self.frame_count;
+    }
+
+    pub fn get_current_frame(&self) -> &FrameData {
+        &self.frames[self.current_frame]
+    }
+
+    /// Frame `index` of the `frame_count`, current or not.
+    pub fn frame_at(&self, index: usize) -> Option<&FrameData> {
+        self.frames.get(index)
+    }
+
+    pub fn frame_at_mut(&mut self, index: usize) -> Option<&mut FrameData> {
+        self.frames.get_mut(index)
+    }
+
+    /// Records the current frame's final image, the tonemap pass's output or draw_image, onto
+    /// `swapchain_image` and leaves it ready to present, see `present::blit_to_swapchain`.
+    /// Linear values go straight through and `_SRGB` formats encode them on write, the
+    /// tonemap output is already encoded and in the swapchain's encoding.
+    pub fn blit_to_swapchain(
+        &self,
+        device_info: &DeviceInfo,
+        command_buffer: vk::CommandBuffer,
+        swapchain_image: vk::Image,
+        swapchain_extent: Extent2D,
+    ) {
+        let frame = self.get_current_frame();
+        present::blit_to_swapchain(
+            device_info,
+            &command_buffer,
+            frame.tonemap_image.as_ref().unwrap_or(&frame.draw_image),
+            swapchain_image,
+            swapchain_extent,
+            self.swapchain_format.format,
+            self.present_filter,
+        );
+    }
+
+    /// The draw image of the frame submitted last as tightly packed sRGB RGBA8 rows, see
+    /// `format::linear_to_srgb_rgba8`, the first view only with multiview. Waits for the
+    /// device to go idle, copies it out through a staging buffer and puts it back into the
+    /// layout the frame left it in.
+    pub fn read_draw_image(&self, device_info: &DeviceInfo, instance: &Instance) -> Vec<u8> {
+        unsafe {
+            device_info
+                .logical_device
+                .device_wait_idle()
+                .expect("failed to wait for device idle");
+        }
+
+        // advance_frame follows every submit
+        let frame = &self.frames[(self.current_frame + self.frame_count - 1) % self.frame_count];
+        let draw_image = &frame.draw_image;
+        let format = self.config.gbuffer_formats.draw;
+        let extent = Extent2D {
+            width: draw_image.image_extent.width,
+            height: draw_image.image_extent.height,
+        };
+        let (regions, size) =
+            format::buffer_image_copies(format, extent, 1, ImageAspectFlags::COLOR);
+
+        let staging = AllocatedBuffer::new(
+            device_info,
+            instance,
+            size,
+            BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        let command_buffer = BufferInfo::begin_single_time_command(device_info);
+        draw_image.transition_layout(
+            device_info,
+            &command_buffer,
+            self.preserved_draw_image_layout,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        );
+        unsafe {
+            device_info.logical_device.cmd_copy_image_to_buffer(
+                command_buffer,
+                draw_image.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                staging.buffer,
+                &regions,
+            );
+        }
+        // Nothing was rendered yet with UNDEFINED, the next frame discards it anyway
+        if self.preserved_draw_image_layout != vk::ImageLayout::UNDEFINED {
+            draw_image.transition_layout(
+                device_info,
+                &command_buffer,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.preserved_draw_image_layout,
+            );
+        }
+        BufferInfo::end_single_time_command(device_info, command_buffer);
+
+        let bytes =
+            unsafe { slice::from_raw_parts(staging.mapped_buffer as *const u8, size as usize) };
+        let pixels = format::linear_to_srgb_rgba8(format, bytes, extent.width, extent.height);
+        staging.destroy_buffer(&device_info.logical_device);
+        pixels
+    }
+
+    /// `RenderConfig::frame_timeout` in nanoseconds, `u64::MAX` to wait forever.
+    pub fn frame_timeout_ns(&self) -> u64 {
+        self.config.frame_timeout.map_or(u64::MAX, |timeout| {
+            timeout.as_nanos().min(u64::MAX as u128) as u64
+        })
+    }
+
+    /// Waits up to `timeout_ns` for the current frame's last submit. Never resets the fence:
+    /// one that timed out still belongs to a pending submit, so the next wait covers it too.
+    pub fn wait_for_current_frame(
+        &self,
+        device: &ash::Device,
+        timeout_ns: u64,
+    ) -> Result<(), FrameWaitError> {
+        let render_fence = self.get_current_frame().render_fence;
+        match unsafe { device.wait_for_fences(&[render_fence], true, timeout_ns) } {
+            Ok(()) => Ok(()),
+            Err(vk::Result::TIMEOUT) => Err(FrameWaitError::Timeout),
+            Err(vk::Result::ERROR_DEVICE_LOST) => Err(FrameWaitError::DeviceLost),
+            Err(error) => Err(FrameWaitError::Vulkan(error)),
+        }
+    }
+
+    pub fn get_mut_current_frame(&mut self) -> &mut FrameData {
+        self.frame_at_mut(self.current_frame).unwrap()
+    }
+
+    fn allocate_command_buffer(device_info: &DeviceInfo) -> vk::CommandBuffer {
+        let command_buffer_alloc_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(device_info.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+
+        unsafe {
+            device_info
+                .logical_device
+                .allocate_command_buffers(&command_buffer_alloc_info)
+                .expect("failed to allocate command buffer")[0]
+        }
+    }
+
+    fn create_command_buffers(device_info: &DeviceInfo, count: usize) -> Vec<vk::CommandBuffer> {
+        let command_buffer_alloc_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(device_info.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(count as u32);
+
+        unsafe {
+            device_info
+                .logical_device
+                .allocate_command_buffers(&command_buffer_alloc_info)
+                .expect("failed to allocate command buffer")
+        }
+    }
+
+    fn create_camera_mvp_buffer(device_info: &DeviceInfo, instance: &Instance) -> AllocatedBuffer {
+        let buffer_size = CAMERA_BUFFER_SIZE as u64;
+        AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )
+    }
+
+    fn create_globals_buffer(device_info: &DeviceInfo, instance: &Instance) -> AllocatedBuffer {
+        let buffer_size = mem::size_of::<GlobalsUbo>() as u64;
+        AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )
+    }
+
+    fn create_light_buffer(device_info: &DeviceInfo, instance: &Instance) -> AllocatedBuffer {
+        let buffer_size =
+            (mem::size_of::<LightListHeader>() + MAX_LIGHTS * mem::size_of::<Light>()) as u64;
+        let mut buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
+            BufferUsageFlags::STORAGE_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        buffer.update_buffer(
+            device_info,
+            &[LightListHeader {
+                count: 0,
+                _pad: [0; 3],
+            }],
+        );
+        buffer
+    }
+
+    fn create_lighting_buffer(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        lighting: LightingUbo,
+    ) -> AllocatedBuffer {
+        let buffer_size = mem::size_of::<LightingUbo>() as u64;
+        let mut buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
             BufferUsageFlags::UNIFORM_BUFFER,
             MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
         );
-
-        let light_dir = normalize(&vec3(-1.0, -1.0, -1.0));
-
-        buffer.update_buffer(&[LightingUbo {
-            light_direction: vec3_to_vec4(&light_dir),
-
-            // w is intensity
-            light_color: vec4(1.0, 1.0, 0.0, 2.0),
-            ambient_light: vec4(0.1, 0.1, 0.1, 0.2),
-        }]);
+        buffer.update_buffer(device_info, &[lighting]);
 
         buffer
     }
@@ -241,15 +3673,13 @@ impl FrameManager {
     ) -> AllocatedBuffer {
         let dynamic_buffer_size = dynamic_alignment * mesh_count as u64;
 
-        let buffer = AllocatedBuffer::new(
+        AllocatedBuffer::new(
             device_info,
             instance,
             dynamic_buffer_size,
             BufferUsageFlags::UNIFORM_BUFFER,
             MemoryPropertyFlags::HOST_VISIBLE,
-        );
-
-        buffer
+        )
     }
 
     //noinspection DuplicatedCode
@@ -258,35 +3688,33 @@ impl FrameManager {
         instance: &Instance,
         image_width: u32,
         image_height: u32,
-    ) -> (
-        AllocatedImage,
-        AllocatedImage,
-        AllocatedImage,
-        AllocatedImage,
-        AllocatedImage,
-    ) {
-        let albedo_image = AllocatedImage::new(
+        config: &RenderConfig,
+    ) -> (AllocatedImage, AllocatedImage, AllocatedImage) {
+        let albedo_format = config.gbuffer_formats.albedo;
+        let albedo_image = AllocatedImage::new_layered(
             device_info,
             instance,
             image_width,
             image_height,
-            Format::R16G16B16A16_SFLOAT,
+            VIEW_COUNT,
+            albedo_format,
             ImageAspectFlags::COLOR,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_DST
                 | vk::ImageUsageFlags::TRANSFER_SRC
-                | vk::ImageUsageFlags::STORAGE
+                | Self::storage_usage(device_info, instance, albedo_format)
                 | vk::ImageUsageFlags::COLOR_ATTACHMENT
                 | vk::ImageUsageFlags::SAMPLED,
             MemoryPropertyFlags::DEVICE_LOCAL,
         );
 
-        let normal_image = AllocatedImage::new(
+        let normal_image = AllocatedImage::new_layered(
             device_info,
             instance,
             image_width,
             image_height,
-            Format::R16G16B16A16_SNORM,
+            VIEW_COUNT,
+            config.normal_encoding.format(),
             ImageAspectFlags::COLOR,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_DST
@@ -296,12 +3724,13 @@ impl FrameManager {
             MemoryPropertyFlags::DEVICE_LOCAL,
         );
 
-        let depth_image = AllocatedImage::new(
+        let depth_image = AllocatedImage::new_layered(
             device_info,
             instance,
             image_width,
             image_height,
-            Format::D32_SFLOAT,
+            VIEW_COUNT,
+            config.gbuffer_formats.depth,
             ImageAspectFlags::DEPTH,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_DST
@@ -311,46 +3740,537 @@ impl FrameManager {
             MemoryPropertyFlags::DEVICE_LOCAL,
         );
 
-        let shadow_map_image = AllocatedImage::new(
+        (albedo_image, normal_image, depth_image)
+    }
+
+    fn create_msaa_targets(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        extent: Extent2D,
+        samples: vk::SampleCountFlags,
+        config: &RenderConfig,
+    ) -> MsaaTargets {
+        let create = |format, aspect_flags, usage| {
+            AllocatedImage::new_multisampled(
+                device_info,
+                instance,
+                extent.width,
+                extent.height,
+                VIEW_COUNT,
+                samples,
+                format,
+                aspect_flags,
+                vk::ImageTiling::OPTIMAL,
+                usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                MemoryPropertyFlags::DEVICE_LOCAL,
+            )
+        };
+
+        MsaaTargets {
+            albedo: create(
+                config.gbuffer_formats.albedo,
+                ImageAspectFlags::COLOR,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ),
+            normal: create(
+                config.normal_encoding.format(),
+                ImageAspectFlags::COLOR,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ),
+            depth: create(
+                config.gbuffer_formats.depth,
+                ImageAspectFlags::DEPTH,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            ),
+        }
+    }
+
+    fn create_draw_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        image_width: u32,
+        image_height: u32,
+        format: Format,
+    ) -> AllocatedImage {
+        AllocatedImage::new_layered(
             device_info,
             instance,
-            2048,
-            2048,
-            Format::D32_SFLOAT,
-            ImageAspectFlags::DEPTH,
+            image_width,
+            image_height,
+            VIEW_COUNT,
+            format,
+            ImageAspectFlags::COLOR,
             vk::ImageTiling::OPTIMAL,
             vk::ImageUsageFlags::TRANSFER_DST
                 | vk::ImageUsageFlags::TRANSFER_SRC
-                | vk::ImageUsageFlags::STORAGE
-                | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                | Self::storage_usage(device_info, instance, format)
+                | vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    /// One layer per cascade, see `shadow_cascade_count`.
+    fn create_shadow_map(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        resolution: u32,
+        cascades: u32,
+    ) -> ShadowCasterMaps {
+        Self::create_layered_shadow_maps(
+            device_info,
+            instance,
+            resolution,
+            cascades,
+            vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED,
+        )
+    }
+
+    /// Single layer, cleared to the far plane and left in SHADER_READ_ONLY_OPTIMAL, so
+    /// anything sampling it is lit.
+    fn create_shadow_fallback(device_info: &DeviceInfo, instance: &Instance) -> ShadowCasterMaps {
+        let maps = Self::create_layered_shadow_maps(
+            device_info,
+            instance,
+            1,
+            1,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+        );
+
+        let command_buffer = BufferInfo::begin_single_time_command(device_info);
+        maps.image.transition_layout(
+            device_info,
+            &command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+        let range = vk::ImageSubresourceRange::default()
+            .aspect_mask(ImageAspectFlags::DEPTH)
+            .level_count(1)
+            .layer_count(1);
+        unsafe {
+            device_info.logical_device.cmd_clear_depth_stencil_image(
+                command_buffer,
+                maps.image.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+                &[range],
+            );
+        }
+        maps.image.transition_layout(
+            device_info,
+            &command_buffer,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        BufferInfo::end_single_time_command(device_info, command_buffer);
+
+        maps
+    }
+
+    /// `max_shadow_casters` layers, clamped to `MAX_SHADOW_CASTERS` and at least 1.
+    fn create_shadow_caster_maps(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        resolution: u32,
+        max_shadow_casters: u32,
+    ) -> ShadowCasterMaps {
+        Self::create_layered_shadow_maps(
+            device_info,
+            instance,
+            resolution,
+            max_shadow_casters.clamp(1, MAX_SHADOW_CASTERS as u32),
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+        )
+    }
+
+    fn create_layered_shadow_maps(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        resolution: u32,
+        layers: u32,
+        usage: vk::ImageUsageFlags,
+    ) -> ShadowCasterMaps {
+        let image = AllocatedImage::new_layered(
+            device_info,
+            instance,
+            resolution,
+            resolution,
+            layers,
+            Format::D32_SFLOAT,
+            ImageAspectFlags::DEPTH,
+            vk::ImageTiling::OPTIMAL,
+            usage,
             MemoryPropertyFlags::DEVICE_LOCAL,
         );
 
-        let draw_image = AllocatedImage::new(
+        let layer_views = (0..layers)
+            .map(|layer| {
+                AllocatedImage::create_layer_view(
+                    device_info,
+                    &image.image,
+                    Format::D32_SFLOAT,
+                    ImageAspectFlags::DEPTH,
+                    layer,
+                )
+            })
+            .collect();
+        let array_view = AllocatedImage::create_array_view(
+            device_info,
+            &image.image,
+            Format::D32_SFLOAT,
+            ImageAspectFlags::DEPTH,
+            layers,
+        );
+
+        ShadowCasterMaps {
+            image,
+            layer_views,
+            array_view,
+        }
+    }
+
+    /// STORAGE when optimal tiling `format` images support it, nothing otherwise.
+    fn storage_usage(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        format: Format,
+    ) -> vk::ImageUsageFlags {
+        let features = unsafe {
+            instance
+                .get_physical_device_format_properties(device_info._physical_device, format)
+                .optimal_tiling_features
+        };
+        if features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE) {
+            vk::ImageUsageFlags::STORAGE
+        } else {
+            vk::ImageUsageFlags::empty()
+        }
+    }
+
+    /// The highest sample count up to `requested` the device supports for both color and
+    /// depth attachments, TYPE_1 when `None`.
+    fn clamp_msaa_samples(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        requested: Option<vk::SampleCountFlags>,
+    ) -> vk::SampleCountFlags {
+        let limits = unsafe {
+            instance
+                .get_physical_device_properties(device_info._physical_device)
+                .limits
+        };
+        let supported =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+        let mut samples = requested.map_or(1, |requested| requested.as_raw().max(1));
+        // Highest requested bit, then down until the device has it
+        samples = 1 << (31 - samples.leading_zeros());
+        while samples > 1 && !supported.contains(vk::SampleCountFlags::from_raw(samples)) {
+            samples >>= 1;
+        }
+        vk::SampleCountFlags::from_raw(samples)
+    }
+
+    fn cascade_count(shadow: &ShadowConfig, shadow_technique: ShadowTechnique) -> usize {
+        match shadow_technique {
+            ShadowTechnique::Esm { .. } => 1,
+            ShadowTechnique::Pcf | ShadowTechnique::PoissonPcf(_) => {
+                (shadow.cascades as usize).clamp(1, MAX_SHADOW_CASCADES)
+            }
+        }
+    }
+
+    fn clamp_shadow_resolution(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        resolution: u32,
+    ) -> u32 {
+        let device_properties =
+            unsafe { instance.get_physical_device_properties(device_info._physical_device) };
+
+        resolution.clamp(1, device_properties.limits.max_image_dimension2_d)
+    }
+
+    fn create_linear_depth_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        image_width: u32,
+        image_height: u32,
+    ) -> AllocatedImage {
+        AllocatedImage::new(
             device_info,
             instance,
             image_width,
             image_height,
-            Format::R16G16B16A16_SFLOAT,
+            Format::R32_SFLOAT,
             ImageAspectFlags::COLOR,
             vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_DST
-                | vk::ImageUsageFlags::TRANSFER_SRC
-                | vk::ImageUsageFlags::STORAGE
-                | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    /// Half of `extent` in each dimension, rendered and sampled.
+    fn create_ssao_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        extent: Extent2D,
+    ) -> AllocatedImage {
+        AllocatedImage::new(
+            device_info,
+            instance,
+            (extent.width / 2).max(1),
+            (extent.height / 2).max(1),
+            Format::R8_UNORM,
+            ImageAspectFlags::COLOR,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
             MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    fn create_esm_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        resolution: u32,
+    ) -> AllocatedImage {
+        AllocatedImage::new(
+            device_info,
+            instance,
+            resolution,
+            resolution,
+            Format::R32_SFLOAT,
+            ImageAspectFlags::COLOR,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    fn create_ssr_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        image_width: u32,
+        image_height: u32,
+        format: Format,
+    ) -> AllocatedImage {
+        AllocatedImage::new(
+            device_info,
+            instance,
+            image_width,
+            image_height,
+            format,
+            ImageAspectFlags::COLOR,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    fn create_ssr_buffer(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        ssr_config: SsrConfig,
+    ) -> AllocatedBuffer {
+        let buffer_size = mem::size_of::<SsrUbo>() as u64;
+        let mut buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
         );
 
-        (
-            albedo_image,
-            normal_image,
-            depth_image,
-            shadow_map_image,
-            draw_image,
+        buffer.update_buffer(
+            device_info,
+            &[SsrUbo {
+                max_steps: ssr_config.max_steps,
+                thickness: ssr_config.thickness,
+                max_roughness: ssr_config.max_roughness,
+                intensity: ssr_config.intensity,
+            }],
+        );
+
+        buffer
+    }
+
+    /// R8G8B8A8_SRGB for sRGB swapchain formats, so the blit decodes what it re-encodes, and
+    /// R8G8B8A8_UNORM otherwise, so it's copied as is. Both are written through a UNORM
+    /// storage view.
+    fn create_tonemap_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        image_width: u32,
+        image_height: u32,
+        swapchain_format: vk::SurfaceFormatKHR,
+    ) -> AllocatedImage {
+        let format = if present::is_srgb_format(swapchain_format.format) {
+            Format::R8G8B8A8_SRGB
+        } else {
+            Format::R8G8B8A8_UNORM
+        };
+        AllocatedImage::new_reinterpreted(
+            device_info,
+            instance,
+            image_width,
+            image_height,
+            format,
+            Format::R8G8B8A8_UNORM,
+            // Sampled by the FXAA pass
+            vk::ImageUsageFlags::STORAGE
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
         )
     }
 
-    fn create_sync_objects(device: &ash::Device) -> (vk::Semaphore, vk::Semaphore, vk::Fence) {
+    fn create_tonemap_buffer(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        tonemap: TonemapConfig,
+    ) -> AllocatedBuffer {
+        let buffer_size = mem::size_of::<PostProcessUbo>() as u64;
+        let mut buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        buffer.update_buffer(device_info, &[Self::post_process_ubo(tonemap)]);
+
+        buffer
+    }
+
+    fn create_fxaa_buffer(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        fxaa: FxaaConfig,
+    ) -> AllocatedBuffer {
+        let buffer_size = mem::size_of::<FxaaUbo>() as u64;
+        let mut buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            buffer_size,
+            BufferUsageFlags::UNIFORM_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        buffer.update_buffer(device_info, &[Self::fxaa_ubo(fxaa)]);
+
+        buffer
+    }
+
+    fn fxaa_ubo(fxaa: FxaaConfig) -> FxaaUbo {
+        FxaaUbo {
+            edge_threshold: fxaa.edge_threshold,
+            search_steps: fxaa.quality.search_steps(),
+            _padding: [0.0; 2],
+        }
+    }
+
+    fn post_process_ubo(tonemap: TonemapConfig) -> PostProcessUbo {
+        let auto_exposure = tonemap.auto_exposure.unwrap_or_default();
+        assert!(
+            auto_exposure.min_ev < auto_exposure.max_ev,
+            "auto exposure range {}..{} EV is empty",
+            auto_exposure.min_ev,
+            auto_exposure.max_ev
+        );
+        PostProcessUbo {
+            exposure: tonemap.exposure,
+            operator: tonemap.operator.shader_value(),
+            auto_exposure: tonemap.auto_exposure.is_some() as u32,
+            adaptation_speed: auto_exposure.adaptation_speed,
+            min_log_luminance: auto_exposure.min_ev,
+            log_luminance_range: auto_exposure.max_ev - auto_exposure.min_ev,
+            _padding: [0.0; 2],
+        }
+    }
+
+    fn create_cull_buffers(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        mesh_count: usize,
+    ) -> CullBuffers {
+        let bounds_buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            (mesh_count.max(1) * mem::size_of::<Vector4<f32>>()) as u64,
+            BufferUsageFlags::STORAGE_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        // Read back before the frame first writes it
+        let mut stats_buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            mem::size_of::<FrameStats>() as u64,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        stats_buffer.update_buffer(device_info, &[FrameStats::default()]);
+
+        let draw_buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            (mesh_count.max(1) * mem::size_of::<vk::DrawIndexedIndirectCommand>()) as u64,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::INDIRECT_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        let draw_count_buffer = AllocatedBuffer::new(
+            device_info,
+            instance,
+            (mesh_count.max(1) * mem::size_of::<u32>()) as u64,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::INDIRECT_BUFFER,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        CullBuffers {
+            bounds: bounds_buffer,
+            stats: stats_buffer,
+            draws: draw_buffer,
+            draw_counts: draw_count_buffer,
+        }
+    }
+
+    /// Reset right away, so `resolve_timings` can ask for results before the first submit.
+    fn create_timestamp_pool(device_info: &DeviceInfo) -> vk::QueryPool {
+        let create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(GpuPass::TIMESTAMP_COUNT);
+        let pool = unsafe {
+            device_info
+                .logical_device
+                .create_query_pool(&create_info, None)
+                .expect("failed to create timestamp query pool")
+        };
+
+        let command_buffer = BufferInfo::begin_single_time_command(device_info);
+        unsafe {
+            device_info.logical_device.cmd_reset_query_pool(
+                command_buffer,
+                pool,
+                0,
+                GpuPass::TIMESTAMP_COUNT,
+            )
+        };
+        BufferInfo::end_single_time_command(device_info, command_buffer);
+        pool
+    }
+
+    fn create_sync_objects(device: &ash::Device) -> (vk::Semaphore, vk::Fence) {
         let semaphore_create_info = vk::SemaphoreCreateInfo {
             s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
             ..Default::default()
@@ -363,10 +4283,6 @@ impl FrameManager {
         };
 
         unsafe {
-            let swapchain_semaphore = device
-                .create_semaphore(&semaphore_create_info, None)
-                .expect("failed to create semaphore for swapchain");
-
             let render_semaphore = device
                 .create_semaphore(&semaphore_create_info, None)
                 .expect("failed to create semaphore for render semaphore");
@@ -375,7 +4291,7 @@ impl FrameManager {
                 .create_fence(&fence_create_info, None)
                 .expect("failed to create fence for render fence");
 
-            (swapchain_semaphore, render_semaphore, render_fence)
+            (render_semaphore, render_fence)
         }
     }
 }
@@ -1,82 +1,768 @@
-use std::{ffi::CString, fs, io, path::Path, ptr, slice};
+use std::time::{Duration, Instant};
+use std::{ffi::CString, fs, io, mem, ptr};
 
-use super::structs::Vertex;
+use super::config::{GBufferFormats, NormalEncoding, RenderConfig, ShaderPaths};
+use super::constants::VIEW_COUNT;
+use super::pipeline_description::{BlendDescription, PipelineDescription, SpecConstant};
+use super::structs::{PipelineError, Vertex};
+use super::vertex_layout::VertexLayout;
 use ash::vk;
 use ash::vk::{DynamicState, PipelineColorBlendStateCreateInfo, PipelineDynamicStateCreateInfo};
 
 const FRAGMENT_SHADER: &str = "frag";
 const VERTEX_SHADER: &str = "vert";
+const MULTIVIEW_VERTEX_SHADER: &str = "vert_multiview";
+const INSTANCED_VERTEX_SHADER: &str = "vert_instanced";
+const INSTANCED_MULTIVIEW_VERTEX_SHADER: &str = "vert_instanced_multiview";
 const LIGHTING_SHADER: &str = "lighting";
-const SHADER_PATH: &str = ".\\resources\\shaders";
-const SHADER_EXTENSION: &str = ".spv";
+const LINEAR_DEPTH_SHADER: &str = "linear_depth";
+const SSR_SHADER: &str = "ssr";
+const SSAO_SHADER: &str = "ssao";
+const SSAO_BLUR_SHADER: &str = "ssao_blur";
+const QUAD_SHADER: &str = "quad";
+const SHADOW_SHADER: &str = "shadow";
+const SHADOW_CASTER_SHADER: &str = "shadow_caster";
+const PRESENT_SHADER: &str = "present";
+const FXAA_SHADER: &str = "fxaa";
+/// `NORMAL_ENCODING` in `normal_encoding.glsl`.
+const NORMAL_ENCODING_SPEC_ID: u32 = 1;
+/// `SSAO_ENABLED` in `lighting.frag`.
+const SSAO_SPEC_ID: u32 = 2;
+/// `REVERSE_Z` in `depth_range.glsl`.
+const REVERSE_Z_SPEC_ID: u32 = 3;
+
+/// What every G-buffer pipeline is built with, see `PipelineInfo::new_gbuffer_pipeline`.
+#[derive(Clone, Copy, Debug)]
+pub struct GBufferSettings {
+    /// Must match the G-buffer attachments the pipelines render into.
+    pub samples: vk::SampleCountFlags,
+    pub formats: GBufferFormats,
+    pub normal_encoding: NormalEncoding,
+    pub instancing: bool,
+    pub reverse_z: bool,
+}
+
+impl GBufferSettings {
+    pub fn new(config: &RenderConfig, samples: vk::SampleCountFlags) -> Self {
+        Self {
+            samples,
+            formats: config.gbuffer_formats,
+            normal_encoding: config.normal_encoding,
+            instancing: config.instancing,
+            reverse_z: config.reverse_z,
+        }
+    }
+}
 
 pub struct PipelineInfo {
+    /// A single pipeline, see `pipeline`.
     pub pipelines: Vec<vk::Pipeline>,
     pub pipeline_layout: vk::PipelineLayout,
+    /// Time spent in `create_graphics_pipelines`, shader module creation excluded. Pipelines
+    /// created in one batch all get the whole call's time.
+    pub creation_time: Duration,
+    /// Pipelines created by the same `create_graphics_pipelines` call, 1 unless built with
+    /// `PipelineBuilder::build_batch`.
+    pub batch_size: usize,
+    description: PipelineDescription,
 }
 
 impl PipelineInfo {
+    /// `set_layouts` end with the texture array, indexed by the albedo and normal map slots,
+    /// two `u32` fragment push constants. With `settings.instancing` every
+    /// draw needs an `InstanceData` buffer bound at binding 1, see `Vertex::instanced_layout`.
+    /// With `depth_equal` it only draws the fragments the depth prepass left in the depth
+    /// image, testing EQUAL without writing. `settings.reverse_z` tests GREATER instead of
+    /// LESS, see `RenderConfig::reverse_z`.
     pub fn new_gbuffer_pipeline(
         logical_device: &ash::Device,
-        set_layout: &vk::DescriptorSetLayout,
-    ) -> PipelineInfo {
-        let vert_shader_code =
-            Self::read_shader_file(VERTEX_SHADER).expect("Unable to read vertex file");
-        let frag_shader_code =
-            Self::read_shader_file(FRAGMENT_SHADER).expect("Unable to read fragment shader");
+        set_layouts: &[vk::DescriptorSetLayout],
+        settings: GBufferSettings,
+        depth_equal: bool,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        Self::gbuffer_builder(
+            set_layouts,
+            settings,
+            depth_equal,
+            shader_paths,
+            pipeline_cache,
+        )
+        .build(logical_device)
+    }
 
-        let vert_shader_module = Self::create_shader_module(&vert_shader_code, logical_device);
-        let frag_shader_module = Self::create_shader_module(&frag_shader_code, logical_device);
+    fn gbuffer_builder<'a>(
+        set_layouts: &'a [vk::DescriptorSetLayout],
+        settings: GBufferSettings,
+        depth_equal: bool,
+        shader_paths: &'a ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> PipelineBuilder<'a> {
+        let GBufferSettings {
+            samples,
+            formats,
+            normal_encoding,
+            instancing,
+            reverse_z,
+        } = settings;
+        let name = if depth_equal {
+            "gbuffer_depth_equal"
+        } else {
+            "gbuffer"
+        };
 
-        let shader_name = CString::new("main").unwrap();
+        // Albedo + normal
+        let builder = PipelineBuilder::gbuffer(name, instancing, shader_paths, pipeline_cache)
+            .fragment_shader(FRAGMENT_SHADER)
+            .set_layouts(set_layouts)
+            .color_attachment(formats.albedo, None)
+            .color_attachment(normal_encoding.format(), None)
+            .spec_constant(NORMAL_ENCODING_SPEC_ID, normal_encoding.spec_constant())
+            .push_constants(
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                mem::size_of::<[u32; 2]>() as u32,
+            )
+            .samples(samples);
+        if depth_equal {
+            builder.depth_test(formats.depth, vk::CompareOp::EQUAL)
+        } else {
+            builder
+                .depth(formats.depth, vk::CompareOp::LESS)
+                .reverse_z(reverse_z)
+        }
+    }
 
-        let vert_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::VERTEX)
-            .module(vert_shader_module)
-            .name(&shader_name);
+    /// Depth-only G-buffer geometry, no fragment stage, filling the depth image before the
+    /// `depth_equal` G-buffer pipeline draws, created in one batch with the latter.
+    /// `prepass_set_layouts` are the gbuffer and globals ones, `gbuffer_set_layouts` as for
+    /// `new_gbuffer_pipeline`. Returns the prepass pipeline first.
+    pub fn new_depth_prepass_pipelines(
+        logical_device: &ash::Device,
+        prepass_set_layouts: &[vk::DescriptorSetLayout],
+        gbuffer_set_layouts: &[vk::DescriptorSetLayout],
+        settings: GBufferSettings,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<(PipelineInfo, PipelineInfo), PipelineError> {
+        let prepass = PipelineBuilder::gbuffer(
+            "depth_prepass",
+            settings.instancing,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(prepass_set_layouts)
+        .depth(settings.formats.depth, vk::CompareOp::LESS)
+        .reverse_z(settings.reverse_z)
+        .samples(settings.samples);
+        let depth_equal = Self::gbuffer_builder(
+            gbuffer_set_layouts,
+            settings,
+            true,
+            shader_paths,
+            pipeline_cache,
+        );
 
-        let frag_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(frag_shader_module)
-            .name(&shader_name);
+        let mut pipelines =
+            PipelineBuilder::build_batch(logical_device, vec![prepass, depth_equal])?;
+        let depth_equal = pipelines.pop().unwrap();
+        Ok((pipelines.pop().unwrap(), depth_equal))
+    }
 
-        let shader_stages = [vert_shader_stage_create_info, frag_shader_stage_create_info];
+    /// `new_gbuffer_pipeline` without `depth_equal`, rasterizing only the triangles' edges,
+    /// with the line width set when recording through LINE_WIDTH. The device needs the
+    /// `fillModeNonSolid` feature.
+    pub fn new_gbuffer_wireframe_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        settings: GBufferSettings,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        Self::gbuffer_builder(set_layouts, settings, false, shader_paths, pipeline_cache)
+            .name("gbuffer_wireframe")
+            .polygon_mode(vk::PolygonMode::LINE)
+            .dynamic_state(DynamicState::LINE_WIDTH)
+            .build(logical_device)
+    }
 
-        let dynamic_states = vec![DynamicState::VIEWPORT, DynamicState::SCISSOR];
+    /// Depth-only pass from the light's point of view, no fragment stage. Takes the `uint`
+    /// cascade it renders as a push constant.
+    pub fn new_shadow_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        cull_mode: vk::CullModeFlags,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::shadow("shadow", SHADOW_SHADER, shader_paths, pipeline_cache)
+            .set_layouts(set_layouts)
+            .cull_mode(cull_mode)
+            .build(logical_device)
+    }
 
-        let dynamic_state_create_info =
-            PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+    /// The shadow pipeline for caster layers, taking the `uint` layer as a push constant.
+    pub fn new_shadow_caster_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        cull_mode: vk::CullModeFlags,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::shadow(
+            "shadow_caster",
+            SHADOW_CASTER_SHADER,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .cull_mode(cull_mode)
+        .build(logical_device)
+    }
 
-        let vertex_binding_description = Vertex::get_binding_descriptions();
-        let vertex_attribute_description = Vertex::get_attribute_descriptions();
+    /// `pcf_samples` becomes the shader's `PCF_SAMPLES` spec constant so the Poisson loop unrolls.
+    /// `fragment_shader` replaces the built-in shader, see `RenderConfig::lighting_shader`.
+    /// `ssao` says whether binding 9 holds ambient occlusion to multiply the ambient term by.
+    /// `reverse_z` says the sky is at depth 0.0 rather than 1.0, see `RenderConfig::reverse_z`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_lighing_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        pcf_samples: u32,
+        fragment_shader: Option<&str>,
+        draw_format: vk::Format,
+        normal_encoding: NormalEncoding,
+        ssao: bool,
+        reverse_z: bool,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen(
+            "lighting",
+            fragment_shader.unwrap_or(LIGHTING_SHADER),
+            draw_format,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .spec_constant(0, pcf_samples)
+        .spec_constant(NORMAL_ENCODING_SPEC_ID, normal_encoding.spec_constant())
+        .spec_constant(SSAO_SPEC_ID, ssao as u32)
+        .spec_constant(REVERSE_Z_SPEC_ID, reverse_z as u32)
+        .build(logical_device)
+    }
 
-        let vertex_input_info_create_info = vk::PipelineVertexInputStateCreateInfo::default()
-            .vertex_attribute_descriptions(&vertex_attribute_description)
-            .vertex_binding_descriptions(&vertex_binding_description);
+    pub fn new_linear_depth_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen(
+            "linear_depth",
+            LINEAR_DEPTH_SHADER,
+            vk::Format::R32_SFLOAT,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .build(logical_device)
+    }
 
-        let input_assembly_create_info = vk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-            .primitive_restart_enable(false);
+    /// Raw hemisphere occlusion into an R8_UNORM image, see `RenderConfig::ssao`.
+    pub fn new_ssao_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        normal_encoding: NormalEncoding,
+        reverse_z: bool,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen(
+            "ssao",
+            SSAO_SHADER,
+            vk::Format::R8_UNORM,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .spec_constant(NORMAL_ENCODING_SPEC_ID, normal_encoding.spec_constant())
+        .spec_constant(REVERSE_Z_SPEC_ID, reverse_z as u32)
+        .build(logical_device)
+    }
+
+    /// 4x4 box blur over the raw occlusion, matching the noise tile it removes.
+    pub fn new_ssao_blur_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen(
+            "ssao_blur",
+            SSAO_BLUR_SHADER,
+            vk::Format::R8_UNORM,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .build(logical_device)
+    }
+
+    pub fn new_ssr_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        draw_format: vk::Format,
+        normal_encoding: NormalEncoding,
+        reverse_z: bool,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen("ssr", SSR_SHADER, draw_format, shader_paths, pipeline_cache)
+            .set_layouts(set_layouts)
+            .spec_constant(NORMAL_ENCODING_SPEC_ID, normal_encoding.spec_constant())
+            .spec_constant(REVERSE_Z_SPEC_ID, reverse_z as u32)
+            .build(logical_device)
+    }
+
+    pub fn new_present_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        swapchain_format: vk::Format,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen(
+            "present",
+            PRESENT_SHADER,
+            swapchain_format,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .build(logical_device)
+    }
+
+    /// FXAA from the final image into the swapchain. `linear_input` says whether sampling
+    /// the source gives linear color, `encode_srgb` whether the shader has to encode what it
+    /// writes, as the present pass does.
+    pub fn new_fxaa_pipeline(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        swapchain_format: vk::Format,
+        linear_input: bool,
+        encode_srgb: bool,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        PipelineBuilder::fullscreen(
+            "fxaa",
+            FXAA_SHADER,
+            swapchain_format,
+            shader_paths,
+            pipeline_cache,
+        )
+        .set_layouts(set_layouts)
+        .spec_constant(0, linear_input as u32)
+        .spec_constant(1, encode_srgb as u32)
+        .build(logical_device)
+    }
+
+    /// The pipeline to bind. Panics unless creation returned exactly one.
+    pub fn pipeline(&self) -> vk::Pipeline {
+        assert_eq!(
+            self.pipelines.len(),
+            1,
+            "pipeline {} has {} handles instead of one",
+            self.description.name,
+            self.pipelines.len()
+        );
+        self.pipelines[0]
+    }
+
+    /// Destroys the pipelines and their layout. Nothing may still be using them.
+    pub fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            for &pipeline in self.pipelines.iter() {
+                device.destroy_pipeline(pipeline, None);
+            }
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+
+    /// The state this pipeline was built from.
+    pub fn describe(&self) -> &PipelineDescription {
+        &self.description
+    }
+
+    pub fn from_description(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        description: PipelineDescription,
+        shader_paths: &ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<PipelineInfo, PipelineError> {
+        let mut pipelines = Self::from_descriptions(
+            logical_device,
+            vec![(set_layouts, description, shader_paths)],
+            pipeline_cache,
+        )?;
+        Ok(pipelines.remove(0))
+    }
+
+    /// `from_description` for several pipelines at once, all created by a single
+    /// `create_graphics_pipelines` call so the driver can compile them in parallel. That
+    /// call's time is each one's `creation_time`, their `batch_size` says how many share it.
+    /// Fails as a whole, destroying whatever was already created.
+    fn from_descriptions(
+        logical_device: &ash::Device,
+        pipelines: Vec<(
+            &[vk::DescriptorSetLayout],
+            PipelineDescription,
+            &ShaderPaths,
+        )>,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Result<Vec<PipelineInfo>, PipelineError> {
+        let mut states: Vec<PipelineState> = vec![];
+        for (set_layouts, description, shader_paths) in pipelines {
+            match PipelineState::new(logical_device, set_layouts, description, shader_paths) {
+                Ok(state) => states.push(state),
+                Err(error) => {
+                    for state in states {
+                        state.destroy_shader_modules(logical_device);
+                        unsafe {
+                            logical_device.destroy_pipeline_layout(state.pipeline_layout, None)
+                        };
+                    }
+                    return Err(error);
+                }
+            }
+        }
+
+        let shader_name = CString::new("main").unwrap();
+        let specialization_infos = states
+            .iter()
+            .map(|state| {
+                vk::SpecializationInfo::default()
+                    .map_entries(&state.map_entries)
+                    .data(&state.spec_data)
+            })
+            .collect::<Vec<_>>();
+        let shader_stages = states
+            .iter()
+            .zip(specialization_infos.iter())
+            .map(|(state, specialization_info)| {
+                let mut stages = vec![vk::PipelineShaderStageCreateInfo::default()
+                    .stage(vk::ShaderStageFlags::VERTEX)
+                    .module(state.vert_shader_module)
+                    .name(&shader_name)];
+                if let Some(frag_shader_module) = state.frag_shader_module {
+                    let mut frag_stage = vk::PipelineShaderStageCreateInfo::default()
+                        .stage(vk::ShaderStageFlags::FRAGMENT)
+                        .module(frag_shader_module)
+                        .name(&shader_name);
+                    if !state.map_entries.is_empty() {
+                        frag_stage = frag_stage.specialization_info(specialization_info);
+                    }
+                    stages.push(frag_stage);
+                }
+                stages
+            })
+            .collect::<Vec<_>>();
+
+        let dynamic_state_create_infos = states
+            .iter()
+            .map(|state| {
+                PipelineDynamicStateCreateInfo::default().dynamic_states(&state.dynamic_states)
+            })
+            .collect::<Vec<_>>();
+        let vertex_input_create_infos = states
+            .iter()
+            .map(|state| {
+                vk::PipelineVertexInputStateCreateInfo::default()
+                    .vertex_attribute_descriptions(&state.vertex_attribute_descriptions)
+                    .vertex_binding_descriptions(&state.vertex_binding_descriptions)
+            })
+            .collect::<Vec<_>>();
+        // Logic ops would override blending and aren't allowed on float attachments
+        let color_blending_create_infos = states
+            .iter()
+            .map(|state| {
+                PipelineColorBlendStateCreateInfo::default()
+                    .logic_op_enable(false)
+                    .logic_op(vk::LogicOp::COPY)
+                    .attachments(&state.color_blend_attachments)
+            })
+            .collect::<Vec<_>>();
+        let mut rendering_infos = states
+            .iter()
+            .map(|state| {
+                let rendering_info = vk::PipelineRenderingCreateInfo::default()
+                    .view_mask(state.description.view_mask)
+                    .color_attachment_formats(&state.color_attachment_formats);
+                match &state.description.depth {
+                    Some(depth) => {
+                        rendering_info.depth_attachment_format(vk::Format::from_raw(depth.format))
+                    }
+                    None => rendering_info,
+                }
+            })
+            .collect::<Vec<_>>();
 
         let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::default()
             .viewport_count(1)
             .scissor_count(1);
 
-        let rasterizer_create_info = vk::PipelineRasterizationStateCreateInfo::default()
+        let pipeline_create_infos = states
+            .iter()
+            .zip(shader_stages.iter())
+            .zip(dynamic_state_create_infos.iter())
+            .zip(vertex_input_create_infos.iter())
+            .zip(color_blending_create_infos.iter())
+            .zip(rendering_infos.iter_mut())
+            .map(
+                |(
+                    ((((state, stages), dynamic_state), vertex_input), color_blending),
+                    rendering,
+                )| {
+                    vk::GraphicsPipelineCreateInfo::default()
+                        .stages(stages)
+                        .vertex_input_state(vertex_input)
+                        .input_assembly_state(&state.input_assembly)
+                        .viewport_state(&viewport_state_create_info)
+                        .rasterization_state(&state.rasterizer)
+                        .multisample_state(&state.multisampling)
+                        .color_blend_state(color_blending)
+                        .dynamic_state(dynamic_state)
+                        .layout(state.pipeline_layout)
+                        .subpass(0)
+                        .base_pipeline_handle(vk::Pipeline::null())
+                        .base_pipeline_index(-1)
+                        .depth_stencil_state(&state.depth_stencil)
+                        .push_next(rendering)
+                },
+            )
+            .collect::<Vec<_>>();
+
+        let start = Instant::now();
+        let graphics_pipelines = unsafe {
+            logical_device.create_graphics_pipelines(pipeline_cache, &pipeline_create_infos, None)
+        };
+        let creation_time = start.elapsed();
+        let batch_size = states.len();
+        for state in states.iter() {
+            state.destroy_shader_modules(logical_device);
+        }
+
+        let graphics_pipelines = graphics_pipelines.map_err(|(pipelines, result)| {
+            unsafe {
+                // Pipelines that failed are null, which destroying ignores
+                for pipeline in pipelines {
+                    logical_device.destroy_pipeline(pipeline, None);
+                }
+                for state in states.iter() {
+                    logical_device.destroy_pipeline_layout(state.pipeline_layout, None);
+                }
+            }
+            PipelineError::PipelineCreation(result)
+        })?;
+
+        Ok(states
+            .into_iter()
+            .zip(graphics_pipelines)
+            .map(|(state, pipeline)| {
+                match batch_size {
+                    1 => log::info!(
+                        "Created pipeline {} in {:?}",
+                        state.description.name,
+                        creation_time
+                    ),
+                    _ => log::info!(
+                        "Created pipeline {} in a batch of {} taking {:?}",
+                        state.description.name,
+                        batch_size,
+                        creation_time
+                    ),
+                }
+                Self {
+                    pipelines: vec![pipeline],
+                    pipeline_layout: state.pipeline_layout,
+                    creation_time,
+                    batch_size,
+                    description: state.description,
+                }
+            })
+            .collect())
+    }
+
+    fn is_strip_or_fan(topology: vk::PrimitiveTopology) -> bool {
+        matches!(
+            topology,
+            vk::PrimitiveTopology::LINE_STRIP
+                | vk::PrimitiveTopology::TRIANGLE_STRIP
+                | vk::PrimitiveTopology::TRIANGLE_FAN
+                | vk::PrimitiveTopology::LINE_STRIP_WITH_ADJACENCY
+                | vk::PrimitiveTopology::TRIANGLE_STRIP_WITH_ADJACENCY
+        )
+    }
+
+    /// Reads `shader_name` as `ShaderPaths::resolve` finds it.
+    pub fn read_shader_file(
+        shader_paths: &ShaderPaths,
+        shader_name: &str,
+    ) -> Result<Vec<u8>, io::Error> {
+        let path = shader_paths.resolve(shader_name);
+
+        log::debug!("Reading shader {:?}", path);
+        fs::read(path)
+    }
+
+    pub fn create_shader_module(
+        code: &[u8],
+        device: &ash::Device,
+    ) -> Result<vk::ShaderModule, PipelineError> {
+        unsafe {
+            let (_prefix, shorts, _suffix) = code.align_to::<u32>();
+            let create_info = vk::ShaderModuleCreateInfo::default().code(shorts);
+            device
+                .create_shader_module(&create_info, None)
+                .map_err(PipelineError::ShaderModuleCreation)
+        }
+    }
+}
+
+/// What a `vk::GraphicsPipelineCreateInfo` points at besides the parts shared by every
+/// pipeline, owned so a batch of them can be created in one call.
+struct PipelineState {
+    description: PipelineDescription,
+    vert_shader_module: vk::ShaderModule,
+    frag_shader_module: Option<vk::ShaderModule>,
+    pipeline_layout: vk::PipelineLayout,
+    map_entries: Vec<vk::SpecializationMapEntry>,
+    spec_data: Vec<u8>,
+    dynamic_states: Vec<DynamicState>,
+    vertex_binding_descriptions: Vec<vk::VertexInputBindingDescription>,
+    vertex_attribute_descriptions: Vec<vk::VertexInputAttributeDescription>,
+    color_blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    color_attachment_formats: Vec<vk::Format>,
+    input_assembly: vk::PipelineInputAssemblyStateCreateInfo<'static>,
+    rasterizer: vk::PipelineRasterizationStateCreateInfo<'static>,
+    multisampling: vk::PipelineMultisampleStateCreateInfo<'static>,
+    depth_stencil: vk::PipelineDepthStencilStateCreateInfo<'static>,
+}
+
+impl PipelineState {
+    /// Rejects descriptions Vulkan doesn't allow, which may have been deserialized from
+    /// anywhere, before anything is created for them.
+    fn validate_description(description: &PipelineDescription) -> Result<(), PipelineError> {
+        let topology = vk::PrimitiveTopology::from_raw(description.topology);
+        if description.primitive_restart && !PipelineInfo::is_strip_or_fan(topology) {
+            return Err(PipelineError::InvalidDescription {
+                pipeline: description.name.clone(),
+                reason: format!(
+                    "primitive restart needs a strip or fan topology, not {:?}",
+                    topology
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks the vertex shader `spirv` only reads what `description`'s vertex layout feeds it.
+    #[cfg(feature = "shader-validation")]
+    fn validate_vertex_inputs(
+        description: &PipelineDescription,
+        spirv: &[u8],
+    ) -> Result<(), PipelineError> {
+        description
+            .vertex_input
+            .clone()
+            .unwrap_or_default()
+            .validate_shader_inputs(spirv)
+            .map_err(|mismatches| PipelineError::ShaderInterfaceMismatch {
+                pipeline: description.name.clone(),
+                mismatches,
+            })
+    }
+
+    /// Loads the shaders and creates the layout, destroying them again on errors.
+    fn new(
+        logical_device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        description: PipelineDescription,
+        shader_paths: &ShaderPaths,
+    ) -> Result<Self, PipelineError> {
+        Self::validate_description(&description)?;
+        let vert_shader_code =
+            PipelineInfo::read_shader_file(shader_paths, &description.vertex_shader)
+                .map_err(PipelineError::ShaderReadError)?;
+        #[cfg(feature = "shader-validation")]
+        Self::validate_vertex_inputs(&description, &vert_shader_code)?;
+        let vert_shader_module =
+            PipelineInfo::create_shader_module(&vert_shader_code, logical_device)?;
+        let destroy_shader_modules = |frag_shader_module: Option<vk::ShaderModule>| unsafe {
+            logical_device.destroy_shader_module(vert_shader_module, None);
+            if let Some(frag_shader_module) = frag_shader_module {
+                logical_device.destroy_shader_module(frag_shader_module, None);
+            }
+        };
+
+        let frag_shader_module = description
+            .fragment_shader
+            .as_ref()
+            .map(|fragment_shader| {
+                let frag_shader_code =
+                    PipelineInfo::read_shader_file(shader_paths, fragment_shader)
+                        .map_err(PipelineError::ShaderReadError)?;
+                PipelineInfo::create_shader_module(&frag_shader_code, logical_device)
+            })
+            .transpose()
+            .inspect_err(|_| destroy_shader_modules(None))?;
+
+        let map_entries = description
+            .spec_constants
+            .iter()
+            .enumerate()
+            .map(|(i, constant)| {
+                vk::SpecializationMapEntry::default()
+                    .constant_id(constant.id)
+                    .offset((i * mem::size_of::<u32>()) as u32)
+                    .size(mem::size_of::<u32>())
+            })
+            .collect::<Vec<_>>();
+        let spec_data = description
+            .spec_constants
+            .iter()
+            .flat_map(|constant| constant.value.to_ne_bytes())
+            .collect::<Vec<_>>();
+
+        let dynamic_states = description
+            .dynamic_states
+            .iter()
+            .map(|&state| DynamicState::from_raw(state))
+            .collect::<Vec<_>>();
+
+        let vertex_layout = description.vertex_input.clone().unwrap_or_default();
+
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::from_raw(description.topology))
+            .primitive_restart_enable(description.primitive_restart);
+
+        let rasterizer = vk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(false)
-            .depth_bias_enable(false)
+            .depth_bias_enable(description.depth_bias)
             .rasterizer_discard_enable(false)
-            .polygon_mode(vk::PolygonMode::FILL)
-            .line_width(1.0_f32)
-            .cull_mode(vk::CullModeFlags::BACK)
-            .front_face(vk::FrontFace::CLOCKWISE)
-            .cull_mode(vk::CullModeFlags::BACK)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+            .polygon_mode(vk::PolygonMode::from_raw(description.polygon_mode))
+            .line_width(description.line_width)
+            .cull_mode(vk::CullModeFlags::from_raw(description.cull_mode))
+            .front_face(vk::FrontFace::from_raw(description.front_face));
 
-        let multisampling_create_info = vk::PipelineMultisampleStateCreateInfo {
+        let multisampling = vk::PipelineMultisampleStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_MULTISAMPLE_STATE_CREATE_INFO,
             sample_shading_enable: vk::FALSE,
-            rasterization_samples: vk::SampleCountFlags::TYPE_1,
+            rasterization_samples: vk::SampleCountFlags::from_raw(description.samples),
             min_sample_shading: 1.0,
             p_sample_mask: ptr::null(),
             alpha_to_coverage_enable: vk::FALSE,
@@ -84,206 +770,587 @@ impl PipelineInfo {
             ..Default::default()
         };
 
-        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::default()
-            .color_write_mask(vk::ColorComponentFlags::RGBA)
-            .blend_enable(false)
-            .src_color_blend_factor(vk::BlendFactor::ONE)
-            .dst_color_blend_factor(vk::BlendFactor::ZERO)
-            .color_blend_op(vk::BlendOp::ADD)
-            .src_alpha_blend_factor(vk::BlendFactor::ONE)
-            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
-            .alpha_blend_op(vk::BlendOp::ADD);
-
-        let color_blend_attachments = [color_blend_attachment];
-        let color_blending_create_info = PipelineColorBlendStateCreateInfo::default()
-            .logic_op_enable(false)
-            .logic_op(vk::LogicOp::COPY)
-            .attachments(&color_blend_attachments);
-
-        let pipeline_layout_create_info =
-            vk::PipelineLayoutCreateInfo::default().set_layouts(slice::from_ref(set_layout));
-
-        let pipeline_layout = unsafe {
-            logical_device
-                .create_pipeline_layout(&pipeline_layout_create_info, None)
-                .expect("Unable to create pipeline layout")
-        };
+        let color_blend_attachments = description
+            .color_attachments
+            .iter()
+            .map(|attachment| {
+                let color_blend_attachment = if attachment.blend {
+                    attachment.blend_equation.attachment_state()
+                } else {
+                    vk::PipelineColorBlendAttachmentState::default()
+                        .blend_enable(false)
+                        .src_color_blend_factor(vk::BlendFactor::ONE)
+                        .dst_color_blend_factor(vk::BlendFactor::ZERO)
+                        .color_blend_op(vk::BlendOp::ADD)
+                        .src_alpha_blend_factor(vk::BlendFactor::ONE)
+                        .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+                        .alpha_blend_op(vk::BlendOp::ADD)
+                };
 
-        let depth_stencil_state_create_info = vk::PipelineDepthStencilStateCreateInfo::default()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(vk::CompareOp::LESS)
-            .depth_bounds_test_enable(false)
-            .min_depth_bounds(0.0_f32)
-            .max_depth_bounds(1.0_f32)
-            .stencil_test_enable(false);
-
-        let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
-            .depth_attachment_format(vk::Format::D32_SFLOAT)
-            .color_attachment_formats(&[vk::Format::R16G16B16A16_SFLOAT]);
-
-        let pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
-            .stages(&shader_stages)
-            .vertex_input_state(&vertex_input_info_create_info)
-            .input_assembly_state(&input_assembly_create_info)
-            .viewport_state(&viewport_state_create_info)
-            .rasterization_state(&rasterizer_create_info)
-            .multisample_state(&multisampling_create_info)
-            .color_blend_state(&color_blending_create_info)
-            .dynamic_state(&dynamic_state_create_info)
-            .layout(pipeline_layout)
-            .subpass(0)
-            .base_pipeline_handle(vk::Pipeline::null())
-            .base_pipeline_index(-1)
-            .depth_stencil_state(&depth_stencil_state_create_info)
-            .push_next(&mut rendering_info);
+                color_blend_attachment.color_write_mask(vk::ColorComponentFlags::RGBA)
+            })
+            .collect::<Vec<_>>();
 
-        let graphics_pipelines = unsafe {
-            logical_device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None)
-                .expect("Unable to create graphics pipeline")
-        };
+        let push_constant_ranges = description.push_constant_ranges();
+        let mut pushed_stages = vk::ShaderStageFlags::empty();
+        for range in push_constant_ranges.iter() {
+            assert!(
+                !pushed_stages.intersects(range.stage_flags),
+                "pipeline {}: {:?} is in more than one push constant range",
+                description.name,
+                range.stage_flags & pushed_stages
+            );
+            pushed_stages |= range.stage_flags;
+        }
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+
+        let pipeline_layout =
+            unsafe { logical_device.create_pipeline_layout(&pipeline_layout_create_info, None) }
+                .map_err(|result| {
+                    destroy_shader_modules(frag_shader_module);
+                    PipelineError::PipelineCreation(result)
+                })?;
+
+        let depth_stencil = match &description.depth {
+            Some(depth) => vk::PipelineDepthStencilStateCreateInfo::default()
+                .depth_test_enable(depth.test)
+                .depth_write_enable(depth.write)
+                .depth_compare_op(vk::CompareOp::from_raw(depth.compare_op)),
+            None => vk::PipelineDepthStencilStateCreateInfo::default(),
+        }
+        .depth_bounds_test_enable(false)
+        .min_depth_bounds(0.0_f32)
+        .max_depth_bounds(1.0_f32)
+        .stencil_test_enable(false);
+
+        let color_attachment_formats = description
+            .color_attachments
+            .iter()
+            .map(|attachment| vk::Format::from_raw(attachment.format))
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            vert_shader_module,
+            frag_shader_module,
+            pipeline_layout,
+            map_entries,
+            spec_data,
+            dynamic_states,
+            vertex_binding_descriptions: vertex_layout.binding_descriptions(),
+            vertex_attribute_descriptions: vertex_layout.attribute_descriptions(),
+            color_blend_attachments,
+            color_attachment_formats,
+            input_assembly,
+            rasterizer,
+            multisampling,
+            depth_stencil,
+            description,
+        })
+    }
 
+    /// Once the pipelines are created the modules aren't needed anymore.
+    fn destroy_shader_modules(&self, logical_device: &ash::Device) {
         unsafe {
-            logical_device.destroy_shader_module(vert_shader_module, None);
-            logical_device.destroy_shader_module(frag_shader_module, None);
-        };
+            logical_device.destroy_shader_module(self.vert_shader_module, None);
+            if let Some(frag_shader_module) = self.frag_shader_module {
+                logical_device.destroy_shader_module(frag_shader_module, None);
+            }
+        }
+    }
+}
 
+/// The parts of a graphics pipeline that differ between passes, built into a `PipelineInfo`
+/// through `PipelineInfo::from_description`. Starts as `PipelineDescription::new` does,
+/// without a fragment stage, set layouts or attachments.
+pub struct PipelineBuilder<'a> {
+    description: PipelineDescription,
+    set_layouts: &'a [vk::DescriptorSetLayout],
+    shader_paths: &'a ShaderPaths,
+    pipeline_cache: vk::PipelineCache,
+}
+
+impl<'a> PipelineBuilder<'a> {
+    pub fn new(
+        name: &str,
+        vertex_shader: &str,
+        shader_paths: &'a ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Self {
         Self {
-            pipelines: graphics_pipelines,
-            pipeline_layout,
+            description: PipelineDescription::new(name, vertex_shader, None),
+            set_layouts: &[],
+            shader_paths,
+            pipeline_cache,
         }
     }
 
-    pub fn new_lighing_pipeline(
-        logical_device: &ash::Device,
-        set_layout: &vk::DescriptorSetLayout,
-    ) -> PipelineInfo {
-        let binding = [*set_layout];
-        let pipeline_layout_create_info =
-            vk::PipelineLayoutCreateInfo::default().set_layouts(&binding);
-
-        let pipeline_layout = unsafe {
-            logical_device
-                .create_pipeline_layout(&pipeline_layout_create_info, None)
-                .expect("Unable to create pipeline layout")
+    /// Fullscreen triangle pass: `quad` vertex shader plus the given fragment shader,
+    /// writing a single color attachment of `color_format`.
+    pub fn fullscreen(
+        name: &str,
+        fragment_shader: &str,
+        color_format: vk::Format,
+        shader_paths: &'a ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Self {
+        Self::new(name, QUAD_SHADER, shader_paths, pipeline_cache)
+            .fragment_shader(fragment_shader)
+            .color_attachment(color_format, None)
+            .vertex_input(None)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .dynamic_state(DynamicState::DEPTH_BIAS)
+    }
+
+    /// The G-buffer pass's vertex stage, culling and view mask, shared with its depth prepass
+    /// so both compute the same depth.
+    pub fn gbuffer(
+        name: &str,
+        instancing: bool,
+        shader_paths: &'a ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Self {
+        let vertex_shader = match (VIEW_COUNT > 1, instancing) {
+            (true, true) => INSTANCED_MULTIVIEW_VERTEX_SHADER,
+            (true, false) => MULTIVIEW_VERTEX_SHADER,
+            (false, true) => INSTANCED_VERTEX_SHADER,
+            (false, false) => VERTEX_SHADER,
+        };
+        let vertex_layout = if instancing {
+            Vertex::instanced_layout()
+        } else {
+            Vertex::layout()
+        };
+        let view_mask = if VIEW_COUNT > 1 {
+            (1 << VIEW_COUNT) - 1
+        } else {
+            0
         };
 
-        let lighting_shader_code =
-            Self::read_shader_file(LIGHTING_SHADER).expect("Unable to read lighting file");
+        Self::new(name, vertex_shader, shader_paths, pipeline_cache)
+            .cull_mode(vk::CullModeFlags::BACK)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
+            .vertex_input(Some(vertex_layout))
+            .view_mask(view_mask)
+    }
 
-        let lighting_shader_module =
-            Self::create_shader_module(&lighting_shader_code, logical_device);
+    /// Depth-only pass into a `D32_SFLOAT` shadow map, reading vertex positions and a `uint`
+    /// vertex push constant. Depth bias is on, with its factors set when recording. Shadow
+    /// maps keep the standard depth range under `RenderConfig::reverse_z`, so the positive
+    /// `ShadowDepthBias` factors still push depths away from the light; a reversed shadow
+    /// map would need them negated.
+    pub fn shadow(
+        name: &str,
+        vertex_shader: &str,
+        shader_paths: &'a ShaderPaths,
+        pipeline_cache: vk::PipelineCache,
+    ) -> Self {
+        Self::new(name, vertex_shader, shader_paths, pipeline_cache)
+            .depth(vk::Format::D32_SFLOAT, vk::CompareOp::LESS)
+            .depth_bias()
+            .vertex_input(Some(Vertex::position_layout()))
+            .vertex_push_constants(mem::size_of::<u32>() as u32)
+    }
 
-        let shader_name = CString::new("main").unwrap();
+    /// Replaces the name given to the constructor, used in logs and the creation report.
+    pub fn name(mut self, name: &str) -> Self {
+        self.description.name = name.to_string();
+        self
+    }
 
-        let frag_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(lighting_shader_module)
-            .name(&shader_name);
+    pub fn fragment_shader(mut self, fragment_shader: &str) -> Self {
+        self.description.fragment_shader = Some(fragment_shader.to_string());
+        self
+    }
 
-        let vert_shader_code =
-            Self::read_shader_file("quad").expect("Unable to read vertex shader file");
+    pub fn set_layouts(mut self, set_layouts: &'a [vk::DescriptorSetLayout]) -> Self {
+        self.set_layouts = set_layouts;
+        self
+    }
 
-        let vert_shader_module = Self::create_shader_module(&vert_shader_code, logical_device);
+    /// `None` for passes without vertex input.
+    pub fn vertex_input(mut self, vertex_input: Option<VertexLayout>) -> Self {
+        self.description.vertex_input = vertex_input;
+        self
+    }
 
-        let vert_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::VERTEX)
-            .module(vert_shader_module)
-            .name(&shader_name);
+    /// FILL unless set, see `PipelineDescription::polygon_mode`.
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.description.polygon_mode = polygon_mode.as_raw();
+        self
+    }
 
-        let shader_stages = [vert_shader_stage_create_info, frag_shader_stage_create_info];
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.description.cull_mode = cull_mode.as_raw();
+        self
+    }
 
-        // Rasterizer discard (bypassing rasterization completely)
-        let rasterizer_state = vk::PipelineRasterizationStateCreateInfo::default()
-            .depth_clamp_enable(false)
-            .rasterizer_discard_enable(false) // Key: Enable rasterizer discard
-            .polygon_mode(vk::PolygonMode::FILL)
-            .cull_mode(vk::CullModeFlags::NONE)
-            .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-            .depth_bias_enable(false)
-            .line_width(1.0);
-
-        // Color Blend State
-        let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::default()
-            .blend_enable(false)
-            .color_write_mask(vk::ColorComponentFlags::RGBA);
-
-        let binding = [color_blend_attachment_state];
-        let color_blend_state = PipelineColorBlendStateCreateInfo::default()
-            .logic_op_enable(false)
-            .attachments(&binding)
-            .blend_constants([0.0, 0.0, 0.0, 0.0]);
-
-        // No multisampling for a fullscreen quad
-        let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
-            .rasterization_samples(vk::SampleCountFlags::TYPE_1)
-            .sample_shading_enable(false)
-            .min_sample_shading(1.0);
-
-        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default();
-
-        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST) // Defines primitive topology (e.g., triangles)
-            .primitive_restart_enable(false); // No primitive restart
-
-        let dynamic_states = vec![
-            DynamicState::VIEWPORT,
-            DynamicState::SCISSOR,
-            DynamicState::DEPTH_BIAS,
-        ];
+    /// COUNTER_CLOCKWISE unless set, see `PipelineDescription::front_face`.
+    pub fn front_face(mut self, front_face: vk::FrontFace) -> Self {
+        self.description.front_face = front_face.as_raw();
+        self
+    }
 
-        let dynamic_state_create_info =
-            PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+    /// Depth tested and written. Without it the pass has no depth attachment.
+    pub fn depth(mut self, format: vk::Format, compare_op: vk::CompareOp) -> Self {
+        self.description = self.description.with_depth(format, compare_op);
+        self
+    }
 
-        let viewport_state_create_info = vk::PipelineViewportStateCreateInfo::default()
-            .viewport_count(1)
-            .scissor_count(1);
+    /// Depth tested without writing, see `PipelineDescription::with_depth_test`.
+    pub fn depth_test(mut self, format: vk::Format, compare_op: vk::CompareOp) -> Self {
+        self.description = self.description.with_depth_test(format, compare_op);
+        self
+    }
 
-        let mut rendering_info = vk::PipelineRenderingCreateInfo::default()
-            .color_attachment_formats(&[vk::Format::R16G16B16A16_SFLOAT]);
-
-        let pipeline_create_info = vk::GraphicsPipelineCreateInfo::default()
-            .stages(&shader_stages)
-            .vertex_input_state(&vertex_input_state)
-            .multisample_state(&multisample_state)
-            .rasterization_state(&rasterizer_state)
-            .color_blend_state(&color_blend_state)
-            .input_assembly_state(&input_assembly_state)
-            .dynamic_state(&dynamic_state_create_info)
-            .viewport_state(&viewport_state_create_info)
-            .layout(pipeline_layout)
-            .push_next(&mut rendering_info);
+    /// With `reverse_z`, flips the depth compare op set so far, LESS to GREATER and so on,
+    /// so it tests the same against a depth range running from 1.0 at the near plane to 0.0
+    /// at the far one. EQUAL, ALWAYS and NEVER stay as they are.
+    pub fn reverse_z(mut self, reverse_z: bool) -> Self {
+        if let Some(depth) = self.description.depth.as_mut().filter(|_| reverse_z) {
+            depth.compare_op =
+                reversed_compare_op(vk::CompareOp::from_raw(depth.compare_op)).as_raw();
+        }
+        self
+    }
 
-        let graphics_pipelines = unsafe {
-            logical_device
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_create_info], None)
-                .expect("Unable to create graphics pipeline")
+    /// Depth bias with dynamic factors, see `cmd_set_depth_bias`. The factors push depths
+    /// towards 1.0, so a pipeline with `reverse_z` needs them negated.
+    pub fn depth_bias(self) -> Self {
+        let mut builder = self.dynamic_state(DynamicState::DEPTH_BIAS);
+        builder.description.depth_bias = true;
+        builder
+    }
+
+    /// Blends with `blend`'s equation, otherwise writes replace the attachment.
+    pub fn color_attachment(mut self, format: vk::Format, blend: Option<BlendDescription>) -> Self {
+        self.description = match blend {
+            Some(blend_equation) => self
+                .description
+                .with_blended_color_attachment(format, blend_equation),
+            None => self.description.with_color_attachment(format),
         };
+        self
+    }
 
-        unsafe {
-            logical_device.destroy_shader_module(lighting_shader_module, None);
+    /// Fragment stage specialization constant.
+    pub fn spec_constant(mut self, id: u32, value: u32) -> Self {
+        self.description
+            .spec_constants
+            .push(SpecConstant { id, value });
+        self
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.description.samples = samples.as_raw();
+        self
+    }
+
+    /// Views rendered by each draw with multiview, 0 without.
+    pub fn view_mask(mut self, view_mask: u32) -> Self {
+        self.description.view_mask = view_mask;
+        self
+    }
+
+    /// Bytes of push constants the vertex stage reads from offset 0.
+    pub fn vertex_push_constants(mut self, size: u32) -> Self {
+        self.description.vertex_push_constant_size = size;
+        self
+    }
+
+    /// A push constant range besides the vertex one, see
+    /// `PipelineDescription::with_push_constants`.
+    pub fn push_constants(
+        mut self,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        size: u32,
+    ) -> Self {
+        self.description = self
+            .description
+            .with_push_constants(stage_flags, offset, size);
+        self
+    }
+
+    /// Adds to the viewport and scissor, which are always dynamic.
+    pub fn dynamic_state(mut self, state: DynamicState) -> Self {
+        self.description.dynamic_states.push(state.as_raw());
+        self
+    }
+
+    pub fn build(self, logical_device: &ash::Device) -> Result<PipelineInfo, PipelineError> {
+        PipelineInfo::from_description(
+            logical_device,
+            self.set_layouts,
+            self.description,
+            self.shader_paths,
+            self.pipeline_cache,
+        )
+    }
+
+    /// Builds every builder in one `create_graphics_pipelines` call, in order, see
+    /// `PipelineInfo::from_descriptions`. The builders must share a pipeline cache.
+    pub fn build_batch(
+        logical_device: &ash::Device,
+        builders: Vec<PipelineBuilder<'a>>,
+    ) -> Result<Vec<PipelineInfo>, PipelineError> {
+        let pipeline_cache = builders
+            .first()
+            .map_or(vk::PipelineCache::null(), |builder| builder.pipeline_cache);
+        assert!(
+            builders
+                .iter()
+                .all(|builder| builder.pipeline_cache == pipeline_cache),
+            "batched pipelines use different pipeline caches"
+        );
+        PipelineInfo::from_descriptions(
+            logical_device,
+            builders
+                .into_iter()
+                .map(|builder| {
+                    (
+                        builder.set_layouts,
+                        builder.description,
+                        builder.shader_paths,
+                    )
+                })
+                .collect(),
+            pipeline_cache,
+        )
+    }
+}
+
+/// `compare_op` with its sides swapped, testing the same in a reversed depth range.
+fn reversed_compare_op(compare_op: vk::CompareOp) -> vk::CompareOp {
+    match compare_op {
+        vk::CompareOp::LESS => vk::CompareOp::GREATER,
+        vk::CompareOp::LESS_OR_EQUAL => vk::CompareOp::GREATER_OR_EQUAL,
+        vk::CompareOp::GREATER => vk::CompareOp::LESS,
+        vk::CompareOp::GREATER_OR_EQUAL => vk::CompareOp::LESS_OR_EQUAL,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder(shader_paths: &ShaderPaths) -> PipelineBuilder<'_> {
+        PipelineBuilder::new("test", "test.vert", shader_paths, vk::PipelineCache::null())
+    }
+
+    #[test]
+    fn reversed_compare_op_swaps_the_sides() {
+        let pairs = [
+            (vk::CompareOp::LESS, vk::CompareOp::GREATER),
+            (
+                vk::CompareOp::LESS_OR_EQUAL,
+                vk::CompareOp::GREATER_OR_EQUAL,
+            ),
+            (vk::CompareOp::GREATER, vk::CompareOp::LESS),
+            (
+                vk::CompareOp::GREATER_OR_EQUAL,
+                vk::CompareOp::LESS_OR_EQUAL,
+            ),
+        ];
+        for (compare_op, reversed) in pairs {
+            assert_eq!(reversed_compare_op(compare_op), reversed);
+            assert_eq!(reversed_compare_op(reversed), compare_op);
+        }
+    }
+
+    #[test]
+    fn reversed_compare_op_keeps_symmetric_ops() {
+        for compare_op in [
+            vk::CompareOp::EQUAL,
+            vk::CompareOp::NOT_EQUAL,
+            vk::CompareOp::ALWAYS,
+            vk::CompareOp::NEVER,
+        ] {
+            assert_eq!(reversed_compare_op(compare_op), compare_op);
+        }
+    }
+
+    #[test]
+    fn reverse_z_flips_only_when_enabled() {
+        let shader_paths = ShaderPaths::default();
+        let compare_op = |reverse_z: bool| {
+            let builder = builder(&shader_paths)
+                .depth(vk::Format::D32_SFLOAT, vk::CompareOp::LESS)
+                .reverse_z(reverse_z);
+            vk::CompareOp::from_raw(builder.description.depth.unwrap().compare_op)
         };
-        Self {
-            pipelines: graphics_pipelines,
-            pipeline_layout,
+
+        assert_eq!(compare_op(false), vk::CompareOp::LESS);
+        assert_eq!(compare_op(true), vk::CompareOp::GREATER);
+        assert!(builder(&shader_paths)
+            .reverse_z(true)
+            .description
+            .depth
+            .is_none());
+    }
+
+    #[test]
+    fn only_strips_and_fans_can_restart() {
+        for topology in [
+            vk::PrimitiveTopology::LINE_STRIP,
+            vk::PrimitiveTopology::TRIANGLE_STRIP,
+            vk::PrimitiveTopology::TRIANGLE_FAN,
+            vk::PrimitiveTopology::LINE_STRIP_WITH_ADJACENCY,
+            vk::PrimitiveTopology::TRIANGLE_STRIP_WITH_ADJACENCY,
+        ] {
+            assert!(PipelineInfo::is_strip_or_fan(topology), "{:?}", topology);
+        }
+        for topology in [
+            vk::PrimitiveTopology::POINT_LIST,
+            vk::PrimitiveTopology::LINE_LIST,
+            vk::PrimitiveTopology::TRIANGLE_LIST,
+            vk::PrimitiveTopology::TRIANGLE_LIST_WITH_ADJACENCY,
+            vk::PrimitiveTopology::PATCH_LIST,
+        ] {
+            assert!(!PipelineInfo::is_strip_or_fan(topology), "{:?}", topology);
         }
     }
 
-    fn read_shader_file(shader_name: &str) -> Result<Vec<u8>, io::Error> {
-        let path = Path::new(SHADER_PATH).join(format!("{}{}", shader_name, SHADER_EXTENSION));
+    #[test]
+    fn primitive_restart_on_a_list_is_an_invalid_description() {
+        let shader_paths = ShaderPaths::default();
+        let mut description = builder(&shader_paths).description;
+        description.primitive_restart = true;
 
-        println!("{:?}", path);
-        fs::read(path)
+        description.topology = vk::PrimitiveTopology::TRIANGLE_STRIP.as_raw();
+        assert!(PipelineState::validate_description(&description).is_ok());
+
+        description.topology = vk::PrimitiveTopology::TRIANGLE_LIST.as_raw();
+        match PipelineState::validate_description(&description) {
+            Err(PipelineError::InvalidDescription { pipeline, reason }) => {
+                assert_eq!(pipeline, "test");
+                assert!(reason.contains("TRIANGLE_LIST"), "{}", reason);
+            }
+            other => panic!("expected an invalid description, got {:?}", other),
+        }
     }
 
-    fn create_shader_module(code: &[u8], device: &ash::Device) -> vk::ShaderModule {
-        unsafe {
-            let (_prefix, shorts, _suffix) = code.align_to::<u32>();
-            let create_info = vk::ShaderModuleCreateInfo::default().code(shorts);
-            device
-                .create_shader_module(&create_info, None)
-                .expect("Unable to create shader module")
+    #[test]
+    fn primitive_restart_is_off_by_default() {
+        let shader_paths = ShaderPaths::default();
+
+        assert!(!builder(&shader_paths).description.primitive_restart);
+    }
+
+    #[test]
+    fn front_face_overrides_the_default_winding() {
+        let shader_paths = ShaderPaths::default();
+        let builder = builder(&shader_paths).front_face(vk::FrontFace::CLOCKWISE);
+
+        assert_eq!(
+            builder.description.front_face,
+            vk::FrontFace::CLOCKWISE.as_raw()
+        );
+    }
+
+    #[test]
+    fn name_replaces_the_constructor_name() {
+        let shader_paths = ShaderPaths::default();
+
+        assert_eq!(
+            builder(&shader_paths).name("renamed").description.name,
+            "renamed"
+        );
+    }
+
+    /// A vertex shader whose only input is a `vec3` at location 0.
+    #[cfg(feature = "shader-validation")]
+    fn vec3_input_spirv() -> Vec<u8> {
+        let words: [u32; 24] = [
+            0x0723_0203,
+            0x0001_0000,
+            0,
+            5,
+            0,
+            // OpTypeFloat %1 32
+            (3 << 16) | 22,
+            1,
+            32,
+            // OpTypeVector %2 %1 3
+            (4 << 16) | 23,
+            2,
+            1,
+            3,
+            // OpTypePointer %3 Input %2
+            (4 << 16) | 32,
+            3,
+            1,
+            2,
+            // OpVariable %3 %4 Input
+            (4 << 16) | 59,
+            3,
+            4,
+            1,
+            // OpDecorate %4 Location 0
+            (4 << 16) | 71,
+            4,
+            30,
+            0,
+        ];
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[cfg(feature = "shader-validation")]
+    #[test]
+    fn mismatched_vertex_inputs_are_an_error() {
+        use crate::vulkan_render::vertex_layout::{InterfaceMismatch, ShaderInterfaceError};
+        use crate::vulkan_render::vertex_layout::{VertexAttribute, VertexBinding};
+
+        let shader_paths = ShaderPaths::default();
+        let mut description = builder(&shader_paths).description;
+        let layout = |format: vk::Format| VertexLayout {
+            bindings: vec![VertexBinding {
+                binding: 0,
+                stride: 12,
+                input_rate: vk::VertexInputRate::VERTEX.as_raw(),
+            }],
+            attributes: vec![VertexAttribute {
+                location: 0,
+                binding: 0,
+                format: format.as_raw(),
+                offset: 0,
+            }],
+        };
+
+        description.vertex_input = Some(layout(vk::Format::R32G32B32_SFLOAT));
+        assert!(PipelineState::validate_vertex_inputs(&description, &vec3_input_spirv()).is_ok());
+
+        description.vertex_input = Some(layout(vk::Format::R32G32B32_SINT));
+        match PipelineState::validate_vertex_inputs(&description, &vec3_input_spirv()) {
+            Err(PipelineError::ShaderInterfaceMismatch {
+                pipeline,
+                mismatches: ShaderInterfaceError::Mismatches(mismatches),
+            }) => {
+                assert_eq!(pipeline, "test");
+                assert!(matches!(
+                    mismatches[..],
+                    [InterfaceMismatch::IncompatibleFormat {
+                        location: 0,
+                        format: vk::Format::R32G32B32_SINT,
+                        ..
+                    }]
+                ));
+            }
+            other => panic!("expected an interface mismatch, got {:?}", other),
+        }
+
+        description.vertex_input = None;
+        match PipelineState::validate_vertex_inputs(&description, &vec3_input_spirv()) {
+            Err(PipelineError::ShaderInterfaceMismatch {
+                mismatches: ShaderInterfaceError::Mismatches(mismatches),
+                ..
+            }) => assert!(matches!(
+                mismatches[..],
+                [InterfaceMismatch::MissingAttribute { location: 0, .. }]
+            )),
+            other => panic!("expected a missing attribute, got {:?}", other),
         }
     }
 }
@@ -1,17 +1,37 @@
 use crate::vulkan_render::device::DeviceInfo;
+use crate::vulkan_render::format;
+use crate::vulkan_render::sync;
 use crate::vulkan_render::utils;
 use ash::vk::{DeviceMemory, Extent3D, Format, Image, ImageAspectFlags, ImageCreateInfo, ImageSubresourceLayers, ImageTiling, ImageUsageFlags, ImageView, MemoryPropertyFlags};
 use ash::{vk, Device, Instance};
+use std::slice;
 
+#[derive(Clone)]
 pub struct AllocatedImage {
     pub image: Image,
+    /// 2D view of layer 0, the whole image unless it's layered.
     pub image_view: ImageView,
+    /// 2D array view of every layer, for multiview rendering. `None` with a single layer.
+    pub array_view: Option<ImageView>,
+    /// Depth aspect only view of a depth/stencil image, as sampling requires. `None` for
+    /// images with a single aspect.
+    pub depth_view: Option<ImageView>,
+    /// View in another format that storage writes go through, for formats that can't be
+    /// storage images themselves. `None` unless made with `new_reinterpreted`.
+    pub storage_view: Option<ImageView>,
     pub image_memory: DeviceMemory,
     pub image_extent: Extent3D,
     pub image_format: Format,
+    /// Array layers, 1 unless made with `new_layered`.
+    pub layers: u32,
+    /// TYPE_1 unless made with `new_multisampled`.
+    pub samples: vk::SampleCountFlags,
+    /// Mip levels `image_view` covers, 1 unless made with `new_mipmapped`.
+    pub mip_levels: u32,
 }
 
 impl AllocatedImage {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device_info: &DeviceInfo,
         instance: &Instance,
@@ -22,6 +42,127 @@ impl AllocatedImage {
         tiling: ImageTiling,
         usage: ImageUsageFlags,
         mem_properties: MemoryPropertyFlags,
+    ) -> Self {
+        Self::new_layered(
+            device_info,
+            instance,
+            width,
+            height,
+            1,
+            format,
+            aspect_flags,
+            tiling,
+            usage,
+            mem_properties,
+        )
+    }
+
+    /// A 2D image with `layers` array layers, see `array_view`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_layered(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        width: u32,
+        height: u32,
+        layers: u32,
+        format: Format,
+        aspect_flags: ImageAspectFlags,
+        tiling: ImageTiling,
+        usage: ImageUsageFlags,
+        mem_properties: MemoryPropertyFlags,
+    ) -> Self {
+        Self::new_multisampled(
+            device_info,
+            instance,
+            width,
+            height,
+            layers,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            aspect_flags,
+            tiling,
+            usage,
+            mem_properties,
+        )
+    }
+
+    /// A layered image with `samples` samples per pixel. Multisampled images can only be
+    /// rendered into and resolved, not sampled or blitted.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_multisampled(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        width: u32,
+        height: u32,
+        layers: u32,
+        samples: vk::SampleCountFlags,
+        format: Format,
+        aspect_flags: ImageAspectFlags,
+        tiling: ImageTiling,
+        usage: ImageUsageFlags,
+        mem_properties: MemoryPropertyFlags,
+    ) -> Self {
+        Self::new_with_levels(
+            device_info,
+            instance,
+            width,
+            height,
+            layers,
+            samples,
+            1,
+            format,
+            aspect_flags,
+            tiling,
+            usage,
+            mem_properties,
+        )
+    }
+
+    /// An image with `mip_levels` mips, all of them in `image_view`, to fill with
+    /// `generate_mipmaps`. Mipmapped images are sampled, not rendered into.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_mipmapped(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        width: u32,
+        height: u32,
+        mip_levels: u32,
+        format: Format,
+        aspect_flags: ImageAspectFlags,
+        tiling: ImageTiling,
+        usage: ImageUsageFlags,
+        mem_properties: MemoryPropertyFlags,
+    ) -> Self {
+        Self::new_with_levels(
+            device_info,
+            instance,
+            width,
+            height,
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            mip_levels,
+            format,
+            aspect_flags,
+            tiling,
+            usage,
+            mem_properties,
+        )
+    }
+
+    /// A single-layer `format` image whose storage writes go through a `storage_format` view,
+    /// see `storage_view`. Like an R8G8B8A8_SRGB image a compute shader writes encoded bytes
+    /// into through R8G8B8A8_UNORM, where blits still decode it as sRGB. The formats must be
+    /// in the same compatibility class and `usage` include STORAGE.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_reinterpreted(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        width: u32,
+        height: u32,
+        format: Format,
+        storage_format: Format,
+        usage: ImageUsageFlags,
+        mem_properties: MemoryPropertyFlags,
     ) -> Self {
         let extent = Extent3D {
             width,
@@ -29,38 +170,142 @@ impl AllocatedImage {
             depth: 1,
         };
 
-        let image = Self::create_image(&device_info.logical_device, format, tiling, usage, extent);
+        // EXTENDED_USAGE allows STORAGE though `format` itself doesn't support it
+        let image = Self::create_image_with_flags(
+            &device_info.logical_device,
+            format,
+            ImageTiling::OPTIMAL,
+            usage,
+            extent,
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            1,
+            vk::ImageCreateFlags::MUTABLE_FORMAT | vk::ImageCreateFlags::EXTENDED_USAGE,
+        );
         let image_memory = Self::allocate_image(device_info, instance, &image, mem_properties);
-        let image_view = Self::create_image_view(device_info, &image, format, aspect_flags);
+        let image_view =
+            Self::create_image_view(device_info, &image, format, ImageAspectFlags::COLOR);
+        let storage_view =
+            Self::create_image_view(device_info, &image, storage_format, ImageAspectFlags::COLOR);
 
         Self {
             image,
             image_view,
+            array_view: None,
+            depth_view: None,
+            storage_view: Some(storage_view),
             image_memory,
             image_format: format,
             image_extent: extent,
+            layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn new_with_levels(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        width: u32,
+        height: u32,
+        layers: u32,
+        samples: vk::SampleCountFlags,
+        mip_levels: u32,
+        format: Format,
+        aspect_flags: ImageAspectFlags,
+        tiling: ImageTiling,
+        usage: ImageUsageFlags,
+        mem_properties: MemoryPropertyFlags,
+    ) -> Self {
+        let extent = Extent3D {
+            width,
+            height,
+            depth: 1,
+        };
+
+        let image = Self::create_image(
+            &device_info.logical_device,
+            format,
+            tiling,
+            usage,
+            extent,
+            layers,
+            samples,
+            mip_levels,
+        );
+        let image_memory = Self::allocate_image(device_info, instance, &image, mem_properties);
+        let image_view =
+            Self::create_mip_view(device_info, &image, format, aspect_flags, mip_levels);
+        let array_view = (layers > 1)
+            .then(|| Self::create_array_view(device_info, &image, format, aspect_flags, layers));
+        let depth_view = aspect_flags
+            .contains(ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL)
+            .then(|| Self::create_image_view(device_info, &image, format, ImageAspectFlags::DEPTH));
+
+        Self {
+            image,
+            image_view,
+            array_view,
+            depth_view,
+            storage_view: None,
+            image_memory,
+            image_format: format,
+            image_extent: extent,
+            layers,
+            samples,
+            mip_levels,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn create_image(
         device: &Device,
         format: Format,
         tiling: ImageTiling,
         usage: ImageUsageFlags,
-        extent: Extent3D
+        extent: Extent3D,
+        layers: u32,
+        samples: vk::SampleCountFlags,
+        mip_levels: u32,
+    ) -> Image {
+        Self::create_image_with_flags(
+            device,
+            format,
+            tiling,
+            usage,
+            extent,
+            layers,
+            samples,
+            mip_levels,
+            vk::ImageCreateFlags::empty(),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create_image_with_flags(
+        device: &Device,
+        format: Format,
+        tiling: ImageTiling,
+        usage: ImageUsageFlags,
+        extent: Extent3D,
+        layers: u32,
+        samples: vk::SampleCountFlags,
+        mip_levels: u32,
+        flags: vk::ImageCreateFlags,
     ) -> Image {
         let image_create_info = ImageCreateInfo::default()
             .image_type(vk::ImageType::TYPE_2D)
             .extent(extent)
-            .mip_levels(1)
-            .array_layers(1)
+            .mip_levels(mip_levels)
+            .array_layers(layers)
             .format(format)
             .tiling(tiling)
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .usage(usage)
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .flags(vk::ImageCreateFlags::empty());
+            .samples(samples)
+            .flags(flags);
 
         unsafe {
             device
@@ -106,11 +351,323 @@ impl AllocatedImage {
         allocated_memory
     }
 
+    /// The view to sample through, only the depth aspect of depth/stencil images.
+    pub fn sampled_view(&self) -> ImageView {
+        self.depth_view.unwrap_or(self.image_view)
+    }
+
+    /// The view to render into, covering every layer.
+    pub fn render_view(&self) -> ImageView {
+        self.array_view.unwrap_or(self.image_view)
+    }
+
+    /// Records a barrier moving every mip and layer from `old_layout` to `new_layout`. It
+    /// waits for the writes an image usually gets in `old_layout` and holds back the work
+    /// that usually uses it in `new_layout`, see `layout_usage`; layouts without a typical use,
+    /// like GENERAL, wait for and hold back everything. Coming from UNDEFINED discards the
+    /// contents. Depth and stencil images transition the aspects their format has.
+    pub fn transition_layout(
+        &self,
+        device_info: &DeviceInfo,
+        command_buffer: &vk::CommandBuffer,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    ) {
+        assert_ne!(
+            new_layout,
+            vk::ImageLayout::UNDEFINED,
+            "images can't be transitioned to UNDEFINED"
+        );
+        let (src_stage_mask, _, src_access_mask) = layout_usage(old_layout);
+        let (dst_stage_mask, dst_reads, dst_writes) = layout_usage(new_layout);
+
+        let barrier = vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(src_stage_mask)
+            .src_access_mask(src_access_mask)
+            .dst_stage_mask(dst_stage_mask)
+            .dst_access_mask(dst_reads | dst_writes)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(format_aspects(self.image_format))
+                    .base_mip_level(0)
+                    .level_count(vk::REMAINING_MIP_LEVELS)
+                    .base_array_layer(0)
+                    .layer_count(vk::REMAINING_ARRAY_LAYERS),
+            );
+
+        sync::pipeline_barrier(
+            device_info,
+            *command_buffer,
+            &vk::DependencyInfo::default().image_memory_barriers(slice::from_ref(&barrier)),
+        );
+    }
+
+    /// Records a blit of the whole image into the whole of `dst`, scaling and converting
+    /// formats as needed, over the layers both images have. `src_layout` and `dst_layout` are
+    /// the images' current layouts; they're left in TRANSFER_SRC_OPTIMAL and
+    /// TRANSFER_DST_OPTIMAL for the caller to transition on from. Both images need optimal
+    /// tiling and the TRANSFER_SRC and TRANSFER_DST usage respectively. Panics if the device
+    /// can't blit between the formats with `filter`. Depth/stencil images only blit to the same
+    /// format with NEAREST.
+    #[allow(clippy::too_many_arguments)]
+    pub fn blit_to(
+        &self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        command_buffer: &vk::CommandBuffer,
+        dst: &AllocatedImage,
+        src_layout: vk::ImageLayout,
+        dst_layout: vk::ImageLayout,
+        filter: vk::Filter,
+    ) {
+        let aspect_mask = format_aspects(self.image_format);
+        assert_eq!(
+            aspect_mask,
+            format_aspects(dst.image_format),
+            "can't blit {:?} to {:?}, their aspects differ",
+            self.image_format,
+            dst.image_format
+        );
+        check_blit_support(
+            device_info,
+            instance,
+            self.image_format,
+            dst.image_format,
+            filter,
+        );
+
+        let layer_count = self.layers.min(dst.layers);
+        let range = vk::ImageSubresourceRange::default()
+            .aspect_mask(aspect_mask)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(layer_count);
+
+        let to_transfer = [
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .src_access_mask(vk::AccessFlags2::MEMORY_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::BLIT)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_READ)
+                .old_layout(src_layout)
+                .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(self.image)
+                .subresource_range(range),
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .src_access_mask(vk::AccessFlags2::MEMORY_READ | vk::AccessFlags2::MEMORY_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags2::BLIT)
+                .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+                .old_layout(dst_layout)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(dst.image)
+                .subresource_range(range),
+        ];
+        sync::pipeline_barrier(
+            device_info,
+            *command_buffer,
+            &vk::DependencyInfo::default().image_memory_barriers(&to_transfer),
+        );
+
+        let region = self.full_blit_region(dst);
+        let blit_info = vk::BlitImageInfo2::default()
+            .src_image(self.image)
+            .src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .dst_image(dst.image)
+            .dst_image_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .filter(filter)
+            .regions(std::slice::from_ref(&region));
+
+        unsafe {
+            device_info
+                .logical_device
+                .cmd_blit_image2(*command_buffer, &blit_info)
+        }
+    }
+
+    /// Region of `blit_to`: mip 0 of the layers both images have, corner to corner.
+    fn full_blit_region(&self, dst: &AllocatedImage) -> vk::ImageBlit2<'static> {
+        let layers = ImageSubresourceLayers {
+            aspect_mask: format_aspects(self.image_format),
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: self.layers.min(dst.layers),
+        };
+        let corner = |extent: Extent3D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: extent.depth as i32,
+        };
+        vk::ImageBlit2::default()
+            .src_subresource(layers)
+            .src_offsets([vk::Offset3D::default(), corner(self.image_extent)])
+            .dst_subresource(layers)
+            .dst_offsets([vk::Offset3D::default(), corner(dst.image_extent)])
+    }
+
+    /// Records filling mips 1 and up by linearly downsampling the level above, over every
+    /// layer. Expects the whole image in TRANSFER_DST_OPTIMAL with mip 0 written and leaves it
+    /// in SHADER_READ_ONLY_OPTIMAL. Needs the TRANSFER_SRC usage and, with more than one mip,
+    /// a format `supports_linear_blit` accepts, panicking otherwise.
+    pub fn generate_mipmaps(
+        &self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        command_buffer: &vk::CommandBuffer,
+    ) {
+        assert!(
+            self.mip_levels == 1 || supports_linear_blit(device_info, instance, self.image_format),
+            "{:?} can't be linearly blitted to generate mipmaps",
+            self.image_format
+        );
+
+        let aspect_mask = format_aspects(self.image_format);
+        let level_barrier = |level: u32, old_layout, new_layout| {
+            let (src_stage_mask, src_access_mask) = match old_layout {
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+                    vk::PipelineStageFlags2::BLIT,
+                    vk::AccessFlags2::TRANSFER_READ,
+                ),
+                _ => (
+                    vk::PipelineStageFlags2::COPY | vk::PipelineStageFlags2::BLIT,
+                    vk::AccessFlags2::TRANSFER_WRITE,
+                ),
+            };
+            let (dst_stage_mask, dst_access_mask) = match new_layout {
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+                    vk::PipelineStageFlags2::BLIT,
+                    vk::AccessFlags2::TRANSFER_READ,
+                ),
+                _ => (
+                    vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                    vk::AccessFlags2::SHADER_SAMPLED_READ,
+                ),
+            };
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(src_stage_mask)
+                .src_access_mask(src_access_mask)
+                .dst_stage_mask(dst_stage_mask)
+                .dst_access_mask(dst_access_mask)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(self.image)
+                .subresource_range(
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(aspect_mask)
+                        .base_mip_level(level)
+                        .level_count(1)
+                        .base_array_layer(0)
+                        .layer_count(self.layers),
+                )
+        };
+        let barrier = |barriers: &[vk::ImageMemoryBarrier2]| {
+            sync::pipeline_barrier(
+                device_info,
+                *command_buffer,
+                &vk::DependencyInfo::default().image_memory_barriers(barriers),
+            )
+        };
+        let corner = |level: u32| {
+            let extent = format::mip_extent(
+                vk::Extent2D {
+                    width: self.image_extent.width,
+                    height: self.image_extent.height,
+                },
+                level,
+            );
+            vk::Offset3D {
+                x: extent.width as i32,
+                y: extent.height as i32,
+                z: 1,
+            }
+        };
+        let layers = |level: u32| ImageSubresourceLayers {
+            aspect_mask,
+            mip_level: level,
+            base_array_layer: 0,
+            layer_count: self.layers,
+        };
+
+        for level in 1..self.mip_levels {
+            let src_level = level - 1;
+            barrier(&[level_barrier(
+                src_level,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            )]);
+
+            let region = vk::ImageBlit2::default()
+                .src_subresource(layers(src_level))
+                .src_offsets([vk::Offset3D::default(), corner(src_level)])
+                .dst_subresource(layers(level))
+                .dst_offsets([vk::Offset3D::default(), corner(level)]);
+            let blit_info = vk::BlitImageInfo2::default()
+                .src_image(self.image)
+                .src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .dst_image(self.image)
+                .dst_image_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .filter(vk::Filter::LINEAR)
+                .regions(std::slice::from_ref(&region));
+            unsafe {
+                device_info
+                    .logical_device
+                    .cmd_blit_image2(*command_buffer, &blit_info)
+            }
+
+            barrier(&[level_barrier(
+                src_level,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )]);
+        }
+
+        barrier(&[level_barrier(
+            self.mip_levels - 1,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )]);
+    }
+
+    pub fn destroy_image(self, logical_device: &Device) {
+        unsafe {
+            let views = self.array_view.into_iter().chain(self.depth_view);
+            for view in views.chain(self.storage_view) {
+                logical_device.destroy_image_view(view, None);
+            }
+            logical_device.destroy_image_view(self.image_view, None);
+            logical_device.destroy_image(self.image, None);
+            logical_device.free_memory(self.image_memory, None);
+        };
+    }
+
     pub fn create_image_view(
         device_info: &DeviceInfo,
         image: &Image,
         format: Format,
         image_aspect_flags: ImageAspectFlags,
+    ) -> ImageView {
+        Self::create_mip_view(device_info, image, format, image_aspect_flags, 1)
+    }
+
+    /// 2D view of layer 0 covering mips `0..mip_levels`.
+    pub fn create_mip_view(
+        device_info: &DeviceInfo,
+        image: &Image,
+        format: Format,
+        image_aspect_flags: ImageAspectFlags,
+        mip_levels: u32,
     ) -> ImageView {
         let view_info = vk::ImageViewCreateInfo::default()
             .image(*image)
@@ -120,7 +677,7 @@ impl AllocatedImage {
                 vk::ImageSubresourceRange::default()
                     .aspect_mask(image_aspect_flags)
                     .base_mip_level(0)
-                    .level_count(1)
+                    .level_count(mip_levels)
                     .base_array_layer(0)
                     .layer_count(1),
             );
@@ -132,6 +689,192 @@ impl AllocatedImage {
                 .expect("failed to create image view")
         }
     }
+
+    pub fn create_array_view(
+        device_info: &DeviceInfo,
+        image: &Image,
+        format: Format,
+        image_aspect_flags: ImageAspectFlags,
+        layers: u32,
+    ) -> ImageView {
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(*image)
+            .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+            .format(format)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(image_aspect_flags)
+                    .base_mip_level(0)
+                    .level_count(1)
+                    .base_array_layer(0)
+                    .layer_count(layers),
+            );
+
+        unsafe {
+            device_info
+                .logical_device
+                .create_image_view(&view_info, None)
+                .expect("failed to create image array view")
+        }
+    }
+
+    /// 2D view of array layer `layer` only.
+    pub fn create_layer_view(
+        device_info: &DeviceInfo,
+        image: &Image,
+        format: Format,
+        image_aspect_flags: ImageAspectFlags,
+        layer: u32,
+    ) -> ImageView {
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(*image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(image_aspect_flags)
+                    .base_mip_level(0)
+                    .level_count(1)
+                    .base_array_layer(layer)
+                    .layer_count(1),
+            );
+
+        unsafe {
+            device_info
+                .logical_device
+                .create_image_view(&view_info, None)
+                .expect("failed to create image layer view")
+        }
+    }
+}
+
+/// Stages that use an image in `layout`, with the accesses they read and write it through,
+/// for `AllocatedImage::transition_layout`.
+fn layout_usage(
+    layout: vk::ImageLayout,
+) -> (vk::PipelineStageFlags2, vk::AccessFlags2, vk::AccessFlags2) {
+    use vk::{AccessFlags2 as Access, ImageLayout as Layout, PipelineStageFlags2 as Stage};
+
+    let fragment_tests = Stage::EARLY_FRAGMENT_TESTS | Stage::LATE_FRAGMENT_TESTS;
+    match layout {
+        Layout::UNDEFINED => (Stage::NONE, Access::NONE, Access::NONE),
+        Layout::TRANSFER_SRC_OPTIMAL => (Stage::ALL_TRANSFER, Access::TRANSFER_READ, Access::NONE),
+        Layout::TRANSFER_DST_OPTIMAL => (Stage::ALL_TRANSFER, Access::NONE, Access::TRANSFER_WRITE),
+        Layout::COLOR_ATTACHMENT_OPTIMAL => (
+            Stage::COLOR_ATTACHMENT_OUTPUT,
+            Access::COLOR_ATTACHMENT_READ,
+            Access::COLOR_ATTACHMENT_WRITE,
+        ),
+        Layout::DEPTH_ATTACHMENT_OPTIMAL | Layout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => (
+            fragment_tests,
+            Access::DEPTH_STENCIL_ATTACHMENT_READ,
+            Access::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        Layout::DEPTH_READ_ONLY_OPTIMAL | Layout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => (
+            fragment_tests | Stage::FRAGMENT_SHADER | Stage::COMPUTE_SHADER,
+            Access::DEPTH_STENCIL_ATTACHMENT_READ | Access::SHADER_SAMPLED_READ,
+            Access::NONE,
+        ),
+        Layout::SHADER_READ_ONLY_OPTIMAL => (
+            Stage::FRAGMENT_SHADER | Stage::COMPUTE_SHADER,
+            Access::SHADER_SAMPLED_READ,
+            Access::NONE,
+        ),
+        // Presentation needs no access, the semaphores order it
+        Layout::PRESENT_SRC_KHR => (Stage::ALL_COMMANDS, Access::NONE, Access::NONE),
+        _ => (
+            Stage::ALL_COMMANDS,
+            Access::MEMORY_READ,
+            Access::MEMORY_WRITE,
+        ),
+    }
+}
+
+/// Aspects a view or copy of the whole of a `format` image covers.
+fn format_aspects(format: Format) -> ImageAspectFlags {
+    match format {
+        Format::D16_UNORM | Format::X8_D24_UNORM_PACK32 | Format::D32_SFLOAT => {
+            ImageAspectFlags::DEPTH
+        }
+        Format::S8_UINT => ImageAspectFlags::STENCIL,
+        Format::D16_UNORM_S8_UINT | Format::D24_UNORM_S8_UINT | Format::D32_SFLOAT_S8_UINT => {
+            ImageAspectFlags::DEPTH | ImageAspectFlags::STENCIL
+        }
+        _ => ImageAspectFlags::COLOR,
+    }
+}
+
+/// Whether optimal tiling `format` images can be blitted to themselves with LINEAR
+/// filtering, as `AllocatedImage::generate_mipmaps` does.
+pub fn supports_linear_blit(device_info: &DeviceInfo, instance: &Instance, format: Format) -> bool {
+    let features = unsafe {
+        instance
+            .get_physical_device_format_properties(device_info._physical_device, format)
+            .optimal_tiling_features
+    };
+    features.contains(
+        vk::FormatFeatureFlags::BLIT_SRC
+            | vk::FormatFeatureFlags::BLIT_DST
+            | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+    )
+}
+
+/// Whether optimal tiling `format` images can be copied into and sampled, what a texture
+/// upload needs.
+pub fn supports_texture_upload(
+    device_info: &DeviceInfo,
+    instance: &Instance,
+    format: Format,
+) -> bool {
+    let features = unsafe {
+        instance
+            .get_physical_device_format_properties(device_info._physical_device, format)
+            .optimal_tiling_features
+    };
+    features.contains(vk::FormatFeatureFlags::TRANSFER_DST | vk::FormatFeatureFlags::SAMPLED_IMAGE)
+}
+
+/// Panics unless optimal tiling `src` images can be blitted to `dst` images with `filter`.
+fn check_blit_support(
+    device_info: &DeviceInfo,
+    instance: &Instance,
+    src: Format,
+    dst: Format,
+    filter: vk::Filter,
+) {
+    let features = |format| unsafe {
+        instance
+            .get_physical_device_format_properties(device_info._physical_device, format)
+            .optimal_tiling_features
+    };
+    let src_features = features(src);
+    let dst_features = features(dst);
+
+    assert!(
+        src_features.contains(vk::FormatFeatureFlags::BLIT_SRC),
+        "{:?} can't be a blit source",
+        src
+    );
+    assert!(
+        dst_features.contains(vk::FormatFeatureFlags::BLIT_DST),
+        "{:?} can't be a blit destination",
+        dst
+    );
+    if filter == vk::Filter::LINEAR {
+        assert!(
+            src_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR),
+            "{:?} can't be blitted with LINEAR filtering",
+            src
+        );
+    }
+    if format_aspects(src) != ImageAspectFlags::COLOR {
+        assert!(
+            src == dst && filter == vk::Filter::NEAREST,
+            "depth/stencil blits need matching formats and NEAREST, got {:?} to {:?}",
+            src,
+            dst
+        );
+    }
 }
 
 pub fn copy_image_to_image(
@@ -141,6 +884,7 @@ pub fn copy_image_to_image(
     dst_image: Image,
     src_size: vk::Extent2D,
     dst_size: vk::Extent2D,
+    filter: vk::Filter,
 ) {
     let mut blit_region = vk::ImageBlit2::default()
         .src_subresource(ImageSubresourceLayers {
@@ -171,66 +915,60 @@ pub fn copy_image_to_image(
         .src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
         .dst_image(dst_image)
         .dst_image_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
-        .filter(vk::Filter::LINEAR)
+        .filter(filter)
         .regions(&regions);
 
     unsafe { device.cmd_blit_image2(*command_buffer, &blit_info) }
 }
 
-pub fn transition_image_layout(
+/// Ends the gbuffer pass's depth writes. DEPTH_READ_ONLY_OPTIMAL serves both the fullscreen
+/// passes sampling depth and later passes that load it to depth test without writing, so
+/// no further transitions are needed for the rest of the frame.
+pub fn depth_write_to_read_only(
     device_info: &DeviceInfo,
     command_buffer: &vk::CommandBuffer,
     image: Image,
-    old_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout,
-    depth: bool,
 ) {
-    let mut aspect_mask = ImageAspectFlags::COLOR;
-    if depth {
-        aspect_mask = ImageAspectFlags::DEPTH;
-    }
-
-    let src_access_mask;
-    let dst_access_mask;
-    let source_stage;
-    let destination_stage;
-
-    src_access_mask = vk::AccessFlags::MEMORY_WRITE;
-    dst_access_mask = vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE;
-
-    source_stage = vk::PipelineStageFlags::ALL_COMMANDS;
-    destination_stage = vk::PipelineStageFlags::ALL_COMMANDS;
-
-    let barrier = vk::ImageMemoryBarrier::default()
-        .src_access_mask(src_access_mask)
-        .dst_access_mask(dst_access_mask)
-        .old_layout(old_layout)
-        .new_layout(new_layout)
+    // A multisample depth resolve writes in the color attachment output stage
+    let barrier = vk::ImageMemoryBarrier2::default()
+        .src_stage_mask(
+            vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+        )
+        .src_access_mask(
+            vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+        )
+        .dst_stage_mask(
+            vk::PipelineStageFlags2::FRAGMENT_SHADER
+                | vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+        )
+        .dst_access_mask(
+            vk::AccessFlags2::SHADER_SAMPLED_READ | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ,
+        )
+        .old_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+        .new_layout(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
         .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
         .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
         .image(image)
         .subresource_range(
             vk::ImageSubresourceRange::default()
-                .aspect_mask(aspect_mask)
+                .aspect_mask(ImageAspectFlags::DEPTH)
                 .base_mip_level(0)
                 .level_count(1)
                 .base_array_layer(0)
-                .layer_count(1),
+                .layer_count(vk::REMAINING_ARRAY_LAYERS),
         );
 
-    unsafe {
-        device_info.logical_device.cmd_pipeline_barrier(
-            *command_buffer,
-            source_stage,
-            destination_stage,
-            vk::DependencyFlags::empty(),
-            &[],
-            &[],
-            &[barrier],
-        )
-    }
+    let dependency_info =
+        vk::DependencyInfo::default().image_memory_barriers(std::slice::from_ref(&barrier));
+
+    sync::pipeline_barrier(device_info, *command_buffer, &dependency_info);
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_image(
     device_info: &DeviceInfo,
     instance: &Instance,
@@ -292,5 +1030,92 @@ pub fn create_image(
             .expect("failed to bind image memory");
     }
 
-    return (x, allocated_memory);
+    (x, allocated_memory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    /// Image without any Vulkan objects behind it, for the parts that only read its fields.
+    fn image(width: u32, height: u32, format: Format, layers: u32) -> AllocatedImage {
+        AllocatedImage {
+            image: Image::null(),
+            image_view: ImageView::null(),
+            array_view: None,
+            depth_view: None,
+            storage_view: None,
+            image_memory: DeviceMemory::null(),
+            image_extent: Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+            image_format: format,
+            layers,
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+        }
+    }
+
+    #[test]
+    fn full_blit_region_spans_both_extents() {
+        let src = image(1920, 1080, Format::R16G16B16A16_SFLOAT, 1);
+        let dst = image(960, 540, Format::R8G8B8A8_UNORM, 1);
+
+        let region = src.full_blit_region(&dst);
+
+        let origin = vk::Offset3D::default();
+        assert_eq!(
+            region.src_offsets,
+            [
+                origin,
+                vk::Offset3D {
+                    x: 1920,
+                    y: 1080,
+                    z: 1
+                }
+            ]
+        );
+        assert_eq!(
+            region.dst_offsets,
+            [
+                origin,
+                vk::Offset3D {
+                    x: 960,
+                    y: 540,
+                    z: 1
+                }
+            ]
+        );
+        assert_eq!(region.src_subresource.aspect_mask, ImageAspectFlags::COLOR);
+        assert_eq!(region.src_subresource.mip_level, 0);
+    }
+
+    #[test]
+    fn full_blit_region_covers_the_shared_layers() {
+        let src = image(64, 64, Format::D32_SFLOAT, 4);
+        let dst = image(64, 64, Format::D32_SFLOAT, 2);
+
+        let region = src.full_blit_region(&dst);
+
+        for layers in [region.src_subresource, region.dst_subresource] {
+            assert_eq!(layers.aspect_mask, ImageAspectFlags::DEPTH);
+            assert_eq!(layers.base_array_layer, 0);
+            assert_eq!(layers.layer_count, 2);
+        }
+    }
+
+    #[test]
+    fn sampled_view_prefers_the_depth_only_view() {
+        let mut depth_stencil = image(64, 64, Format::D24_UNORM_S8_UINT, 1);
+        depth_stencil.image_view = ImageView::from_raw(1);
+        depth_stencil.depth_view = Some(ImageView::from_raw(2));
+        assert_eq!(depth_stencil.sampled_view(), ImageView::from_raw(2));
+
+        let mut depth = image(64, 64, Format::D32_SFLOAT, 1);
+        depth.image_view = ImageView::from_raw(1);
+        assert_eq!(depth.sampled_view(), ImageView::from_raw(1));
+    }
 }
@@ -1,7 +1,16 @@
 pub mod buffer;
+mod compute_pipeline;
+pub mod config;
 mod constants;
 mod device;
+pub mod format;
 mod graphics_pipeline;
+mod pipeline_cache;
+pub mod pipeline_description;
+pub mod raw_handles;
+pub mod readback_pool;
+pub mod vertex_layout;
+mod present;
 pub mod structs;
 mod surface;
 mod swapchain;
@@ -9,6 +18,17 @@ pub mod vulkan_backend;
 pub mod scene;
 pub mod image_util;
 mod camera;
+mod deletion_queue;
 mod descriptor;
 mod utils;
 mod frame_manager;
+mod semaphore_pool;
+mod ssao;
+mod exposure;
+mod bloom;
+mod sync;
+mod upload;
+mod validation;
+mod worker_pool;
+#[cfg(feature = "shader-validation")]
+mod spirv_reflect;
@@ -0,0 +1,101 @@
+use crate::vulkan_render::device::DeviceInfo;
+use ash::{vk, Instance};
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// `VkPipelineCacheHeaderVersionOne`: length, version, vendor and device id, then the UUID.
+/// Always little-endian.
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// The `vk::PipelineCache` every pipeline is created through, loaded from and saved to
+/// `RenderConfig::pipeline_cache_path` when one is set.
+pub struct PipelineCacheManager {
+    pub cache: vk::PipelineCache,
+    path: Option<PathBuf>,
+    /// Bytes of the file the cache started from, 0 when it started empty.
+    pub loaded_size: usize,
+    /// Time spent reading the file and in `create_pipeline_cache`.
+    pub creation_time: Duration,
+}
+
+impl PipelineCacheManager {
+    /// Starts from the file at `path` if it was written by the same driver and device,
+    /// empty otherwise.
+    pub fn new(device_info: &DeviceInfo, instance: &Instance, path: Option<&Path>) -> Self {
+        let start = Instant::now();
+        let initial_data = path
+            .and_then(|path| match fs::read(path) {
+                Ok(data) => Some(data),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                Err(error) => {
+                    log::warn!("Ignoring pipeline cache {:?}: {}", path, error);
+                    None
+                }
+            })
+            .filter(|data| {
+                let compatible = Self::is_compatible(device_info, instance, data);
+                if !compatible {
+                    log::info!("Ignoring pipeline cache from another driver or device");
+                }
+                compatible
+            })
+            .unwrap_or_default();
+
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&initial_data);
+        let cache = unsafe {
+            device_info
+                .logical_device
+                .create_pipeline_cache(&create_info, None)
+                .expect("failed to create pipeline cache")
+        };
+
+        Self {
+            cache,
+            path: path.map(Path::to_path_buf),
+            loaded_size: initial_data.len(),
+            creation_time: start.elapsed(),
+        }
+    }
+
+    /// Writes the cache to its file, doing nothing without a `pipeline_cache_path` or once
+    /// destroyed.
+    pub fn save(&self, device: &ash::Device) -> io::Result<()> {
+        let Some(path) = self
+            .path
+            .as_ref()
+            .filter(|_| self.cache != vk::PipelineCache::null())
+        else {
+            return Ok(());
+        };
+        let data = unsafe { device.get_pipeline_cache_data(self.cache) }
+            .map_err(|result| io::Error::other(format!("get_pipeline_cache_data: {}", result)))?;
+        fs::write(path, data)
+    }
+
+    /// Later calls do nothing.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        let cache = mem::replace(&mut self.cache, vk::PipelineCache::null());
+        unsafe { device.destroy_pipeline_cache(cache, None) };
+    }
+
+    /// Whether `data` starts with a version one header matching this device's vendor,
+    /// device id and pipeline cache UUID.
+    fn is_compatible(device_info: &DeviceInfo, instance: &Instance, data: &[u8]) -> bool {
+        if data.len() < HEADER_SIZE {
+            return false;
+        }
+        let word =
+            |index: usize| u32::from_le_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+        let properties =
+            unsafe { instance.get_physical_device_properties(device_info._physical_device) };
+
+        word(0) as usize >= HEADER_SIZE
+            && word(1) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+            && word(2) == properties.vendor_id
+            && word(3) == properties.device_id
+            && data[16..HEADER_SIZE] == properties.pipeline_cache_uuid
+    }
+}
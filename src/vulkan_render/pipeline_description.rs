@@ -0,0 +1,361 @@
+use ash::vk;
+use serde::{Deserialize, Serialize};
+
+use super::structs::Vertex;
+use super::vertex_layout::VertexLayout;
+
+/// Every state field that goes into a graphics pipeline. `PipelineInfo::describe` returns
+/// it for bug reports and `PipelineInfo::from_description` rebuilds the same pipeline.
+/// Vulkan enums and flags are stored as their raw values.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PipelineDescription {
+    pub name: String,
+    /// Shader names resolved through `RenderConfig::shader_paths`, or `.spv` paths.
+    pub vertex_shader: String,
+    pub fragment_shader: Option<String>,
+    /// `None` for passes without vertex input (fullscreen passes).
+    pub vertex_input: Option<VertexLayout>,
+    pub topology: i32,
+    /// Restart strips and fans at the all-ones index. List topologies can't restart.
+    #[serde(default)]
+    pub primitive_restart: bool,
+    /// FILL unless set. LINE and POINT need the `fillModeNonSolid` device feature.
+    #[serde(default = "fill")]
+    pub polygon_mode: i32,
+    /// Width of rasterized lines, set when recording instead when LINE_WIDTH is a dynamic
+    /// state. Widths other than 1.0 need the `wideLines` device feature.
+    #[serde(default = "unit_line_width")]
+    pub line_width: f32,
+    pub cull_mode: u32,
+    /// Winding of front faces in framebuffer space. The projections flip Y instead of the
+    /// viewport, which keeps counter-clockwise model winding counter-clockwise on screen, so
+    /// pipelines drawing meshes want COUNTER_CLOCKWISE. A negative-height viewport would
+    /// invert it.
+    pub front_face: i32,
+    /// Rasterizer depth bias, with factors from `cmd_set_depth_bias` when DEPTH_BIAS is a
+    /// dynamic state.
+    #[serde(default)]
+    pub depth_bias: bool,
+    pub depth: Option<DepthDescription>,
+    pub color_attachments: Vec<ColorAttachmentDescription>,
+    pub dynamic_states: Vec<i32>,
+    /// Fragment stage specialization constants.
+    pub spec_constants: Vec<SpecConstant>,
+    /// Views rendered by each draw with multiview, 0 without.
+    #[serde(default)]
+    pub view_mask: u32,
+    /// Bytes of push constants the vertex stage reads from offset 0, 0 for none.
+    #[serde(default)]
+    pub vertex_push_constant_size: u32,
+    /// Further push constant ranges in the pipeline layout, besides the vertex one. Each
+    /// stage may be in one range at most.
+    #[serde(default)]
+    pub push_constant_ranges: Vec<PushConstantRangeDescription>,
+    /// Rasterization samples, matching the attachments' sample count.
+    #[serde(default = "single_sample")]
+    pub samples: u32,
+}
+
+fn single_sample() -> u32 {
+    vk::SampleCountFlags::TYPE_1.as_raw()
+}
+
+fn fill() -> i32 {
+    vk::PolygonMode::FILL.as_raw()
+}
+
+fn unit_line_width() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DepthDescription {
+    pub format: i32,
+    pub test: bool,
+    pub write: bool,
+    pub compare_op: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ColorAttachmentDescription {
+    pub format: i32,
+    /// Blends with `blend_equation`, otherwise writes replace the attachment.
+    pub blend: bool,
+    /// Standard alpha blending unless set otherwise, unused without `blend`.
+    #[serde(default)]
+    pub blend_equation: BlendDescription,
+}
+
+/// Per attachment blend factors and ops, `vk::BlendFactor` and `vk::BlendOp` raw values.
+/// Float attachments blend too, logic ops are never enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlendDescription {
+    pub src_color_factor: i32,
+    pub dst_color_factor: i32,
+    pub color_op: i32,
+    pub src_alpha_factor: i32,
+    pub dst_alpha_factor: i32,
+    pub alpha_op: i32,
+}
+
+impl BlendDescription {
+    /// `src * src.a + dst * (1 - src.a)`, keeping the destination's alpha weighted the same.
+    pub fn alpha() -> Self {
+        Self {
+            src_color_factor: vk::BlendFactor::SRC_ALPHA.as_raw(),
+            dst_color_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA.as_raw(),
+            color_op: vk::BlendOp::ADD.as_raw(),
+            src_alpha_factor: vk::BlendFactor::ONE.as_raw(),
+            dst_alpha_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA.as_raw(),
+            alpha_op: vk::BlendOp::ADD.as_raw(),
+        }
+    }
+
+    /// `src + dst`, for accumulating light contributions.
+    pub fn additive() -> Self {
+        Self {
+            src_color_factor: vk::BlendFactor::ONE.as_raw(),
+            dst_color_factor: vk::BlendFactor::ONE.as_raw(),
+            color_op: vk::BlendOp::ADD.as_raw(),
+            src_alpha_factor: vk::BlendFactor::ONE.as_raw(),
+            dst_alpha_factor: vk::BlendFactor::ONE.as_raw(),
+            alpha_op: vk::BlendOp::ADD.as_raw(),
+        }
+    }
+
+    pub fn attachment_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        vk::PipelineColorBlendAttachmentState::default()
+            .blend_enable(true)
+            .src_color_blend_factor(vk::BlendFactor::from_raw(self.src_color_factor))
+            .dst_color_blend_factor(vk::BlendFactor::from_raw(self.dst_color_factor))
+            .color_blend_op(vk::BlendOp::from_raw(self.color_op))
+            .src_alpha_blend_factor(vk::BlendFactor::from_raw(self.src_alpha_factor))
+            .dst_alpha_blend_factor(vk::BlendFactor::from_raw(self.dst_alpha_factor))
+            .alpha_blend_op(vk::BlendOp::from_raw(self.alpha_op))
+    }
+}
+
+impl Default for BlendDescription {
+    fn default() -> Self {
+        Self::alpha()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PushConstantRangeDescription {
+    pub stage_flags: u32,
+    pub offset: u32,
+    pub size: u32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SpecConstant {
+    pub id: u32,
+    pub value: u32,
+}
+
+impl PipelineDescription {
+    /// Filled triangle list reading the full `Vertex` layout, with counter-clockwise front
+    /// faces and back faces culled, single sampled, with dynamic viewport and scissor. No
+    /// depth, attachments, push constants or specialization constants yet.
+    pub fn new(name: &str, vertex_shader: &str, fragment_shader: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            vertex_shader: vertex_shader.to_string(),
+            fragment_shader: fragment_shader.map(str::to_string),
+            vertex_input: Some(Vertex::layout()),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST.as_raw(),
+            primitive_restart: false,
+            polygon_mode: fill(),
+            line_width: unit_line_width(),
+            cull_mode: vk::CullModeFlags::BACK.as_raw(),
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE.as_raw(),
+            depth_bias: false,
+            depth: None,
+            color_attachments: vec![],
+            dynamic_states: vec![
+                vk::DynamicState::VIEWPORT.as_raw(),
+                vk::DynamicState::SCISSOR.as_raw(),
+            ],
+            spec_constants: vec![],
+            view_mask: 0,
+            vertex_push_constant_size: 0,
+            push_constant_ranges: vec![],
+            samples: single_sample(),
+        }
+    }
+
+    pub fn with_color_attachment(mut self, format: vk::Format) -> Self {
+        self.color_attachments.push(ColorAttachmentDescription {
+            format: format.as_raw(),
+            blend: false,
+            blend_equation: BlendDescription::default(),
+        });
+        self
+    }
+
+    pub fn with_blended_color_attachment(
+        mut self,
+        format: vk::Format,
+        blend_equation: BlendDescription,
+    ) -> Self {
+        self.color_attachments.push(ColorAttachmentDescription {
+            format: format.as_raw(),
+            blend: true,
+            blend_equation,
+        });
+        self
+    }
+
+    pub fn with_depth(mut self, format: vk::Format, compare_op: vk::CompareOp) -> Self {
+        self.depth = Some(DepthDescription {
+            format: format.as_raw(),
+            test: true,
+            write: true,
+            compare_op: compare_op.as_raw(),
+        });
+        self
+    }
+
+    /// Adds a push constant range `stage_flags` read, for `cmd_push_constants` with the
+    /// pipeline's layout.
+    pub fn with_push_constants(
+        mut self,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        size: u32,
+    ) -> Self {
+        self.push_constant_ranges
+            .push(PushConstantRangeDescription {
+                stage_flags: stage_flags.as_raw(),
+                offset,
+                size,
+            });
+        self
+    }
+
+    /// Every push constant range of the pipeline layout, the vertex one first.
+    pub fn push_constant_ranges(&self) -> Vec<vk::PushConstantRange> {
+        let vertex_range = (self.vertex_push_constant_size > 0).then(|| {
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .offset(0)
+                .size(self.vertex_push_constant_size)
+        });
+        let ranges = self.push_constant_ranges.iter().map(|range| {
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::from_raw(range.stage_flags))
+                .offset(range.offset)
+                .size(range.size)
+        });
+        vertex_range.into_iter().chain(ranges).collect()
+    }
+
+    /// Depth tested against what earlier passes wrote, without writing it.
+    pub fn with_depth_test(mut self, format: vk::Format, compare_op: vk::CompareOp) -> Self {
+        self.depth = Some(DepthDescription {
+            format: format.as_raw(),
+            test: true,
+            write: false,
+            compare_op: compare_op.as_raw(),
+        });
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_blend_is_alpha() {
+        assert_eq!(BlendDescription::default(), BlendDescription::alpha());
+    }
+
+    #[test]
+    fn alpha_attachment_state_blends_over_the_destination() {
+        let state = BlendDescription::alpha().attachment_state();
+
+        assert_eq!(state.blend_enable, vk::TRUE);
+        assert_eq!(state.src_color_blend_factor, vk::BlendFactor::SRC_ALPHA);
+        assert_eq!(
+            state.dst_color_blend_factor,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+        );
+        assert_eq!(state.color_blend_op, vk::BlendOp::ADD);
+        assert_eq!(state.src_alpha_blend_factor, vk::BlendFactor::ONE);
+        assert_eq!(
+            state.dst_alpha_blend_factor,
+            vk::BlendFactor::ONE_MINUS_SRC_ALPHA
+        );
+        assert_eq!(state.alpha_blend_op, vk::BlendOp::ADD);
+    }
+
+    #[test]
+    fn additive_attachment_state_sums_both_sides() {
+        let state = BlendDescription::additive().attachment_state();
+
+        assert_eq!(state.blend_enable, vk::TRUE);
+        for factor in [
+            state.src_color_blend_factor,
+            state.dst_color_blend_factor,
+            state.src_alpha_blend_factor,
+            state.dst_alpha_blend_factor,
+        ] {
+            assert_eq!(factor, vk::BlendFactor::ONE);
+        }
+        assert_eq!(state.color_blend_op, vk::BlendOp::ADD);
+        assert_eq!(state.alpha_blend_op, vk::BlendOp::ADD);
+    }
+
+    #[test]
+    fn blended_color_attachment_keeps_its_equation() {
+        let description = PipelineDescription::new("test", "test.vert", None)
+            .with_color_attachment(vk::Format::R8G8B8A8_UNORM)
+            .with_blended_color_attachment(
+                vk::Format::R16G16B16A16_SFLOAT,
+                BlendDescription::additive(),
+            );
+
+        let [opaque, blended] = &description.color_attachments[..] else {
+            panic!("expected two color attachments");
+        };
+        assert!(!opaque.blend);
+        assert!(blended.blend);
+        assert_eq!(blended.format, vk::Format::R16G16B16A16_SFLOAT.as_raw());
+        assert_eq!(blended.blend_equation, BlendDescription::additive());
+    }
+
+    #[test]
+    fn with_depth_test_tests_without_writing() {
+        let description = PipelineDescription::new("test", "test.vert", None)
+            .with_depth_test(vk::Format::D32_SFLOAT, vk::CompareOp::LESS_OR_EQUAL);
+
+        let depth = description.depth.unwrap();
+        assert!(depth.test);
+        assert!(!depth.write);
+        assert_eq!(depth.format, vk::Format::D32_SFLOAT.as_raw());
+        assert_eq!(depth.compare_op, vk::CompareOp::LESS_OR_EQUAL.as_raw());
+    }
+
+    #[test]
+    fn with_depth_tests_and_writes() {
+        let description = PipelineDescription::new("test", "test.vert", None)
+            .with_depth(vk::Format::D32_SFLOAT, vk::CompareOp::LESS);
+
+        let depth = description.depth.unwrap();
+        assert!(depth.test);
+        assert!(depth.write);
+    }
+
+    #[test]
+    fn new_culls_clockwise_faces() {
+        let description = PipelineDescription::new("test", "test.vert", None);
+
+        assert_eq!(description.cull_mode, vk::CullModeFlags::BACK.as_raw());
+        assert_eq!(
+            description.front_face,
+            vk::FrontFace::COUNTER_CLOCKWISE.as_raw()
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::vulkan_render::device::DeviceInfo;
+use crate::vulkan_render::image_util::{self, AllocatedImage};
+use crate::vulkan_render::sync;
+use ash::vk;
+use std::slice;
+
+/// Whether the present pass has to encode linear color to sRGB itself.
+///
+/// draw_image always holds linear color. `_SRGB` swapchain formats encode on every
+/// write (blits included), so they must be given linear values or the result is
+/// gamma corrected twice. UNORM formats store what they are given, so for an
+/// sRGB color space the encode has to happen in `present.frag`.
+pub fn needs_manual_srgb_encode(surface_format: vk::SurfaceFormatKHR) -> bool {
+    surface_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+        && !is_srgb_format(surface_format.format)
+}
+
+pub fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8_SRGB
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+    )
+}
+
+/// Blits `source` (in GENERAL), draw_image or the tonemap pass's output, over the whole
+/// swapchain image and leaves the swapchain image ready to present. Its old contents are
+/// discarded, so it's entered from UNDEFINED, which is also the only valid layout before its
+/// first present. A plain copy replaces the blit when the size and format already match,
+/// anything else (like an HDR draw_image into an 8-bit swapchain) needs the blit's
+/// conversion.
+pub fn blit_to_swapchain(
+    device_info: &DeviceInfo,
+    command_buffer: &vk::CommandBuffer,
+    source: &AllocatedImage,
+    swapchain_image: vk::Image,
+    swapchain_extent: vk::Extent2D,
+    swapchain_format: vk::Format,
+    filter: vk::Filter,
+) {
+    let color_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1);
+
+    let to_transfer = [
+        // Lighting, SSR, the overlays or the tonemap pass wrote it last
+        vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags2::COPY
+                    | vk::PipelineStageFlags2::COMPUTE_SHADER,
+            )
+            .src_access_mask(
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                    | vk::AccessFlags2::TRANSFER_WRITE
+                    | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            )
+            .dst_stage_mask(vk::PipelineStageFlags2::BLIT | vk::PipelineStageFlags2::COPY)
+            .dst_access_mask(vk::AccessFlags2::TRANSFER_READ)
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(source.image)
+            .subresource_range(color_range),
+        // Chains with the acquire semaphore wait at COLOR_ATTACHMENT_OUTPUT
+        vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags2::NONE)
+            .dst_stage_mask(vk::PipelineStageFlags2::BLIT | vk::PipelineStageFlags2::COPY)
+            .dst_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(swapchain_image)
+            .subresource_range(color_range),
+    ];
+
+    // Chains with the render semaphore signal at ALL_GRAPHICS, which present waits on
+    let to_present = vk::ImageMemoryBarrier2::default()
+        .src_stage_mask(vk::PipelineStageFlags2::BLIT | vk::PipelineStageFlags2::COPY)
+        .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags2::ALL_GRAPHICS)
+        .dst_access_mask(vk::AccessFlags2::NONE)
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(swapchain_image)
+        .subresource_range(color_range);
+
+    sync::pipeline_barrier(
+        device_info,
+        *command_buffer,
+        &vk::DependencyInfo::default().image_memory_barriers(&to_transfer),
+    );
+
+    let source_extent = vk::Extent2D {
+        width: source.image_extent.width,
+        height: source.image_extent.height,
+    };
+    if source_extent == swapchain_extent && source.image_format == swapchain_format {
+        copy_whole_image(
+            device_info,
+            command_buffer,
+            source.image,
+            swapchain_image,
+            source_extent,
+        );
+    } else {
+        image_util::copy_image_to_image(
+            &device_info.logical_device,
+            command_buffer,
+            source.image,
+            swapchain_image,
+            source_extent,
+            swapchain_extent,
+            filter,
+        );
+    }
+
+    sync::pipeline_barrier(
+        device_info,
+        *command_buffer,
+        &vk::DependencyInfo::default().image_memory_barriers(std::slice::from_ref(&to_present)),
+    );
+}
+
+/// `vkCmdCopyImage2` of mip 0 from TRANSFER_SRC_OPTIMAL `source` to TRANSFER_DST_OPTIMAL
+/// `destination`, both `extent` sized and of the same format.
+/// Moves `swapchain_image` from UNDEFINED to COLOR_ATTACHMENT_OPTIMAL for a pass drawing
+/// over all of it.
+pub fn swapchain_to_attachment(
+    device_info: &DeviceInfo,
+    command_buffer: &vk::CommandBuffer,
+    swapchain_image: vk::Image,
+) {
+    // Chains with the acquire semaphore wait at COLOR_ATTACHMENT_OUTPUT
+    let barrier = vk::ImageMemoryBarrier2::default()
+        .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags2::NONE)
+        .dst_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+        .dst_access_mask(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE)
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(swapchain_image)
+        .subresource_range(swapchain_range());
+
+    sync::pipeline_barrier(
+        device_info,
+        *command_buffer,
+        &vk::DependencyInfo::default().image_memory_barriers(slice::from_ref(&barrier)),
+    );
+}
+
+/// Moves `swapchain_image` from COLOR_ATTACHMENT_OPTIMAL, after the pass drawing into it, to
+/// PRESENT_SRC_KHR.
+pub fn swapchain_attachment_to_present(
+    device_info: &DeviceInfo,
+    command_buffer: &vk::CommandBuffer,
+    swapchain_image: vk::Image,
+) {
+    // Chains with the render semaphore signal at ALL_GRAPHICS, which present waits on
+    let barrier = vk::ImageMemoryBarrier2::default()
+        .src_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+        .src_access_mask(vk::AccessFlags2::COLOR_ATTACHMENT_WRITE)
+        .dst_stage_mask(vk::PipelineStageFlags2::ALL_GRAPHICS)
+        .dst_access_mask(vk::AccessFlags2::NONE)
+        .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(swapchain_image)
+        .subresource_range(swapchain_range());
+
+    sync::pipeline_barrier(
+        device_info,
+        *command_buffer,
+        &vk::DependencyInfo::default().image_memory_barriers(slice::from_ref(&barrier)),
+    );
+}
+
+fn swapchain_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1)
+}
+
+fn copy_whole_image(
+    device_info: &DeviceInfo,
+    command_buffer: &vk::CommandBuffer,
+    source: vk::Image,
+    destination: vk::Image,
+    extent: vk::Extent2D,
+) {
+    let subresource = vk::ImageSubresourceLayers::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .mip_level(0)
+        .base_array_layer(0)
+        .layer_count(1);
+    let region = vk::ImageCopy2::default()
+        .src_subresource(subresource)
+        .dst_subresource(subresource)
+        .extent(vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        });
+
+    let copy_info = vk::CopyImageInfo2::default()
+        .src_image(source)
+        .src_image_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+        .dst_image(destination)
+        .dst_image_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .regions(std::slice::from_ref(&region));
+
+    unsafe {
+        device_info
+            .logical_device
+            .cmd_copy_image2(*command_buffer, &copy_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface_format(format: vk::Format, color_space: vk::ColorSpaceKHR) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space,
+        }
+    }
+
+    #[test]
+    fn unorm_swapchains_with_srgb_color_space_need_the_manual_encode() {
+        assert!(needs_manual_srgb_encode(surface_format(
+            vk::Format::B8G8R8A8_UNORM,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR
+        )));
+        assert!(!needs_manual_srgb_encode(surface_format(
+            vk::Format::B8G8R8A8_SRGB,
+            vk::ColorSpaceKHR::SRGB_NONLINEAR
+        )));
+    }
+
+    #[test]
+    fn other_color_spaces_are_never_encoded() {
+        assert!(!needs_manual_srgb_encode(surface_format(
+            vk::Format::A2B10G10R10_UNORM_PACK32,
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT
+        )));
+        assert!(!needs_manual_srgb_encode(surface_format(
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT
+        )));
+    }
+
+    #[test]
+    fn is_srgb_format_only_matches_srgb_formats() {
+        assert!(is_srgb_format(vk::Format::R8G8B8A8_SRGB));
+        assert!(is_srgb_format(vk::Format::A8B8G8R8_SRGB_PACK32));
+        assert!(!is_srgb_format(vk::Format::R8G8B8A8_UNORM));
+        assert!(!is_srgb_format(vk::Format::R16G16B16A16_SFLOAT));
+    }
+}
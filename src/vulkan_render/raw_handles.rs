@@ -0,0 +1,41 @@
+//! Escape hatch to the underlying `ash` objects, for calling Vulkan directly where the crate
+//! has no wrapper.
+//!
+//! You're on your own with these: the crate doesn't track anything recorded or changed
+//! through them. Restore any state you change (image layouts, bound pipelines, dynamic state),
+//! don't destroy the handles, and don't keep them past a swapchain or shadow map recreation.
+use ash::vk;
+
+/// Device level handles, valid for the backend's lifetime.
+#[derive(Clone, Copy)]
+pub struct RawDeviceHandles<'a> {
+    pub instance: &'a ash::Instance,
+    pub device: &'a ash::Device,
+    pub physical_device: vk::PhysicalDevice,
+    pub graphics_queue: vk::Queue,
+    pub graphics_queue_index: u32,
+    pub present_queue: vk::Queue,
+    pub present_queue_index: u32,
+    pub command_pool: vk::CommandPool,
+}
+
+/// Handles of one frame in flight, the one being recorded from `VulkanBackend::raw_handles`.
+/// The command buffer is only recording inside `VulkanBackend::draw_frame`.
+#[derive(Clone, Copy)]
+pub struct RawFrameHandles {
+    pub command_buffer: vk::CommandBuffer,
+    pub draw_image: vk::Image,
+    pub draw_image_view: vk::ImageView,
+    pub depth_image: vk::Image,
+    pub depth_image_view: vk::ImageView,
+    pub albedo_image: vk::Image,
+    pub albedo_image_view: vk::ImageView,
+    pub normal_image: vk::Image,
+    pub normal_image_view: vk::ImageView,
+}
+
+#[derive(Clone, Copy)]
+pub struct RawHandles<'a> {
+    pub device: RawDeviceHandles<'a>,
+    pub frame: RawFrameHandles,
+}
@@ -0,0 +1,339 @@
+use crate::vulkan_render::buffer::AllocatedBuffer;
+use crate::vulkan_render::device::DeviceInfo;
+use ash::vk::{self, BufferUsageFlags, DeviceSize, MemoryPropertyFlags};
+use ash::Instance;
+use std::collections::HashMap;
+use std::slice;
+
+/// Offsets are aligned for buffer copies and image to buffer copies of any format the crate uses
+const SLOT_ALIGNMENT: DeviceSize = 16;
+
+/// A region of a host-visible buffer to copy into during the frame it was requested for.
+/// Its bytes come back from `ReadbackPool::take` once that frame's fence has signaled.
+#[derive(Clone, Copy, Debug)]
+pub struct ReadbackSlot {
+    pub buffer: vk::Buffer,
+    pub offset: DeviceSize,
+    pub size: DeviceSize,
+    id: u64,
+}
+
+#[derive(Clone, Copy)]
+struct PendingSlot {
+    id: u64,
+    chunk: usize,
+    offset: DeviceSize,
+    size: DeviceSize,
+}
+
+struct Chunk<B> {
+    buffer: B,
+    size: DeviceSize,
+    used: DeviceSize,
+}
+
+/// Generic over the buffer so the bookkeeping doesn't need a device.
+struct FrameReadbacks<B> {
+    /// Slots go into the first chunk with room. Chunks no requested slot points into are
+    /// reset on recycle, and merged into one when there are several.
+    chunks: Vec<Chunk<B>>,
+    /// Handed out for the frame's next submit.
+    requested: Vec<PendingSlot>,
+    /// Submitted, waiting on the frame's fence.
+    in_flight: Vec<PendingSlot>,
+}
+
+impl<B> FrameReadbacks<B> {
+    fn new(initial: Option<B>, initial_size: DeviceSize) -> Self {
+        Self {
+            chunks: initial
+                .map(|buffer| Chunk {
+                    buffer,
+                    size: initial_size,
+                    used: 0,
+                })
+                .into_iter()
+                .collect(),
+            requested: Vec::new(),
+            in_flight: Vec::new(),
+        }
+    }
+
+    /// The chunk and offset of `size` new bytes, adding a chunk from `create` when none has room.
+    fn place(
+        &mut self,
+        size: DeviceSize,
+        min_chunk_size: DeviceSize,
+        create: impl FnOnce(DeviceSize) -> B,
+    ) -> (usize, DeviceSize) {
+        let found = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.used.next_multiple_of(SLOT_ALIGNMENT) + size <= chunk.size);
+        let chunk = found.unwrap_or_else(|| {
+            let largest = self
+                .chunks
+                .iter()
+                .map(|chunk| chunk.size)
+                .max()
+                .unwrap_or(0);
+            let chunk_size = size.max(largest * 2).max(min_chunk_size);
+            self.chunks.push(Chunk {
+                buffer: create(chunk_size),
+                size: chunk_size,
+                used: 0,
+            });
+            self.chunks.len() - 1
+        });
+
+        let offset = self.chunks[chunk].used.next_multiple_of(SLOT_ALIGNMENT);
+        self.chunks[chunk].used = offset + size;
+        (chunk, offset)
+    }
+
+    /// Resets the chunks no requested slot points into and merges them when there are several.
+    /// Chunks with requested slots keep their buffer, so the handed out slots stay valid.
+    fn reclaim(&mut self, mut destroy: impl FnMut(B), create: impl FnOnce(DeviceSize) -> B) {
+        let busy = |chunk: usize, requested: &[PendingSlot]| {
+            requested.iter().any(|slot| slot.chunk == chunk)
+        };
+        let idle_count = (0..self.chunks.len())
+            .filter(|&chunk| !busy(chunk, &self.requested))
+            .count();
+
+        if idle_count <= 1 {
+            for (index, chunk) in self.chunks.iter_mut().enumerate() {
+                if !busy(index, &self.requested) {
+                    chunk.used = 0;
+                }
+            }
+            return;
+        }
+
+        let mut total_size = 0;
+        let mut remap = vec![0; self.chunks.len()];
+        let mut kept = Vec::with_capacity(self.chunks.len() - idle_count + 1);
+        for (index, chunk) in self.chunks.drain(..).enumerate() {
+            if busy(index, &self.requested) {
+                remap[index] = kept.len();
+                kept.push(chunk);
+            } else {
+                total_size += chunk.size;
+                destroy(chunk.buffer);
+            }
+        }
+        for slot in &mut self.requested {
+            slot.chunk = remap[slot.chunk];
+        }
+
+        kept.push(Chunk {
+            buffer: create(total_size),
+            size: total_size,
+            used: 0,
+        });
+        self.chunks = kept;
+    }
+}
+
+/// Host-visible readback buffers per frame in flight, reused across frames and grown when a
+/// frame asks for more than they hold. Follows the frame ring like `SemaphorePool`.
+pub struct ReadbackPool {
+    frames: Vec<FrameReadbacks<AllocatedBuffer>>,
+    initial_size: DeviceSize,
+    next_id: u64,
+    ready: HashMap<u64, Vec<u8>>,
+}
+
+impl ReadbackPool {
+    /// Preallocates `initial_size` bytes per frame, none when it's 0.
+    pub fn new(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        frame_count: usize,
+        initial_size: DeviceSize,
+    ) -> Self {
+        let frames = (0..frame_count)
+            .map(|_| {
+                let initial = (initial_size > 0)
+                    .then(|| Self::create_buffer(device_info, instance, initial_size));
+                FrameReadbacks::new(initial, initial_size)
+            })
+            .collect();
+
+        Self {
+            frames,
+            initial_size,
+            next_id: 0,
+            ready: HashMap::new(),
+        }
+    }
+
+    /// Hands out `size` bytes to copy into from `frame`'s next submit.
+    pub fn request(
+        &mut self,
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        frame: usize,
+        size: DeviceSize,
+    ) -> ReadbackSlot {
+        let frame_readbacks = &mut self.frames[frame];
+        let (chunk, offset) = frame_readbacks.place(size, self.initial_size, |chunk_size| {
+            Self::create_buffer(device_info, instance, chunk_size)
+        });
+
+        let id = self.next_id;
+        self.next_id += 1;
+        frame_readbacks.requested.push(PendingSlot {
+            id,
+            chunk,
+            offset,
+            size,
+        });
+
+        ReadbackSlot {
+            buffer: frame_readbacks.chunks[chunk].buffer.buffer,
+            offset,
+            size,
+            id,
+        }
+    }
+
+    /// Marks `frame`'s requested slots as part of the submit that was just made.
+    pub fn submit(&mut self, frame: usize) {
+        let frame_readbacks = &mut self.frames[frame];
+        frame_readbacks
+            .in_flight
+            .append(&mut frame_readbacks.requested);
+    }
+
+    /// Copies out the slots `frame` submitted last time around, so `take` returns them, and
+    /// reuses its buffers. Call after waiting on that frame's fence.
+    pub fn recycle(&mut self, device_info: &DeviceInfo, instance: &Instance, frame: usize) {
+        let frame_readbacks = &mut self.frames[frame];
+
+        for slot in frame_readbacks.in_flight.drain(..) {
+            let chunk = &frame_readbacks.chunks[slot.chunk].buffer;
+            let bytes = unsafe {
+                slice::from_raw_parts(
+                    (chunk.mapped_buffer as *const u8).add(slot.offset as usize),
+                    slot.size as usize,
+                )
+            };
+            self.ready.insert(slot.id, bytes.to_vec());
+        }
+
+        // Slots requested since the submit keep their chunks
+        frame_readbacks.reclaim(
+            |buffer| buffer.destroy_buffer(&device_info.logical_device),
+            |size| Self::create_buffer(device_info, instance, size),
+        );
+    }
+
+    /// The bytes copied into `slot`, once its frame has finished. Each slot is returned once.
+    pub fn take(&mut self, slot: &ReadbackSlot) -> Option<Vec<u8>> {
+        self.ready.remove(&slot.id)
+    }
+
+    /// Frees every frame's buffers. Call once the device is idle.
+    pub fn destroy(&mut self, device_info: &DeviceInfo) {
+        for frame_readbacks in self.frames.drain(..) {
+            for chunk in frame_readbacks.chunks {
+                chunk.buffer.destroy_buffer(&device_info.logical_device);
+            }
+        }
+        self.ready.clear();
+    }
+
+    fn create_buffer(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        size: DeviceSize,
+    ) -> AllocatedBuffer {
+        AllocatedBuffer::new(
+            device_info,
+            instance,
+            size,
+            BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(frame: &mut FrameReadbacks<DeviceSize>, id: u64, size: DeviceSize) {
+        let (chunk, offset) = frame.place(size, 0, |chunk_size| chunk_size);
+        frame.requested.push(PendingSlot {
+            id,
+            chunk,
+            offset,
+            size,
+        });
+    }
+
+    fn recycle(frame: &mut FrameReadbacks<DeviceSize>) {
+        frame.in_flight.clear();
+        frame.reclaim(|_| {}, |size| size);
+    }
+
+    #[test]
+    fn slots_are_aligned_and_disjoint() {
+        let mut frame = FrameReadbacks::new(Some(64), 64);
+        request(&mut frame, 0, 5);
+        request(&mut frame, 1, 5);
+
+        assert_eq!(frame.requested[0].offset, 0);
+        assert_eq!(frame.requested[1].offset, SLOT_ALIGNMENT);
+        assert_eq!(frame.chunks.len(), 1);
+    }
+
+    #[test]
+    fn idle_chunks_are_merged() {
+        let mut frame = FrameReadbacks::new(Some(16), 16);
+        request(&mut frame, 0, 16);
+        request(&mut frame, 1, 32);
+        frame.in_flight.append(&mut frame.requested);
+        recycle(&mut frame);
+
+        assert_eq!(frame.chunks.len(), 1);
+        assert_eq!(frame.chunks[0].size, 16 + 32);
+        assert_eq!(frame.chunks[0].used, 0);
+    }
+
+    #[test]
+    fn requests_before_recycle_keep_their_chunk() {
+        let mut frame = FrameReadbacks::new(Some(64), 64);
+        request(&mut frame, 0, 48);
+        frame.in_flight.append(&mut frame.requested);
+        request(&mut frame, 1, 48);
+        let kept = frame.requested[0];
+        recycle(&mut frame);
+
+        let chunk = &frame.chunks[frame.requested[0].chunk];
+        assert_eq!(chunk.buffer, 128);
+        assert_eq!(frame.requested[0].offset, kept.offset);
+        assert!(chunk.used >= kept.offset + kept.size);
+    }
+
+    #[test]
+    fn requests_before_recycle_stay_bounded() {
+        let mut frame = FrameReadbacks::new(Some(64), 64);
+        for id in 0..1000 {
+            frame.in_flight.append(&mut frame.requested);
+            request(&mut frame, id, 40);
+            recycle(&mut frame);
+        }
+
+        assert!(frame.chunks.len() <= 2);
+        assert!(
+            frame
+                .chunks
+                .iter()
+                .map(|chunk| chunk.size)
+                .sum::<DeviceSize>()
+                <= 256
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use crate::vulkan_render::structs::Vertex;
+use crate::vulkan_render::structs::{Light, Vertex};
+use image::imageops::FilterType;
 use nalgebra::{Matrix4, Vector2, Vector3};
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
 use std::path::Path;
 use std::rc::{Rc, Weak};
 
@@ -29,6 +32,69 @@ pub struct ImageResource {
     pub height: u32,
 }
 
+impl ImageResource {
+    /// Loads an image as RGBA8, converting from whatever the file stores.
+    pub fn load<P>(path: P) -> ImageResource
+    where
+        P: AsRef<Path>,
+    {
+        let dyn_image = image::open(path).unwrap();
+        let image_width = dyn_image.width();
+        let image_height = dyn_image.height();
+        let image_data = dyn_image.to_rgba8().into_raw();
+
+        ImageResource {
+            image_data,
+            width: image_width,
+            height: image_height,
+        }
+    }
+
+    /// Downscales the image so neither side exceeds `max_size`, keeping its aspect ratio.
+    /// Images that already fit come back unchanged.
+    pub fn fit_within(self, max_size: u32) -> ImageResource {
+        let max_size = max_size.max(1);
+        if self.width <= max_size && self.height <= max_size {
+            return self;
+        }
+
+        let (width, height) = (self.width, self.height);
+        let Some(image) = image::RgbaImage::from_raw(width, height, self.image_data) else {
+            panic!("{}x{} texture doesn't hold RGBA8 data", width, height);
+        };
+
+        let scale = max_size as f32 / width.max(height) as f32;
+        let new_width = ((width as f32 * scale).round() as u32).clamp(1, max_size);
+        let new_height = ((height as f32 * scale).round() as u32).clamp(1, max_size);
+        let resized = image::imageops::resize(&image, new_width, new_height, FilterType::Triangle);
+        log::info!(
+            "Downscaled {}x{} texture to {}x{} to fit {}",
+            width, height, new_width, new_height, max_size
+        );
+
+        ImageResource {
+            image_data: resized.into_raw(),
+            width: new_width,
+            height: new_height,
+        }
+    }
+}
+
+/// Content swapped in at runtime with `VulkanBackend::set_scene`.
+pub struct Scene {
+    pub meshes: Vec<SceneMesh>,
+    /// Sampled by every mesh as albedo, uploaded as sRGB, see `ColorSpace::texture_format`.
+    /// `None` keeps the current texture.
+    pub texture: Option<ImageResource>,
+    /// Replaces the lights added so far, at most 64.
+    pub lights: Vec<Light>,
+}
+
+pub struct SceneMesh {
+    pub mesh: Mesh,
+    pub world_model: Matrix4<f32>,
+}
+
 pub struct SceneNode {
     pub transform: Transform,
     pub mesh: Mesh,
@@ -38,16 +104,77 @@ pub struct SceneNode {
     pub texture: ImageResource,
 }
 
+/// Triangles `Mesh::winding_report` looks at, spread evenly over the index buffer.
+const WINDING_SAMPLE_SIZE: usize = 1024;
+
+/// Share of the non-degenerate sampled triangles that has to agree for a dominant winding.
+const WINDING_AGREEMENT: f32 = 0.9;
+
+/// How `SceneNode::new_with_options` treats the meshes it loads.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MeshLoadOptions {
+    /// Flip every triangle of meshes that are mostly clockwise, so they face outward with the
+    /// counter-clockwise front face of the built-in pipelines. Mixed and degenerate meshes
+    /// are reported and left as they are.
+    pub normalize_winding: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Winding {
+    CounterClockwise,
+    Clockwise,
+    /// Neither winding reaches `WINDING_AGREEMENT` of the sampled triangles.
+    Mixed,
+    /// Every sampled triangle has no area.
+    Degenerate,
+}
+
+/// Counts of sampled triangles by the way they face, counter-clockwise meaning outward.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WindingReport {
+    pub counter_clockwise: usize,
+    pub clockwise: usize,
+    pub degenerate: usize,
+}
+
+impl WindingReport {
+    pub fn dominant(&self) -> Winding {
+        let oriented = self.counter_clockwise + self.clockwise;
+        if oriented == 0 {
+            Winding::Degenerate
+        } else if self.counter_clockwise as f32 >= oriented as f32 * WINDING_AGREEMENT {
+            Winding::CounterClockwise
+        } else if self.clockwise as f32 >= oriented as f32 * WINDING_AGREEMENT {
+            Winding::Clockwise
+        } else {
+            Winding::Mixed
+        }
+    }
+}
+
 impl SceneNode {
     pub fn new<P>(model_path: P, texture_path: P) -> Rc<RefCell<Self>>
     where
         P: AsRef<Path>,
     {
-        let mesh = Self::load_model(model_path);
-        let texture = Self::load_texture(texture_path);
-        let mut transform = Transform::default();
-        transform.scale = Vector3::new(1.0, 1.0, 1.0);
-        transform.position = Vector3::new(0.0, 1.0, 0.5);
+        Self::new_with_options(model_path, texture_path, MeshLoadOptions::default())
+    }
+
+    pub fn new_with_options<P>(
+        model_path: P,
+        texture_path: P,
+        options: MeshLoadOptions,
+    ) -> Rc<RefCell<Self>>
+    where
+        P: AsRef<Path>,
+    {
+        let mesh = Self::load_model(model_path, options);
+        let texture = ImageResource::load(texture_path);
+        let transform = Transform {
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            position: Vector3::new(0.0, 1.0, 0.5),
+            ..Default::default()
+        };
         Rc::new(RefCell::new(SceneNode {
             transform,
             mesh,
@@ -124,64 +251,165 @@ impl SceneNode {
         self.parent = parent.map(|p| Rc::downgrade(&p));
     }
 
-    fn load_texture<P>(path: P) -> ImageResource
+    /// Logs the mesh's winding, judged by the file's normals when it has them. Every
+    /// submesh is loaded, drawn with the node's one texture.
+    fn load_model<P>(path: P, options: MeshLoadOptions) -> Mesh
     where
         P: AsRef<Path>,
     {
-        let dyn_image = image::open(path).unwrap();
-        let image_width = dyn_image.width();
-        let image_height = dyn_image.height();
+        let ObjMesh {
+            mesh: mut loaded,
+            file_normals,
+            ..
+        } = load_obj(path.as_ref()).expect("failed to load model file");
 
-        let image_data = match &dyn_image {
-            image::DynamicImage::ImageLuma8(_) | image::DynamicImage::ImageRgb8(_) => {
-                dyn_image.to_rgba8().into_raw()
-            }
-            _ => vec![],
-        };
+        let normals = file_normals.then(|| {
+            loaded
+                .vertices
+                .iter()
+                .map(|vertex| vertex.normal)
+                .collect::<Vec<_>>()
+        });
+        let report = loaded.sample_winding(normals.as_deref());
+        let winding = report.dominant();
+        log::info!(
+            "{}: {:?} winding ({} ccw, {} cw, {} degenerate sampled triangles)",
+            path.as_ref().display(),
+            winding,
+            report.counter_clockwise,
+            report.clockwise,
+            report.degenerate
+        );
 
-        ImageResource {
-            image_data,
-            width: image_width,
-            height: image_height,
+        match winding {
+            Winding::Clockwise if options.normalize_winding => loaded.flip_winding(),
+            Winding::Mixed | Winding::Degenerate if options.normalize_winding => {
+                log::warn!("{}: winding left as is", path.as_ref().display())
+            }
+            _ => {}
         }
+
+        loaded
     }
+}
 
-    fn load_model<P>(path: P) -> Mesh
-    where
-        P: AsRef<Path>,
-    {
-        let (models, _mat) = tobj::load_obj(path.as_ref(), &tobj::GPU_LOAD_OPTIONS)
-            .expect("failed to load model file");
+/// What `load_obj` read from an OBJ file.
+pub struct ObjMesh {
+    /// Every object and group of the file, sharing one vertex and index buffer.
+    pub mesh: Mesh,
+    /// One per object or group, in file order, together covering all of `mesh.indices`.
+    pub submeshes: Vec<SubMesh>,
+    /// Whether every face had normals in the file. Otherwise the mesh's normals are all
+    /// computed, see `load_obj`.
+    pub file_normals: bool,
+}
 
-        let model = models.first().unwrap();
-        let mesh = &model.mesh;
+/// Part of an `ObjMesh` drawn with a single material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubMesh {
+    /// The object or group name.
+    pub name: String,
+    /// Index into the file's MTL materials, `None` without `usemtl`.
+    pub material: Option<usize>,
+    /// Range of `Mesh::indices`, a multiple of 3 long.
+    pub indices: Range<u32>,
+}
+
+/// Loads a triangulated OBJ file through `tobj`. Vertices with the same position, texture
+/// coordinate and normal are stored once, also across submeshes. When any face lacks
+/// normals, all normals are computed instead: each vertex gets the area-weighted average of
+/// the faces around its position, so they're smooth over UV seams. Missing texture
+/// coordinates are zero. Tangents are generated from the normals either way.
+pub fn load_obj<P>(path: P) -> Result<ObjMesh, tobj::LoadError>
+where
+    P: AsRef<Path>,
+{
+    let (models, _materials) = tobj::load_obj(path.as_ref(), &obj_load_options())?;
+    Ok(obj_mesh(&models))
+}
 
-        let mut vertices = vec![];
+fn obj_load_options() -> tobj::LoadOptions {
+    tobj::LoadOptions {
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+        ..Default::default()
+    }
+}
 
-        let vert_count = mesh.positions.len() / 3;
-        for i in 0..vert_count {
-            let pos: Vector3<f32> = Vector3::new(
-                mesh.positions[i * 3],
-                mesh.positions[i * 3 + 1],
-                mesh.positions[i * 3 + 2],
-            );
+/// `load_obj`'s mesh of the models `tobj` read with `obj_load_options`.
+fn obj_mesh(models: &[tobj::Model]) -> ObjMesh {
+    let file_normals = models.iter().all(|model| {
+        let mesh = &model.mesh;
+        !mesh.normals.is_empty() && mesh.normal_indices.len() == mesh.indices.len()
+    });
 
-            let tex_coord: Vector2<f32> =
-                Vector2::new(mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1]);
+    let mut vertices = vec![];
+    let mut indices = vec![];
+    let mut submeshes = vec![];
+    let mut unique = HashMap::new();
+    for model in models {
+        let mesh = &model.mesh;
+        let has_tex_coords =
+            !mesh.texcoords.is_empty() && mesh.texcoord_indices.len() == mesh.indices.len();
 
-            let vert = Vertex {
-                pos,
-                color: Vector3::new(1.0, 1.0, 1.0),
-                tex_coord,
+        let first_index = indices.len() as u32;
+        for (i, &position_index) in mesh.indices.iter().enumerate() {
+            let position = position_index as usize * 3;
+            let pos = Vector3::from_column_slice(&mesh.positions[position..position + 3]);
+            let tex_coord = if has_tex_coords {
+                let tex_coord = mesh.texcoord_indices[i] as usize * 2;
+                Vector2::from_column_slice(&mesh.texcoords[tex_coord..tex_coord + 2])
+            } else {
+                Vector2::zeros()
+            };
+            let normal = if file_normals {
+                let normal = mesh.normal_indices[i] as usize * 3;
+                Vector3::from_column_slice(&mesh.normals[normal..normal + 3])
+            } else {
+                Vector3::zeros()
             };
 
-            vertices.push(vert);
+            let key = [
+                pos.x,
+                pos.y,
+                pos.z,
+                tex_coord.x,
+                tex_coord.y,
+                normal.x,
+                normal.y,
+                normal.z,
+            ]
+            .map(f32::to_bits);
+            let index = *unique.entry(key).or_insert_with(|| {
+                vertices.push(Vertex {
+                    pos,
+                    color: Vector3::new(1.0, 1.0, 1.0),
+                    tex_coord,
+                    normal,
+                    ..Default::default()
+                });
+                vertices.len() as u32 - 1
+            });
+            indices.push(index);
         }
 
-        Mesh {
-            vertices,
-            indices: mesh.indices.clone(),
-        }
+        submeshes.push(SubMesh {
+            name: model.name.clone(),
+            material: mesh.material_id,
+            indices: first_index..indices.len() as u32,
+        });
+    }
+
+    let mut mesh = Mesh { vertices, indices };
+    if !file_normals {
+        mesh.compute_normals();
+    }
+    mesh.generate_tangents();
+    ObjMesh {
+        mesh,
+        submeshes,
+        file_normals,
     }
 }
 
@@ -190,3 +418,347 @@ pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
 }
+
+impl Mesh {
+    /// Which way a sample of the triangles faces, taking outward as away from the mesh's
+    /// bounding box center. Meshes that aren't closed around their center, like terrain, can
+    /// come out `Mixed`.
+    pub fn winding_report(&self) -> WindingReport {
+        self.sample_winding(None)
+    }
+
+    /// Sets each vertex's `normal` to the area-weighted average of the faces around its
+    /// position, so vertices split only by their texture coordinates get the same normal.
+    /// Vertices only in degenerate triangles keep a zero normal.
+    pub fn compute_normals(&mut self) {
+        let position_key = |vertex: &Vertex| vertex.pos.map(|value| value.to_bits());
+        let mut face_normals = HashMap::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]];
+            let [a, b, c] = corners.map(|i| self.vertices[i as usize]);
+            // As long as twice the triangle's area, so bigger faces weigh more
+            let face_normal = (b.pos - a.pos).cross(&(c.pos - a.pos));
+            for corner in [a, b, c] {
+                *face_normals
+                    .entry(position_key(&corner))
+                    .or_insert_with(Vector3::zeros) += face_normal;
+            }
+        }
+
+        for vertex in &mut self.vertices {
+            vertex.normal = face_normals
+                .get(&position_key(vertex))
+                .and_then(|normal| normal.try_normalize(f32::EPSILON))
+                .unwrap_or_else(Vector3::zeros);
+        }
+    }
+
+    /// Swaps two corners of every triangle.
+    pub fn flip_winding(&mut self) {
+        for triangle in self.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    /// Fills in each vertex's `tangent` from the texture coordinates of the triangles around
+    /// it, made orthogonal to its `normal`. Vertices without a normal, or only in triangles
+    /// with degenerate texture coordinates, keep a zero tangent.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vector3::zeros(); self.vertices.len()];
+        let mut bitangents = vec![Vector3::zeros(); self.vertices.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let corners = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+            let [a, b, c] = corners.map(|i| self.vertices[i]);
+            let (edge1, edge2) = (b.pos - a.pos, c.pos - a.pos);
+            let (uv1, uv2) = (b.tex_coord - a.tex_coord, c.tex_coord - a.tex_coord);
+
+            let determinant = uv1.x * uv2.y - uv2.x * uv1.y;
+            if determinant.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * uv2.y - edge2 * uv1.y) / determinant;
+            let bitangent = (edge2 * uv1.x - edge1 * uv2.x) / determinant;
+            for i in corners {
+                tangents[i] += tangent;
+                bitangents[i] += bitangent;
+            }
+        }
+
+        let accumulated = tangents.iter().zip(&bitangents);
+        for (vertex, (tangent, bitangent)) in self.vertices.iter_mut().zip(accumulated) {
+            let normal = vertex.normal;
+            if normal == Vector3::zeros() {
+                continue;
+            }
+            let orthogonal = tangent - normal * normal.dot(tangent);
+            let Some(tangent) = orthogonal.try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            let handedness = if normal.cross(&tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.tangent = tangent.push(handedness);
+        }
+    }
+
+    /// Outward is along the vertex normals when given, one per vertex.
+    fn sample_winding(&self, normals: Option<&[Vector3<f32>]>) -> WindingReport {
+        let mut report = WindingReport::default();
+        let triangle_count = self.indices.len() / 3;
+        if triangle_count == 0 {
+            return report;
+        }
+
+        let (min, max) = self.vertices.iter().fold(
+            (Vector3::repeat(f32::MAX), Vector3::repeat(f32::MIN)),
+            |(min, max), vertex| (min.inf(&vertex.pos), max.sup(&vertex.pos)),
+        );
+        let center = (min + max) * 0.5;
+        // Areas below this, relative to the mesh's size, count as degenerate
+        let min_area = (max - min).norm_squared() * 1e-12;
+
+        let step = triangle_count.div_ceil(WINDING_SAMPLE_SIZE);
+        for triangle in self.indices.chunks_exact(3).step_by(step) {
+            let corners = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+            let [a, b, c] = corners.map(|i| self.vertices[i].pos);
+            let face_normal = (b - a).cross(&(c - a));
+            if face_normal.norm() <= min_area {
+                report.degenerate += 1;
+                continue;
+            }
+
+            let outward = match normals {
+                Some(normals) => corners.iter().map(|&i| normals[i]).sum::<Vector3<f32>>(),
+                None => (a + b + c) / 3.0 - center,
+            };
+            if face_normal.dot(&outward) >= 0.0 {
+                report.counter_clockwise += 1;
+            } else {
+                report.clockwise += 1;
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Vector4;
+
+    fn vertex(pos: [f32; 3], tex_coord: [f32; 2], normal: [f32; 3]) -> Vertex {
+        Vertex {
+            pos: pos.into(),
+            color: Vector3::zeros(),
+            tex_coord: tex_coord.into(),
+            joint_indices: [0; 4],
+            joint_weights: Vector4::zeros(),
+            normal: normal.into(),
+            tangent: Vector4::zeros(),
+        }
+    }
+
+    /// Unit quad in the xy plane facing +z, `v` running along `v_sign * y`.
+    fn quad(v_sign: f32) -> Mesh {
+        let normal = [0.0, 0.0, 1.0];
+        Mesh {
+            vertices: vec![
+                vertex([0.0, 0.0, 0.0], [0.0, 0.0], normal),
+                vertex([1.0, 0.0, 0.0], [1.0, 0.0], normal),
+                vertex([1.0, 1.0, 0.0], [1.0, v_sign], normal),
+                vertex([0.0, 1.0, 0.0], [0.0, v_sign], normal),
+            ],
+            indices: vec![0, 1, 2, 2, 3, 0],
+        }
+    }
+
+    #[test]
+    fn generate_tangents_follows_u() {
+        let mut mesh = quad(1.0);
+        mesh.generate_tangents();
+
+        for vertex in &mesh.vertices {
+            assert!((vertex.tangent - Vector4::new(1.0, 0.0, 0.0, 1.0)).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn generate_tangents_flips_handedness_for_mirrored_v() {
+        let mut mesh = quad(-1.0);
+        mesh.generate_tangents();
+
+        for vertex in &mesh.vertices {
+            assert!((vertex.tangent - Vector4::new(1.0, 0.0, 0.0, -1.0)).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn generate_tangents_is_orthogonal_to_the_normal() {
+        let mut mesh = quad(1.0);
+        let tilted = Vector3::new(1.0, 0.0, 1.0).normalize();
+        for vertex in &mut mesh.vertices {
+            vertex.normal = tilted;
+        }
+        mesh.generate_tangents();
+
+        for vertex in &mesh.vertices {
+            assert!(vertex.tangent.xyz().dot(&tilted).abs() < 1e-6);
+            assert!((vertex.tangent.xyz().norm() - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn generate_tangents_skips_degenerate_uvs_and_missing_normals() {
+        let mut mesh = quad(1.0);
+        for vertex in &mut mesh.vertices {
+            vertex.tex_coord = Vector2::zeros();
+        }
+        mesh.generate_tangents();
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.tangent == Vector4::zeros()));
+
+        let mut mesh = quad(1.0);
+        mesh.vertices[0].normal = Vector3::zeros();
+        mesh.generate_tangents();
+        assert_eq!(mesh.vertices[0].tangent, Vector4::zeros());
+        assert_ne!(mesh.vertices[1].tangent, Vector4::zeros());
+    }
+
+    /// `load_obj` of an inline OBJ file, indented to fit in the test.
+    fn load(source: &str) -> ObjMesh {
+        let source = source.lines().map(str::trim).collect::<Vec<_>>().join("\n");
+        let (models, _materials) =
+            tobj::load_obj_buf(&mut source.as_bytes(), &obj_load_options(), |_| {
+                Ok(Default::default())
+            })
+            .expect("inline OBJ should parse");
+        obj_mesh(&models)
+    }
+
+    const QUAD: &str = "
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 1
+        f 1/1/1 2/2/1 3/3/1
+        f 3/3/1 4/4/1 1/1/1
+    ";
+
+    #[test]
+    fn load_obj_stores_shared_vertices_once() {
+        let ObjMesh {
+            mesh,
+            submeshes,
+            file_normals,
+        } = load(QUAD);
+
+        assert!(file_normals);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 2, 3, 0]);
+        assert_eq!(submeshes.len(), 1);
+        assert_eq!(submeshes[0].indices, 0..6);
+        for vertex in &mesh.vertices {
+            assert_eq!(vertex.normal, Vector3::z());
+            assert!((vertex.tangent - Vector4::new(1.0, 0.0, 0.0, 1.0)).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn load_obj_splits_groups_into_submeshes() {
+        let ObjMesh {
+            mesh, submeshes, ..
+        } = load(
+            "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            g first
+            f 1 2 3
+            g second
+            f 3 4 1
+            ",
+        );
+
+        let ranges = submeshes
+            .iter()
+            .map(|submesh| (submesh.name.as_str(), submesh.indices.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges, [("first", 0..3), ("second", 3..6)]);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.tex_coord == Vector2::zeros()));
+    }
+
+    #[test]
+    fn load_obj_computes_normals_and_tangents_without_file_normals() {
+        let ObjMesh {
+            mesh, file_normals, ..
+        } = load(
+            "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            f 1/1 2/2 3/3
+            f 3/3 4/4 1/1
+            ",
+        );
+
+        assert!(!file_normals);
+        for vertex in &mesh.vertices {
+            assert!((vertex.normal - Vector3::z()).norm() < 1e-6);
+            assert!((vertex.tangent - Vector4::new(1.0, 0.0, 0.0, 1.0)).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn compute_normals_is_smooth_over_uv_seams() {
+        // Two faces folded along the edge from 1 to 2, the second with its own texture
+        // coordinates so the edge's vertices are stored twice
+        let ObjMesh { mesh, .. } = load(
+            "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 0 1
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0.5 0
+            vt 0.5 1
+            vt 1 1
+            f 1/1 2/2 3/3
+            f 1/4 4/5 2/6
+            ",
+        );
+
+        assert_eq!(mesh.vertices.len(), 6);
+        let folded = Vector3::new(0.0, 1.0, 1.0).normalize();
+        for vertex in &mesh.vertices {
+            let expected = match vertex.pos {
+                pos if pos == Vector3::new(1.0, 1.0, 0.0) => Vector3::z(),
+                pos if pos == Vector3::new(0.0, 0.0, 1.0) => Vector3::y(),
+                _ => folded,
+            };
+            assert!((vertex.normal - expected).norm() < 1e-6, "{:?}", vertex.pos);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use ash::vk;
+
+/// Hands out binary semaphores per submit and recycles them once the frame that waited on
+/// them has finished, so passes don't each need their own semaphore fields on `FrameData`.
+pub struct SemaphorePool {
+    free: Vec<vk::Semaphore>,
+    /// Semaphores handed out for each frame in flight, waited on by that frame's submit.
+    in_use: Vec<Vec<vk::Semaphore>>,
+}
+
+impl SemaphorePool {
+    pub fn new(frame_count: usize) -> Self {
+        Self {
+            free: vec![],
+            in_use: vec![vec![]; frame_count],
+        }
+    }
+
+    /// Returns an unsignaled semaphore whose wait must be part of `frame`'s next submit.
+    pub fn acquire(&mut self, device: &ash::Device, frame: usize) -> vk::Semaphore {
+        let semaphore = self.free.pop().unwrap_or_else(|| {
+            let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+
+            unsafe {
+                device
+                    .create_semaphore(&semaphore_create_info, None)
+                    .expect("failed to create pooled semaphore")
+            }
+        });
+
+        self.in_use[frame].push(semaphore);
+        semaphore
+    }
+
+    /// Makes `frame`'s semaphores available again. Call after waiting on that frame's fence.
+    pub fn recycle(&mut self, frame: usize) {
+        self.free.append(&mut self.in_use[frame]);
+    }
+
+    /// Destroys every semaphore, handed out or not. Call once the device is idle.
+    pub fn destroy(&mut self, device: &ash::Device) {
+        for frame in 0..self.in_use.len() {
+            self.recycle(frame);
+        }
+        for semaphore in self.free.drain(..) {
+            unsafe { device.destroy_semaphore(semaphore, None) };
+        }
+    }
+}
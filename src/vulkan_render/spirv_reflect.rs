@@ -0,0 +1,221 @@
+//! Just enough SPIR-V parsing to list a shader's `layout(location = N) in` variables.
+
+use std::collections::HashMap;
+use std::fmt;
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+
+const DECORATION_LOCATION: u32 = 30;
+const STORAGE_CLASS_INPUT: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalarKind {
+    Float,
+    Int,
+    Uint,
+    Bool,
+}
+
+/// Scalar or vector type of one input location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputType {
+    pub kind: ScalarKind,
+    pub width: u32,
+    pub components: u32,
+}
+
+impl fmt::Display for InputType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (scalar, prefix) = match (self.kind, self.width) {
+            (ScalarKind::Float, 64) => ("double", "d"),
+            (ScalarKind::Float, _) => ("float", ""),
+            (ScalarKind::Int, _) => ("int", "i"),
+            (ScalarKind::Uint, _) => ("uint", "u"),
+            (ScalarKind::Bool, _) => ("bool", "b"),
+        };
+        if self.components == 1 {
+            write!(f, "{}", scalar)
+        } else {
+            write!(f, "{}vec{}", prefix, self.components)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Type {
+    Scalar(ScalarKind, u32),
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Array { element: u32, length: u32 },
+}
+
+/// Every location the shader's `Input` variables occupy, with its type. Matrices and arrays
+/// take one location per column or element. Built-ins have no location and are skipped.
+pub fn input_locations(spirv: &[u8]) -> Result<Vec<(u32, InputType)>, String> {
+    if !spirv.len().is_multiple_of(4) || spirv.len() < HEADER_WORDS * 4 {
+        return Err("not a SPIR-V module".to_string());
+    }
+    let mut words = spirv
+        .chunks_exact(4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect::<Vec<_>>();
+    if words[0] == MAGIC.swap_bytes() {
+        words.iter_mut().for_each(|word| *word = word.swap_bytes());
+    } else if words[0] != MAGIC {
+        return Err("not a SPIR-V module".to_string());
+    }
+
+    let mut types = HashMap::new();
+    let mut constants = HashMap::new();
+    let mut pointers = HashMap::new();
+    let mut locations = HashMap::new();
+    let mut inputs = vec![];
+
+    let mut index = HEADER_WORDS;
+    while index < words.len() {
+        let word_count = (words[index] >> 16) as usize;
+        let opcode = words[index] & 0xffff;
+        if word_count == 0 || index + word_count > words.len() {
+            return Err(format!("truncated instruction at word {}", index));
+        }
+        let operands = &words[index + 1..index + word_count];
+
+        match opcode {
+            OP_TYPE_BOOL => {
+                types.insert(operands[0], Type::Scalar(ScalarKind::Bool, 32));
+            }
+            OP_TYPE_INT => {
+                let kind = if operands[2] == 1 {
+                    ScalarKind::Int
+                } else {
+                    ScalarKind::Uint
+                };
+                types.insert(operands[0], Type::Scalar(kind, operands[1]));
+            }
+            OP_TYPE_FLOAT => {
+                types.insert(operands[0], Type::Scalar(ScalarKind::Float, operands[1]));
+            }
+            OP_TYPE_VECTOR => {
+                let vector = Type::Vector {
+                    component: operands[1],
+                    count: operands[2],
+                };
+                types.insert(operands[0], vector);
+            }
+            OP_TYPE_MATRIX => {
+                let matrix = Type::Matrix {
+                    column: operands[1],
+                    count: operands[2],
+                };
+                types.insert(operands[0], matrix);
+            }
+            OP_TYPE_ARRAY => {
+                let array = Type::Array {
+                    element: operands[1],
+                    length: operands[2],
+                };
+                types.insert(operands[0], array);
+            }
+            OP_TYPE_POINTER if operands[1] == STORAGE_CLASS_INPUT => {
+                pointers.insert(operands[0], operands[2]);
+            }
+            OP_CONSTANT => {
+                constants.insert(operands[1], operands[2]);
+            }
+            OP_VARIABLE if operands[2] == STORAGE_CLASS_INPUT => {
+                inputs.push((operands[1], operands[0]));
+            }
+            OP_DECORATE if operands[1] == DECORATION_LOCATION => {
+                locations.insert(operands[0], operands[2]);
+            }
+            _ => {}
+        }
+
+        index += word_count;
+    }
+
+    let mut result = vec![];
+    for (variable, pointer) in inputs {
+        let Some(&location) = locations.get(&variable) else {
+            continue;
+        };
+        let pointee = *pointers
+            .get(&pointer)
+            .ok_or_else(|| format!("input {} isn't an input pointer", variable))?;
+        flatten(&types, &constants, pointee, location, &mut result)?;
+    }
+    result.sort_by_key(|&(location, _)| location);
+
+    Ok(result)
+}
+
+/// Pushes the per-location types of `type_id` starting at `location`, returning the number of
+/// locations it takes.
+fn flatten(
+    types: &HashMap<u32, Type>,
+    constants: &HashMap<u32, u32>,
+    type_id: u32,
+    location: u32,
+    result: &mut Vec<(u32, InputType)>,
+) -> Result<u32, String> {
+    let ty = *types
+        .get(&type_id)
+        .ok_or_else(|| format!("unsupported input type %{}", type_id))?;
+
+    match ty {
+        Type::Scalar(kind, width) => {
+            result.push((
+                location,
+                InputType {
+                    kind,
+                    width,
+                    components: 1,
+                },
+            ));
+            Ok(1)
+        }
+        Type::Vector { component, count } => match types.get(&component) {
+            Some(&Type::Scalar(kind, width)) => {
+                result.push((
+                    location,
+                    InputType {
+                        kind,
+                        width,
+                        components: count,
+                    },
+                ));
+                Ok(if width == 64 && count > 2 { 2 } else { 1 })
+            }
+            _ => Err(format!("unsupported vector component type %{}", component)),
+        },
+        Type::Matrix { column, count } => {
+            let mut used = 0;
+            for _ in 0..count {
+                used += flatten(types, constants, column, location + used, result)?;
+            }
+            Ok(used)
+        }
+        Type::Array { element, length } => {
+            let length = *constants
+                .get(&length)
+                .ok_or_else(|| format!("array length %{} isn't a constant", length))?;
+            let mut used = 0;
+            for _ in 0..length {
+                used += flatten(types, constants, element, location + used, result)?;
+            }
+            Ok(used)
+        }
+    }
+}
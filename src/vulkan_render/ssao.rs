@@ -0,0 +1,186 @@
+//! Kernel and noise texture of the SSAO pass, see `RenderConfig::ssao`. Built once and
+//! shared by every frame, only the AO images themselves are per frame.
+
+use crate::vulkan_render::buffer::{AllocatedBuffer, BufferInfo};
+use crate::vulkan_render::config::{SamplerConfig, SsaoConfig};
+use crate::vulkan_render::constants::MAX_SSAO_SAMPLES;
+use crate::vulkan_render::device::DeviceInfo;
+use crate::vulkan_render::format;
+use crate::vulkan_render::image_util::AllocatedImage;
+use crate::vulkan_render::structs::SsaoUbo;
+use crate::vulkan_render::utils;
+use ash::vk::{BufferUsageFlags, ImageAspectFlags, MemoryPropertyFlags};
+use ash::{vk, Device, Instance};
+use nalgebra::{Vector3, Vector4};
+
+/// Width and height of the tiled rotation noise, which the blur pass averages out.
+pub const NOISE_SIZE: u32 = 4;
+
+pub struct SsaoResources {
+    /// `SsaoUbo` with the kernel and the config's parameters.
+    pub kernel_buffer: AllocatedBuffer,
+    /// Random rotations around the normal, xy stored as `* 0.5 + 0.5`.
+    pub noise_image: AllocatedImage,
+    /// Nearest and repeating, so the noise tiles over the screen.
+    pub noise_sampler: vk::Sampler,
+    /// Linear and clamped, for the AO images in the blur and lighting passes.
+    pub ao_sampler: vk::Sampler,
+}
+
+impl SsaoResources {
+    pub fn new(device_info: &DeviceInfo, instance: &Instance, config: SsaoConfig) -> Self {
+        let mut random = XorShift(0x9e37_79b9);
+
+        let kernel_buffer = AllocatedBuffer::new_device_local_with_staging(
+            device_info,
+            instance,
+            &[Self::kernel_ubo(&mut random, config)],
+            BufferUsageFlags::UNIFORM_BUFFER,
+        );
+        let noise_image = Self::create_noise_image(device_info, instance, &mut random);
+
+        let noise_sampler = utils::create_texture_sampler(
+            device_info,
+            instance,
+            &SamplerConfig {
+                min_filter: vk::Filter::NEAREST,
+                mag_filter: vk::Filter::NEAREST,
+                mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+                address_mode: vk::SamplerAddressMode::REPEAT,
+                max_anisotropy: None,
+            },
+            1,
+        );
+        let ao_sampler =
+            utils::create_texture_sampler(device_info, instance, &SamplerConfig::screen(), 1);
+
+        Self {
+            kernel_buffer,
+            noise_image,
+            noise_sampler,
+            ao_sampler,
+        }
+    }
+
+    /// Hemisphere samples around +z, denser towards the center so close occluders count
+    /// more.
+    fn kernel_ubo(random: &mut XorShift, config: SsaoConfig) -> SsaoUbo {
+        let samples = config.samples.clamp(1, MAX_SSAO_SAMPLES as u32);
+        let mut kernel = [Vector4::zeros(); MAX_SSAO_SAMPLES];
+        for (i, sample) in kernel.iter_mut().take(samples as usize).enumerate() {
+            let direction = Vector3::new(
+                random.next_f32() * 2.0 - 1.0,
+                random.next_f32() * 2.0 - 1.0,
+                random.next_f32(),
+            )
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(Vector3::z());
+
+            let t = i as f32 / samples as f32;
+            let scale = 0.1 + 0.9 * t * t;
+            let offset = direction * random.next_f32() * scale;
+            *sample = Vector4::new(offset.x, offset.y, offset.z, 0.0);
+        }
+
+        SsaoUbo {
+            kernel,
+            params: Vector4::new(samples as f32, config.radius, config.bias, 0.0),
+        }
+    }
+
+    /// Uploads the noise through a staging buffer and leaves it in SHADER_READ_ONLY_OPTIMAL.
+    fn create_noise_image(
+        device_info: &DeviceInfo,
+        instance: &Instance,
+        random: &mut XorShift,
+    ) -> AllocatedImage {
+        let format = vk::Format::R8G8B8A8_UNORM;
+        let noise = (0..NOISE_SIZE * NOISE_SIZE)
+            .map(|_| {
+                let x = random.next_f32();
+                let y = random.next_f32();
+                [(x * 255.0) as u8, (y * 255.0) as u8, 0, 255]
+            })
+            .collect::<Vec<_>>();
+
+        let mut staging = AllocatedBuffer::new(
+            device_info,
+            instance,
+            (noise.len() * 4) as vk::DeviceSize,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        staging.update_buffer(device_info, &noise);
+
+        let image = AllocatedImage::new(
+            device_info,
+            instance,
+            NOISE_SIZE,
+            NOISE_SIZE,
+            format,
+            ImageAspectFlags::COLOR,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let (regions, _) = format::buffer_image_copies(
+            format,
+            vk::Extent2D {
+                width: NOISE_SIZE,
+                height: NOISE_SIZE,
+            },
+            1,
+            ImageAspectFlags::COLOR,
+        );
+
+        let command_buffer = BufferInfo::begin_single_time_command(device_info);
+        image.transition_layout(
+            device_info,
+            &command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+        unsafe {
+            device_info.logical_device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer,
+                image.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        }
+        image.transition_layout(
+            device_info,
+            &command_buffer,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        BufferInfo::end_single_time_command(device_info, command_buffer);
+
+        staging.destroy_buffer(&device_info.logical_device);
+        image
+    }
+
+    /// Nothing may still be using the resources.
+    pub fn destroy(self, device: &Device) {
+        unsafe {
+            device.destroy_sampler(self.noise_sampler, None);
+            device.destroy_sampler(self.ao_sampler, None);
+        }
+        self.kernel_buffer.destroy_buffer(device);
+        self.noise_image.destroy_image(device);
+    }
+}
+
+/// Marsaglia's xorshift32, so the kernel is the same on every run.
+struct XorShift(u32);
+
+impl XorShift {
+    /// Uniform in 0..1.
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+}
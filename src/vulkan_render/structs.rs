@@ -1,13 +1,23 @@
-use std::mem::offset_of;
+use std::fmt;
+use std::io;
+use std::mem::{self, offset_of};
+use std::time::Duration;
 
 use crate::vulkan_render::buffer::AllocatedBuffer;
+use crate::vulkan_render::config::{GBufferAttachment, ShadowTechnique};
+use crate::vulkan_render::constants::{
+    DEFAULT_ROUGHNESS, MAX_SHADOW_CASCADES, MAX_SHADOW_CASTERS, MAX_SSAO_SAMPLES, SHADOW_DEPTH_BIAS,
+};
 use crate::vulkan_render::device::DeviceInfo;
 use crate::vulkan_render::image_util;
 use crate::vulkan_render::image_util::AllocatedImage;
+use crate::vulkan_render::utils;
+#[cfg(feature = "shader-validation")]
+use crate::vulkan_render::vertex_layout::ShaderInterfaceError;
+use crate::vulkan_render::vertex_layout::VertexLayout;
 use ash::vk::{
     Extent3D, Format, ImageAspectFlags, ImageSubresourceRange, ImageTiling, ImageUsageFlags,
-    ImageViewCreateInfo, MemoryPropertyFlags, Sampler, SamplerAddressMode, SamplerCreateInfo,
-    SamplerMipmapMode,
+    ImageViewCreateInfo, MemoryPropertyFlags, Sampler,
 };
 use ash::{vk, Instance};
 use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
@@ -15,9 +25,506 @@ use serde::Serialize;
 
 pub struct GPUMeshData {
     pub vertex_buffer: AllocatedBuffer,
+    /// `u32` indices into `vertex_buffer`, bound for every mesh draw.
     pub index_buffer: AllocatedBuffer,
     pub index_count: u32,
+    pub vertex_count: u32,
     pub world_model: Matrix4<f32>,
+    pub skin: Option<Skin>,
+    /// Model space bounding sphere, center in xyz and radius in w.
+    pub bounding_sphere: Vector4<f32>,
+    /// Model space box around the vertices, tighter than the sphere for long meshes.
+    pub bounds: Bounds,
+    /// Clips the mesh's gbuffer draw, the full render extent when `None`.
+    pub scissor: Option<vk::Rect2D>,
+    /// Viewport depth range of the mesh's gbuffer draw, 0..1 when `None`.
+    pub depth_range: Option<(f32, f32)>,
+    /// Texture array slot the gbuffer pass samples albedo from, see
+    /// `VulkanBackend::register_texture`.
+    pub texture_index: u32,
+    /// Slot of a tangent space normal map perturbing the vertex normals, see
+    /// `VulkanBackend::set_mesh_normal_map`.
+    pub normal_texture_index: Option<u32>,
+    /// Uploaded with `world_model` every frame.
+    pub material: MaterialUbo,
+    /// `instance_count` `InstanceData`s, see `VulkanBackend::set_mesh_instances`. Drawn once
+    /// without instancing when `None`.
+    pub instance_buffer: Option<AllocatedBuffer>,
+    pub instance_count: u32,
+}
+
+impl GPUMeshData {
+    /// `bounding_sphere` moved by `world_model`, its radius grown by the largest axis scale.
+    pub fn world_bounding_sphere(&self) -> Vector4<f32> {
+        let sphere = self.bounding_sphere;
+        let center = self.world_model * Vector4::new(sphere.x, sphere.y, sphere.z, 1.0);
+        let scale = (0..3)
+            .map(|i| self.world_model.column(i).xyz().norm())
+            .fold(0.0, f32::max);
+        Vector4::new(center.x, center.y, center.z, sphere.w * scale)
+    }
+
+    /// `bounds` transformed by `world_model`.
+    pub fn world_bounds(&self) -> Bounds {
+        self.bounds.transformed(&self.world_model)
+    }
+}
+
+/// Axis aligned bounding box, in whatever space its corners were given in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Bounds {
+    /// Box around every vertex position, an empty box at the origin without vertices.
+    pub fn from_vertices(vertices: &[Vertex]) -> Bounds {
+        if vertices.is_empty() {
+            return Bounds {
+                min: Vector3::zeros(),
+                max: Vector3::zeros(),
+            };
+        }
+
+        vertices.iter().fold(
+            Bounds {
+                min: Vector3::repeat(f32::MAX),
+                max: Vector3::repeat(f32::MIN),
+            },
+            |bounds, vertex| Bounds {
+                min: bounds.min.inf(&vertex.pos),
+                max: bounds.max.sup(&vertex.pos),
+            },
+        )
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    /// The eight corners, x changing fastest.
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        std::array::from_fn(|i| {
+            Vector3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
+    }
+
+    /// Box around the corners moved by `model`, so it grows when rotated.
+    pub fn transformed(&self, model: &Matrix4<f32>) -> Bounds {
+        let corners = self
+            .corners()
+            .map(|corner| (model * Vector4::new(corner.x, corner.y, corner.z, 1.0)).xyz());
+        Bounds {
+            min: corners
+                .iter()
+                .fold(Vector3::repeat(f32::MAX), |min, c| min.inf(c)),
+            max: corners
+                .iter()
+                .fold(Vector3::repeat(f32::MIN), |max, c| max.sup(c)),
+        }
+    }
+
+    /// Box around both.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Center and radius of the sphere through the corners, at least 0.001.
+    pub fn bounding_sphere(&self) -> (Vector3<f32>, f32) {
+        let center = self.center();
+        (center, (self.max - center).norm().max(0.001))
+    }
+}
+
+/// Normalized planes of a `[0, 1]` depth view-projection, normals pointing inside: left,
+/// right, bottom, top, near, far.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Frustum {
+    pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Planes of `CameraMvpUbo`'s `proj * view`, or any other view-projection.
+    pub fn from_view_proj(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i: usize| view_proj.row(i).transpose();
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ];
+
+        Self {
+            planes: planes.map(|plane| plane / plane.xyz().norm()),
+        }
+    }
+
+    /// Whether any part of the sphere may be inside, the same test as the cull pass.
+    pub fn intersects_sphere(&self, center: &Vector3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(center) + plane.w >= -radius)
+    }
+
+    /// Whether any part of the box may be inside: its corner furthest along each plane's
+    /// normal is in front of the plane.
+    pub fn intersects_bounds(&self, bounds: &Bounds) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let furthest = Vector3::from_fn(|i, _| {
+                if normal[i] >= 0.0 {
+                    bounds.max[i]
+                } else {
+                    bounds.min[i]
+                }
+            });
+            normal.dot(&furthest) + plane.w >= 0.0
+        })
+    }
+}
+
+/// Per-frame input of `VulkanBackend::render_frame`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameInputs {
+    /// Seconds since the previous frame, added to the globals buffer's time.
+    pub delta_time: f32,
+    /// World to view matrix to render with, `VulkanBackend::camera`'s when `None`.
+    pub view: Option<Matrix4<f32>>,
+    /// Replaces the lighting from this frame on, like `VulkanBackend::set_lighting` but only
+    /// written to each frame's buffer once it's no longer in flight. Kept when `None`.
+    pub lighting: Option<LightingUbo>,
+}
+
+/// Why `VulkanBackend::render_frame` didn't present a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderError {
+    /// The swapchain no longer matched the surface and has been recreated.
+    SwapchainOutOfDate,
+    /// Nothing renders until `VulkanBackend::recreate_device`.
+    DeviceLost,
+    /// Any other failed call, the frame is left as it was.
+    Vulkan(vk::Result),
+    /// ERROR messages the validation layer reported since the last frame, with
+    /// `ValidationConfig::fail_on_error`. Nothing was rendered.
+    Validation(usize),
+    /// The frame's fence didn't signal within `RenderConfig::frame_timeout`. The device is
+    /// treated as lost from then on.
+    FrameTimeout,
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SwapchainOutOfDate => write!(f, "swapchain out of date, recreated"),
+            Self::DeviceLost => write!(f, "device lost"),
+            Self::Vulkan(result) => write!(f, "frame failed: {}", result),
+            Self::Validation(count) => write!(f, "{} validation errors", count),
+            Self::FrameTimeout => write!(f, "frame fence timed out"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// Why `FrameManager::wait_for_current_frame` gave up on the frame's fence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameWaitError {
+    /// Still unsignaled after the timeout, the fence is left as it was.
+    Timeout,
+    DeviceLost,
+    Vulkan(vk::Result),
+}
+
+impl fmt::Display for FrameWaitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "frame fence timed out"),
+            Self::DeviceLost => write!(f, "device lost"),
+            Self::Vulkan(result) => write!(f, "fence wait failed: {}", result),
+        }
+    }
+}
+
+impl std::error::Error for FrameWaitError {}
+
+impl From<FrameWaitError> for RenderError {
+    fn from(error: FrameWaitError) -> Self {
+        match error {
+            FrameWaitError::Timeout => Self::FrameTimeout,
+            FrameWaitError::DeviceLost => Self::DeviceLost,
+            FrameWaitError::Vulkan(result) => Self::Vulkan(result),
+        }
+    }
+}
+
+/// G-buffer formats the device can't use, each with the attachment it was chosen for. See
+/// `GBufferFormats::validate`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsupportedFormatsError {
+    pub formats: Vec<(GBufferAttachment, vk::Format)>,
+}
+
+impl fmt::Display for UnsupportedFormatsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported G-buffer formats:")?;
+        for (index, (attachment, format)) in self.formats.iter().enumerate() {
+            let separator = if index == 0 { " " } else { ", " };
+            write!(f, "{}{:?} {:?}", separator, attachment, format)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UnsupportedFormatsError {}
+
+/// A texture format the device can't upload to and sample with optimal tiling, see
+/// `VulkanBackend::add_texture`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnsupportedTextureFormatError {
+    pub format: vk::Format,
+}
+
+impl fmt::Display for UnsupportedTextureFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported texture format {:?}", self.format)
+    }
+}
+
+impl std::error::Error for UnsupportedTextureFormatError {}
+
+/// Why a graphics pipeline couldn't be built. Nothing it created is left behind.
+#[derive(Debug)]
+pub enum PipelineError {
+    ShaderReadError(io::Error),
+    ShaderModuleCreation(vk::Result),
+    /// Creating the pipeline layout or the pipeline itself failed.
+    PipelineCreation(vk::Result),
+    /// The pipeline needs a device feature that isn't supported, by its Vulkan name.
+    MissingFeature(&'static str),
+    /// The description of the pipeline named `pipeline` asks for an invalid combination.
+    InvalidDescription {
+        pipeline: String,
+        reason: String,
+    },
+    /// The vertex shader of the pipeline named `pipeline` reads inputs its vertex layout
+    /// doesn't provide, or couldn't be reflected.
+    #[cfg(feature = "shader-validation")]
+    ShaderInterfaceMismatch {
+        pipeline: String,
+        mismatches: ShaderInterfaceError,
+    },
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ShaderReadError(error) => write!(f, "unable to read shader: {}", error),
+            Self::ShaderModuleCreation(result) => {
+                write!(f, "unable to create shader module: {}", result)
+            }
+            Self::PipelineCreation(result) => write!(f, "unable to create pipeline: {}", result),
+            Self::MissingFeature(feature) => write!(f, "device doesn't support {}", feature),
+            Self::InvalidDescription { pipeline, reason } => {
+                write!(f, "pipeline {}: {}", pipeline, reason)
+            }
+            #[cfg(feature = "shader-validation")]
+            Self::ShaderInterfaceMismatch {
+                pipeline,
+                mismatches,
+            } => write!(f, "pipeline {}: {}", pipeline, mismatches),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ShaderReadError(error) => Some(error),
+            Self::ShaderModuleCreation(_)
+            | Self::PipelineCreation(_)
+            | Self::MissingFeature(_)
+            | Self::InvalidDescription { .. } => None,
+            #[cfg(feature = "shader-validation")]
+            Self::ShaderInterfaceMismatch { mismatches, .. } => Some(mismatches),
+        }
+    }
+}
+
+/// How long creating one pipeline took, see `PipelineCreationReport`.
+#[derive(Clone, Debug)]
+pub struct PipelineCreationTime {
+    /// The graphics pipeline's description name, or the compute shader's name.
+    pub name: String,
+    /// The whole `create_graphics_pipelines` call's time when it was created in a batch.
+    pub duration: Duration,
+    /// Pipelines sharing `duration`, 1 unless they were created in one call. Compute
+    /// pipelines are never batched.
+    pub batch_size: usize,
+}
+
+/// Creation times of every pipeline the renderer holds, all created through one pipeline
+/// cache. Started from a `RenderConfig::pipeline_cache_path` file, most of them are cache hits.
+#[derive(Clone, Debug, Default)]
+pub struct PipelineCreationReport {
+    pub pipelines: Vec<PipelineCreationTime>,
+    /// Bytes the pipeline cache was loaded with, 0 when it started empty.
+    pub cache_loaded_size: usize,
+    /// Time spent loading and creating the pipeline cache, not part of `total`.
+    pub cache_creation_time: Duration,
+}
+
+impl PipelineCreationReport {
+    /// Counts the time of each batch once.
+    pub fn total(&self) -> Duration {
+        // Every pipeline of a batch has the batch's duration, so their shares add up to it
+        self.pipelines
+            .iter()
+            .map(|pipeline| pipeline.duration / pipeline.batch_size.max(1) as u32)
+            .sum()
+    }
+
+    pub fn slowest(&self) -> Option<&PipelineCreationTime> {
+        self.pipelines
+            .iter()
+            .max_by_key(|pipeline| pipeline.duration)
+    }
+}
+
+/// GPU cull pass counters, read back a few frames late to avoid stalling.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub drawn: u32,
+    pub frustum_culled: u32,
+    /// Always 0 until there's an occlusion test.
+    pub occlusion_culled: u32,
+}
+
+/// Passes `VulkanBackend::pass_timings` measures, in recording order. A pass that's
+/// disabled still gets its timestamps and takes about 0 ms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuPass {
+    Cull,
+    Skin,
+    /// The main light's cascades and the other shadow casters.
+    Shadow,
+    Esm,
+    /// The depth prepass, when on, and the G-buffer pass.
+    GBuffer,
+    LinearDepth,
+    /// Occlusion and its blur.
+    Ssao,
+    Lighting,
+    Ssr,
+    Overlays,
+    /// Threshold, downsamples, upsamples and the add into the draw image.
+    Bloom,
+    Tonemap,
+    /// The present or FXAA pass, or the blit, into the swapchain image.
+    Present,
+}
+
+impl GpuPass {
+    pub const ALL: [GpuPass; 13] = [
+        Self::Cull,
+        Self::Skin,
+        Self::Shadow,
+        Self::Esm,
+        Self::GBuffer,
+        Self::LinearDepth,
+        Self::Ssao,
+        Self::Lighting,
+        Self::Ssr,
+        Self::Overlays,
+        Self::Bloom,
+        Self::Tonemap,
+        Self::Present,
+    ];
+    /// One timestamp at the start of the frame, then one after each pass.
+    pub const TIMESTAMP_COUNT: u32 = Self::ALL.len() as u32 + 1;
+
+    /// Query written once the pass has finished, query 0 marks the frame's start.
+    pub fn end_query(self) -> u32 {
+        self as u32 + 1
+    }
+}
+
+/// Compute skinning resources of a mesh, one of each buffer and set per frame in flight.
+pub struct Skin {
+    pub joint_matrices: Vec<Matrix4<f32>>,
+    pub joint_capacity: usize,
+    pub joint_buffers: Vec<AllocatedBuffer>,
+    /// Skinned copy of the mesh's vertices, drawn instead of `vertex_buffer`.
+    pub skinned_vertex_buffers: Vec<AllocatedBuffer>,
+    pub descriptor_sets: Vec<vk::DescriptorSet>,
+}
+
+/// Depth layers rendered one at a time and sampled as an array: one per cascade of the main
+/// light, or one per shadow casting light, see `ShadowConfig`.
+#[derive(Clone)]
+pub struct ShadowCasterMaps {
+    /// At least one layer, so the lighting pass always has something to bind.
+    pub image: AllocatedImage,
+    /// 2D view of each layer, rendered one at a time.
+    pub layer_views: Vec<vk::ImageView>,
+    /// 2D array view of every layer, sampled by the lighting pass.
+    pub array_view: vk::ImageView,
+}
+
+impl ShadowCasterMaps {
+    pub fn destroy(self, logical_device: &ash::Device) {
+        unsafe {
+            for view in self.layer_views.into_iter().chain([self.array_view]) {
+                logical_device.destroy_image_view(view, None);
+            }
+        }
+        self.image.destroy_image(logical_device);
+    }
+}
+
+/// Multisampled G-buffer attachments, resolved into the frame's single-sample albedo, normal
+/// and depth images at the end of the G-buffer pass. See `RenderConfig::msaa_samples`.
+#[derive(Clone)]
+pub struct MsaaTargets {
+    pub albedo: AllocatedImage,
+    pub normal: AllocatedImage,
+    pub depth: AllocatedImage,
+}
+
+impl MsaaTargets {
+    pub fn destroy(self, logical_device: &ash::Device) {
+        self.albedo.destroy_image(logical_device);
+        self.normal.destroy_image(logical_device);
+        self.depth.destroy_image(logical_device);
+    }
+}
+
+/// The sampled mesh texture and its view.
+#[derive(Default)]
+pub struct Texture {
+    pub image: vk::Image,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+}
+
+impl Texture {
+    pub fn destroy(self, logical_device: &ash::Device) {
+        unsafe {
+            logical_device.destroy_image_view(self.view, None);
+            logical_device.destroy_image(self.image, None);
+            logical_device.free_memory(self.memory, None);
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -79,32 +586,21 @@ impl ShadowMap {
                 .expect("failed to create shadow image view")
         };
 
-        let sampler_create_info = SamplerCreateInfo::default()
-            .mag_filter(vk::Filter::LINEAR)
-            .min_filter(vk::Filter::LINEAR)
-            .mipmap_mode(SamplerMipmapMode::LINEAR)
-            .address_mode_u(SamplerAddressMode::CLAMP_TO_EDGE)
-            .address_mode_v(SamplerAddressMode::CLAMP_TO_EDGE)
-            .address_mode_w(SamplerAddressMode::CLAMP_TO_EDGE)
-            .mip_lod_bias(0.0)
-            .max_anisotropy(16.0)
-            .min_lod(0.0)
-            .max_lod(1.0)
-            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE);
-
-        let sampler = unsafe {
-            device_info
-                .logical_device
-                .create_sampler(&sampler_create_info, None)
-                .expect("failed to create shadow sampler")
-        };
+        // Same comparison sampler the lighting pass's sampler2DShadow reads through
+        let sampler = utils::create_shadow_sampler(device_info, Some(vk::CompareOp::LESS_OR_EQUAL));
 
         let shadow_image = AllocatedImage {
             image,
             image_view,
+            array_view: None,
+            depth_view: None,
+            storage_view: None,
             image_memory,
             image_format: Format::D16_UNORM,
             image_extent: shadow_extend,
+            layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
         };
 
         Self {
@@ -114,12 +610,21 @@ impl ShadowMap {
     }
 }
 
+/// 84 bytes, 21 floats as `skin.comp` reads them.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
 pub struct Vertex {
     pub pos: Vector3<f32>,
     pub color: Vector3<f32>,
     pub tex_coord: Vector2<f32>,
+    pub joint_indices: [u16; 4],
+    /// All zero for vertices that aren't skinned.
+    pub joint_weights: Vector4<f32>,
+    /// Unit normal, only read by the gbuffer pass with a normal map.
+    pub normal: Vector3<f32>,
+    /// Unit tangent along +u in xyz and the bitangent's handedness in w, see
+    /// `Mesh::generate_tangents`. All zero without normal mapping.
+    pub tangent: Vector4<f32>,
 }
 
 impl Default for Vertex {
@@ -128,40 +633,80 @@ impl Default for Vertex {
             pos: Vector3::new(0.0, 0.0, 0.0),
             color: Vector3::new(0.0, 0.0, 0.0),
             tex_coord: Vector2::new(0.0, 0.0),
+            joint_indices: [0; 4],
+            joint_weights: Vector4::new(0.0, 0.0, 0.0, 0.0),
+            normal: Vector3::zeros(),
+            tangent: Vector4::zeros(),
         }
     }
 }
 
 impl Vertex {
-    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
-        [vk::VertexInputBindingDescription {
-            binding: 0,
-            stride: std::mem::size_of::<Self>() as u32,
-            input_rate: vk::VertexInputRate::VERTEX,
-        }]
-    }
-
-    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
-        [
-            vk::VertexInputAttributeDescription {
-                location: 0,
-                binding: 0,
-                format: Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Self, pos) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 1,
-                format: Format::R32G32B32_SFLOAT,
-                offset: offset_of!(Self, color) as u32,
-            },
-            vk::VertexInputAttributeDescription {
-                binding: 0,
-                location: 2,
-                format: Format::R32G32_SFLOAT,
-                offset: offset_of!(Self, tex_coord) as u32,
+    /// Every field, as the gbuffer pass reads it. `normal` and `tangent` come after the
+    /// instance matrix's locations, in 9 and 10.
+    pub fn layout() -> VertexLayout {
+        Self::position_layout()
+            .with_attribute(
+                1,
+                0,
+                Format::R32G32B32_SFLOAT,
+                offset_of!(Self, color) as u32,
+            )
+            .with_attribute(
+                2,
+                0,
+                Format::R32G32_SFLOAT,
+                offset_of!(Self, tex_coord) as u32,
+            )
+            .with_attribute(
+                3,
+                0,
+                Format::R16G16B16A16_UINT,
+                offset_of!(Self, joint_indices) as u32,
+            )
+            .with_attribute(
+                4,
+                0,
+                Format::R32G32B32A32_SFLOAT,
+                offset_of!(Self, joint_weights) as u32,
+            )
+            .with_attribute(
+                9,
+                0,
+                Format::R32G32B32_SFLOAT,
+                offset_of!(Self, normal) as u32,
+            )
+            .with_attribute(
+                10,
+                0,
+                Format::R32G32B32A32_SFLOAT,
+                offset_of!(Self, tangent) as u32,
+            )
+    }
+
+    /// `layout` plus `InstanceData` at binding 1, stepped per instance, its model matrix
+    /// in locations 5 to 8. See `RenderConfig::instancing`.
+    pub fn instanced_layout() -> VertexLayout {
+        let stride = mem::size_of::<InstanceData>() as u32;
+        let column = mem::size_of::<Vector4<f32>>() as u32;
+        let model = offset_of!(InstanceData, model) as u32;
+        (0..4).fold(
+            Self::layout().with_binding(1, stride, vk::VertexInputRate::INSTANCE),
+            |layout, i| {
+                layout.with_attribute(5 + i, 1, Format::R32G32B32A32_SFLOAT, model + i * column)
             },
-        ]
+        )
+    }
+
+    /// Only the position, for depth-only passes like the shadow map.
+    pub fn position_layout() -> VertexLayout {
+        VertexLayout::default()
+            .with_binding(
+                0,
+                mem::size_of::<Self>() as u32,
+                vk::VertexInputRate::VERTEX,
+            )
+            .with_attribute(0, 0, Format::R32G32B32_SFLOAT, offset_of!(Self, pos) as u32)
     }
 }
 
@@ -170,12 +715,93 @@ impl Vertex {
 pub struct CameraMvpUbo {
     pub view: Matrix4<f32>,
     pub proj: Matrix4<f32>,
+    pub light_view_proj: Matrix4<f32>,
+    /// Left and right eye view-projections, indexed by `gl_ViewIndex` with multiview.
+    pub eye_view_proj: [Matrix4<f32>; 2],
+    /// View-projection of each shadow caster layer, indexed by `Light::shadow_layer`.
+    pub caster_view_proj: [Matrix4<f32>; MAX_SHADOW_CASTERS],
+    /// World space eye position, w unused, so the lighting pass doesn't invert `view`.
+    pub position: Vector4<f32>,
+}
+
+/// The camera uniform buffer holds a `CameraMvpUbo` followed by the view-projection of each
+/// shadow cascade, written by `FrameData::update_shadow_cascades`.
+pub const CAMERA_BUFFER_SIZE: usize =
+    mem::size_of::<CameraMvpUbo>() + mem::size_of::<[Matrix4<f32>; MAX_SHADOW_CASCADES]>();
+
+/// Per-frame shader globals, bound at set 1, binding 0 of every pipeline (std140).
+#[repr(C)]
+#[derive(Clone, Debug, Copy, Default)]
+pub struct GlobalsUbo {
+    /// Seconds since the renderer started, or since the last `set_time`.
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame_index: u32,
+    pub _pad: u32,
+    /// Draw image size in pixels.
+    pub resolution: Vector2<f32>,
+    /// Sub-pixel projection offset in pixels, e.g. for TAA.
+    pub jitter: Vector2<f32>,
 }
 
+/// A mesh's slot of the model dynamic buffer, set 0 binding 1 of the gbuffer pipeline.
+/// Shadow vertex shaders only declare `model`.
 #[repr(C)]
 #[derive(Clone, Debug, Copy)]
 pub struct ModelDynamicUbo {
     pub model: Matrix4<f32>,
+    /// Inverse transpose of `model`'s upper 3x3, so normals stay perpendicular to their
+    /// surface under non-uniform scale. A `mat4` rather than a `mat3`, whose std140 columns
+    /// would need padding.
+    pub normal_model: Matrix4<f32>,
+    pub material: MaterialUbo,
+}
+
+impl ModelDynamicUbo {
+    pub fn new(model: Matrix4<f32>, material: MaterialUbo) -> Self {
+        let linear = model.fixed_view::<3, 3>(0, 0).into_owned();
+        // A zero scale flattens the mesh anyway, its normals don't matter
+        let normal_model = linear
+            .try_inverse()
+            .map_or(linear, |inverse| inverse.transpose());
+        Self {
+            model,
+            normal_model: normal_model.to_homogeneous(),
+            material,
+        }
+    }
+}
+
+/// Metallic-roughness material, written to the G-buffer for the lighting pass's
+/// Cook-Torrance BRDF (std140).
+#[repr(C)]
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub struct MaterialUbo {
+    /// Linear color multiplying the albedo texture, alpha unused.
+    pub base_color: Vector4<f32>,
+    /// Linear color, w is intensity. Not lit yet, the G-buffer has no channel left for it.
+    pub emissive: Vector4<f32>,
+    // x: metallic, y: perceptual roughness
+    pub params: Vector4<f32>,
+}
+
+impl Default for MaterialUbo {
+    /// White dielectric, as rough as surfaces were before materials.
+    fn default() -> Self {
+        Self {
+            base_color: Vector4::repeat(1.0),
+            emissive: Vector4::zeros(),
+            params: Vector4::new(0.0, DEFAULT_ROUGHNESS, 0.0, 0.0),
+        }
+    }
+}
+
+/// Per-instance vertex data of `Vertex::instanced_layout`.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct InstanceData {
+    /// Applied before the mesh's own model matrix.
+    pub model: Matrix4<f32>,
 }
 
 #[repr(C, align(16))]
@@ -210,4 +836,501 @@ pub struct LightingUbo {
     pub light_direction: Vector4<f32>,
     pub light_color: Vector4<f32>,
     pub ambient_light: Vector4<f32>,
+    // x: technique (0 = PCF, 1 = ESM), y: ESM exponent, z: depth bias
+    pub shadow_params: Vector4<f32>,
+    // x: Poisson PCF radius in texels, y: per-pixel rotation (0 or 1)
+    pub pcf_params: Vector4<f32>,
+}
+
+impl LightingUbo {
+    /// A yellow sun of intensity 2 shining along `light_direction` over a dim grey ambient,
+    /// with the shadow parameters `shadow_technique` needs.
+    pub fn new(light_direction: Vector3<f32>, shadow_technique: ShadowTechnique) -> Self {
+        let (shadow_params, pcf_params) = match shadow_technique {
+            ShadowTechnique::Pcf => (
+                Vector4::new(0.0, 0.0, SHADOW_DEPTH_BIAS, 0.0),
+                Vector4::zeros(),
+            ),
+            ShadowTechnique::Esm { c } => (
+                Vector4::new(1.0, c, SHADOW_DEPTH_BIAS, 0.0),
+                Vector4::zeros(),
+            ),
+            ShadowTechnique::PoissonPcf(pcf_config) => (
+                Vector4::new(2.0, 0.0, SHADOW_DEPTH_BIAS, 0.0),
+                Vector4::new(
+                    pcf_config.radius,
+                    if pcf_config.rotate { 1.0 } else { 0.0 },
+                    0.0,
+                    0.0,
+                ),
+            ),
+        };
+
+        Self {
+            light_direction: light_direction.push(0.0),
+            // w is intensity
+            light_color: Vector4::new(1.0, 1.0, 0.0, 2.0),
+            ambient_light: Vector4::new(0.1, 0.1, 0.1, 0.2),
+            shadow_params,
+            pcf_params,
+        }
+    }
+}
+
+/// Kernel and parameters of the SSAO pass, see `SsaoConfig`.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct SsaoUbo {
+    /// Offsets in the hemisphere around +z in units of the radius, w unused. Only the
+    /// first `SsaoConfig::samples` are read.
+    pub kernel: [Vector4<f32>; MAX_SSAO_SAMPLES],
+    // x: sample count, y: radius, z: bias
+    pub params: Vector4<f32>,
+}
+
+/// Parameters of the tonemap pass, see `TonemapConfig`.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct PostProcessUbo {
+    pub exposure: f32,
+    /// `TonemapOperator::shader_value`.
+    pub operator: u32,
+    /// 1 when `exposure` is scaled by the adapted luminance, see `AutoExposureConfig`.
+    pub auto_exposure: u32,
+    pub adaptation_speed: f32,
+    /// `AutoExposureConfig::min_ev`, the log2 luminance histogram bin 1 starts at.
+    pub min_log_luminance: f32,
+    /// `max_ev - min_ev`, spread over bins 1 and up.
+    pub log_luminance_range: f32,
+    pub _padding: [f32; 2],
+}
+
+/// Parameters of the bloom pass, see `BloomConfig`.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct BloomUbo {
+    pub threshold: f32,
+    pub knee: f32,
+    pub intensity: f32,
+    pub _padding: f32,
+}
+
+/// Parameters of the FXAA pass, see `FxaaConfig`.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct FxaaUbo {
+    pub edge_threshold: f32,
+    /// `FxaaQuality::search_steps`.
+    pub search_steps: u32,
+    pub _padding: [f32; 2],
+}
+
+/// How the components of a color or texture given to the renderer are encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Already linear, stored as is.
+    #[default]
+    Linear,
+    /// sRGB encoded, like colors picked in image editors.
+    Srgb,
+}
+
+impl ColorSpace {
+    /// `color` converted to linear. Alpha is linear in both spaces and stays as is.
+    pub fn to_linear(self, color: [f32; 4]) -> [f32; 4] {
+        match self {
+            Self::Linear => color,
+            Self::Srgb => {
+                let decode = |c: f32| {
+                    if c <= 0.04045 {
+                        c / 12.92
+                    } else {
+                        ((c + 0.055) / 1.055).powf(2.4)
+                    }
+                };
+                [
+                    decode(color[0]),
+                    decode(color[1]),
+                    decode(color[2]),
+                    color[3],
+                ]
+            }
+        }
+    }
+
+    /// Format an RGBA8 texture is uploaded as. sRGB textures, like albedo is authored in,
+    /// get R8G8B8A8_SRGB, so sampling decodes them before the lighting math sees them. Linear
+    /// ones, like normal maps, get R8G8B8A8_UNORM and are sampled as stored.
+    pub fn texture_format(self) -> vk::Format {
+        match self {
+            Self::Linear => vk::Format::R8G8B8A8_UNORM,
+            Self::Srgb => vk::Format::R8G8B8A8_SRGB,
+        }
+    }
+}
+
+/// What each attachment starts as when its pass begins, see `VulkanBackend::set_clear_values`.
+/// Colors are linear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClearValues {
+    /// Pixels without geometry, which the lighting pass multiplies like any albedo.
+    pub albedo: [f32; 4],
+    /// Stored as is, so encoded as `RenderConfig::normal_encoding` says.
+    pub normal: [f32; 4],
+    /// The lit image before the lighting pass, with `DrawImagePolicy::Discard`.
+    pub draw: [f32; 4],
+    /// The G-buffer depth, or the depth prepass's. The far plane by default: 1.0, or 0.0 with
+    /// `RenderConfig::reverse_z`.
+    pub depth: f32,
+    /// Every shadow map cascade and caster layer, 1.0 by default.
+    pub shadow_depth: f32,
+}
+
+impl Default for ClearValues {
+    fn default() -> Self {
+        Self {
+            albedo: [0.0; 4],
+            normal: [0.0; 4],
+            draw: [0.0; 4],
+            depth: 1.0,
+            shadow_depth: 1.0,
+        }
+    }
+}
+
+impl ClearValues {
+    /// The defaults with the depth cleared to the far plane of the range `reverse_z` picks.
+    pub fn new(reverse_z: bool) -> Self {
+        Self {
+            depth: if reverse_z { 0.0 } else { 1.0 },
+            ..Self::default()
+        }
+    }
+
+    /// `vk::ClearValue` of a float color attachment.
+    pub fn color(color: [f32; 4]) -> vk::ClearValue {
+        vk::ClearValue {
+            color: vk::ClearColorValue { float32: color },
+        }
+    }
+
+    /// `vk::ClearValue` of a depth attachment, without stencil.
+    pub fn depth_value(depth: f32) -> vk::ClearValue {
+        vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue { depth, stencil: 0 },
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    Directional = 0,
+    Point = 1,
+    Spot = 2,
+}
+
+/// One entry of the lighting pass's light list (std430). These add to the shadowed
+/// directional light of `LightingUbo` and cast no shadows unless made `with_shadow`.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    /// 1 to ask for one of the `ShadowConfig::max_shadow_casters` layers, 0 otherwise.
+    pub casts_shadow: u32,
+    /// Layer of the shadow caster maps the light samples, -1 without one. Assigned on upload
+    /// in light order, point lights never get one.
+    pub shadow_layer: i32,
+    pub _pad: u32,
+    /// World space, unused by directional lights.
+    pub position: Vector4<f32>,
+    /// World space direction the light travels, unused by point lights.
+    pub direction: Vector4<f32>,
+    /// w is intensity
+    pub color: Vector4<f32>,
+    /// x: range, y: cos of the spot's inner cone angle, z: cos of its outer cone angle
+    pub params: Vector4<f32>,
+}
+
+impl Light {
+    pub fn directional(direction: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Self {
+        Self::new(
+            LightKind::Directional,
+            Vector3::zeros(),
+            direction.normalize(),
+            color,
+        )
+        .with_intensity(intensity)
+    }
+
+    pub fn point(position: Vector3<f32>, color: Vector3<f32>, intensity: f32, range: f32) -> Self {
+        let mut light = Self::new(LightKind::Point, position, Vector3::zeros(), color)
+            .with_intensity(intensity);
+        light.params.x = range;
+        light
+    }
+
+    /// `inner_angle` and `outer_angle` are half angles in radians, `inner_angle` at most
+    /// `outer_angle`. The light fades out between them.
+    pub fn spot(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        assert!(
+            0.0 <= inner_angle && inner_angle <= outer_angle,
+            "spot light cone angles {} and {} not 0 <= inner <= outer",
+            inner_angle,
+            outer_angle
+        );
+        let mut light = Self::new(LightKind::Spot, position, direction.normalize(), color)
+            .with_intensity(intensity);
+        light.params = Vector4::new(range, inner_angle.cos(), outer_angle.cos(), 0.0);
+        light
+    }
+
+    fn new(
+        kind: LightKind,
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+    ) -> Self {
+        Self {
+            kind,
+            casts_shadow: 0,
+            shadow_layer: -1,
+            _pad: 0,
+            position: position.push(1.0),
+            direction: direction.push(0.0),
+            color: color.push(1.0),
+            params: Vector4::zeros(),
+        }
+    }
+
+    /// Casts shadows while a shadow caster layer is free. Point lights would need six and
+    /// never cast.
+    pub fn with_shadow(mut self) -> Self {
+        self.casts_shadow = 1;
+        self
+    }
+
+    /// Whether the light takes a caster layer while one is free.
+    pub fn wants_shadow_layer(&self) -> bool {
+        self.casts_shadow != 0 && self.kind != LightKind::Point
+    }
+
+    fn with_intensity(mut self, intensity: f32) -> Self {
+        self.color.w = intensity;
+        self
+    }
+}
+
+/// Header of the light storage buffer, followed by `count` `Light`s.
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct LightListHeader {
+    pub count: u32,
+    pub _pad: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Clone, Debug, Copy)]
+pub struct SsrUbo {
+    pub max_steps: u32,
+    pub thickness: f32,
+    pub max_roughness: f32,
+    pub intensity: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perspective_frustum() -> Frustum {
+        let proj = glm::perspective_rh_zo(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        Frustum::from_view_proj(&proj)
+    }
+
+    #[test]
+    fn from_view_proj_of_identity_is_the_clip_volume() {
+        let frustum = Frustum::from_view_proj(&Matrix4::identity());
+
+        assert_eq!(
+            frustum.planes,
+            [
+                Vector4::new(1.0, 0.0, 0.0, 1.0),
+                Vector4::new(-1.0, 0.0, 0.0, 1.0),
+                Vector4::new(0.0, 1.0, 0.0, 1.0),
+                Vector4::new(0.0, -1.0, 0.0, 1.0),
+                Vector4::new(0.0, 0.0, 1.0, 0.0),
+                Vector4::new(0.0, 0.0, -1.0, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn from_view_proj_normalizes_the_planes() {
+        for plane in perspective_frustum().planes {
+            assert!((plane.xyz().norm() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn intersects_sphere_culls_outside_each_plane() {
+        let frustum = perspective_frustum();
+
+        assert!(frustum.intersects_sphere(&Vector3::new(0.0, 0.0, -10.0), 1.0));
+        assert!(!frustum.intersects_sphere(&Vector3::new(0.0, 0.0, 10.0), 1.0));
+        assert!(!frustum.intersects_sphere(&Vector3::new(0.0, 0.0, -200.0), 1.0));
+        assert!(!frustum.intersects_sphere(&Vector3::new(20.0, 0.0, -10.0), 1.0));
+        assert!(!frustum.intersects_sphere(&Vector3::new(0.0, -20.0, -10.0), 1.0));
+    }
+
+    #[test]
+    fn intersects_sphere_keeps_spheres_straddling_a_plane() {
+        let frustum = perspective_frustum();
+
+        assert!(frustum.intersects_sphere(&Vector3::new(20.0, 0.0, -10.0), 8.0));
+        assert!(frustum.intersects_sphere(&Vector3::new(0.0, 0.0, 0.5), 1.0));
+    }
+
+    fn vertex_at(pos: Vector3<f32>) -> Vertex {
+        Vertex {
+            pos,
+            color: Vector3::zeros(),
+            tex_coord: Vector2::zeros(),
+            joint_indices: [0; 4],
+            joint_weights: Vector4::zeros(),
+            normal: Vector3::zeros(),
+            tangent: Vector4::zeros(),
+        }
+    }
+
+    #[test]
+    fn bounds_from_vertices_spans_every_position() {
+        let bounds = Bounds::from_vertices(&[
+            vertex_at(Vector3::new(1.0, -2.0, 3.0)),
+            vertex_at(Vector3::new(-1.0, 4.0, 0.0)),
+        ]);
+
+        assert_eq!(bounds.min, Vector3::new(-1.0, -2.0, 0.0));
+        assert_eq!(bounds.max, Vector3::new(1.0, 4.0, 3.0));
+    }
+
+    #[test]
+    fn bounds_from_no_vertices_is_empty_at_the_origin() {
+        let bounds = Bounds::from_vertices(&[]);
+
+        assert_eq!(bounds.min, Vector3::zeros());
+        assert_eq!(bounds.max, Vector3::zeros());
+    }
+
+    #[test]
+    fn intersects_bounds_culls_boxes_outside_a_plane() {
+        let frustum = perspective_frustum();
+        let inside = Bounds {
+            min: Vector3::new(-1.0, -1.0, -11.0),
+            max: Vector3::new(1.0, 1.0, -9.0),
+        };
+        let behind = Bounds {
+            min: Vector3::new(-1.0, -1.0, 1.0),
+            max: Vector3::new(1.0, 1.0, 2.0),
+        };
+        let right = Bounds {
+            min: Vector3::new(15.0, -1.0, -11.0),
+            max: Vector3::new(17.0, 1.0, -9.0),
+        };
+
+        assert!(frustum.intersects_bounds(&inside));
+        assert!(!frustum.intersects_bounds(&behind));
+        assert!(!frustum.intersects_bounds(&right));
+    }
+
+    #[test]
+    fn intersects_bounds_keeps_boxes_larger_than_the_frustum() {
+        let frustum = perspective_frustum();
+        let around = Bounds {
+            min: Vector3::repeat(-500.0),
+            max: Vector3::repeat(500.0),
+        };
+
+        assert!(frustum.intersects_bounds(&around));
+    }
+
+    #[test]
+    fn clear_values_new_clears_depth_to_the_far_plane() {
+        assert_eq!(ClearValues::new(false).depth, 1.0);
+        assert_eq!(ClearValues::new(true).depth, 0.0);
+        assert_eq!(ClearValues::new(true).shadow_depth, 1.0);
+        assert_eq!(
+            ClearValues::new(false).albedo,
+            ClearValues::default().albedo
+        );
+    }
+
+    #[test]
+    fn clear_value_helpers_fill_the_right_union_member() {
+        let color = ClearValues::color([0.25, 0.5, 0.75, 1.0]);
+        let depth = ClearValues::depth_value(0.5);
+
+        unsafe {
+            assert_eq!(color.color.float32, [0.25, 0.5, 0.75, 1.0]);
+            assert_eq!(depth.depth_stencil.depth, 0.5);
+            assert_eq!(depth.depth_stencil.stencil, 0);
+        }
+    }
+
+    #[test]
+    fn texture_format_decodes_only_srgb_textures() {
+        assert_eq!(ColorSpace::Srgb.texture_format(), vk::Format::R8G8B8A8_SRGB);
+        assert_eq!(
+            ColorSpace::Linear.texture_format(),
+            vk::Format::R8G8B8A8_UNORM
+        );
+        assert_eq!(
+            ColorSpace::default().texture_format(),
+            vk::Format::R8G8B8A8_UNORM
+        );
+    }
+
+    #[test]
+    fn creation_report_total_counts_each_batch_once() {
+        let time = |name: &str, millis, batch_size| PipelineCreationTime {
+            name: name.to_string(),
+            duration: Duration::from_millis(millis),
+            batch_size,
+        };
+        let report = PipelineCreationReport {
+            pipelines: vec![
+                time("gbuffer", 4, 1),
+                time("depth_prepass", 9, 2),
+                time("gbuffer_depth_equal", 9, 2),
+                time("cull", 1, 1),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(report.total(), Duration::from_millis(14));
+        assert_eq!(report.slowest().unwrap().duration, Duration::from_millis(9));
+    }
+
+    #[test]
+    fn normal_model_keeps_normals_perpendicular_under_non_uniform_scale() {
+        let model = Matrix4::new_nonuniform_scaling(&Vector3::new(4.0, 1.0, 1.0))
+            * Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0));
+        let ubo = ModelDynamicUbo::new(model, MaterialUbo::default());
+
+        // A 45 degree slope in the xy plane
+        let normal = Vector4::new(1.0, 1.0, 0.0, 0.0);
+        let along_surface = Vector4::new(1.0, -1.0, 0.0, 0.0);
+        let normal = ubo.normal_model * normal;
+        let along_surface = model * along_surface;
+
+        assert!(normal.dot(&along_surface).abs() < 1e-5);
+        assert_eq!(ubo.normal_model.column(3).into_owned(), Vector4::w());
+    }
 }
@@ -111,7 +111,7 @@ impl SwapchainInfo {
             }
         }
 
-        return *available_formats.first().unwrap();
+        *available_formats.first().unwrap()
     }
 
     fn choose_swap_present_mode(
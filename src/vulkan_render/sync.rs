@@ -0,0 +1,281 @@
+//! Barriers and submits written against synchronization2, recorded through the classic
+//! commands when the device doesn't support it. See `DeviceInfo::synchronization2`.
+
+use crate::vulkan_render::device::DeviceInfo;
+use ash::prelude::VkResult;
+use ash::vk;
+use std::slice;
+
+/// Sync2 stages without a classic bit of their own, and what they're part of.
+const STAGE_FALLBACKS: [(vk::PipelineStageFlags2, vk::PipelineStageFlags); 3] = [
+    (
+        vk::PipelineStageFlags2::from_raw(
+            vk::PipelineStageFlags2::COPY.as_raw()
+                | vk::PipelineStageFlags2::RESOLVE.as_raw()
+                | vk::PipelineStageFlags2::BLIT.as_raw()
+                | vk::PipelineStageFlags2::CLEAR.as_raw(),
+        ),
+        vk::PipelineStageFlags::TRANSFER,
+    ),
+    (
+        vk::PipelineStageFlags2::from_raw(
+            vk::PipelineStageFlags2::INDEX_INPUT.as_raw()
+                | vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT.as_raw(),
+        ),
+        vk::PipelineStageFlags::VERTEX_INPUT,
+    ),
+    // Tessellation and geometry shaders aren't enabled, so naming their stages is invalid
+    (
+        vk::PipelineStageFlags2::PRE_RASTERIZATION_SHADERS,
+        vk::PipelineStageFlags::VERTEX_SHADER,
+    ),
+];
+
+const ACCESS_FALLBACKS: [(vk::AccessFlags2, vk::AccessFlags); 2] = [
+    (
+        vk::AccessFlags2::from_raw(
+            vk::AccessFlags2::SHADER_SAMPLED_READ.as_raw()
+                | vk::AccessFlags2::SHADER_STORAGE_READ.as_raw(),
+        ),
+        vk::AccessFlags::SHADER_READ,
+    ),
+    (
+        vk::AccessFlags2::SHADER_STORAGE_WRITE,
+        vk::AccessFlags::SHADER_WRITE,
+    ),
+];
+
+/// Bits shared by both flag types: sync2 kept every classic bit at its old value.
+const CLASSIC_BITS: u64 = u32::MAX as u64;
+
+/// Classic stages covering `stages`. Sync2-only stages the crate doesn't use widen to
+/// ALL_COMMANDS.
+fn classic_stages(stages: vk::PipelineStageFlags2) -> vk::PipelineStageFlags {
+    let mut classic = vk::PipelineStageFlags::from_raw((stages.as_raw() & CLASSIC_BITS) as u32);
+    let mut remaining = vk::PipelineStageFlags2::from_raw(stages.as_raw() & !CLASSIC_BITS);
+    for (stages2, fallback) in STAGE_FALLBACKS {
+        if remaining.intersects(stages2) {
+            classic |= fallback;
+            remaining &= !stages2;
+        }
+    }
+    if !remaining.is_empty() {
+        classic |= vk::PipelineStageFlags::ALL_COMMANDS;
+    }
+    classic
+}
+
+/// Classic access covering `access`. Sync2-only bits the crate doesn't use widen to memory
+/// reads and writes.
+fn classic_access(access: vk::AccessFlags2) -> vk::AccessFlags {
+    let mut classic = vk::AccessFlags::from_raw((access.as_raw() & CLASSIC_BITS) as u32);
+    let mut remaining = vk::AccessFlags2::from_raw(access.as_raw() & !CLASSIC_BITS);
+    for (access2, fallback) in ACCESS_FALLBACKS {
+        if remaining.intersects(access2) {
+            classic |= fallback;
+            remaining &= !access2;
+        }
+    }
+    if !remaining.is_empty() {
+        classic |= vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE;
+    }
+    classic
+}
+
+/// `cmd_pipeline_barrier2`, or one classic barrier over the union of every barrier's stages.
+pub fn pipeline_barrier(
+    device_info: &DeviceInfo,
+    command_buffer: vk::CommandBuffer,
+    dependency_info: &vk::DependencyInfo,
+) {
+    let device = &device_info.logical_device;
+    if device_info.synchronization2 {
+        unsafe { device.cmd_pipeline_barrier2(command_buffer, dependency_info) };
+        return;
+    }
+
+    let (memory_barriers, buffer_barriers, image_barriers) = unsafe {
+        (
+            raw_slice(
+                dependency_info.p_memory_barriers,
+                dependency_info.memory_barrier_count,
+            ),
+            raw_slice(
+                dependency_info.p_buffer_memory_barriers,
+                dependency_info.buffer_memory_barrier_count,
+            ),
+            raw_slice(
+                dependency_info.p_image_memory_barriers,
+                dependency_info.image_memory_barrier_count,
+            ),
+        )
+    };
+
+    let mut src_stages = vk::PipelineStageFlags2::NONE;
+    let mut dst_stages = vk::PipelineStageFlags2::NONE;
+
+    let classic_memory_barriers = memory_barriers
+        .iter()
+        .map(|barrier| {
+            src_stages |= barrier.src_stage_mask;
+            dst_stages |= barrier.dst_stage_mask;
+            vk::MemoryBarrier::default()
+                .src_access_mask(classic_access(barrier.src_access_mask))
+                .dst_access_mask(classic_access(barrier.dst_access_mask))
+        })
+        .collect::<Vec<_>>();
+
+    let classic_buffer_barriers = buffer_barriers
+        .iter()
+        .map(|barrier| {
+            src_stages |= barrier.src_stage_mask;
+            dst_stages |= barrier.dst_stage_mask;
+            vk::BufferMemoryBarrier::default()
+                .src_access_mask(classic_access(barrier.src_access_mask))
+                .dst_access_mask(classic_access(barrier.dst_access_mask))
+                .src_queue_family_index(barrier.src_queue_family_index)
+                .dst_queue_family_index(barrier.dst_queue_family_index)
+                .buffer(barrier.buffer)
+                .offset(barrier.offset)
+                .size(barrier.size)
+        })
+        .collect::<Vec<_>>();
+
+    let classic_image_barriers = image_barriers
+        .iter()
+        .map(|barrier| {
+            src_stages |= barrier.src_stage_mask;
+            dst_stages |= barrier.dst_stage_mask;
+            vk::ImageMemoryBarrier::default()
+                .src_access_mask(classic_access(barrier.src_access_mask))
+                .dst_access_mask(classic_access(barrier.dst_access_mask))
+                .old_layout(barrier.old_layout)
+                .new_layout(barrier.new_layout)
+                .src_queue_family_index(barrier.src_queue_family_index)
+                .dst_queue_family_index(barrier.dst_queue_family_index)
+                .image(barrier.image)
+                .subresource_range(barrier.subresource_range)
+        })
+        .collect::<Vec<_>>();
+
+    // Classic barriers can't have empty stage masks, these are what NONE means on each side
+    let mut src_stage_mask = classic_stages(src_stages);
+    if src_stage_mask.is_empty() {
+        src_stage_mask = vk::PipelineStageFlags::TOP_OF_PIPE;
+    }
+    let mut dst_stage_mask = classic_stages(dst_stages);
+    if dst_stage_mask.is_empty() {
+        dst_stage_mask = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+    }
+
+    unsafe {
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            src_stage_mask,
+            dst_stage_mask,
+            dependency_info.dependency_flags,
+            &classic_memory_barriers,
+            &classic_buffer_barriers,
+            &classic_image_barriers,
+        )
+    }
+}
+
+/// `cmd_write_timestamp2`, or `cmd_write_timestamp` at the classic stage covering `stage`.
+pub fn write_timestamp(
+    device_info: &DeviceInfo,
+    command_buffer: vk::CommandBuffer,
+    stage: vk::PipelineStageFlags2,
+    query_pool: vk::QueryPool,
+    query: u32,
+) {
+    let device = &device_info.logical_device;
+    unsafe {
+        if device_info.synchronization2 {
+            device.cmd_write_timestamp2(command_buffer, stage, query_pool, query);
+        } else {
+            device.cmd_write_timestamp(command_buffer, classic_stages(stage), query_pool, query);
+        }
+    }
+}
+
+/// `queue_submit2`, or the same submits through `queue_submit`. Semaphores must be binary:
+/// their values and signal stages are dropped, classic signals happen after all commands.
+pub fn queue_submit(
+    device_info: &DeviceInfo,
+    queue: vk::Queue,
+    submits: &[vk::SubmitInfo2],
+    fence: vk::Fence,
+) -> VkResult<()> {
+    let device = &device_info.logical_device;
+    if device_info.synchronization2 {
+        return unsafe { device.queue_submit2(queue, submits, fence) };
+    }
+
+    struct ClassicSubmit {
+        wait_semaphores: Vec<vk::Semaphore>,
+        wait_stages: Vec<vk::PipelineStageFlags>,
+        command_buffers: Vec<vk::CommandBuffer>,
+        signal_semaphores: Vec<vk::Semaphore>,
+    }
+
+    let classic_submits = submits
+        .iter()
+        .map(|submit| {
+            let (waits, command_buffers, signals) = unsafe {
+                (
+                    raw_slice(
+                        submit.p_wait_semaphore_infos,
+                        submit.wait_semaphore_info_count,
+                    ),
+                    raw_slice(
+                        submit.p_command_buffer_infos,
+                        submit.command_buffer_info_count,
+                    ),
+                    raw_slice(
+                        submit.p_signal_semaphore_infos,
+                        submit.signal_semaphore_info_count,
+                    ),
+                )
+            };
+
+            ClassicSubmit {
+                wait_semaphores: waits.iter().map(|wait| wait.semaphore).collect(),
+                wait_stages: waits
+                    .iter()
+                    .map(|wait| match classic_stages(wait.stage_mask) {
+                        stages if stages.is_empty() => vk::PipelineStageFlags::ALL_COMMANDS,
+                        stages => stages,
+                    })
+                    .collect(),
+                command_buffers: command_buffers
+                    .iter()
+                    .map(|info| info.command_buffer)
+                    .collect(),
+                signal_semaphores: signals.iter().map(|signal| signal.semaphore).collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let submit_infos = classic_submits
+        .iter()
+        .map(|submit| {
+            vk::SubmitInfo::default()
+                .wait_semaphores(&submit.wait_semaphores)
+                .wait_dst_stage_mask(&submit.wait_stages)
+                .command_buffers(&submit.command_buffers)
+                .signal_semaphores(&submit.signal_semaphores)
+        })
+        .collect::<Vec<_>>();
+
+    unsafe { device.queue_submit(queue, &submit_infos, fence) }
+}
+
+/// Slice behind a Vulkan pointer and count pair, empty when the count is 0.
+unsafe fn raw_slice<'a, T>(pointer: *const T, count: u32) -> &'a [T] {
+    if count == 0 || pointer.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(pointer, count as usize)
+    }
+}
@@ -0,0 +1,267 @@
+//! Staged copies into DEVICE_LOCAL buffers and images, batched so a whole scene goes up in a
+//! single wait instead of one per buffer.
+
+use crate::vulkan_render::buffer::{AllocatedBuffer, BufferInfo};
+use crate::vulkan_render::device::{DeviceInfo, TransferQueue};
+use crate::vulkan_render::image_util::AllocatedImage;
+use crate::vulkan_render::sync;
+use ash::vk::{self, BufferUsageFlags, DeviceSize, MemoryPropertyFlags};
+use ash::Instance;
+use std::mem;
+
+/// Records its copies on `DeviceInfo::transfer` when there is one, releasing what they wrote
+/// to the graphics queue family. The graphics submit waits on a semaphore the copies signal
+/// and acquires it all back. Without a transfer queue everything goes into the graphics
+/// command buffer. Nothing is copied until `submit`, which every batch must end with.
+pub struct UploadBatch<'a> {
+    device_info: &'a DeviceInfo,
+    instance: &'a Instance,
+    /// The copies, `None` without a transfer queue.
+    transfer: Option<(&'a TransferQueue, vk::CommandBuffer)>,
+    /// The acquires and mip generation, and the copies without a transfer queue.
+    graphics_commands: vk::CommandBuffer,
+    /// Freed once the batch is submitted.
+    staging: Vec<AllocatedBuffer>,
+}
+
+impl<'a> UploadBatch<'a> {
+    pub fn new(device_info: &'a DeviceInfo, instance: &'a Instance) -> Self {
+        let transfer = device_info.transfer.as_ref().map(|transfer| {
+            let command_buffer =
+                BufferInfo::begin_single_time_command_in(device_info, transfer.command_pool);
+            (transfer, command_buffer)
+        });
+
+        Self {
+            device_info,
+            instance,
+            transfer,
+            graphics_commands: BufferInfo::begin_single_time_command(device_info),
+            staging: vec![],
+        }
+    }
+
+    fn copy_commands(&self) -> vk::CommandBuffer {
+        self.transfer
+            .map_or(self.graphics_commands, |(_, command_buffer)| command_buffer)
+    }
+
+    /// The family a release hands over from and an acquire takes over to, `None` without a
+    /// transfer queue.
+    fn ownership_transfer(&self) -> Option<(u32, u32)> {
+        self.transfer.map(|(transfer, _)| {
+            (
+                transfer.queue_index,
+                self.device_info.queue_info.graphics_queue_index,
+            )
+        })
+    }
+
+    fn stage<T>(&mut self, data: &[T]) -> vk::Buffer {
+        let mut staging = AllocatedBuffer::new(
+            self.device_info,
+            self.instance,
+            mem::size_of_val(data) as DeviceSize,
+            BufferUsageFlags::TRANSFER_SRC,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        );
+        staging.update_buffer(self.device_info, data);
+        let buffer = staging.buffer;
+        self.staging.push(staging);
+        buffer
+    }
+
+    /// DEVICE_LOCAL buffer holding `data` once the batch is submitted. `usage` gets
+    /// TRANSFER_DST added.
+    pub fn buffer<T>(&mut self, data: &[T], usage: BufferUsageFlags) -> AllocatedBuffer {
+        let size = mem::size_of_val(data) as DeviceSize;
+        let staging = self.stage(data);
+        let buffer = AllocatedBuffer::new(
+            self.device_info,
+            self.instance,
+            size,
+            usage | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+
+        let copy_region = vk::BufferCopy::default().size(size);
+        unsafe {
+            self.device_info.logical_device.cmd_copy_buffer(
+                self.copy_commands(),
+                staging,
+                buffer.buffer,
+                &[copy_region],
+            )
+        };
+
+        if let Some((src_family, dst_family)) = self.ownership_transfer() {
+            let ownership_barrier = vk::BufferMemoryBarrier2::default()
+                .src_queue_family_index(src_family)
+                .dst_queue_family_index(dst_family)
+                .buffer(buffer.buffer)
+                .size(vk::WHOLE_SIZE);
+            let release_barrier = ownership_barrier
+                .src_stage_mask(vk::PipelineStageFlags2::COPY)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE);
+            let acquire_barrier = ownership_barrier
+                .dst_stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+                .dst_access_mask(vk::AccessFlags2::MEMORY_READ);
+
+            sync::pipeline_barrier(
+                self.device_info,
+                self.copy_commands(),
+                &vk::DependencyInfo::default().buffer_memory_barriers(&[release_barrier]),
+            );
+            sync::pipeline_barrier(
+                self.device_info,
+                self.graphics_commands,
+                &vk::DependencyInfo::default().buffer_memory_barriers(&[acquire_barrier]),
+            );
+        }
+
+        buffer
+    }
+
+    /// Fills mip 0 of the color `image` from `data` through `regions`, then the rest of its
+    /// mips from it, leaving it in SHADER_READ_ONLY_OPTIMAL once the batch is submitted. See
+    /// `AllocatedImage::generate_mipmaps` for what the image needs.
+    pub fn image(&mut self, image: &AllocatedImage, data: &[u8], regions: &[vk::BufferImageCopy]) {
+        let staging = self.stage(data);
+        let copy_commands = self.copy_commands();
+
+        image.transition_layout(
+            self.device_info,
+            &copy_commands,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        );
+        unsafe {
+            self.device_info.logical_device.cmd_copy_buffer_to_image(
+                copy_commands,
+                staging,
+                image.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                regions,
+            );
+        }
+
+        if let Some((src_family, dst_family)) = self.ownership_transfer() {
+            // Stays in TRANSFER_DST_OPTIMAL, which generate_mipmaps starts from
+            let ownership_barrier = vk::ImageMemoryBarrier2::default()
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_queue_family_index(src_family)
+                .dst_queue_family_index(dst_family)
+                .image(image.image)
+                .subresource_range(
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .base_mip_level(0)
+                        .level_count(vk::REMAINING_MIP_LEVELS)
+                        .base_array_layer(0)
+                        .layer_count(vk::REMAINING_ARRAY_LAYERS),
+                );
+            let release_barrier = ownership_barrier
+                .src_stage_mask(vk::PipelineStageFlags2::COPY)
+                .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE);
+            let acquire_barrier = ownership_barrier
+                .dst_stage_mask(vk::PipelineStageFlags2::COPY | vk::PipelineStageFlags2::BLIT)
+                .dst_access_mask(
+                    vk::AccessFlags2::TRANSFER_READ | vk::AccessFlags2::TRANSFER_WRITE,
+                );
+
+            sync::pipeline_barrier(
+                self.device_info,
+                copy_commands,
+                &vk::DependencyInfo::default().image_memory_barriers(&[release_barrier]),
+            );
+            sync::pipeline_barrier(
+                self.device_info,
+                self.graphics_commands,
+                &vk::DependencyInfo::default().image_memory_barriers(&[acquire_barrier]),
+            );
+        }
+
+        image.generate_mipmaps(self.device_info, self.instance, &self.graphics_commands);
+    }
+
+    /// Submits the batch and waits for it, then frees the staging buffers. With a transfer
+    /// queue the graphics submit waits on a semaphore the copies signal, so only the second
+    /// submit is waited on.
+    pub fn submit(self) {
+        let device = &self.device_info.logical_device;
+        let graphics_queue = self.device_info.queue_info.graphics_queue;
+        let fence = unsafe {
+            device
+                .end_command_buffer(self.graphics_commands)
+                .expect("Failed to end command buffer!");
+            device
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+                .expect("failed to create upload fence")
+        };
+
+        let graphics_commands =
+            [vk::CommandBufferSubmitInfo::default().command_buffer(self.graphics_commands)];
+        let graphics_submit = vk::SubmitInfo2::default().command_buffer_infos(&graphics_commands);
+        let copies_done = match self.transfer {
+            Some((transfer, transfer_commands)) => {
+                let semaphore = unsafe {
+                    device
+                        .end_command_buffer(transfer_commands)
+                        .expect("Failed to end command buffer!");
+                    device
+                        .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                        .expect("failed to create upload semaphore")
+                };
+                // Signaled once the copies complete, waited on before any of the acquires
+                let semaphore_infos = [vk::SemaphoreSubmitInfo::default()
+                    .semaphore(semaphore)
+                    .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)];
+                let transfer_commands =
+                    [vk::CommandBufferSubmitInfo::default().command_buffer(transfer_commands)];
+                let transfer_submit = vk::SubmitInfo2::default()
+                    .command_buffer_infos(&transfer_commands)
+                    .signal_semaphore_infos(&semaphore_infos);
+
+                sync::queue_submit(
+                    self.device_info,
+                    transfer.queue,
+                    &[transfer_submit],
+                    vk::Fence::null(),
+                )
+                .expect("Failed to submit queue!");
+                sync::queue_submit(
+                    self.device_info,
+                    graphics_queue,
+                    &[graphics_submit.wait_semaphore_infos(&semaphore_infos)],
+                    fence,
+                )
+                .expect("Failed to submit queue!");
+                Some(semaphore)
+            }
+            None => {
+                sync::queue_submit(self.device_info, graphics_queue, &[graphics_submit], fence)
+                    .expect("Failed to submit queue!");
+                None
+            }
+        };
+
+        unsafe {
+            device
+                .wait_for_fences(&[fence], true, u64::MAX)
+                .expect("Failed to wait on upload fence!");
+            device.destroy_fence(fence, None);
+            // The graphics submit waited on the copies, so they're done too
+            if let (Some((transfer, transfer_commands)), Some(semaphore)) =
+                (self.transfer, copies_done)
+            {
+                device.destroy_semaphore(semaphore, None);
+                device.free_command_buffers(transfer.command_pool, &[transfer_commands]);
+            }
+            device.free_command_buffers(self.device_info.command_pool, &[self.graphics_commands]);
+        }
+        for staging in self.staging {
+            staging.destroy_buffer(device);
+        }
+    }
+}
@@ -1,7 +1,31 @@
 use std::mem;
 use ash::{vk, Instance};
 use ash::vk::{MemoryPropertyFlags, PhysicalDeviceMemoryProperties, Sampler};
+use crate::vulkan_render::config::SamplerConfig;
+use crate::vulkan_render::constants::MAX_SHADOW_CASCADES;
 use crate::vulkan_render::device::DeviceInfo;
+use crate::vulkan_render::structs::{Bounds, Vertex};
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+/// Labels `object` for validation messages and capture tools. Does nothing without the
+/// `debug-names` feature.
+pub fn set_debug_name<T: vk::Handle>(device_info: &DeviceInfo, object: T, name: &str) {
+    #[cfg(feature = "debug-names")]
+    {
+        let name = std::ffi::CString::new(name).expect("debug name contains a nul byte");
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(object)
+            .object_name(&name);
+        unsafe {
+            device_info
+                .debug_utils
+                .set_debug_utils_object_name(&name_info)
+                .expect("failed to set debug name");
+        }
+    }
+    #[cfg(not(feature = "debug-names"))]
+    let _ = (device_info, object, name);
+}
 
 pub fn find_memory_type(
     type_filter: u32,
@@ -31,26 +55,36 @@ pub fn get_buffer_alignment<T>(
     dynamic_alignment
 }
 
-pub fn create_texture_sampler(device_info: &DeviceInfo, instance: &Instance) -> Sampler {
+/// A sampler set up as `config` says for images of up to `mip_levels` mips, its anisotropy
+/// clamped to the device limit.
+pub fn create_texture_sampler(
+    device_info: &DeviceInfo,
+    instance: &Instance,
+    config: &SamplerConfig,
+    mip_levels: u32,
+) -> Sampler {
     let device_properties =
         unsafe { instance.get_physical_device_properties(device_info._physical_device) };
+    let max_anisotropy = config.max_anisotropy.map(|max_anisotropy| {
+        max_anisotropy.clamp(1.0, device_properties.limits.max_sampler_anisotropy)
+    });
 
     let sampler_info = vk::SamplerCreateInfo::default()
-        .mag_filter(vk::Filter::LINEAR)
-        .min_filter(vk::Filter::LINEAR)
-        .address_mode_u(vk::SamplerAddressMode::REPEAT)
-        .address_mode_v(vk::SamplerAddressMode::REPEAT)
-        .address_mode_w(vk::SamplerAddressMode::REPEAT)
-        .anisotropy_enable(true)
-        .max_anisotropy(device_properties.limits.max_sampler_anisotropy)
+        .mag_filter(config.mag_filter)
+        .min_filter(config.min_filter)
+        .address_mode_u(config.address_mode)
+        .address_mode_v(config.address_mode)
+        .address_mode_w(config.address_mode)
+        .anisotropy_enable(max_anisotropy.is_some())
+        .max_anisotropy(max_anisotropy.unwrap_or(1.0))
         .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
         .unnormalized_coordinates(false)
         .compare_enable(false)
         .compare_op(vk::CompareOp::ALWAYS)
-        .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
+        .mipmap_mode(config.mipmap_mode)
         .mip_lod_bias(0.0)
         .min_lod(0.0)
-        .max_lod(0.0);
+        .max_lod(mip_levels as f32);
 
     unsafe {
         device_info
@@ -58,4 +92,177 @@ pub fn create_texture_sampler(device_info: &DeviceInfo, instance: &Instance) ->
             .create_sampler(&sampler_info, None)
             .expect("failed to create sampler")
     }
-}
\ No newline at end of file
+}
+
+/// Clamp-to-border sampler for shadow maps, with depth comparison when `compare_op` is set.
+pub fn create_shadow_sampler(
+    device_info: &DeviceInfo,
+    compare_op: Option<vk::CompareOp>,
+) -> Sampler {
+    let sampler_info = vk::SamplerCreateInfo::default()
+        .mag_filter(vk::Filter::LINEAR)
+        .min_filter(vk::Filter::LINEAR)
+        .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        .anisotropy_enable(false)
+        .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+        .unnormalized_coordinates(false)
+        .compare_enable(compare_op.is_some())
+        .compare_op(compare_op.unwrap_or(vk::CompareOp::ALWAYS))
+        .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+        .mip_lod_bias(0.0)
+        .min_lod(0.0)
+        .max_lod(0.0);
+
+    unsafe {
+        device_info
+            .logical_device
+            .create_sampler(&sampler_info, None)
+            .expect("failed to create shadow sampler")
+    }
+}
+
+/// Center and radius of a sphere enclosing all vertices, see `Bounds::bounding_sphere`.
+pub fn bounding_sphere(vertices: &[Vertex]) -> (Vector3<f32>, f32) {
+    if vertices.is_empty() {
+        return (Vector3::zeros(), 1.0);
+    }
+    Bounds::from_vertices(vertices).bounding_sphere()
+}
+
+/// Light space transform without a shadow map, putting every point past the far plane, which
+/// the lighting pass counts as lit.
+pub fn unshadowed_light_view_proj() -> Matrix4<f32> {
+    let mut view_proj = Matrix4::zeros();
+    view_proj[(2, 3)] = 2.0;
+    view_proj[(3, 3)] = 1.0;
+    view_proj
+}
+
+/// Orthographic view-projection for a directional light covering the given bounding sphere.
+pub fn directional_light_view_proj(
+    direction: &Vector3<f32>,
+    center: &Vector3<f32>,
+    radius: f32,
+) -> Matrix4<f32> {
+    let direction = direction.normalize();
+    let eye = center - direction * radius * 2.0;
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+
+    let view = glm::look_at(&eye, center, &up);
+    let mut projection = glm::ortho_rh_zo(-radius, radius, -radius, radius, radius, radius * 3.0);
+    // Same flip as the camera so both passes agree on winding
+    projection[(1, 1)] *= -1.0;
+
+    projection * view
+}
+
+/// View distance at which each of `cascades` slices of `near..far` ends, the last one at
+/// `far`. `lambda` blends uniform splits (0.0) with logarithmic ones (1.0), which spend
+/// more of the shadow map close to the camera.
+pub fn cascade_splits(near: f32, far: f32, cascades: usize, lambda: f32) -> Vec<f32> {
+    (1..=cascades)
+        .map(|cascade| {
+            let fraction = cascade as f32 / cascades as f32;
+            let logarithmic = near * (far / near).powf(fraction);
+            let uniform = near + (far - near) * fraction;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// Directional light view-projection of each shadow cascade, fitted around the slices of the
+/// camera frustum from `near` to each of `splits` in turn. `fov_y` and `aspect_ratio` are the
+/// camera projection's. The depth range stretches back toward the light until it covers the
+/// scene's bounding sphere, so geometry outside a slice still casts into it. Cascades past
+/// `splits` are left as the identity.
+#[allow(clippy::too_many_arguments)]
+pub fn cascade_view_projs(
+    light_direction: &Vector3<f32>,
+    view: &Matrix4<f32>,
+    fov_y: f32,
+    aspect_ratio: f32,
+    near: f32,
+    splits: &[f32],
+    scene_center: &Vector3<f32>,
+    scene_radius: f32,
+) -> [Matrix4<f32>; MAX_SHADOW_CASCADES] {
+    let inverse_view = view.try_inverse().unwrap_or_else(Matrix4::identity);
+    let tan_half_fov = (fov_y * 0.5).tan();
+    let direction = light_direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+
+    let mut view_projs = [Matrix4::identity(); MAX_SHADOW_CASCADES];
+    let mut slice_near = near;
+    for (view_proj, &slice_far) in view_projs.iter_mut().zip(splits) {
+        // The camera looks down -z in view space
+        let corners = [slice_near, slice_far].into_iter().flat_map(|distance| {
+            let half_height = distance * tan_half_fov;
+            let half_width = half_height * aspect_ratio;
+            [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+                let corner = Vector4::new(x * half_width, y * half_height, -distance, 1.0);
+                (inverse_view * corner).xyz()
+            })
+        });
+        let corners = corners.collect::<Vec<_>>();
+        let center = corners.iter().sum::<Vector3<f32>>() / corners.len() as f32;
+        let radius = corners
+            .iter()
+            .map(|corner| (corner - center).norm())
+            .fold(0.001, f32::max);
+        let reach = (center - scene_center).norm() + scene_radius;
+
+        let eye = center - direction * reach;
+        let light_view = glm::look_at(&eye, &center, &up);
+        let mut projection =
+            glm::ortho_rh_zo(-radius, radius, -radius, radius, 0.0, reach + radius);
+        projection[(1, 1)] *= -1.0;
+
+        *view_proj = projection * light_view;
+        slice_near = slice_far;
+    }
+
+    view_projs
+}
+
+/// Perspective view-projection of a spot light, covering its outer cone out to `range`.
+pub fn spot_light_view_proj(
+    position: &Vector3<f32>,
+    direction: &Vector3<f32>,
+    cos_outer_angle: f32,
+    range: f32,
+) -> Matrix4<f32> {
+    let direction = direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+
+    let view = glm::look_at(position, &(position + direction), &up);
+    let fov = (cos_outer_angle.clamp(-1.0, 1.0).acos() * 2.0).clamp(0.01, 3.1);
+    let mut projection = glm::perspective_rh_zo(1.0, fov, (range * 0.001).max(0.001), range);
+    projection[(1, 1)] *= -1.0;
+
+    projection * view
+}
+
+/// View-projections of two eyes `separation` apart along the camera's x axis, left first.
+pub fn eye_view_projs(
+    view: &Matrix4<f32>,
+    projection: &Matrix4<f32>,
+    separation: f32,
+) -> [Matrix4<f32>; 2] {
+    let eye = |offset: f32| projection * glm::translation(&Vector3::new(offset, 0.0, 0.0)) * view;
+    // The left eye sits at -x, so the world moves the other way
+    [eye(separation * 0.5), eye(-separation * 0.5)]
+}
@@ -0,0 +1,112 @@
+//! `VK_LAYER_KHRONOS_validation` messages routed through the `log` crate, see
+//! `RenderConfig::validation`.
+
+use ash::vk;
+use std::ffi::{c_void, CStr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+/// A debug utils messenger logging every message at its severity's level, and counting
+/// the errors.
+pub struct ValidationMessenger {
+    debug_utils: ash::ext::debug_utils::Instance,
+    messenger: vk::DebugUtilsMessengerEXT,
+    /// Written by the callback, boxed so its address stays the messenger's user data.
+    error_count: Box<AtomicUsize>,
+    pub fail_on_error: bool,
+}
+
+impl ValidationMessenger {
+    /// Whether the validation layer is installed, so the instance can enable it.
+    pub fn layer_available(entry: &ash::Entry) -> bool {
+        unsafe { entry.enumerate_instance_layer_properties() }
+            .unwrap_or_default()
+            .iter()
+            .any(|layer| layer.layer_name_as_c_str() == Ok(VALIDATION_LAYER))
+    }
+
+    /// The instance must have been created with the validation layer and
+    /// `VK_EXT_debug_utils`.
+    pub fn new(entry: &ash::Entry, instance: &ash::Instance, fail_on_error: bool) -> Self {
+        let debug_utils = ash::ext::debug_utils::Instance::new(entry, instance);
+        let error_count = Box::new(AtomicUsize::new(0));
+
+        let create_info =
+            Self::create_info().user_data(&*error_count as *const AtomicUsize as *mut c_void);
+        let messenger = unsafe {
+            debug_utils
+                .create_debug_utils_messenger(&create_info, None)
+                .expect("failed to create debug utils messenger")
+        };
+
+        Self {
+            debug_utils,
+            messenger,
+            error_count,
+            fail_on_error,
+        }
+    }
+
+    /// Logs every message like the messenger, without counting errors. Chained into
+    /// `InstanceCreateInfo` it also covers instance creation and destruction.
+    pub fn create_info<'a>() -> vk::DebugUtilsMessengerCreateInfoEXT<'a> {
+        vk::DebugUtilsMessengerCreateInfoEXT::default()
+            .message_severity(
+                vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+                    | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            )
+            .message_type(
+                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
+                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
+                    | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,
+            )
+            .pfn_user_callback(Some(log_message))
+    }
+
+    /// ERROR messages since the last call.
+    pub fn take_errors(&self) -> usize {
+        self.error_count.swap(0, Ordering::Relaxed)
+    }
+
+    /// Later calls do nothing.
+    pub fn destroy(&mut self) {
+        let messenger = std::mem::take(&mut self.messenger);
+        if messenger != vk::DebugUtilsMessengerEXT::null() {
+            unsafe {
+                self.debug_utils
+                    .destroy_debug_utils_messenger(messenger, None)
+            };
+        }
+    }
+}
+
+unsafe extern "system" fn log_message(
+    severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    user_data: *mut c_void,
+) -> vk::Bool32 {
+    let message = callback_data
+        .as_ref()
+        .and_then(|data| data.message_as_c_str())
+        .map_or("".into(), CStr::to_string_lossy);
+
+    let level = match severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => {
+            if let Some(error_count) = (user_data as *const AtomicUsize).as_ref() {
+                error_count.fetch_add(1, Ordering::Relaxed);
+            }
+            log::Level::Error
+        }
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => log::Level::Warn,
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => log::Level::Info,
+        _ => log::Level::Debug,
+    };
+    log::log!(target: "vulkan", level, "{:?}: {}", message_type, message);
+
+    // Returning true would abort the call that triggered the message
+    vk::FALSE
+}
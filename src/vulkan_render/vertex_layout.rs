@@ -0,0 +1,240 @@
+use ash::vk;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "shader-validation")]
+use crate::vulkan_render::spirv_reflect::{self, InputType, ScalarKind};
+#[cfg(feature = "shader-validation")]
+use std::fmt;
+use std::mem;
+
+/// Declarative vertex input: the bindings and attributes a pipeline reads, and the layout
+/// uploaded vertex data is checked against. Vulkan enums are stored as their raw values.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct VertexLayout {
+    pub bindings: Vec<VertexBinding>,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct VertexBinding {
+    pub binding: u32,
+    pub stride: u32,
+    pub input_rate: i32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub binding: u32,
+    pub format: i32,
+    pub offset: u32,
+}
+
+impl VertexLayout {
+    pub fn with_binding(
+        mut self,
+        binding: u32,
+        stride: u32,
+        input_rate: vk::VertexInputRate,
+    ) -> Self {
+        self.bindings.push(VertexBinding {
+            binding,
+            stride,
+            input_rate: input_rate.as_raw(),
+        });
+        self
+    }
+
+    pub fn with_attribute(
+        mut self,
+        location: u32,
+        binding: u32,
+        format: vk::Format,
+        offset: u32,
+    ) -> Self {
+        self.attributes.push(VertexAttribute {
+            location,
+            binding,
+            format: format.as_raw(),
+            offset,
+        });
+        self
+    }
+
+    pub fn binding_descriptions(&self) -> Vec<vk::VertexInputBindingDescription> {
+        self.bindings
+            .iter()
+            .map(|binding| vk::VertexInputBindingDescription {
+                binding: binding.binding,
+                stride: binding.stride,
+                input_rate: vk::VertexInputRate::from_raw(binding.input_rate),
+            })
+            .collect()
+    }
+
+    pub fn attribute_descriptions(&self) -> Vec<vk::VertexInputAttributeDescription> {
+        self.attributes
+            .iter()
+            .map(|attribute| vk::VertexInputAttributeDescription {
+                location: attribute.location,
+                binding: attribute.binding,
+                format: vk::Format::from_raw(attribute.format),
+                offset: attribute.offset,
+            })
+            .collect()
+    }
+
+    /// Panics unless `T` matches the stride of `binding` and every attribute read from
+    /// `binding` fits inside one `T`.
+    pub fn validate<T>(&self, binding: u32) {
+        let stride = self
+            .bindings
+            .iter()
+            .find(|description| description.binding == binding)
+            .unwrap_or_else(|| panic!("vertex layout has no binding {}", binding))
+            .stride;
+        assert_eq!(
+            mem::size_of::<T>() as u32,
+            stride,
+            "vertex data doesn't match the stride of binding {}",
+            binding
+        );
+
+        for attribute in self.attributes.iter().filter(|a| a.binding == binding) {
+            let size = format_size(vk::Format::from_raw(attribute.format))
+                .unwrap_or_else(|| panic!("unsupported vertex format {}", attribute.format));
+            assert!(
+                attribute.offset + size <= stride,
+                "attribute at location {} reads past the end of the vertex",
+                attribute.location
+            );
+        }
+    }
+
+    /// Checks every `layout(location = N) in` of the vertex shader `spirv` against this
+    /// layout's attributes, listing each location without an attribute or whose attribute
+    /// format has a different numeric type.
+    #[cfg(feature = "shader-validation")]
+    pub fn validate_shader_inputs(&self, spirv: &[u8]) -> Result<(), ShaderInterfaceError> {
+        let inputs = spirv_reflect::input_locations(spirv).map_err(ShaderInterfaceError::Spirv)?;
+
+        let mismatches = inputs
+            .into_iter()
+            .filter_map(|(location, input)| {
+                let attribute = self.attributes.iter().find(|a| a.location == location);
+                match attribute {
+                    None => Some(InterfaceMismatch::MissingAttribute { location, input }),
+                    Some(attribute) => {
+                        let format = vk::Format::from_raw(attribute.format);
+                        (format_kind(format) != Some(input.kind)).then_some(
+                            InterfaceMismatch::IncompatibleFormat {
+                                location,
+                                input,
+                                format,
+                            },
+                        )
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(ShaderInterfaceError::Mismatches(mismatches))
+        }
+    }
+}
+
+#[cfg(feature = "shader-validation")]
+#[derive(Debug)]
+pub enum InterfaceMismatch {
+    MissingAttribute {
+        location: u32,
+        input: InputType,
+    },
+    IncompatibleFormat {
+        location: u32,
+        input: InputType,
+        format: vk::Format,
+    },
+}
+
+#[cfg(feature = "shader-validation")]
+#[derive(Debug)]
+pub enum ShaderInterfaceError {
+    /// The shader couldn't be reflected.
+    Spirv(String),
+    Mismatches(Vec<InterfaceMismatch>),
+}
+
+#[cfg(feature = "shader-validation")]
+impl fmt::Display for ShaderInterfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Spirv(error) => write!(f, "invalid vertex shader: {}", error),
+            Self::Mismatches(mismatches) => {
+                write!(f, "vertex shader inputs don't match the vertex layout:")?;
+                for mismatch in mismatches {
+                    match mismatch {
+                        InterfaceMismatch::MissingAttribute { location, input } => {
+                            write!(f, "\n  location {} ({}) has no attribute", location, input)?
+                        }
+                        InterfaceMismatch::IncompatibleFormat {
+                            location,
+                            input,
+                            format,
+                        } => write!(
+                            f,
+                            "\n  location {} ({}) is fed {:?}",
+                            location, input, format
+                        )?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "shader-validation")]
+impl std::error::Error for ShaderInterfaceError {}
+
+/// Numeric type a shader reads a vertex format as.
+#[cfg(feature = "shader-validation")]
+fn format_kind(format: vk::Format) -> Option<ScalarKind> {
+    match format {
+        vk::Format::R32_UINT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32B32_UINT
+        | vk::Format::R32G32B32A32_UINT
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R8G8B8A8_UINT => Some(ScalarKind::Uint),
+        vk::Format::R32_SINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32B32_SINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R16G16B16A16_SINT
+        | vk::Format::R8G8B8A8_SINT => Some(ScalarKind::Int),
+        vk::Format::R32_SFLOAT
+        | vk::Format::R32G32_SFLOAT
+        | vk::Format::R32G32B32_SFLOAT
+        | vk::Format::R32G32B32A32_SFLOAT
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SNORM
+        | vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM => Some(ScalarKind::Float),
+        _ => None,
+    }
+}
+
+/// Size in bytes of the vertex formats the crate uses.
+fn format_size(format: vk::Format) -> Option<u32> {
+    match format {
+        vk::Format::R32_SFLOAT | vk::Format::R32_UINT | vk::Format::R8G8B8A8_UNORM => Some(4),
+        vk::Format::R32G32_SFLOAT | vk::Format::R16G16B16A16_UINT => Some(8),
+        vk::Format::R32G32B32_SFLOAT => Some(12),
+        vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_UINT => Some(16),
+        _ => None,
+    }
+}
@@ -1,25 +1,46 @@
 use super::{
-    buffer::BufferInfo,
     device::DeviceInfo,
-    image_util,
+    format, image_util, present,
     structs::{CameraMvpUbo, Vertex},
     surface::SurfaceInfo,
     swapchain::SwapchainInfo,
-    utils,
+    sync, utils,
 };
 use crate::vulkan_render::buffer::AllocatedBuffer;
 use crate::vulkan_render::camera::Camera;
-use crate::vulkan_render::constants::MAX_FRAMES_IN_FLIGHT;
-use crate::vulkan_render::frame_manager::FrameManager;
+use crate::vulkan_render::compute_pipeline::ComputePipelineInfo;
+use crate::vulkan_render::config::{
+    BloomConfig, DrawImagePolicy, FxaaConfig, GBufferFormats, NormalEncoding, RenderConfig,
+    SamplerConfig, ShadowDepthBias, ShadowTechnique, TonemapConfig, WireframeConfig,
+};
+use crate::vulkan_render::constants::{
+    MAX_FRAMES_IN_FLIGHT, MAX_SHADOW_CASCADES, MAX_SHADOW_CASTERS, MAX_TEXTURES, NO_TEXTURE,
+    SCENE_TEXTURE,
+};
+use crate::vulkan_render::deletion_queue::Retired;
+use crate::vulkan_render::frame_manager::{FrameData, FrameManager};
+use crate::vulkan_render::graphics_pipeline::PipelineInfo;
 use crate::vulkan_render::image_util::AllocatedImage;
-use crate::vulkan_render::scene::{Mesh, SceneNode};
-use crate::vulkan_render::structs::{GPUMeshData, ModelDynamicUbo};
-use ash::vk::{self, Extent2D, Extent3D, ImageView, Rect2D};
+use crate::vulkan_render::pipeline_description::PipelineDescription;
+use crate::vulkan_render::raw_handles::{RawFrameHandles, RawHandles};
+use crate::vulkan_render::readback_pool::ReadbackSlot;
+use crate::vulkan_render::scene::{ImageResource, Mesh, Scene, SceneNode};
+use crate::vulkan_render::structs::{
+    Bounds, ClearValues, ColorSpace, FrameInputs, FrameStats, FrameWaitError, Frustum, GPUMeshData,
+    GpuPass, InstanceData, Light, LightKind, LightingUbo, MaterialUbo, ModelDynamicUbo,
+    PipelineCreationReport, PipelineError, RenderError, ShadowCasterMaps, Texture,
+    UnsupportedTextureFormatError,
+};
+use crate::vulkan_render::upload::UploadBatch;
+use crate::vulkan_render::validation::{ValidationMessenger, VALIDATION_LAYER};
+use ash::vk::{self, Extent2D, ImageView, Rect2D};
 use ash::vk::{ImageAspectFlags, MemoryPropertyFlags};
 use ash::Instance;
+use nalgebra::{Matrix4, Vector2, Vector3, Vector4};
 use std::cell::RefCell;
+use std::path::Path;
 use std::rc::Rc;
-use std::{error::Error, ffi::CString, mem, ptr};
+use std::{error::Error, ffi::CString, io, mem, ops, slice};
 use winit::{raw_window_handle::HasDisplayHandle, window::Window};
 
 pub struct VulkanBackend {
@@ -30,8 +51,26 @@ pub struct VulkanBackend {
     swapchain_info: SwapchainInfo,
     image_views: Vec<ImageView>,
     gpu_mesh_data: Vec<GPUMeshData>,
+    /// Sampled by every mesh, replaced by `set_scene`.
+    texture: Texture,
+    /// Uploaded by `add_texture`, destroyed with the backend.
+    added_textures: Vec<Texture>,
+    /// Textures are downscaled to fit before upload, see `RenderConfig::max_texture_size`.
+    max_texture_size: Option<u32>,
+    /// `RenderConfig::samplers.texture`, kept for `recreate_device`.
+    texture_sampler_config: SamplerConfig,
     pub camera: Camera,
     frame_manager: FrameManager,
+    /// Bounds the directional light's shadow frustum is fitted to.
+    scene_center: Vector3<f32>,
+    scene_radius: f32,
+    /// Camera frustum of the frame being recorded, tested by the cull pass.
+    frustum: Frustum,
+    frame_stats: FrameStats,
+    /// Set on `ERROR_DEVICE_LOST`, frames are skipped until `recreate_device`.
+    device_lost: bool,
+    /// Logs validation layer messages with `RenderConfig::validation`.
+    validation: Option<ValidationMessenger>,
 }
 
 impl VulkanBackend {
@@ -39,30 +78,54 @@ impl VulkanBackend {
         window: &Window,
         scene: Rc<RefCell<SceneNode>>,
         terrain_mesh: Mesh,
+        config: RenderConfig,
     ) -> Result<Self, Box<dyn Error>> {
         let entry = unsafe { ash::Entry::load()? };
-        let instance = Self::create_instance(&entry, window);
+        let validation = config
+            .validation
+            .filter(|_| ValidationMessenger::layer_available(&entry));
+        if config.validation.is_some() && validation.is_none() {
+            log::warn!("{:?} isn't installed, running without it", VALIDATION_LAYER);
+        }
+        let instance = Self::create_instance(&entry, window, validation.is_some());
+        let validation = validation.map(|validation| {
+            ValidationMessenger::new(&entry, &instance, validation.fail_on_error)
+        });
         let surface_info = SurfaceInfo::new(&entry, &instance, window);
-        let device_info = DeviceInfo::new(&instance, &surface_info);
+        let device_info =
+            DeviceInfo::new(&instance, &surface_info, !config.disable_synchronization2);
         let swapchain_info = SwapchainInfo::new(&instance, &device_info, &surface_info);
 
         let image_views = Self::create_image_views(&swapchain_info, &device_info);
 
-        let texture_image = Self::create_texture_image(&device_info, &instance);
-        let texture_image_view = Self::create_texture_image_view(&device_info, texture_image.0);
-        let texture_sampler = utils::create_texture_sampler(&device_info, &instance);
-
-        let gpu_mesh_data = Self::upload_meshes(&instance, &device_info, scene, terrain_mesh);
+        let mut uploads = UploadBatch::new(&device_info, &instance);
+        let (texture, texture_sampler) = Self::create_texture(
+            &instance,
+            &device_info,
+            &mut uploads,
+            config.max_texture_size,
+            &config.samplers.texture,
+        );
+        let gpu_mesh_data = Self::upload_meshes(&mut uploads, scene, terrain_mesh);
+        uploads.submit();
+        let (scene_center, scene_radius) = Self::scene_bounds(&gpu_mesh_data)
+            .map_or((Vector3::zeros(), 1.0), |bounds| bounds.bounding_sphere());
 
         let frame_manager = FrameManager::new(
             &device_info,
             &instance,
-            MAX_FRAMES_IN_FLIGHT as usize,
+            config
+                .frames_in_flight
+                .unwrap_or(MAX_FRAMES_IN_FLIGHT as usize),
             swapchain_info.swapchain_extent,
+            swapchain_info.swapchain_image_format,
             gpu_mesh_data.len(),
             &texture_sampler,
-            &texture_image_view,
-        );
+            &texture.view,
+            &config,
+        )?;
+        frame_manager.warm_up(&device_info, &instance);
+
         Ok(Self {
             _entry: entry,
             instance,
@@ -71,358 +134,2252 @@ impl VulkanBackend {
             swapchain_info,
             image_views,
             gpu_mesh_data,
+            texture,
+            added_textures: vec![],
+            max_texture_size: config.max_texture_size,
+            texture_sampler_config: config.samplers.texture,
             camera: Camera::new(),
             frame_manager,
+            scene_center,
+            scene_radius,
+            frustum: Frustum::default(),
+            frame_stats: FrameStats::default(),
+            device_lost: false,
+            validation,
         })
     }
 
-    fn upload_meshes(
-        instance: &Instance,
-        device_info: &DeviceInfo,
-        scene: Rc<RefCell<SceneNode>>,
-        mesh: Mesh,
-    ) -> Vec<GPUMeshData> {
-        let node = scene.borrow();
-        let vertices = mesh.vertices;
-        let indices = mesh.indices;
-
-        let vertex_buffer = Self::create_vertex_buffer(instance, device_info, &vertices);
-        let index_buffer = Self::create_index_buffer(instance, device_info, &indices);
+    /// Raw `ash` handles of the device and the current frame. Nothing recorded through them
+    /// is tracked, see `raw_handles` for what's safe.
+    pub fn raw_handles(&self) -> RawHandles<'_> {
+        RawHandles {
+            device: self.device_info.raw_handles(&self.instance),
+            frame: self.frame_manager.get_current_frame().raw_handles(),
+        }
+    }
 
-        let mut mesh_data = vec![];
+    /// Frame `frame` of the `frame_count`'s handles, e.g. to prerecord work for every frame.
+    /// `None` past the last frame.
+    pub fn frame_raw_handles(&self, frame: usize) -> Option<RawFrameHandles> {
+        self.frame_manager
+            .frame_at(frame)
+            .map(FrameData::raw_handles)
+    }
 
-        mesh_data.push(GPUMeshData {
-            vertex_buffer,
-            index_buffer,
-            index_count: indices.len() as u32,
-            world_model: node.transform.model,
-        });
+    /// Which of the `frame_count` frames is recorded next.
+    pub fn current_frame_index(&self) -> usize {
+        self.frame_manager.current_frame_index()
+    }
 
-        mesh_data
+    /// Whether the device was lost, the app then has to call `recreate_device` to draw again.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost
     }
 
-    pub fn draw_frame(&mut self, _delta_time: f32) {
-        self.update_camera();
-        self.update_world();
+    /// Recovers from `ERROR_DEVICE_LOST` by destroying the lost device and everything made from
+    /// it, then creating a new device and rebuilding the swapchain, textures, meshes and frame
+    /// resources on it. Mesh skins, scissors, depth ranges, textures and `register_texture` or
+    /// `add_texture` slots aren't kept.
+    pub fn recreate_device(&mut self, scene: Rc<RefCell<SceneNode>>, terrain_mesh: Mesh) {
+        self.cleanup_swapchain();
+        self.destroy_device_objects();
+        self.device_info.destroy();
 
-        let current_frame = self.frame_manager.get_current_frame();
-        unsafe {
-            self.device_info
-                .logical_device
-                .wait_for_fences(&[current_frame.render_fence], true, u64::MAX)
-                .expect("Unable to wait for fence")
-        }
+        // Same GPU and config, so the lost device's choice still holds
+        let allow_synchronization2 = self.device_info.synchronization2;
+        self.device_info =
+            DeviceInfo::new(&self.instance, &self.surface_info, allow_synchronization2);
+        self.swapchain_info =
+            SwapchainInfo::new(&self.instance, &self.device_info, &self.surface_info);
+        self.image_views = Self::create_image_views(&self.swapchain_info, &self.device_info);
 
-        let image_result = unsafe {
-            self.swapchain_info.swapchain_device.acquire_next_image(
-                self.swapchain_info.swapchain,
-                u64::MAX,
-                current_frame.swapchain_semaphore,
-                vk::Fence::null(),
-            )
-        };
+        let mut uploads = UploadBatch::new(&self.device_info, &self.instance);
+        let (texture, texture_sampler) = Self::create_texture(
+            &self.instance,
+            &self.device_info,
+            &mut uploads,
+            self.max_texture_size,
+            &self.texture_sampler_config,
+        );
+        self.texture = texture;
+        self.gpu_mesh_data = Self::upload_meshes(&mut uploads, scene, terrain_mesh);
+        uploads.submit();
 
-        let (image_index, _is_sub_optimal) = match image_result {
-            Ok(result) => result,
-            Err(error_result) => match error_result {
-                vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                    self.recreate_swapchain();
-                    println!("Error SWAPCHAIN");
-                    return;
-                }
-                _ => panic!(),
-            },
-        };
+        self.frame_manager.recreate_device(
+            &self.device_info,
+            &self.instance,
+            &texture_sampler,
+            &self.texture.view,
+        );
+        self.frame_manager
+            .warm_up(&self.device_info, &self.instance);
 
-        unsafe {
-            self.device_info
-                .logical_device
-                .reset_fences(&[current_frame.render_fence])
-                .expect("Unable to reset fence")
-        };
+        self.device_lost = false;
+    }
 
-        unsafe {
-            self.device_info
-                .logical_device
-                .reset_command_buffer(
-                    current_frame.command_buffer,
-                    vk::CommandBufferResetFlags::empty(),
-                )
-                .expect("Unable to reset command buffer");
+    /// Releases the per-frame images, buffers, samplers and sync objects before shutting
+    /// down, saving the pipeline cache first. Nothing may be rendered afterwards.
+    pub fn destroy(&mut self) {
+        self.destroy_device_objects();
+        if let Some(validation) = self.validation.as_mut() {
+            validation.destroy();
+        }
+    }
 
-            let command_buffer_begin_info = vk::CommandBufferBeginInfo::default();
+    /// Everything made from the device except the swapchain, so `recreate_device` can
+    /// destroy the lost device afterwards.
+    fn destroy_device_objects(&mut self) {
+        let meshes = mem::take(&mut self.gpu_mesh_data);
+        self.retire_meshes(meshes);
+        for texture in self.added_textures.drain(..) {
+            self.frame_manager.retire(Retired::Texture(texture));
+        }
+        self.frame_manager
+            .retire(Retired::Texture(mem::take(&mut self.texture)));
+        // Waits for the device and destroys what was retired before the descriptor pools
+        self.frame_manager.destroy(&self.device_info);
+    }
 
-            self.device_info
-                .logical_device
-                .begin_command_buffer(current_frame.command_buffer, &command_buffer_begin_info)
-                .expect("failed to begin command buffer")
+    fn retire_meshes(&mut self, meshes: Vec<GPUMeshData>) {
+        for mesh in meshes {
+            self.frame_manager
+                .retire(Retired::Buffer(mesh.vertex_buffer));
+            self.frame_manager
+                .retire(Retired::Buffer(mesh.index_buffer));
+            if let Some(instance_buffer) = mesh.instance_buffer {
+                self.frame_manager.retire(Retired::Buffer(instance_buffer));
+            }
+            if let Some(skin) = mesh.skin {
+                self.frame_manager.retire(Retired::Skin(skin));
+            }
         }
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.albedo_image.image,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::GENERAL,
-            false,
-        );
+    /// Writes the pipeline cache to `RenderConfig::pipeline_cache_path` now, rather than only
+    /// in `destroy`. Does nothing without a path.
+    pub fn save_pipeline_cache(&self) -> io::Result<()> {
+        self.frame_manager.save_pipeline_cache(&self.device_info)
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.normal_image.image,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::GENERAL,
-            false,
-        );
+    /// Frames recorded ahead of the GPU, see `RenderConfig::frames_in_flight`.
+    pub fn frame_count(&self) -> usize {
+        self.frame_manager.frame_count()
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.depth_image.image,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
-            true,
-        );
+    /// Cull pass counters from `frame_count` frames ago, so reading them never
+    /// stalls. All zero unless `RenderConfig::cull_stats` or `indirect_draws` is set.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.draw_image.image,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::GENERAL,
-            false,
-        );
+    /// GPU milliseconds per pass from `frame_count` frames ago, when its fence had already
+    /// signaled. Empty unless `RenderConfig::gpu_timings` is set and the device supports it.
+    pub fn pass_timings(&self) -> Vec<(GpuPass, f32)> {
+        GpuPass::ALL
+            .into_iter()
+            .zip(self.frame_manager.pass_timings().iter().copied())
+            .collect()
+    }
 
-        //self.render_offscreen_shadow_map(command_buffer);
-        self.render_scene();
+    /// Full state of every graphics pipeline, serializable for bug reports.
+    pub fn describe_pipelines(&self) -> Vec<PipelineDescription> {
+        self.frame_manager.describe_pipelines()
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.albedo_image.image,
-            vk::ImageLayout::GENERAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            false,
-        );
+    /// How long each pipeline took to create, to find the expensive ones. Each creation is
+    /// also logged as it happens.
+    pub fn pipeline_creation_report(&self) -> PipelineCreationReport {
+        self.frame_manager.pipeline_creation_report()
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.normal_image.image,
-            vk::ImageLayout::GENERAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            false,
-        );
+    pub fn shadow_resolution(&self) -> u32 {
+        self.frame_manager.shadow_resolution()
+    }
 
-        image_util::transition_image_layout(
-            &self.device_info,
-            &current_frame.command_buffer,
-            current_frame.depth_image.image,
-            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            true,
-        );
+    /// Recreates the shadow maps at `resolution`, clamped to what the device supports.
+    pub fn set_shadow_resolution(&mut self, resolution: u32) {
+        self.frame_manager
+            .set_shadow_resolution(&self.device_info, &self.instance, resolution);
+    }
 
-        self.render_lighting();
+    /// `RenderConfig::tonemap` as last set, see `set_tonemap`.
+    pub fn tonemap(&self) -> Option<TonemapConfig> {
+        self.frame_manager.tonemap()
+    }
 
-        unsafe {
-            image_util::transition_image_layout(
-                &self.device_info,
-                &current_frame.command_buffer,
-                current_frame.draw_image.image,
-                vk::ImageLayout::GENERAL,
-                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                false,
-            );
-            image_util::transition_image_layout(
-                &self.device_info,
-                &current_frame.command_buffer,
-                self.swapchain_info.swapchain_images[image_index as usize],
-                vk::ImageLayout::UNDEFINED,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                false,
-            );
+    /// Changes the tonemap pass's operator, exposure and auto exposure in every frame at once,
+    /// after the frames in flight finish. Does nothing visible unless the pass was enabled
+    /// through `RenderConfig::tonemap`. Panics if an `AutoExposureConfig`'s EV range is empty.
+    pub fn set_tonemap(&mut self, tonemap: TonemapConfig) {
+        self.frame_manager
+            .set_tonemap_all(&self.device_info, tonemap);
+    }
 
-            let draw_extend = Extent2D {
-                height: current_frame.draw_image.image_extent.height,
-                width: current_frame.draw_image.image_extent.width,
-            };
+    /// `RenderConfig::bloom` as last set, see `set_bloom`.
+    pub fn bloom(&self) -> Option<BloomConfig> {
+        self.frame_manager.bloom()
+    }
 
-            image_util::copy_image_to_image(
-                &self.device_info.logical_device,
-                &current_frame.command_buffer,
-                current_frame.draw_image.image,
-                self.swapchain_info.swapchain_images[image_index as usize],
-                draw_extend,
-                self.swapchain_info.swapchain_extent,
-            );
-            image_util::transition_image_layout(
-                &self.device_info,
-                &current_frame.command_buffer,
-                self.swapchain_info.swapchain_images[image_index as usize],
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                false,
-            );
-            image_util::transition_image_layout(
-                &self.device_info,
-                &current_frame.command_buffer,
-                self.swapchain_info.swapchain_images[image_index as usize],
-                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                vk::ImageLayout::PRESENT_SRC_KHR,
-                false,
-            );
+    /// Changes the bloom pass's threshold, knee and intensity in every frame at once, after the
+    /// frames in flight finish. Does nothing visible unless the pass was enabled through
+    /// `RenderConfig::bloom`.
+    pub fn set_bloom(&mut self, bloom: BloomConfig) {
+        self.frame_manager.set_bloom_all(&self.device_info, bloom);
+    }
 
-            self.device_info
-                .logical_device
-                .end_command_buffer(current_frame.command_buffer)
-                .expect("failed to end command buffer");
-        }
+    /// `RenderConfig::fxaa` as last set, see `set_fxaa`.
+    pub fn fxaa(&self) -> Option<FxaaConfig> {
+        self.frame_manager.fxaa()
+    }
 
-        let command_buffer_submit_info = [vk::CommandBufferSubmitInfo::default()
-            .command_buffer(current_frame.command_buffer)
-            .device_mask(0)];
+    /// Changes the FXAA pass's quality and edge threshold in every frame at once, after the
+    /// frames in flight finish. Does nothing visible unless the pass was enabled through
+    /// `RenderConfig::fxaa`.
+    pub fn set_fxaa(&mut self, fxaa: FxaaConfig) {
+        self.frame_manager.set_fxaa_all(&self.device_info, fxaa);
+    }
 
-        let wait_info = [vk::SemaphoreSubmitInfo::default()
-            .semaphore(current_frame.swapchain_semaphore)
-            .stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT_KHR)
-            .device_index(0)
-            .value(1)];
+    /// Sets the shadow passes' depth bias, see `ShadowConfig::depth_bias`.
+    pub fn set_shadow_depth_bias(&mut self, depth_bias: ShadowDepthBias) {
+        self.frame_manager.set_shadow_depth_bias(depth_bias);
+    }
 
-        let signal_info = [vk::SemaphoreSubmitInfo::default()
-            .semaphore(current_frame.render_semaphore)
-            .stage_mask(vk::PipelineStageFlags2::ALL_GRAPHICS)
-            .device_index(0)
-            .value(1)];
+    /// Turns the depth-only pass before the G-buffer pass on or off, see
+    /// `RenderConfig::depth_prepass`. Only fails creating its pipelines on first use.
+    pub fn set_depth_prepass(&mut self, enabled: bool) -> Result<(), PipelineError> {
+        self.frame_manager
+            .set_depth_prepass(&self.device_info, enabled)
+    }
 
-        let submit_info = vk::SubmitInfo2::default()
-            .command_buffer_infos(&command_buffer_submit_info)
-            .wait_semaphore_infos(&wait_info)
-            .signal_semaphore_infos(&signal_info);
+    /// Whether the G-buffer pass draws after a depth prepass.
+    pub fn depth_prepass(&self) -> bool {
+        self.frame_manager.depth_prepass
+    }
 
-        unsafe {
-            self.device_info
-                .logical_device
-                .queue_submit2(
-                    self.device_info.queue_info.graphics_queue,
-                    &[submit_info],
-                    current_frame.render_fence,
-                )
-                .expect("Unable to submit draw command buffer");
-        }
+    /// Draws the G-buffer pass as lines or back as triangles, see `RenderConfig::wireframe`.
+    /// Fails when the device lacks a feature the wireframe needs, or creating its pipeline
+    /// on first use.
+    pub fn set_wireframe(
+        &mut self,
+        wireframe: Option<WireframeConfig>,
+    ) -> Result<(), PipelineError> {
+        self.frame_manager
+            .set_wireframe(&self.device_info, wireframe)
+    }
 
-        let render_semaphores = [current_frame.render_semaphore];
-        let swapchains = [self.swapchain_info.swapchain];
-        let image_indices = [image_index];
+    pub fn wireframe(&self) -> Option<WireframeConfig> {
+        self.frame_manager.wireframe
+    }
 
-        let present_info = vk::PresentInfoKHR::default()
-            .wait_semaphores(&render_semaphores)
-            .swapchains(&swapchains)
-            .image_indices(&image_indices);
+    /// Rebuilds the gbuffer and lighting pipelines from the current `.spv` files, keeping the
+    /// old ones when that fails. Waits for the frames in flight first.
+    pub fn reload_pipelines(&mut self) -> Result<(), PipelineError> {
+        self.frame_manager.reload_pipelines(&self.device_info)
+    }
 
-        let present_result = unsafe {
-            self.swapchain_info
-                .swapchain_device
-                .queue_present(self.device_info.queue_info.present_queue, &present_info)
-        };
+    /// Whether the gbuffer or lighting shader files changed since they were loaded, checked
+    /// at most a few times a second. Call every frame and `reload_pipelines` when true.
+    pub fn poll_shader_changes(&mut self) -> bool {
+        self.frame_manager.poll_shader_changes()
+    }
 
-        match present_result {
-            Ok(result) => result,
-            Err(error_result) => match error_result {
-                vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                    self.recreate_swapchain();
-                    println!("Error SWAPCHAIN");
-                    return;
+    /// Poses `mesh` with `joint_matrices`, skinning it in a compute pass before it's drawn.
+    pub fn set_joint_matrices(&mut self, mesh: usize, joint_matrices: &[Matrix4<f32>]) {
+        let gpu_mesh = &mut self.gpu_mesh_data[mesh];
+        let fits = gpu_mesh
+            .skin
+            .as_ref()
+            .is_some_and(|skin| skin.joint_capacity >= joint_matrices.len());
+
+        if !fits {
+            if let Some(skin) = gpu_mesh.skin.take() {
+                unsafe {
+                    self.device_info
+                        .logical_device
+                        .device_wait_idle()
+                        .expect("failed to wait for device idle");
                 }
-                _ => panic!(),
-            },
-        };
+                self.frame_manager.destroy_skin(&self.device_info, skin);
+            }
+            self.frame_manager.invalidate_frozen();
 
-        self.frame_manager.advance_frame();
+            gpu_mesh.skin = Some(self.frame_manager.create_skin(
+                &self.device_info,
+                &self.instance,
+                &gpu_mesh.vertex_buffer,
+                gpu_mesh.vertex_count,
+                joint_matrices.len().next_power_of_two(),
+            ));
+        }
+
+        if let Some(skin) = gpu_mesh.skin.as_mut() {
+            skin.joint_matrices = joint_matrices.to_vec();
+        }
     }
 
-    fn render_lighting(&self) {
-        let current_frame = self.frame_manager.get_current_frame();
+    /// Adds a pipeline that draws every mesh over the lit image. Its description must use
+    /// `with_depth_test` so it tests against the gbuffer depth without writing it. Nothing is
+    /// added when its shaders can't be loaded or the pipeline can't be built.
+    pub fn add_overlay_pipeline(
+        &mut self,
+        description: PipelineDescription,
+    ) -> Result<(), PipelineError> {
+        self.frame_manager
+            .add_overlay_pipeline(&self.device_info, description)
+    }
 
-        let color_attachment = vk::RenderingAttachmentInfo::default()
-            .image_view(current_frame.draw_image.image_view)
-            .image_layout(vk::ImageLayout::GENERAL)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE);
+    /// The main light, ambient and shadow parameters of the lighting pass.
+    pub fn lighting(&self) -> LightingUbo {
+        self.frame_manager.lighting()
+    }
 
-        let color_attachments = [color_attachment];
-        let begin_render_info = vk::RenderingInfo::default()
-            .render_area(Rect2D {
-                extent: self.swapchain_info.swapchain_extent,
-                offset: vk::Offset2D { x: 0, y: 0 },
-            })
-            .layer_count(1)
-            .color_attachments(&color_attachments);
+    /// Replaces the main light, ambient and shadow parameters in every frame at once, after
+    /// the frames in flight finish. The light direction also turns the shadow map.
+    pub fn set_lighting(&mut self, lighting: LightingUbo) {
+        self.frame_manager
+            .set_lighting_all(&self.device_info, lighting);
+    }
 
-        unsafe {
-            self.device_info
-                .logical_device
-                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
-        }
+    /// How the G-buffer normal attachment is encoded, fixed by `RenderConfig::normal_encoding`.
+    /// Custom lighting shaders decode it with `normal_encoding.glsl`.
+    pub fn normal_encoding(&self) -> NormalEncoding {
+        self.frame_manager.normal_encoding()
+    }
 
-        self.set_viewport_scissor();
+    /// The G-buffer and draw image formats in use, `RenderConfig::gbuffer_formats` with any
+    /// fallbacks applied. Overlay pipelines render to `draw` and `depth`.
+    pub fn gbuffer_formats(&self) -> GBufferFormats {
+        self.frame_manager.gbuffer_formats()
+    }
 
-        unsafe {
-            self.device_info.logical_device.cmd_bind_pipeline(
-                current_frame.command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.frame_manager.lighting_pipeline.pipelines[0],
-            );
+    /// The linear color the albedo attachment is cleared to.
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.frame_manager.clear_values.albedo
+    }
 
-            self.device_info.logical_device.cmd_bind_descriptor_sets(
-                current_frame.command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.frame_manager.lighting_pipeline.pipeline_layout,
-                0,
-                &[current_frame.descriptor_lighting_set],
-                &[],
-            );
+    /// Sets what pixels without geometry start as, before the lighting pass multiplies them
+    /// like any albedo. The G-buffer, lit and draw images all hold linear color, so
+    /// `Srgb` colors are converted first; only the swapchain write encodes to sRGB.
+    pub fn set_clear_color(&mut self, color: [f32; 4], color_space: ColorSpace) {
+        self.frame_manager.clear_values.albedo = color_space.to_linear(color);
+        self.frame_manager.invalidate_frozen();
+    }
 
-            self.device_info
-                .logical_device
-                .cmd_draw(current_frame.command_buffer, 3, 1, 0, 0);
+    pub fn clear_values(&self) -> ClearValues {
+        self.frame_manager.clear_values
+    }
 
-            self.device_info
-                .logical_device
-                .cmd_end_rendering(current_frame.command_buffer);
-        }
+    /// Sets what the albedo, normal, draw, depth and shadow map attachments are cleared to
+    /// as their passes begin. Depths short of the far plane, 1.0 or 0.0 with
+    /// `RenderConfig::reverse_z`, also clip what lies behind them.
+    pub fn set_clear_values(&mut self, clear_values: ClearValues) {
+        self.frame_manager.clear_values = clear_values;
+        self.frame_manager.invalidate_frozen();
+    }
+
+    /// Adds a directional light to the lighting pass, returning its index. Unlike the main
+    /// light it casts no shadow until `set_light_casts_shadow`.
+    pub fn add_directional_light(
+        &mut self,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+    ) -> usize {
+        self.frame_manager
+            .add_light(Light::directional(direction, color, intensity))
+    }
+
+    /// Adds a point light reaching `range` world units, returning its index.
+    pub fn add_point_light(
+        &mut self,
+        position: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+        range: f32,
+    ) -> usize {
+        self.frame_manager
+            .add_light(Light::point(position, color, intensity, range))
+    }
+
+    /// Adds a spot light, see `Light::spot` for the cone angles. Returns its index.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_spot_light(
+        &mut self,
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> usize {
+        self.frame_manager.add_light(Light::spot(
+            position,
+            direction,
+            color,
+            intensity,
+            range,
+            inner_angle,
+            outer_angle,
+        ))
+    }
+
+    /// Replaces every light added so far with `lights`, indices follow the slice. The main
+    /// light of `set_lighting` isn't part of the list and stays.
+    pub fn set_lights(&mut self, lights: &[Light]) {
+        self.frame_manager.set_lights(lights);
+    }
+
+    /// The light list the lighting pass reads, in index order.
+    pub fn lights(&self) -> &[Light] {
+        self.frame_manager.lights()
+    }
+
+    /// Gives the light at `index` one of the `ShadowConfig::max_shadow_casters` shadow maps,
+    /// or takes it away. Lights ask in index order, the ones past the last layer and point
+    /// lights stay unshadowed.
+    pub fn set_light_casts_shadow(&mut self, index: usize, casts_shadow: bool) {
+        self.frame_manager
+            .set_light_casts_shadow(index, casts_shadow);
+    }
+
+    /// Reserves `size` bytes of host-visible buffer for the next `draw_frame`. Record a copy
+    /// into the slot's buffer and offset through `raw_handles` while that frame records,
+    /// followed by a barrier to `HOST` / `HOST_READ`, then poll `take_readback` on the
+    /// following frames.
+    pub fn request_readback(&mut self, size: u64) -> ReadbackSlot {
+        let frame = self.frame_manager.current_frame_index();
+        self.frame_manager
+            .readback_pool
+            .request(&self.device_info, &self.instance, frame, size)
+    }
+
+    /// The bytes copied into `slot`, once the frame it was recorded in has finished.
+    pub fn take_readback(&mut self, slot: &ReadbackSlot) -> Option<Vec<u8>> {
+        self.frame_manager.readback_pool.take(slot)
+    }
+
+    /// The lit image of the last `draw_frame`, before tonemapping, as tightly packed sRGB
+    /// RGBA8 rows, e.g. for reference images. Waits for the device to go idle.
+    pub fn read_draw_image(&self) -> Vec<u8> {
+        self.frame_manager
+            .read_draw_image(&self.device_info, &self.instance)
+    }
+
+    /// Restarts the shaders' `GlobalsUbo::time` at `time` seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.frame_manager.set_time(time);
+    }
+
+    /// Sub-pixel projection offset handed to shaders through `GlobalsUbo::jitter`.
+    pub fn set_jitter(&mut self, jitter: Vector2<f32>) {
+        self.frame_manager.set_jitter(jitter);
+    }
+
+    /// Indices `mesh`'s indexed draws cover, from its `u32` index buffer.
+    pub fn mesh_index_count(&self, mesh: usize) -> u32 {
+        self.gpu_mesh_data[mesh].index_count
+    }
+
+    /// Camera frustum of the last recorded frame.
+    pub fn frustum(&self) -> Frustum {
+        self.frustum
+    }
+
+    /// Whether `mesh`'s world bounding sphere and box both intersect `frustum`, ignoring
+    /// its instances.
+    pub fn mesh_in_frustum(&self, mesh: usize) -> bool {
+        let mesh = &self.gpu_mesh_data[mesh];
+        let sphere = mesh.world_bounding_sphere();
+        self.frustum.intersects_sphere(&sphere.xyz(), sphere.w)
+            && self.frustum.intersects_bounds(&mesh.world_bounds())
+    }
+
+    /// Clips `mesh`'s gbuffer draws to `scissor`, or stops clipping it with `None`.
+    pub fn set_mesh_scissor(&mut self, mesh: usize, scissor: Option<Rect2D>) {
+        self.gpu_mesh_data[mesh].scissor = scissor;
+        self.frame_manager.invalidate_frozen();
+    }
+
+    /// Adds a texture to the gbuffer pass's texture array and returns its slot for
+    /// `set_mesh_texture`. Slot 0 holds the scene texture, which every mesh samples by
+    /// default. `image_view` must be in SHADER_READ_ONLY_OPTIMAL and, like
+    /// `sampler`, outlive every frame drawing with it. Registrations are lost on
+    /// `recreate_device`.
+    pub fn register_texture(&mut self, image_view: ImageView, sampler: vk::Sampler) -> u32 {
+        self.frame_manager.register_texture(image_view, sampler)
+    }
+
+    /// Uploads `texture` as `color_space` says, mipmapped and downscaled like the scene
+    /// texture, and adds it to the texture array with the texture sampler. Returns its slot
+    /// for `set_mesh_texture`, which wants `ColorSpace::Srgb`, or `set_mesh_normal_map`,
+    /// which wants `ColorSpace::Linear`. Fails when the device can't sample the format.
+    pub fn add_texture(
+        &mut self,
+        texture: ImageResource,
+        color_space: ColorSpace,
+    ) -> Result<u32, UnsupportedTextureFormatError> {
+        let mut uploads = UploadBatch::new(&self.device_info, &self.instance);
+        let texture = Self::upload_texture(
+            &self.instance,
+            &self.device_info,
+            &mut uploads,
+            texture,
+            color_space,
+            self.max_texture_size,
+        );
+        uploads.submit();
+        let texture = texture?;
+        let slot = self
+            .frame_manager
+            .register_texture(texture.view, self.frame_manager.texture_sampler());
+        self.added_textures.push(texture);
+        Ok(slot)
+    }
+
+    /// Samples `mesh`'s albedo from texture array slot `texture_index`, see
+    /// `register_texture`. Sampling has to decode sRGB albedo, see
+    /// `ColorSpace::texture_format`.
+    pub fn set_mesh_texture(&mut self, mesh: usize, texture_index: u32) {
+        assert!(
+            (texture_index as usize) < MAX_TEXTURES,
+            "texture index {} past the texture array",
+            texture_index
+        );
+        self.gpu_mesh_data[mesh].texture_index = texture_index;
+        self.frame_manager.invalidate_frozen();
+    }
+
+    pub fn mesh_material(&self, mesh: usize) -> MaterialUbo {
+        self.gpu_mesh_data[mesh].material
+    }
+
+    /// Takes effect from the next frame on, frozen frames included.
+    pub fn set_mesh_material(&mut self, mesh: usize, material: MaterialUbo) {
+        self.gpu_mesh_data[mesh].material = material;
+    }
+
+    /// Perturbs `mesh`'s normals by the tangent space normal map in slot `texture_index`, or
+    /// goes back to flat normals with `None`. The mesh needs vertex normals and tangents, and
+    /// the map has to be sampled as stored, see `ColorSpace::texture_format`.
+    pub fn set_mesh_normal_map(&mut self, mesh: usize, texture_index: Option<u32>) {
+        if let Some(texture_index) = texture_index {
+            assert!(
+                (texture_index as usize) < MAX_TEXTURES,
+                "texture index {} past the texture array",
+                texture_index
+            );
+        }
+        self.gpu_mesh_data[mesh].normal_texture_index = texture_index;
+        self.frame_manager.invalidate_frozen();
+    }
+
+    /// Draws `mesh` into the `min_depth..max_depth` part of the depth buffer, or the full
+    /// range with `None`. See `FrameData::set_depth_range`. With `RenderConfig::reverse_z`
+    /// the near end is 1.0, so a mesh meant to be in front of the world takes e.g. 0.9..1.
+    pub fn set_mesh_depth_range(&mut self, mesh: usize, depth_range: Option<(f32, f32)>) {
+        self.gpu_mesh_data[mesh].depth_range = depth_range;
+        self.frame_manager.invalidate_frozen();
+    }
+
+    /// Draws `mesh` once per model matrix in the G-buffer pass, each applied before the mesh's
+    /// own, in a single indexed draw. An empty slice goes back to drawing it once. Needs
+    /// `RenderConfig::instancing`. The old instances are destroyed once the frames in flight
+    /// finish.
+    pub fn set_mesh_instances(&mut self, mesh: usize, models: &[Matrix4<f32>]) {
+        assert!(
+            self.frame_manager.instancing,
+            "mesh instances need RenderConfig::instancing"
+        );
+        let instances = models
+            .iter()
+            .map(|&model| InstanceData { model })
+            .collect::<Vec<_>>();
+        let instance_buffer = (!instances.is_empty()).then(|| {
+            AllocatedBuffer::new_device_local_with_staging(
+                &self.device_info,
+                &self.instance,
+                &instances,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+            )
+        });
+
+        let gpu_mesh = &mut self.gpu_mesh_data[mesh];
+        gpu_mesh.instance_count = instances.len().max(1) as u32;
+        if let Some(old_buffer) = mem::replace(&mut gpu_mesh.instance_buffer, instance_buffer) {
+            self.frame_manager.retire(Retired::Buffer(old_buffer));
+        }
+        self.frame_manager.invalidate_frozen();
+    }
+
+    /// Records each frame once per swapchain image and submits those recordings again from
+    /// then on, skipping the recording cost for scenes that don't change. The camera, model
+    /// matrices, lights and globals still update, they're read from buffers. Culling push
+    /// constants, readbacks and work recorded through `raw_handles` are only part of the
+    /// first recording. Methods here that change what's recorded start a new recording, and
+    /// `recreate_device` unfreezes.
+    pub fn freeze(&mut self) {
+        self.frame_manager.freeze();
+    }
+
+    /// Goes back to recording every frame.
+    pub fn unfreeze(&mut self) {
+        self.frame_manager.unfreeze();
+    }
+
+    /// Grows the per-frame model buffers to fit `mesh_count` meshes ahead of a `set_scene`
+    /// that would otherwise grow them frame by frame. Waits for the device to go idle when
+    /// they have to grow.
+    pub fn reserve_meshes(&mut self, mesh_count: usize) {
+        self.frame_manager
+            .ensure_model_capacity(&self.device_info, &self.instance, mesh_count);
+    }
+
+    /// Replaces every mesh, the texture when given and the lights, without waiting for the
+    /// frames in flight. The old meshes, their skins and the old texture are destroyed once
+    /// those frames finish, and each frame rebuilds its model buffers and descriptor sets the
+    /// next time it's drawn. Mesh scissors, depth ranges and textures don't carry over, the
+    /// new meshes sample the scene texture.
+    pub fn set_scene(&mut self, scene: Scene) {
+        let mut uploads = UploadBatch::new(&self.device_info, &self.instance);
+        let gpu_mesh_data = scene
+            .meshes
+            .iter()
+            .map(|scene_mesh| {
+                Self::upload_mesh(&mut uploads, &scene_mesh.mesh, scene_mesh.world_model)
+            })
+            .collect::<Vec<_>>();
+        let texture = scene.texture.map(|texture| {
+            Self::upload_texture(
+                &self.instance,
+                &self.device_info,
+                &mut uploads,
+                texture,
+                ColorSpace::Srgb,
+                self.max_texture_size,
+            )
+            .expect("Unable to upload scene texture")
+        });
+        uploads.submit();
+
+        if let Some(bounds) = Self::scene_bounds(&gpu_mesh_data) {
+            (self.scene_center, self.scene_radius) = bounds.bounding_sphere();
+        }
+
+        let old_meshes = mem::replace(&mut self.gpu_mesh_data, gpu_mesh_data);
+        self.retire_meshes(old_meshes);
+
+        let texture_view = texture.map(|texture| {
+            let view = texture.view;
+            let old_texture = mem::replace(&mut self.texture, texture);
+            self.frame_manager.retire(Retired::Texture(old_texture));
+            view
+        });
+
+        self.frame_manager
+            .set_scene(self.gpu_mesh_data.len(), texture_view, scene.lights);
+    }
+
+    fn create_texture(
+        instance: &Instance,
+        device_info: &DeviceInfo,
+        uploads: &mut UploadBatch,
+        max_texture_size: Option<u32>,
+        sampler_config: &SamplerConfig,
+    ) -> (Texture, vk::Sampler) {
+        let texture = ImageResource::load(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("resources")
+                .join("textures")
+                .join("texture.png"),
+        );
+        let texture = Self::upload_texture(
+            instance,
+            device_info,
+            uploads,
+            texture,
+            ColorSpace::Srgb,
+            max_texture_size,
+        )
+        .expect("Unable to upload scene texture");
+        // Covers the mips of any texture set_scene may upload later, not just this one
+        let max_texture_size = max_texture_size.unwrap_or_else(|| unsafe {
+            instance
+                .get_physical_device_properties(device_info._physical_device)
+                .limits
+                .max_image_dimension2_d
+        });
+        let max_mip_levels = format::mip_count(Extent2D {
+            width: max_texture_size,
+            height: max_texture_size,
+        });
+        let texture_sampler =
+            utils::create_texture_sampler(device_info, instance, sampler_config, max_mip_levels);
+
+        (texture, texture_sampler)
+    }
+
+    /// Recorded into `uploads`, the texture can only be sampled once they're submitted.
+    fn upload_texture(
+        instance: &Instance,
+        device_info: &DeviceInfo,
+        uploads: &mut UploadBatch,
+        texture: ImageResource,
+        color_space: ColorSpace,
+        max_texture_size: Option<u32>,
+    ) -> Result<Texture, UnsupportedTextureFormatError> {
+        let format = color_space.texture_format();
+        if !image_util::supports_texture_upload(device_info, instance, format) {
+            return Err(UnsupportedTextureFormatError { format });
+        }
+
+        let texture = match max_texture_size {
+            Some(max_size) => texture.fit_within(max_size),
+            None => texture,
+        };
+        let image = Self::create_texture_image(device_info, instance, uploads, &texture, format);
+
+        Ok(Texture {
+            image: image.image,
+            memory: image.image_memory,
+            view: image.image_view,
+        })
+    }
+
+    fn upload_meshes(
+        uploads: &mut UploadBatch,
+        scene: Rc<RefCell<SceneNode>>,
+        mesh: Mesh,
+    ) -> Vec<GPUMeshData> {
+        let node = scene.borrow();
+
+        vec![Self::upload_mesh(uploads, &mesh, node.transform.model)]
+    }
+
+    /// World space box around every mesh with vertices, which the shadow maps are fit to.
+    fn scene_bounds(meshes: &[GPUMeshData]) -> Option<Bounds> {
+        meshes
+            .iter()
+            .filter(|mesh| mesh.vertex_count > 0)
+            .map(GPUMeshData::world_bounds)
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Recorded into `uploads`, the mesh can only be drawn once they're submitted.
+    fn upload_mesh(
+        uploads: &mut UploadBatch,
+        mesh: &Mesh,
+        world_model: Matrix4<f32>,
+    ) -> GPUMeshData {
+        let vertices = &mesh.vertices;
+        let indices = &mesh.indices;
+
+        Vertex::layout().validate::<Vertex>(0);
+        let (center, radius) = utils::bounding_sphere(vertices);
+        let bounds = Bounds::from_vertices(vertices);
+        let vertex_buffer = uploads.buffer(
+            vertices,
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
+        );
+        let index_buffer = uploads.buffer(indices, vk::BufferUsageFlags::INDEX_BUFFER);
+
+        GPUMeshData {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+            vertex_count: vertices.len() as u32,
+            world_model,
+            skin: None,
+            scissor: None,
+            depth_range: None,
+            texture_index: SCENE_TEXTURE,
+            normal_texture_index: None,
+            material: MaterialUbo::default(),
+            bounding_sphere: Vector4::new(center.x, center.y, center.z, radius),
+            bounds,
+            instance_buffer: None,
+            instance_count: 1,
+        }
+    }
+
+    /// Renders a frame with `camera`'s view, printing swapchain recreations and panicking on
+    /// errors other than device loss. See `render_frame` to handle them yourself.
+    pub fn draw_frame(&mut self, delta_time: f32) {
+        let inputs = FrameInputs {
+            delta_time,
+            view: None,
+            lighting: None,
+        };
+        match self.render_frame(&inputs) {
+            Ok(()) | Err(RenderError::DeviceLost) => {}
+            Err(RenderError::SwapchainOutOfDate) => log::warn!("Swapchain out of date"),
+            Err(RenderError::FrameTimeout) => log::error!("Frame fence timed out, device lost"),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    /// One iteration of the frame loop, for embedders driving their own event loop: waits on
+    /// the frame's fence, applies scene and light changes, acquires a swapchain image,
+    /// records or replays the frame, submits, presents and moves on to the next frame.
+    /// Meshes, lights and the rest of the scene are whatever the setters last made them.
+    ///
+    /// An out of date swapchain is recreated before returning `SwapchainOutOfDate`, the
+    /// next call renders at the new size. After `DeviceLost` every call returns it again
+    /// until `recreate_device`, as it does after `FrameTimeout`.
+    pub fn render_frame(&mut self, inputs: &FrameInputs) -> Result<(), RenderError> {
+        if self.device_lost {
+            return Err(RenderError::DeviceLost);
+        }
+        if let Some(validation) = self.validation.as_ref().filter(|v| v.fail_on_error) {
+            match validation.take_errors() {
+                0 => {}
+                errors => return Err(RenderError::Validation(errors)),
+            }
+        }
+
+        // Returning before reset_fences keeps a timed out fence pending, resetting it would
+        // let the next wait pass while the hung submit still runs
+        let timeout_ns = self.frame_manager.frame_timeout_ns();
+        match self
+            .frame_manager
+            .wait_for_current_frame(&self.device_info.logical_device, timeout_ns)
+        {
+            Ok(()) => {}
+            Err(error @ (FrameWaitError::Timeout | FrameWaitError::DeviceLost)) => {
+                self.device_lost = true;
+                return Err(error.into());
+            }
+            Err(error) => return Err(error.into()),
+        }
+
+        // The frame's buffers are only safe to rewrite once its last submit has finished
+        self.frame_manager
+            .sync_scene(&self.device_info, &self.instance);
+        // Also brings frames up to date with lighting other frames were given
+        let lighting = inputs
+            .lighting
+            .unwrap_or_else(|| self.frame_manager.lighting());
+        self.frame_manager.set_lighting(&self.device_info, lighting);
+        self.update_camera(inputs.view.unwrap_or_else(|| self.camera.get_view_matrix()));
+        self.update_world();
+
+        // This frame's fence covers the cull pass it recorded frame_count frames ago
+        let stats_buffer = self
+            .frame_manager
+            .get_current_frame()
+            .cull_stats_buffer
+            .as_ref();
+        if let Some(stats_buffer) = stats_buffer {
+            self.frame_stats = unsafe { *(stats_buffer.mapped_buffer as *const FrameStats) };
+        }
+        self.frame_manager.resolve_pass_timings(&self.device_info);
+        self.frame_manager
+            .advance(&self.device_info, inputs.delta_time);
+        self.frame_manager.upload_lights(&self.device_info);
+
+        let frame_index = self.frame_manager.current_frame_index();
+        self.frame_manager
+            .readback_pool
+            .recycle(&self.device_info, &self.instance, frame_index);
+        let semaphore_pool = &mut self.frame_manager.semaphore_pool;
+        semaphore_pool.recycle(frame_index);
+        let swapchain_semaphore =
+            semaphore_pool.acquire(&self.device_info.logical_device, frame_index);
+
+        let image_result = unsafe {
+            self.swapchain_info.swapchain_device.acquire_next_image(
+                self.swapchain_info.swapchain,
+                u64::MAX,
+                swapchain_semaphore,
+                vk::Fence::null(),
+            )
+        };
+
+        let (image_index, _is_sub_optimal) = match image_result {
+            Ok(result) => result,
+            Err(error_result) => match error_result {
+                vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                    self.recreate_swapchain();
+                    return Err(RenderError::SwapchainOutOfDate);
+                }
+                vk::Result::ERROR_DEVICE_LOST => {
+                    self.device_lost = true;
+                    return Err(RenderError::DeviceLost);
+                }
+                error => return Err(RenderError::Vulkan(error)),
+            },
+        };
+
+        let command_buffer = match self.frame_manager.frozen_command_buffer(image_index) {
+            Some(command_buffer) => command_buffer,
+            None => {
+                self.record_frame(image_index);
+                let command_buffer = self.frame_manager.get_current_frame().command_buffer;
+                if self.frame_manager.is_frozen() {
+                    self.frame_manager
+                        .keep_frozen(&self.device_info, image_index);
+                }
+                command_buffer
+            }
+        };
+        let current_frame = self.frame_manager.get_current_frame();
+
+        // Only once recording is done, `FrameData::begin_command_buffer` checks it's signaled
+        unsafe {
+            self.device_info
+                .logical_device
+                .reset_fences(&[current_frame.render_fence])
+                .map_err(RenderError::Vulkan)?
+        };
+
+        let command_buffer_submit_info = [vk::CommandBufferSubmitInfo::default()
+            .command_buffer(command_buffer)
+            .device_mask(0)];
+
+        let wait_info = [vk::SemaphoreSubmitInfo::default()
+            .semaphore(swapchain_semaphore)
+            .stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT_KHR)
+            .device_index(0)
+            .value(1)];
+
+        let signal_info = [vk::SemaphoreSubmitInfo::default()
+            .semaphore(current_frame.render_semaphore)
+            .stage_mask(vk::PipelineStageFlags2::ALL_GRAPHICS)
+            .device_index(0)
+            .value(1)];
+
+        let submit_info = vk::SubmitInfo2::default()
+            .command_buffer_infos(&command_buffer_submit_info)
+            .wait_semaphore_infos(&wait_info)
+            .signal_semaphore_infos(&signal_info);
+
+        let submit_result = sync::queue_submit(
+            &self.device_info,
+            self.device_info.queue_info.graphics_queue,
+            &[submit_info],
+            current_frame.render_fence,
+        );
+        match submit_result {
+            Ok(()) => {}
+            Err(vk::Result::ERROR_DEVICE_LOST) => {
+                self.device_lost = true;
+                return Err(RenderError::DeviceLost);
+            }
+            Err(error) => return Err(RenderError::Vulkan(error)),
+        }
+
+        let render_semaphores = [current_frame.render_semaphore];
+        self.frame_manager.readback_pool.submit(frame_index);
+
+        // Both present paths leave draw_image as the source of the swapchain write, unless
+        // the tonemap pass's output is
+        self.frame_manager.preserved_draw_image_layout =
+            if self.frame_manager.tonemap_pipeline.is_some() {
+                vk::ImageLayout::GENERAL
+            } else if self.frame_manager.present_pipeline.is_some()
+                || self.frame_manager.fxaa_pipeline.is_some()
+            {
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            } else {
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+            };
+        let swapchains = [self.swapchain_info.swapchain];
+        let image_indices = [image_index];
+
+        let present_info = vk::PresentInfoKHR::default()
+            .wait_semaphores(&render_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+
+        let present_result = unsafe {
+            self.swapchain_info
+                .swapchain_device
+                .queue_present(self.device_info.queue_info.present_queue, &present_info)
+        };
+
+        match present_result {
+            Ok(result) => result,
+            Err(error_result) => match error_result {
+                vk::Result::ERROR_OUT_OF_DATE_KHR => {
+                    self.recreate_swapchain();
+                    return Err(RenderError::SwapchainOutOfDate);
+                }
+                vk::Result::ERROR_DEVICE_LOST => {
+                    self.device_lost = true;
+                    return Err(RenderError::DeviceLost);
+                }
+                error => return Err(RenderError::Vulkan(error)),
+            },
+        };
+
+        self.frame_manager.advance_frame();
+        Ok(())
+    }
+
+    /// Records the whole pass sequence of the current frame into its command buffer.
+    fn record_frame(&self, image_index: u32) {
+        let current_frame = self.frame_manager.get_current_frame();
+
+        current_frame.begin_command_buffer(&self.device_info.logical_device);
+        current_frame.reset_timestamps(&self.device_info, current_frame.command_buffer);
+        current_frame.write_timestamp(
+            &self.device_info,
+            current_frame.command_buffer,
+            vk::PipelineStageFlags2::TOP_OF_PIPE,
+            0,
+        );
+
+        current_frame.albedo_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        );
+
+        current_frame.normal_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        );
+
+        current_frame.depth_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        );
+
+        if let Some(msaa_targets) = current_frame.msaa_targets.as_ref() {
+            for (image, layout) in [
+                (&msaa_targets.albedo, vk::ImageLayout::GENERAL),
+                (&msaa_targets.normal, vk::ImageLayout::GENERAL),
+                (
+                    &msaa_targets.depth,
+                    vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+                ),
+            ] {
+                image.transition_layout(
+                    &self.device_info,
+                    &current_frame.command_buffer,
+                    vk::ImageLayout::UNDEFINED,
+                    layout,
+                );
+            }
+        }
+
+        let draw_image_layout = match self.frame_manager.draw_image_policy {
+            DrawImagePolicy::Discard => vk::ImageLayout::UNDEFINED,
+            DrawImagePolicy::Preserve => self.frame_manager.preserved_draw_image_layout,
+        };
+        current_frame.draw_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            draw_image_layout,
+            vk::ImageLayout::GENERAL,
+        );
+
+        self.cull_meshes();
+        self.end_pass_timestamp(GpuPass::Cull);
+
+        self.skin_meshes();
+        self.end_pass_timestamp(GpuPass::Skin);
+
+        self.render_shadow_map();
+        self.render_shadow_casters();
+        self.end_pass_timestamp(GpuPass::Shadow);
+
+        self.render_esm();
+        self.end_pass_timestamp(GpuPass::Esm);
+
+        self.render_depth_prepass();
+        self.render_scene();
+        self.end_pass_timestamp(GpuPass::GBuffer);
+
+        current_frame.albedo_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        current_frame.normal_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+
+        image_util::depth_write_to_read_only(
+            &self.device_info,
+            &current_frame.command_buffer,
+            current_frame.depth_image.image,
+        );
+
+        self.render_linear_depth();
+        self.end_pass_timestamp(GpuPass::LinearDepth);
+
+        self.render_ssao();
+        self.end_pass_timestamp(GpuPass::Ssao);
+
+        self.render_lighting();
+        self.end_pass_timestamp(GpuPass::Lighting);
+
+        self.render_ssr();
+        self.end_pass_timestamp(GpuPass::Ssr);
+
+        self.render_overlays();
+        self.end_pass_timestamp(GpuPass::Overlays);
+
+        self.render_bloom();
+        self.end_pass_timestamp(GpuPass::Bloom);
+
+        self.render_tonemap();
+        self.end_pass_timestamp(GpuPass::Tonemap);
+
+        if self.frame_manager.fxaa_pipeline.is_some() {
+            self.render_fxaa(image_index);
+        } else if self.frame_manager.present_pipeline.is_some() {
+            self.render_present(image_index);
+        } else {
+            self.frame_manager.blit_to_swapchain(
+                &self.device_info,
+                current_frame.command_buffer,
+                self.swapchain_info.swapchain_images[image_index as usize],
+                self.swapchain_info.swapchain_extent,
+            );
+        }
+        self.end_pass_timestamp(GpuPass::Present);
+
+        current_frame.end_command_buffer(&self.device_info.logical_device);
+    }
+
+    /// Timestamp after everything recorded so far, see `RenderConfig::gpu_timings`.
+    fn end_pass_timestamp(&self, pass: GpuPass) {
+        let current_frame = self.frame_manager.get_current_frame();
+        current_frame.write_timestamp(
+            &self.device_info,
+            current_frame.command_buffer,
+            vk::PipelineStageFlags2::ALL_COMMANDS,
+            pass.end_query(),
+        );
+    }
+
+    fn render_linear_depth(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(linear_depth_image), Some(descriptor_set), Some(pipeline)) = (
+            current_frame.linear_depth_image.as_ref(),
+            current_frame.descriptor_linear_depth_set,
+            self.frame_manager.linear_depth_pipeline.as_ref(),
+        ) else {
+            return;
+        };
+
+        linear_depth_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        );
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(linear_depth_image.image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE);
+
+        let color_attachments = [color_attachment];
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent: self.swapchain_info.swapchain_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        self.set_viewport_scissor(self.swapchain_info.swapchain_extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+
+            self.device_info
+                .logical_device
+                .cmd_draw(current_frame.command_buffer, 3, 1, 0, 0);
+
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+
+        // Leave it readable for any later pass that wants linear depth.
+        linear_depth_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    /// Occlusion at half resolution, then its blur, which the lighting pass samples.
+    fn render_ssao(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let frame_manager = &self.frame_manager;
+        let passes = [
+            (
+                current_frame.ssao_image.as_ref(),
+                current_frame.descriptor_ssao_set,
+                frame_manager.ssao_pipeline.as_ref(),
+            ),
+            (
+                current_frame.ssao_blur_image.as_ref(),
+                current_frame.descriptor_ssao_blur_set,
+                frame_manager.ssao_blur_pipeline.as_ref(),
+            ),
+        ];
+        for pass in passes {
+            let (Some(image), Some(descriptor_set), Some(pipeline)) = pass else {
+                return;
+            };
+            self.render_fullscreen_pass(image, descriptor_set, pipeline);
+        }
+    }
+
+    /// A fullscreen triangle over all of `image`, which is left in SHADER_READ_ONLY_OPTIMAL.
+    fn render_fullscreen_pass(
+        &self,
+        image: &AllocatedImage,
+        descriptor_set: vk::DescriptorSet,
+        pipeline: &PipelineInfo,
+    ) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let extent = Extent2D {
+            width: image.image_extent.width,
+            height: image.image_extent.height,
+        };
+
+        image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        );
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(image.image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE);
+
+        let color_attachments = [color_attachment];
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        self.set_viewport_scissor(extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+
+            self.device_info
+                .logical_device
+                .cmd_draw(current_frame.command_buffer, 3, 1, 0, 0);
+
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+
+        image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    fn render_lighting(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(current_frame.draw_image.image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(match self.frame_manager.draw_image_policy {
+                DrawImagePolicy::Discard => vk::AttachmentLoadOp::CLEAR,
+                DrawImagePolicy::Preserve => vk::AttachmentLoadOp::LOAD,
+            })
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(ClearValues::color(self.frame_manager.clear_values.draw));
+
+        let color_attachments = [color_attachment];
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent: self.swapchain_info.swapchain_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        self.set_viewport_scissor(self.swapchain_info.swapchain_extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.frame_manager.lighting_pipeline.pipeline(),
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.frame_manager.lighting_pipeline.pipeline_layout,
+                0,
+                &[
+                    current_frame.descriptor_lighting_set,
+                    current_frame.descriptor_globals_set,
+                ],
+                &[],
+            );
+
+            self.device_info
+                .logical_device
+                .cmd_draw(current_frame.command_buffer, 3, 1, 0, 0);
+
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+    }
+
+    fn render_ssr(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(ssr_image), Some(descriptor_set), Some(pipeline)) = (
+            current_frame.ssr_image.as_ref(),
+            current_frame.descriptor_ssr_set,
+            self.frame_manager.ssr_pipeline.as_ref(),
+        ) else {
+            return;
+        };
+
+        current_frame.draw_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        ssr_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        );
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(ssr_image.image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE);
+
+        let color_attachments = [color_attachment];
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent: self.swapchain_info.swapchain_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        self.set_viewport_scissor(self.swapchain_info.swapchain_extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+
+            self.device_info
+                .logical_device
+                .cmd_draw(current_frame.command_buffer, 3, 1, 0, 0);
+
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+
+        // Copy the reflected result back so draw_image stays the final composited image.
+        ssr_image.blit_to(
+            &self.device_info,
+            &self.instance,
+            &current_frame.command_buffer,
+            &current_frame.draw_image,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::Filter::NEAREST,
+        );
+
+        current_frame.draw_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::GENERAL,
+        );
+    }
+
+    /// Draws each overlay pipeline into draw_image. Depth is loaded as written by the gbuffer
+    /// pass and stays in DEPTH_READ_ONLY_OPTIMAL, so it's never cleared or stored again.
+    fn render_overlays(&self) {
+        if self.frame_manager.overlay_pipelines.is_empty() {
+            return;
+        }
+        let current_frame = self.frame_manager.get_current_frame();
+
+        // Lighting or the SSR copy-back wrote draw_image last
+        let color_barrier = vk::MemoryBarrier2::default()
+            .src_stage_mask(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags2::COPY,
+            )
+            .src_access_mask(
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE | vk::AccessFlags2::TRANSFER_WRITE,
+            )
+            .dst_stage_mask(vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            );
+        let dependency_info =
+            vk::DependencyInfo::default().memory_barriers(slice::from_ref(&color_barrier));
+
+        sync::pipeline_barrier(
+            &self.device_info,
+            current_frame.command_buffer,
+            &dependency_info,
+        );
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(current_frame.draw_image.image_view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE);
+
+        let depth_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(current_frame.depth_image.image_view)
+            .image_layout(vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::NONE);
+
+        let extent = self.swapchain_info.swapchain_extent;
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .color_attachments(slice::from_ref(&color_attachment))
+            .depth_attachment(&depth_attachment);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        self.set_viewport_scissor(extent);
+
+        for pipeline in self.frame_manager.overlay_pipelines.iter() {
+            unsafe {
+                self.device_info.logical_device.cmd_bind_pipeline(
+                    current_frame.command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline.pipeline(),
+                );
+            }
+
+            self.draw_meshes(pipeline.pipeline_layout, Some(extent), false, false);
+        }
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+    }
+
+    /// Draws draw_image into the swapchain image with the sRGB encode applied in the shader.
+    fn render_present(&self, image_index: u32) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(descriptor_set), Some(pipeline)) = (
+            current_frame.descriptor_present_set,
+            self.frame_manager.present_pipeline.as_ref(),
+        ) else {
+            return;
+        };
+
+        self.render_fullscreen_to_swapchain(
+            image_index,
+            &current_frame.draw_image,
+            pipeline,
+            descriptor_set,
+        );
+    }
+
+    /// Draws the tonemap image, or draw_image without tonemapping, into the swapchain image
+    /// through FXAA.
+    fn render_fxaa(&self, image_index: u32) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(descriptor_set), Some(pipeline)) = (
+            current_frame.descriptor_fxaa_set,
+            self.frame_manager.fxaa_pipeline.as_ref(),
+        ) else {
+            return;
+        };
+        let source = current_frame
+            .tonemap_image
+            .as_ref()
+            .unwrap_or(&current_frame.draw_image);
+
+        self.render_fullscreen_to_swapchain(image_index, source, pipeline, descriptor_set);
+    }
+
+    /// Draws a fullscreen triangle with `pipeline` into the swapchain image, reading `source`
+    /// through `descriptor_set`, and leaves the swapchain image ready to present. `source`
+    /// must be in GENERAL and is left in SHADER_READ_ONLY_OPTIMAL.
+    fn render_fullscreen_to_swapchain(
+        &self,
+        image_index: u32,
+        source: &AllocatedImage,
+        pipeline: &PipelineInfo,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let swapchain_image = self.swapchain_info.swapchain_images[image_index as usize];
+
+        source.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        present::swapchain_to_attachment(
+            &self.device_info,
+            &current_frame.command_buffer,
+            swapchain_image,
+        );
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(self.image_views[image_index as usize])
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE);
+
+        let color_attachments = [color_attachment];
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent: self.swapchain_info.swapchain_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        self.set_viewport_scissor(self.swapchain_info.swapchain_extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+
+            self.device_info
+                .logical_device
+                .cmd_draw(current_frame.command_buffer, 3, 1, 0, 0);
+
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+
+        present::swapchain_attachment_to_present(
+            &self.device_info,
+            &current_frame.command_buffer,
+            swapchain_image,
+        );
+    }
+
+    /// The pipeline `render_scene` draws with and whether it loads the prepass depth. The
+    /// wireframe replaces the depth prepass, whose depth would hide most of its lines.
+    fn gbuffer_pass_pipeline(&self) -> (&PipelineInfo, bool) {
+        let frame_manager = &self.frame_manager;
+        if let Some(pipeline) = frame_manager
+            .gbuffer_wireframe_pipeline
+            .as_ref()
+            .filter(|_| frame_manager.wireframe.is_some())
+        {
+            return (pipeline, false);
+        }
+        match frame_manager.gbuffer_depth_equal_pipeline.as_ref() {
+            Some(pipeline) if frame_manager.depth_prepass => (pipeline, true),
+            _ => (&frame_manager.gbuffer_pipeline, false),
+        }
+    }
+
+    /// Fills the depth image, the multisampled one with MSAA, with the scene's depth and
+    /// makes it visible to the G-buffer pass's EQUAL test. Nothing without the prepass or
+    /// with the wireframe.
+    fn render_depth_prepass(&self) {
+        let Some(pipeline) = self
+            .frame_manager
+            .depth_prepass_pipeline
+            .as_ref()
+            .filter(|_| self.frame_manager.depth_prepass && self.frame_manager.wireframe.is_none())
+        else {
+            return;
+        };
+        let current_frame = self.frame_manager.get_current_frame();
+
+        let depth_view = current_frame
+            .msaa_targets
+            .as_ref()
+            .map_or(&current_frame.depth_image, |msaa_targets| {
+                &msaa_targets.depth
+            })
+            .render_view();
+        let depth_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(depth_view)
+            .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(ClearValues::depth_value(
+                self.frame_manager.clear_values.depth,
+            ));
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent: self.swapchain_info.swapchain_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .view_mask(pipeline.describe().view_mask)
+            .depth_attachment(&depth_attachment);
+
+        let device = &self.device_info.logical_device;
+        unsafe {
+            device.cmd_begin_rendering(current_frame.command_buffer, &begin_render_info);
+        }
+
+        self.set_viewport_scissor(self.swapchain_info.swapchain_extent);
+
+        unsafe {
+            device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+        }
+
+        self.draw_meshes(
+            pipeline.pipeline_layout,
+            Some(self.swapchain_info.swapchain_extent),
+            false,
+            self.frame_manager.instancing,
+        );
+
+        unsafe {
+            device.cmd_end_rendering(current_frame.command_buffer);
+        }
+
+        let depth_written = vk::MemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS)
+            .src_access_mask(vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_stage_mask(
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags2::LATE_FRAGMENT_TESTS,
+            )
+            .dst_access_mask(
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            );
+        sync::pipeline_barrier(
+            &self.device_info,
+            current_frame.command_buffer,
+            &vk::DependencyInfo::default().memory_barriers(slice::from_ref(&depth_written)),
+        );
     }
 
     fn render_scene(&self) {
         let current_frame = self.frame_manager.get_current_frame();
+        let (pipeline, load_depth) = self.gbuffer_pass_pipeline();
+
+        let mut color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(current_frame.albedo_image.render_view())
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(ClearValues::color(self.frame_manager.clear_values.albedo));
+
+        let mut normal_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(current_frame.normal_image.render_view())
+            .image_layout(vk::ImageLayout::GENERAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(ClearValues::color(self.frame_manager.clear_values.normal));
+
+        // The depth prepass already cleared and wrote it
+        let depth_load_op = if load_depth {
+            vk::AttachmentLoadOp::LOAD
+        } else {
+            vk::AttachmentLoadOp::CLEAR
+        };
+        let mut depth_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(current_frame.depth_image.render_view())
+            .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+            .load_op(depth_load_op)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(ClearValues::depth_value(
+                self.frame_manager.clear_values.depth,
+            ));
+
+        // Render into the multisampled targets and resolve into the images above, which
+        // keep their layouts. Only the resolved samples are stored.
+        if let Some(msaa_targets) = current_frame.msaa_targets.as_ref() {
+            color_attachment = color_attachment
+                .image_view(msaa_targets.albedo.render_view())
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                .resolve_image_view(current_frame.albedo_image.render_view())
+                .resolve_image_layout(vk::ImageLayout::GENERAL);
+            normal_attachment = normal_attachment
+                .image_view(msaa_targets.normal.render_view())
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                .resolve_image_view(current_frame.normal_image.render_view())
+                .resolve_image_layout(vk::ImageLayout::GENERAL);
+            depth_attachment = depth_attachment
+                .image_view(msaa_targets.depth.render_view())
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .resolve_mode(vk::ResolveModeFlags::SAMPLE_ZERO)
+                .resolve_image_view(current_frame.depth_image.render_view())
+                .resolve_image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL);
+        }
+
+        // Frozen command buffers would keep executing secondaries rerecorded since
+        let secondaries = &current_frame.gbuffer_secondaries;
+        let parallel = !secondaries.is_empty() && !self.frame_manager.is_frozen();
+        let rendering_flags = if parallel {
+            vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS
+        } else {
+            vk::RenderingFlags::empty()
+        };
+
+        let color_attachments = [color_attachment, normal_attachment];
+        let begin_render_info = vk::RenderingInfo::default()
+            .flags(rendering_flags)
+            .render_area(Rect2D {
+                extent: self.swapchain_info.swapchain_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .view_mask(pipeline.describe().view_mask)
+            .color_attachments(&color_attachments)
+            .depth_attachment(&depth_attachment);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
+        }
+
+        if parallel {
+            let command_buffers = self.record_gbuffer_parallel(
+                pipeline,
+                0..self.gpu_mesh_data.len(),
+                secondaries.len(),
+            );
+            unsafe {
+                self.device_info
+                    .logical_device
+                    .cmd_execute_commands(current_frame.command_buffer, &command_buffers);
+                self.device_info
+                    .logical_device
+                    .cmd_end_rendering(current_frame.command_buffer);
+            }
+            return;
+        }
+
+        self.set_viewport_scissor(self.swapchain_info.swapchain_extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline_layout,
+                2,
+                &[current_frame.descriptor_texture_set],
+                &[],
+            );
+            if let Some(line_width) = self.frame_manager.wireframe_line_width(&self.device_info) {
+                self.device_info
+                    .logical_device
+                    .cmd_set_line_width(current_frame.command_buffer, line_width);
+            }
+        }
+
+        self.draw_meshes(
+            pipeline.pipeline_layout,
+            Some(self.swapchain_info.swapchain_extent),
+            true,
+            self.frame_manager.instancing,
+        );
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+    }
+
+    /// Splits the G-buffer draws of `meshes` into `thread_count` contiguous chunks, each
+    /// recorded on one of the `gbuffer_workers` into one of the frame's `gbuffer_secondaries`,
+    /// continuing the G-buffer rendering `pipeline` draws into. Returns the recorded
+    /// secondaries for the frame's command buffer to execute, in mesh order.
+    fn record_gbuffer_parallel(
+        &self,
+        pipeline: &PipelineInfo,
+        meshes: ops::Range<usize>,
+        thread_count: usize,
+    ) -> Vec<vk::CommandBuffer> {
+        let current_frame = self.frame_manager.get_current_frame();
+        let secondaries = &current_frame.gbuffer_secondaries[..thread_count];
+        let device = &self.device_info.logical_device;
+        let extent = self.swapchain_info.swapchain_extent;
 
-        let color_attachment = vk::RenderingAttachmentInfo::default()
-            .image_view(current_frame.albedo_image.image_view)
-            .image_layout(vk::ImageLayout::GENERAL)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE);
+        let description = pipeline.describe();
+        let color_formats = description
+            .color_attachments
+            .iter()
+            .map(|attachment| vk::Format::from_raw(attachment.format))
+            .collect::<Vec<_>>();
+        let depth_format = description
+            .depth
+            .as_ref()
+            .map_or(vk::Format::UNDEFINED, |depth| {
+                vk::Format::from_raw(depth.format)
+            });
+        let samples = vk::SampleCountFlags::from_raw(description.samples);
+        let line_width = self.frame_manager.wireframe_line_width(&self.device_info);
+
+        let chunk_size = meshes.len().div_ceil(thread_count).max(1);
+        let color_formats = &color_formats;
+        let jobs = secondaries
+            .iter()
+            .enumerate()
+            .map(|(chunk, secondary)| {
+                let start = (meshes.start + chunk * chunk_size).min(meshes.end);
+                let chunk_meshes = start..(start + chunk_size).min(meshes.end);
+                Box::new(move || {
+                    let mut rendering = vk::CommandBufferInheritanceRenderingInfo::default()
+                        .view_mask(description.view_mask)
+                        .color_attachment_formats(color_formats)
+                        .depth_attachment_format(depth_format)
+                        .rasterization_samples(samples);
+                    secondary.begin(device, &mut rendering);
+
+                    // Dynamic state isn't inherited from the primary
+                    let command_buffer = secondary.command_buffer;
+                    let full_scissor = Rect2D {
+                        offset: vk::Offset2D { x: 0, y: 0 },
+                        extent,
+                    };
+                    current_frame.set_depth_range(device, command_buffer, 0.0, 1.0, extent);
+                    current_frame.set_scissor(device, command_buffer, full_scissor, extent);
+                    unsafe {
+                        device.cmd_bind_pipeline(
+                            command_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline.pipeline(),
+                        );
+                        device.cmd_bind_descriptor_sets(
+                            command_buffer,
+                            vk::PipelineBindPoint::GRAPHICS,
+                            pipeline.pipeline_layout,
+                            2,
+                            &[current_frame.descriptor_texture_set],
+                            &[],
+                        );
+                        if let Some(line_width) = line_width {
+                            device.cmd_set_line_width(command_buffer, line_width);
+                        }
+                    }
+
+                    self.draw_meshes_into(
+                        command_buffer,
+                        chunk_meshes,
+                        pipeline.pipeline_layout,
+                        Some(extent),
+                        true,
+                        self.frame_manager.instancing,
+                    );
+                    secondary.end(device);
+                }) as Box<dyn FnOnce() + Send + '_>
+            })
+            .collect();
+        self.frame_manager.gbuffer_workers.run(jobs);
+
+        secondaries
+            .iter()
+            .map(|secondary| secondary.command_buffer)
+            .collect()
+    }
+
+    /// Binds each mesh with its model offset and draws it with the bound pipeline.
+    /// Per-mesh scissors and depth ranges are applied within `render_extent` when one is given.
+    /// With `push_texture_index`, pushes each mesh's `texture_index` and normal map slot as
+    /// the gbuffer pipeline's fragment push constants. With `instanced`, binds each mesh's
+    /// instance buffer at binding 1 and draws all its instances, directly since the cull pass
+    /// doesn't know about them.
+    fn draw_meshes(
+        &self,
+        pipeline_layout: vk::PipelineLayout,
+        render_extent: Option<Extent2D>,
+        push_texture_index: bool,
+        instanced: bool,
+    ) {
+        self.draw_meshes_into(
+            self.frame_manager.get_current_frame().command_buffer,
+            0..self.gpu_mesh_data.len(),
+            pipeline_layout,
+            render_extent,
+            push_texture_index,
+            instanced,
+        );
+    }
+
+    /// `draw_meshes` of only `meshes`, recorded into `command_buffer`, the frame's own or one
+    /// of its secondary ones. Only reads the backend, so threads can record disjoint ranges at
+    /// once.
+    fn draw_meshes_into(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        meshes: ops::Range<usize>,
+        pipeline_layout: vk::PipelineLayout,
+        render_extent: Option<Extent2D>,
+        push_texture_index: bool,
+        instanced: bool,
+    ) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let frame_index = self.frame_manager.current_frame_index();
+        let full_scissor = render_extent.map(|extent| Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        });
+        let mut scissor = full_scissor;
+        let mut depth_range = (0.0, 1.0);
+
+        // The cull pass only tests the camera frustum, shadow passes draw every mesh
+        let indirect_buffers = match (
+            current_frame.cull_draw_buffer.as_ref(),
+            current_frame.cull_draw_count_buffer.as_ref(),
+        ) {
+            (Some(draw_buffer), Some(draw_count_buffer))
+                if self.frame_manager.indirect_draws && render_extent.is_some() =>
+            {
+                Some((draw_buffer.buffer, draw_count_buffer.buffer))
+            }
+            _ => None,
+        };
+        let draw_stride = mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+        let draw_indirect_count = self.device_info.draw_indirect_count;
+        let cpu_culling =
+            self.frame_manager.cpu_culling && render_extent.is_some() && indirect_buffers.is_none();
+
+        let mesh_count = meshes.len();
+        let gpu_meshes = self.gpu_mesh_data.iter().enumerate();
+        for (i, gpu_mesh) in gpu_meshes.skip(meshes.start).take(mesh_count) {
+            let instanced_mesh = instanced && gpu_mesh.instance_buffer.is_some();
+            if cpu_culling && !instanced_mesh && !self.mesh_in_frustum(i) {
+                continue;
+            }
+
+            if let Some(render_extent) = render_extent {
+                let mesh_depth_range = gpu_mesh.depth_range.unwrap_or((0.0, 1.0));
+                if mesh_depth_range != depth_range {
+                    current_frame.set_depth_range(
+                        &self.device_info.logical_device,
+                        command_buffer,
+                        mesh_depth_range.0,
+                        mesh_depth_range.1,
+                        render_extent,
+                    );
+                    depth_range = mesh_depth_range;
+                }
+
+                let mesh_scissor = gpu_mesh.scissor.or(full_scissor);
+                if mesh_scissor != scissor {
+                    if let Some(rect) = mesh_scissor {
+                        current_frame.set_scissor(
+                            &self.device_info.logical_device,
+                            command_buffer,
+                            rect,
+                            render_extent,
+                        );
+                    }
+                    scissor = mesh_scissor;
+                }
+            }
+
+            unsafe {
+                self.device_info.logical_device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline_layout,
+                    0,
+                    &[
+                        current_frame.descriptor_gbuffer_set,
+                        current_frame.descriptor_globals_set,
+                    ],
+                    &[(i as u32 * self.frame_manager.model_ubo_alignment as u32)],
+                );
+                if push_texture_index {
+                    self.device_info.logical_device.cmd_push_constants(
+                        command_buffer,
+                        pipeline_layout,
+                        vk::ShaderStageFlags::FRAGMENT,
+                        0,
+                        &[
+                            gpu_mesh.texture_index,
+                            gpu_mesh.normal_texture_index.unwrap_or(NO_TEXTURE),
+                        ]
+                        .map(u32::to_ne_bytes)
+                        .concat(),
+                    );
+                }
+            }
+
+            unsafe {
+                self.device_info.logical_device.cmd_bind_index_buffer(
+                    command_buffer,
+                    gpu_mesh.index_buffer.buffer,
+                    0,
+                    vk::IndexType::UINT32,
+                );
+
+                self.device_info.logical_device.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[gpu_mesh
+                        .skin
+                        .as_ref()
+                        .map_or(gpu_mesh.vertex_buffer.buffer, |skin| {
+                            skin.skinned_vertex_buffers[frame_index].buffer
+                        })],
+                    &[0],
+                );
+                if instanced {
+                    let instance_buffer = gpu_mesh
+                        .instance_buffer
+                        .as_ref()
+                        .or(self.frame_manager.identity_instance_buffer.as_ref())
+                        .expect("instanced draws have an instance buffer");
+                    self.device_info.logical_device.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        1,
+                        &[instance_buffer.buffer],
+                        &[0],
+                    );
+                }
+
+                match indirect_buffers {
+                    _ if instanced_mesh => self.device_info.logical_device.cmd_draw_indexed(
+                        command_buffer,
+                        gpu_mesh.index_count,
+                        gpu_mesh.instance_count,
+                        0,
+                        0,
+                        0,
+                    ),
+                    Some((draw_buffer, draw_count_buffer)) if draw_indirect_count => self
+                        .device_info
+                        .logical_device
+                        .cmd_draw_indexed_indirect_count(
+                            command_buffer,
+                            draw_buffer,
+                            i as u64 * draw_stride as u64,
+                            draw_count_buffer,
+                            (i * mem::size_of::<u32>()) as u64,
+                            1,
+                            draw_stride,
+                        ),
+                    Some((draw_buffer, _)) => {
+                        self.device_info.logical_device.cmd_draw_indexed_indirect(
+                            command_buffer,
+                            draw_buffer,
+                            i as u64 * draw_stride as u64,
+                            1,
+                            draw_stride,
+                        )
+                    }
+                    None => self.device_info.logical_device.cmd_draw_indexed(
+                        command_buffer,
+                        gpu_mesh.index_count,
+                        1,
+                        0,
+                        0,
+                        0,
+                    ),
+                }
+            }
+        }
+
+        if let (Some(rect), Some(render_extent)) = (full_scissor, render_extent) {
+            if scissor != full_scissor {
+                current_frame.set_scissor(
+                    &self.device_info.logical_device,
+                    command_buffer,
+                    rect,
+                    render_extent,
+                );
+            }
+            if depth_range != (0.0, 1.0) {
+                current_frame.set_depth_range(
+                    &self.device_info.logical_device,
+                    command_buffer,
+                    0.0,
+                    1.0,
+                    render_extent,
+                );
+            }
+        }
+    }
+
+    /// Renders each cascade of the main light's shadow map, if it has one.
+    fn render_shadow_map(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let Some(shadow_map) = current_frame.shadow_map.as_ref() else {
+            return;
+        };
+
+        shadow_map.image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        );
+
+        for cascade in 0..self.frame_manager.shadow_cascade_count() {
+            self.render_shadow_cascade(shadow_map, cascade);
+        }
+
+        shadow_map.image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    fn render_shadow_cascade(&self, shadow_map: &ShadowCasterMaps, cascade: usize) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let shadow_extent = Extent2D {
+            width: shadow_map.image.image_extent.width,
+            height: shadow_map.image.image_extent.height,
+        };
+        let pipeline = &self.frame_manager.shadow_pipeline;
 
         let depth_attachment = vk::RenderingAttachmentInfo::default()
-            .image_view(current_frame.depth_image.image_view)
+            .image_view(shadow_map.layer_views[cascade])
             .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
             .load_op(vk::AttachmentLoadOp::CLEAR)
             .store_op(vk::AttachmentStoreOp::STORE)
-            .clear_value(vk::ClearValue {
-                depth_stencil: vk::ClearDepthStencilValue {
-                    depth: 1.0,
-                    stencil: 0,
-                },
-            });
+            .clear_value(ClearValues::depth_value(
+                self.frame_manager.clear_values.shadow_depth,
+            ));
 
-        let color_attachments = [color_attachment];
         let begin_render_info = vk::RenderingInfo::default()
             .render_area(Rect2D {
-                extent: self.swapchain_info.swapchain_extent,
+                extent: shadow_extent,
                 offset: vk::Offset2D { x: 0, y: 0 },
             })
             .layer_count(1)
-            .color_attachments(&color_attachments)
             .depth_attachment(&depth_attachment);
 
         unsafe {
@@ -431,76 +2388,586 @@ impl VulkanBackend {
                 .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info)
         }
 
-        self.set_viewport_scissor();
+        self.set_viewport_scissor(shadow_extent);
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+            self.device_info.logical_device.cmd_push_constants(
+                current_frame.command_buffer,
+                pipeline.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                &(cascade as u32).to_ne_bytes(),
+            );
+        }
+        self.set_shadow_depth_bias_state();
+
+        self.draw_meshes(pipeline.pipeline_layout, None, false, false);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+    }
+
+    /// Renders every caster layer, used or not, so the recording doesn't depend on the lights.
+    /// Without casters the single layer is only moved to the layout the lighting pass samples.
+    fn render_shadow_casters(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let maps = &current_frame.shadow_caster_maps;
+
+        maps.image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+        );
+
+        if let Some(pipeline) = self.frame_manager.shadow_caster_pipeline.as_ref() {
+            for layer in 0..self.frame_manager.shadow_caster_count() {
+                self.render_shadow_caster_layer(pipeline, layer);
+            }
+        }
+
+        maps.image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    fn render_shadow_caster_layer(&self, pipeline: &PipelineInfo, layer: usize) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let maps = &current_frame.shadow_caster_maps;
+        let shadow_extent = Extent2D {
+            width: maps.image.image_extent.width,
+            height: maps.image.image_extent.height,
+        };
+
+        let depth_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(maps.layer_views[layer])
+            .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(ClearValues::depth_value(
+                self.frame_manager.clear_values.shadow_depth,
+            ));
+
+        let begin_render_info = vk::RenderingInfo::default()
+            .render_area(Rect2D {
+                extent: shadow_extent,
+                offset: vk::Offset2D { x: 0, y: 0 },
+            })
+            .layer_count(1)
+            .depth_attachment(&depth_attachment);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_begin_rendering(current_frame.command_buffer, &begin_render_info);
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.pipeline(),
+            );
+            self.device_info.logical_device.cmd_push_constants(
+                current_frame.command_buffer,
+                pipeline.pipeline_layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                &(layer as u32).to_ne_bytes(),
+            );
+        }
+
+        self.set_viewport_scissor(shadow_extent);
+        self.set_shadow_depth_bias_state();
+        self.draw_meshes(pipeline.pipeline_layout, None, false, false);
+
+        unsafe {
+            self.device_info
+                .logical_device
+                .cmd_end_rendering(current_frame.command_buffer);
+        }
+    }
+
+    /// Records `ShadowConfig::depth_bias` for the bound shadow pipeline.
+    fn set_shadow_depth_bias_state(&self) {
+        let depth_bias = self.frame_manager.shadow_depth_bias();
+        unsafe {
+            self.device_info.logical_device.cmd_set_depth_bias(
+                self.frame_manager.get_current_frame().command_buffer,
+                depth_bias.constant_factor,
+                0.0,
+                depth_bias.slope_factor,
+            );
+        }
+    }
+
+    /// Counts the meshes inside the camera frustum into this frame's stats buffer.
+    fn cull_meshes(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(pipeline), Some(descriptor_set), Some(stats_buffer)) = (
+            self.frame_manager.cull_pipeline.as_ref(),
+            current_frame.descriptor_cull_set,
+            current_frame.cull_stats_buffer.as_ref(),
+        ) else {
+            return;
+        };
+
+        let mesh_count = self.gpu_mesh_data.len() as u32;
+        let push_constants = self
+            .frustum
+            .planes
+            .iter()
+            .flat_map(|plane| plane.iter().flat_map(|value| value.to_ne_bytes()))
+            .chain(mesh_count.to_ne_bytes())
+            .collect::<Vec<_>>();
+
+        let clear_barrier = vk::MemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::CLEAR)
+            .src_access_mask(vk::AccessFlags2::TRANSFER_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .dst_access_mask(
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            );
+        let readback_barrier = vk::MemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .src_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::HOST | vk::PipelineStageFlags2::DRAW_INDIRECT)
+            .dst_access_mask(vk::AccessFlags2::HOST_READ | vk::AccessFlags2::INDIRECT_COMMAND_READ);
+
+        unsafe {
+            self.device_info.logical_device.cmd_fill_buffer(
+                current_frame.command_buffer,
+                stats_buffer.buffer,
+                0,
+                mem::size_of::<FrameStats>() as u64,
+                0,
+            );
+        }
+        sync::pipeline_barrier(
+            &self.device_info,
+            current_frame.command_buffer,
+            &vk::DependencyInfo::default().memory_barriers(slice::from_ref(&clear_barrier)),
+        );
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline,
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+
+            self.device_info.logical_device.cmd_push_constants(
+                current_frame.command_buffer,
+                pipeline.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &push_constants,
+            );
+
+            self.device_info.logical_device.cmd_dispatch(
+                current_frame.command_buffer,
+                mesh_count.div_ceil(64),
+                1,
+                1,
+            );
+        }
+
+        sync::pipeline_barrier(
+            &self.device_info,
+            current_frame.command_buffer,
+            &vk::DependencyInfo::default().memory_barriers(slice::from_ref(&readback_barrier)),
+        );
+    }
+
+    /// Writes this frame's skinned vertex buffers for every mesh with joints.
+    fn skin_meshes(&self) {
+        let Some(pipeline) = self.frame_manager.skin_pipeline.as_ref() else {
+            return;
+        };
+        let current_frame = self.frame_manager.get_current_frame();
+        let frame_index = self.frame_manager.current_frame_index();
+
+        unsafe {
+            self.device_info.logical_device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline,
+            );
+        }
+
+        for gpu_mesh in self.gpu_mesh_data.iter() {
+            let Some(skin) = gpu_mesh.skin.as_ref() else {
+                continue;
+            };
+
+            unsafe {
+                self.device_info.logical_device.cmd_bind_descriptor_sets(
+                    current_frame.command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.pipeline_layout,
+                    0,
+                    &[
+                        skin.descriptor_sets[frame_index],
+                        current_frame.descriptor_globals_set,
+                    ],
+                    &[],
+                );
+
+                self.device_info.logical_device.cmd_push_constants(
+                    current_frame.command_buffer,
+                    pipeline.pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &gpu_mesh.vertex_count.to_ne_bytes(),
+                );
+
+                self.device_info.logical_device.cmd_dispatch(
+                    current_frame.command_buffer,
+                    gpu_mesh.vertex_count.div_ceil(64),
+                    1,
+                    1,
+                );
+            }
+        }
+
+        let memory_barrier = vk::MemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .src_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::VERTEX_ATTRIBUTE_INPUT)
+            .dst_access_mask(vk::AccessFlags2::VERTEX_ATTRIBUTE_READ);
+        let dependency_info =
+            vk::DependencyInfo::default().memory_barriers(slice::from_ref(&memory_barrier));
+
+        sync::pipeline_barrier(
+            &self.device_info,
+            current_frame.command_buffer,
+            &dependency_info,
+        );
+    }
+
+    fn render_esm(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(esm_image), Some(descriptor_set), Some(pipeline), ShadowTechnique::Esm { c }) = (
+            current_frame.esm_image.as_ref(),
+            current_frame.descriptor_esm_set,
+            self.frame_manager.esm_pipeline.as_ref(),
+            self.frame_manager.shadow_technique,
+        ) else {
+            return;
+        };
+
+        esm_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+        );
 
         unsafe {
             self.device_info.logical_device.cmd_bind_pipeline(
                 current_frame.command_buffer,
-                vk::PipelineBindPoint::GRAPHICS,
-                self.frame_manager.gbuffer_pipeline.pipelines[0],
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline,
+            );
+
+            self.device_info.logical_device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+
+            self.device_info.logical_device.cmd_push_constants(
+                current_frame.command_buffer,
+                pipeline.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &c.to_ne_bytes(),
+            );
+
+            self.device_info.logical_device.cmd_dispatch(
+                current_frame.command_buffer,
+                esm_image.image_extent.width.div_ceil(16),
+                esm_image.image_extent.height.div_ceil(16),
+                1,
             );
         }
 
-        for (i, gpu_mesh) in self.gpu_mesh_data.iter().enumerate() {
-            unsafe {
-                self.device_info.logical_device.cmd_bind_descriptor_sets(
-                    current_frame.command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    self.frame_manager.gbuffer_pipeline.pipeline_layout,
-                    0,
-                    &[current_frame.descriptor_gbuffer_set],
-                    &[(i as u32 * self.frame_manager.model_ubo_alignment as u32)],
+        esm_image.transition_layout(
+            &self.device_info,
+            &current_frame.command_buffer,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    /// Adds the bloom mip chain into draw_image, which stays in GENERAL. Every step is a
+    /// dispatch reading what the one before wrote.
+    fn render_bloom(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(bloom), Some(pipeline)) = (
+            current_frame.bloom.as_ref(),
+            self.frame_manager.bloom_pipeline.as_ref(),
+        ) else {
+            return;
+        };
+        let command_buffer = current_frame.command_buffer;
+
+        let color_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+        // Lighting, SSR or the overlays wrote it last
+        let mut to_compute = vec![vk::ImageMemoryBarrier2::default()
+            .src_stage_mask(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags2::COPY,
+            )
+            .src_access_mask(
+                vk::AccessFlags2::COLOR_ATTACHMENT_WRITE | vk::AccessFlags2::TRANSFER_WRITE,
+            )
+            .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .dst_access_mask(
+                vk::AccessFlags2::SHADER_SAMPLED_READ
+                    | vk::AccessFlags2::SHADER_STORAGE_READ
+                    | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            )
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(vk::ImageLayout::GENERAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(current_frame.draw_image.image)
+            .subresource_range(color_range)];
+        // Rewritten from scratch every frame
+        to_compute.extend(bloom.mips.iter().map(|mip| {
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .src_access_mask(vk::AccessFlags2::NONE)
+                .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .dst_access_mask(
+                    vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
                 )
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(mip.image)
+                .subresource_range(color_range)
+        }));
+        sync::pipeline_barrier(
+            &self.device_info,
+            command_buffer,
+            &vk::DependencyInfo::default().image_memory_barriers(&to_compute),
+        );
+
+        let device = &self.device_info.logical_device;
+        unsafe {
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline,
+            );
+        }
+
+        // Each step samples or loads the image the previous one stored to
+        let between_steps = [vk::MemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .src_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .dst_access_mask(
+                vk::AccessFlags2::SHADER_SAMPLED_READ | vk::AccessFlags2::SHADER_STORAGE_READ,
+            )];
+        for (index, step) in bloom.steps().iter().enumerate() {
+            if index > 0 {
+                sync::pipeline_barrier(
+                    &self.device_info,
+                    command_buffer,
+                    &vk::DependencyInfo::default().memory_barriers(&between_steps),
+                );
             }
 
             unsafe {
-                self.device_info.logical_device.cmd_bind_index_buffer(
-                    current_frame.command_buffer,
-                    gpu_mesh.index_buffer.buffer,
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    pipeline.pipeline_layout,
                     0,
-                    vk::IndexType::UINT32,
+                    &[step.descriptor_set, current_frame.descriptor_globals_set],
+                    &[],
                 );
-
-                self.device_info.logical_device.cmd_bind_vertex_buffers(
-                    current_frame.command_buffer,
+                device.cmd_push_constants(
+                    command_buffer,
+                    pipeline.pipeline_layout,
+                    vk::ShaderStageFlags::COMPUTE,
                     0,
-                    &[gpu_mesh.vertex_buffer.buffer],
-                    &[0],
+                    &(step.mode as u32).to_ne_bytes(),
                 );
-
-                self.device_info.logical_device.cmd_draw_indexed(
-                    current_frame.command_buffer,
-                    gpu_mesh.index_count,
+                device.cmd_dispatch(
+                    command_buffer,
+                    step.extent.width.div_ceil(8),
+                    step.extent.height.div_ceil(8),
                     1,
-                    0,
-                    0,
-                    0,
                 );
             }
         }
+    }
 
-        unsafe {
-            self.device_info
-                .logical_device
-                .cmd_end_rendering(current_frame.command_buffer);
+    /// Tonemaps draw_image, left in GENERAL, into the frame's tonemap image, which the
+    /// blit then reads in GENERAL. With auto exposure the histogram and average passes run
+    /// first, in the same set.
+    fn render_tonemap(&self) {
+        let current_frame = self.frame_manager.get_current_frame();
+        let (Some(tonemap_image), Some(descriptor_set), Some(pipeline)) = (
+            current_frame.tonemap_image.as_ref(),
+            current_frame.descriptor_tonemap_set,
+            self.frame_manager.tonemap_pipeline.as_ref(),
+        ) else {
+            return;
+        };
+
+        let color_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1);
+        let to_compute = [
+            // Lighting, SSR, the overlays or bloom wrote it last
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(
+                    vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags2::COPY
+                        | vk::PipelineStageFlags2::COMPUTE_SHADER,
+                )
+                .src_access_mask(
+                    vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags2::TRANSFER_WRITE
+                        | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+                )
+                .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .dst_access_mask(vk::AccessFlags2::SHADER_STORAGE_READ)
+                .old_layout(vk::ImageLayout::GENERAL)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(current_frame.draw_image.image)
+                .subresource_range(color_range),
+            // Overwritten whole, after the last frame's blit read it
+            vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(vk::PipelineStageFlags2::BLIT)
+                .src_access_mask(vk::AccessFlags2::NONE)
+                .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+                .dst_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE)
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(tonemap_image.image)
+                .subresource_range(color_range),
+        ];
+        // The exposure buffers are shared, the last frame's passes may still use them
+        let exposure_barrier = [vk::MemoryBarrier2::default()
+            .src_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .src_access_mask(vk::AccessFlags2::SHADER_STORAGE_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags2::COMPUTE_SHADER)
+            .dst_access_mask(
+                vk::AccessFlags2::SHADER_STORAGE_READ | vk::AccessFlags2::SHADER_STORAGE_WRITE,
+            )];
+        let auto_exposure = self.frame_manager.auto_exposure_pipelines();
+        let memory_barriers = if auto_exposure.is_some() {
+            exposure_barrier.as_slice()
+        } else {
+            &[]
+        };
+        sync::pipeline_barrier(
+            &self.device_info,
+            current_frame.command_buffer,
+            &vk::DependencyInfo::default()
+                .memory_barriers(memory_barriers)
+                .image_memory_barriers(&to_compute),
+        );
+
+        let device = &self.device_info.logical_device;
+        let dispatch = |pipeline: &ComputePipelineInfo, group_counts: (u32, u32)| unsafe {
+            device.cmd_bind_pipeline(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline,
+            );
+            device.cmd_bind_descriptor_sets(
+                current_frame.command_buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.pipeline_layout,
+                0,
+                &[descriptor_set, current_frame.descriptor_globals_set],
+                &[],
+            );
+            device.cmd_dispatch(
+                current_frame.command_buffer,
+                group_counts.0,
+                group_counts.1,
+                1,
+            );
+        };
+        let extent = tonemap_image.image_extent;
+        let groups = (extent.width.div_ceil(16), extent.height.div_ceil(16));
+
+        if let Some((histogram_pipeline, exposure_pipeline)) = auto_exposure {
+            let exposure_dependency =
+                vk::DependencyInfo::default().memory_barriers(&exposure_barrier);
+            dispatch(histogram_pipeline, groups);
+            sync::pipeline_barrier(
+                &self.device_info,
+                current_frame.command_buffer,
+                &exposure_dependency,
+            );
+            // A single workgroup, one invocation per histogram bin
+            dispatch(exposure_pipeline, (1, 1));
+            sync::pipeline_barrier(
+                &self.device_info,
+                current_frame.command_buffer,
+                &exposure_dependency,
+            );
         }
+
+        dispatch(pipeline, groups);
     }
 
-    fn set_viewport_scissor(&self) {
+    fn set_viewport_scissor(&self, extent: Extent2D) {
         let current_frame = self.frame_manager.get_current_frame();
 
         let viewport = vk::Viewport {
             x: 0.0f32,
             y: 0.0f32,
-            width: self.swapchain_info.swapchain_extent.width as f32,
-            height: self.swapchain_info.swapchain_extent.height as f32,
+            width: extent.width as f32,
+            height: extent.height as f32,
             min_depth: 0 as f32,
             max_depth: 1f32,
         };
 
         let scissor = Rect2D {
             offset: vk::Offset2D { x: 0, y: 0 },
-            extent: self.swapchain_info.swapchain_extent,
+            extent,
         };
 
         unsafe {
@@ -518,400 +2985,190 @@ impl VulkanBackend {
         }
     }
 
-    fn update_camera(&mut self) {
-        let current_frame = self.frame_manager.get_mut_current_frame();
-
+    fn update_camera(&mut self, view: Matrix4<f32>) {
         let aspect_ratio = self.swapchain_info.swapchain_extent.width as f32
             / self.swapchain_info.swapchain_extent.height as f32;
 
-        let view = self.camera.get_view_matrix();
-        let mut projection = glm::perspective(aspect_ratio, 70_f32.to_radians(), 0.01, 10000.0);
+        let (fov_y, near, far) = (70_f32.to_radians(), 0.01, 10000.0);
+        let mut projection = if self.frame_manager.reverse_z() {
+            glm::reversed_perspective_rh_zo(aspect_ratio, fov_y, near, far)
+        } else {
+            glm::perspective(aspect_ratio, fov_y, near, far)
+        };
         projection[(1, 1)] *= -1.0;
 
+        let shadows_enabled = self.frame_manager.shadows_enabled();
+        let light_view_proj = if shadows_enabled {
+            utils::directional_light_view_proj(
+                &self.frame_manager.light_direction,
+                &self.scene_center,
+                self.scene_radius,
+            )
+        } else {
+            utils::unshadowed_light_view_proj()
+        };
+
+        let mut caster_view_proj = [Matrix4::identity(); MAX_SHADOW_CASTERS];
+        for (layer, light) in self.frame_manager.shadow_casters().enumerate() {
+            caster_view_proj[layer] = match light.kind {
+                LightKind::Spot => utils::spot_light_view_proj(
+                    &light.position.xyz(),
+                    &light.direction.xyz(),
+                    light.params.z,
+                    light.params.x,
+                ),
+                _ => utils::directional_light_view_proj(
+                    &light.direction.xyz(),
+                    &self.scene_center,
+                    self.scene_radius,
+                ),
+            };
+        }
+
+        self.frustum = Frustum::from_view_proj(&(projection * view));
+
+        let eye_view_proj =
+            utils::eye_view_projs(&view, &projection, self.frame_manager.eye_separation);
+        let eye = view
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .column(3)
+            .xyz();
+
         let ubo = CameraMvpUbo {
             view,
             proj: projection,
+            light_view_proj,
+            eye_view_proj,
+            caster_view_proj,
+            position: eye.push(1.0),
         };
 
-        current_frame.update_camera_mvp_buffer(ubo);
+        // A single cascade keeps covering the whole scene, more split the view up to the
+        // scene's far side
+        let cascades = self.frame_manager.shadow_cascade_count();
+        let cascade_view_proj = if shadows_enabled && cascades > 1 {
+            let shadow_far =
+                ((eye - self.scene_center).norm() + self.scene_radius).clamp(near, far);
+            let splits = utils::cascade_splits(
+                near,
+                shadow_far,
+                cascades,
+                self.frame_manager.cascade_split_lambda(),
+            );
+            utils::cascade_view_projs(
+                &self.frame_manager.light_direction,
+                &view,
+                fov_y,
+                aspect_ratio,
+                near,
+                &splits,
+                &self.scene_center,
+                self.scene_radius,
+            )
+        } else {
+            [light_view_proj; MAX_SHADOW_CASCADES]
+        };
+
+        let current_frame = self.frame_manager.get_mut_current_frame();
+        current_frame.update_camera_mvp_buffer(&self.device_info, ubo);
+        current_frame.update_shadow_cascades(&self.device_info, cascade_view_proj);
     }
 
     fn update_world(&mut self) {
+        let frame_index = self.frame_manager.current_frame_index();
+        for skin in self
+            .gpu_mesh_data
+            .iter_mut()
+            .filter_map(|data| data.skin.as_mut())
+        {
+            skin.joint_buffers[frame_index].update_buffer(&self.device_info, &skin.joint_matrices);
+        }
+
+        let model_ubo_alignment = self.frame_manager.model_ubo_alignment;
         let current_frame = self.frame_manager.get_mut_current_frame();
 
         let world_model_data = self
             .gpu_mesh_data
             .iter()
-            .map(|data| ModelDynamicUbo {
-                model: data.world_model,
-            })
+            .map(|data| ModelDynamicUbo::new(data.world_model, data.material))
             .collect::<Vec<_>>();
 
-        current_frame.update_model_dynamic_buffer(world_model_data);
-
-        let mapped_memory_range = vk::MappedMemoryRange::default()
-            .memory(current_frame.model_dynamic_buffer.buffer_memory)
-            .size(self.frame_manager.model_ubo_alignment);
+        current_frame.update_model_dynamic_buffer(
+            &self.device_info,
+            &world_model_data,
+            model_ubo_alignment,
+        );
 
-        unsafe {
-            self.device_info
-                .logical_device
-                .flush_mapped_memory_ranges(&[mapped_memory_range])
-                .expect("failed to flush mapped memory range");
+        if let Some(bounds_buffer) = current_frame.cull_bounds_buffer.as_mut() {
+            let world_spheres = self
+                .gpu_mesh_data
+                .iter()
+                .map(GPUMeshData::world_bounding_sphere)
+                .collect::<Vec<_>>();
+            bounds_buffer.update_buffer(&self.device_info, &world_spheres);
         }
-    }
 
-    fn create_image_view(
-        device_info: &DeviceInfo,
-        image: vk::Image,
-        format: vk::Format,
-        image_aspect_flags: ImageAspectFlags,
-    ) -> ImageView {
-        let view_info = vk::ImageViewCreateInfo::default()
-            .image(image)
-            .view_type(vk::ImageViewType::TYPE_2D)
-            .format(format)
-            .subresource_range(
-                vk::ImageSubresourceRange::default()
-                    .aspect_mask(image_aspect_flags)
-                    .base_mip_level(0)
-                    .level_count(1)
-                    .base_array_layer(0)
-                    .layer_count(1),
-            );
-
-        unsafe {
-            device_info
-                .logical_device
-                .create_image_view(&view_info, None)
-                .expect("failed to create image view")
+        if let Some(draw_buffer) = current_frame.cull_draw_buffer.as_mut() {
+            let draws = self
+                .gpu_mesh_data
+                .iter()
+                .map(|data| vk::DrawIndexedIndirectCommand {
+                    index_count: data.index_count,
+                    instance_count: 1,
+                    first_index: 0,
+                    vertex_offset: 0,
+                    first_instance: 0,
+                })
+                .collect::<Vec<_>>();
+            draw_buffer.update_buffer(&self.device_info, &draws);
         }
     }
 
-    fn create_texture_image_view(device_info: &DeviceInfo, image: vk::Image) -> ImageView {
-        Self::create_image_view(
-            device_info,
-            image,
-            vk::Format::R8G8B8A8_SRGB,
-            ImageAspectFlags::COLOR,
-        )
-    }
-
-    fn copy_buffy_to_image(
-        device_info: &DeviceInfo,
-        buffer: vk::Buffer,
-        image: vk::Image,
-        width: u32,
-        height: u32,
-    ) {
-        let command_buffer = BufferInfo::begin_single_time_command(device_info);
-
-        let region = vk::BufferImageCopy::default()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(
-                vk::ImageSubresourceLayers::default()
-                    .aspect_mask(ImageAspectFlags::COLOR)
-                    .mip_level(0)
-                    .base_array_layer(0)
-                    .layer_count(1),
-            )
-            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-            .image_extent(Extent3D {
-                height,
-                width,
-                depth: 1,
-            });
-
-        unsafe {
-            device_info.logical_device.cmd_copy_buffer_to_image(
-                command_buffer,
-                buffer,
-                image,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[region],
-            );
-        }
-
-        BufferInfo::end_single_time_command(device_info, command_buffer);
-    }
 
+    /// Uploads `texture` as mip 0 of a `format` image and fills the rest of the chain from
+    /// it, or keeps a single mip when the device can't blit the format linearly. `format`
+    /// must hold RGBA8 texels. Recorded into `uploads`.
     fn create_texture_image(
         device_info: &DeviceInfo,
         instance: &Instance,
-    ) -> (vk::Image, vk::DeviceMemory) {
-        let dyn_image = image::open(".\\resources\\textures\\texture.png").unwrap();
-        let image_width = dyn_image.width();
-        let image_height = dyn_image.height();
-
-        let image_size =
-            (mem::size_of::<u8>() as u32 * image_width * image_height * 4) as vk::DeviceSize;
-
-        let image_data = match &dyn_image {
-            image::DynamicImage::ImageLuma8(_) | image::DynamicImage::ImageRgb8(_) => {
-                dyn_image.to_rgba8().into_raw()
-            }
-            _ => vec![],
+        uploads: &mut UploadBatch,
+        texture: &ImageResource,
+        format: vk::Format,
+    ) -> AllocatedImage {
+        let image_extent = Extent2D {
+            width: texture.width,
+            height: texture.height,
         };
+        let (regions, _) =
+            format::buffer_image_copies(format, image_extent, 1, ImageAspectFlags::COLOR);
 
-        let image_buffer = BufferInfo::new(
-            instance,
-            device_info,
-            image_size,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-        );
-        let image_buffer_mapped = unsafe {
-            device_info
-                .logical_device
-                .map_memory(
-                    image_buffer.buffer_memory,
-                    0,
-                    image_size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .expect("failed to map image buffer memory") as *mut u8
+        let mip_levels = if image_util::supports_linear_blit(device_info, instance, format) {
+            format::mip_count(image_extent)
+        } else {
+            1
         };
-
-        unsafe {
-            image_buffer_mapped.copy_from_nonoverlapping(image_data.as_ptr(), image_data.len());
-
-            device_info
-                .logical_device
-                .unmap_memory(image_buffer.buffer_memory);
-        }
-
-        let (image, device_memory) = image_util::create_image(
+        let image = AllocatedImage::new_mipmapped(
             device_info,
             instance,
-            dyn_image.width(),
-            dyn_image.height(),
-            vk::Format::R8G8B8A8_SRGB,
+            texture.width,
+            texture.height,
+            mip_levels,
+            format,
+            ImageAspectFlags::COLOR,
             vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-            MemoryPropertyFlags::DEVICE_LOCAL,
-        );
-
-        let cmd = BufferInfo::begin_single_time_command(device_info);
-
-        image_util::transition_image_layout(
-            device_info,
-            &cmd,
-            image,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            false,
-        );
-
-        BufferInfo::end_single_time_command(device_info, cmd);
-
-        Self::copy_buffy_to_image(
-            device_info,
-            image_buffer.buffer,
-            image,
-            image_width,
-            image_height,
-        );
-        let cmd = BufferInfo::begin_single_time_command(device_info);
-
-        image_util::transition_image_layout(
-            device_info,
-            &cmd,
-            image,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            false,
-        );
-
-        BufferInfo::end_single_time_command(device_info, cmd);
-
-        unsafe {
-            device_info
-                .logical_device
-                .destroy_buffer(image_buffer.buffer, None);
-            device_info
-                .logical_device
-                .free_memory(image_buffer.buffer_memory, None);
-        }
-
-        (image, device_memory)
-    }
-
-    fn create_buffer(
-        instance: &Instance,
-        device_info: &DeviceInfo,
-        size: vk::DeviceSize,
-        usage: vk::BufferUsageFlags,
-        memory_property_flags: MemoryPropertyFlags,
-    ) -> (vk::Buffer, vk::DeviceMemory) {
-        let buffer_create_info = vk::BufferCreateInfo::default()
-            .size(size)
-            .usage(usage)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE);
-
-        let buffer = unsafe {
-            device_info
-                .logical_device
-                .create_buffer(&buffer_create_info, None)
-                .expect("failed to create buffer")
-        };
-
-        let mem_requirements = unsafe {
-            device_info
-                .logical_device
-                .get_buffer_memory_requirements(buffer)
-        };
-        let memory_properties =
-            unsafe { instance.get_physical_device_memory_properties(device_info._physical_device) };
-        let memory_alloc_info = vk::MemoryAllocateInfo::default()
-            .allocation_size(mem_requirements.size)
-            .memory_type_index(utils::find_memory_type(
-                mem_requirements.memory_type_bits,
-                memory_property_flags,
-                memory_properties,
-            ));
-
-        let buffer_memory = unsafe {
-            device_info
-                .logical_device
-                .allocate_memory(&memory_alloc_info, None)
-                .expect("failed to allocate memory")
-        };
-
-        unsafe {
-            device_info
-                .logical_device
-                .bind_buffer_memory(buffer, buffer_memory, 0)
-                .expect("failed to bind buffer");
-        }
-
-        (buffer, buffer_memory)
-    }
-
-    fn copy_buffer(
-        src_buffer: vk::Buffer,
-        dst_buffer: vk::Buffer,
-        size: u64,
-        device_info: &DeviceInfo,
-    ) {
-        let command_buffer = BufferInfo::begin_single_time_command(&device_info);
-
-        let copy_region = vk::BufferCopy {
-            src_offset: 0,
-            dst_offset: 0,
-            size,
-        };
-
-        unsafe {
-            device_info.logical_device.cmd_copy_buffer(
-                command_buffer,
-                src_buffer,
-                dst_buffer,
-                &[copy_region],
-            )
-        };
-
-        BufferInfo::end_single_time_command(device_info, command_buffer);
-    }
-
-    fn create_index_buffer(
-        instance: &Instance,
-        device_info: &DeviceInfo,
-        indices: &[u32],
-    ) -> AllocatedBuffer {
-        let buffer_size = mem::size_of_val(indices) as vk::DeviceSize;
-
-        let (x, y) = Self::create_buffer(
-            instance,
-            device_info,
-            buffer_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        );
-
-        unsafe {
-            let data = device_info
-                .logical_device
-                .map_memory(y, 0, buffer_size, vk::MemoryMapFlags::empty())
-                .expect("Failed to map memory") as *mut u32;
-
-            data.copy_from_nonoverlapping(indices.as_ptr(), indices.len());
-
-            device_info.logical_device.unmap_memory(y);
-        };
-
-        let (index_buffer, index_buffer_memory) = Self::create_buffer(
-            instance,
-            device_info,
-            buffer_size,
-            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
-            MemoryPropertyFlags::DEVICE_LOCAL,
-        );
-
-        Self::copy_buffer(x, index_buffer, buffer_size, device_info);
-
-        unsafe {
-            device_info.logical_device.destroy_buffer(x, None);
-            device_info.logical_device.free_memory(y, None);
-        }
-
-        AllocatedBuffer {
-            buffer: index_buffer,
-            buffer_memory: index_buffer_memory,
-            mapped_buffer: ptr::null_mut(),
-        }
-    }
-
-    fn create_vertex_buffer(
-        instance: &Instance,
-        device_info: &DeviceInfo,
-        vertices: &[Vertex],
-    ) -> AllocatedBuffer {
-        let buffer_size = mem::size_of_val(vertices) as u64;
-
-        let (x, y) = Self::create_buffer(
-            instance,
-            device_info,
-            buffer_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        );
-
-        unsafe {
-            let data = device_info
-                .logical_device
-                .map_memory(y, 0, buffer_size, vk::MemoryMapFlags::empty())
-                .expect("Failed to map memory") as *mut Vertex;
-
-            data.copy_from_nonoverlapping(vertices.as_ptr(), vertices.len());
-
-            device_info.logical_device.unmap_memory(y);
-        };
-
-        let (vertex_buffer, vertex_buffer_memory) = Self::create_buffer(
-            instance,
-            device_info,
-            buffer_size,
-            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
             MemoryPropertyFlags::DEVICE_LOCAL,
         );
+        uploads.image(&image, &texture.image_data, &regions);
 
-        Self::copy_buffer(x, vertex_buffer, buffer_size, device_info);
-
-        unsafe {
-            device_info.logical_device.destroy_buffer(x, None);
-            device_info.logical_device.free_memory(y, None);
-        }
-
-        AllocatedBuffer {
-            buffer: vertex_buffer,
-            buffer_memory: vertex_buffer_memory,
-            mapped_buffer: ptr::null_mut(),
-        }
+        image
     }
 
-    fn create_instance(entry: &ash::Entry, window: &Window) -> Instance {
+    /// With `validation`, enables the validation layer and `VK_EXT_debug_utils` for it.
+    fn create_instance(entry: &ash::Entry, window: &Window, validation: bool) -> Instance {
         let app_name = CString::new("Vulkan Application").unwrap();
         let engine_name = CString::new("No Engine").unwrap();
 
@@ -928,18 +3185,24 @@ impl VulkanBackend {
         let mut extension_names = extension_names.to_vec();
 
         extension_names.push(vk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_NAME.as_ptr());
-
-        let instance_create_info = vk::InstanceCreateInfo {
-            s_type: vk::StructureType::INSTANCE_CREATE_INFO,
-            p_next: ptr::null(),
-            pp_enabled_layer_names: ptr::null(),
-            enabled_layer_count: 0,
-            pp_enabled_extension_names: extension_names.as_ptr(),
-            enabled_extension_count: extension_names.len() as u32,
-            p_application_info: &app_info,
-            ..Default::default()
+        if validation || cfg!(feature = "debug-names") {
+            extension_names.push(ash::ext::debug_utils::NAME.as_ptr());
+        }
+        let layer_names = if validation {
+            vec![VALIDATION_LAYER.as_ptr()]
+        } else {
+            vec![]
         };
 
+        let mut debug_create_info = ValidationMessenger::create_info();
+        let mut instance_create_info = vk::InstanceCreateInfo::default()
+            .application_info(&app_info)
+            .enabled_layer_names(&layer_names)
+            .enabled_extension_names(&extension_names);
+        if validation {
+            instance_create_info = instance_create_info.push_next(&mut debug_create_info);
+        }
+
         unsafe { entry.create_instance(&instance_create_info, None).unwrap() }
     }
 
@@ -972,6 +3235,11 @@ impl VulkanBackend {
         self.swapchain_info =
             SwapchainInfo::new(&self.instance, &self.device_info, &self.surface_info);
         self.image_views = Self::create_image_views(&self.swapchain_info, &self.device_info);
+        self.frame_manager.recreate_images(
+            &self.device_info,
+            &self.instance,
+            self.swapchain_info.swapchain_extent,
+        );
     }
 
     fn cleanup_swapchain(&mut self) {
@@ -0,0 +1,143 @@
+//! Threads the G-buffer pass records its secondary command buffers on, see
+//! `RenderConfig::gbuffer_threads`. They live as long as the `FrameManager`, so recording a
+//! frame doesn't spawn any.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Mutex};
+use std::thread::{self, JoinHandle};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct WorkerPool {
+    senders: Vec<mpsc::Sender<Job>>,
+    /// One message per finished job, `Err` with the payload if it panicked.
+    finished: Mutex<mpsc::Receiver<thread::Result<()>>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(thread_count: usize) -> Self {
+        let (finished_sender, finished) = mpsc::channel();
+        let (senders, threads) = (0..thread_count)
+            .map(|index| {
+                let (sender, jobs) = mpsc::channel::<Job>();
+                let finished_sender = finished_sender.clone();
+                let thread = thread::Builder::new()
+                    .name(format!("gbuffer-{}", index))
+                    .spawn(move || {
+                        for job in jobs {
+                            let result = panic::catch_unwind(AssertUnwindSafe(job));
+                            if finished_sender.send(result).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                    .expect("failed to spawn worker thread");
+                (sender, thread)
+            })
+            .unzip();
+
+        Self {
+            senders,
+            finished: Mutex::new(finished),
+            threads,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Runs each of `jobs` on a worker of its own and returns once all of them finished, so
+    /// like `thread::scope` they can borrow from the caller. If any of them panicked, panics
+    /// with its payload after the others finished.
+    pub fn run<'scope>(&self, jobs: Vec<Box<dyn FnOnce() + Send + 'scope>>) {
+        assert!(
+            jobs.len() <= self.len(),
+            "{} jobs for {} workers",
+            jobs.len(),
+            self.len()
+        );
+        let finished = self.finished.lock().expect("worker pool lock poisoned");
+
+        let mut running = 0;
+        for (sender, job) in self.senders.iter().zip(jobs) {
+            // SAFETY: the job can't outlive 'scope, every job sent is waited for below before
+            // returning, and the workers catch panics so they never drop a job unfinished
+            let job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+            sender.send(job).expect("worker thread exited");
+            running += 1;
+        }
+
+        let mut panic_payload = None;
+        for _ in 0..running {
+            let result = finished.recv().expect("worker thread exited");
+            if let Err(payload) = result {
+                panic_payload.get_or_insert(payload);
+            }
+        }
+        drop(finished);
+
+        if let Some(payload) = panic_payload {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Closing the job channels ends the workers' loops
+        self.senders.clear();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn run_waits_for_jobs_borrowing_the_caller() {
+        let pool = WorkerPool::new(3);
+        let mut results = [0; 3];
+
+        for _ in 0..2 {
+            let jobs = results
+                .iter_mut()
+                .enumerate()
+                .map(|(index, result)| {
+                    Box::new(move || *result += index + 1) as Box<dyn FnOnce() + Send + '_>
+                })
+                .collect();
+            pool.run(jobs);
+        }
+
+        assert_eq!(results, [2, 4, 6]);
+    }
+
+    #[test]
+    fn run_rethrows_a_panic_after_the_other_jobs_finish() {
+        let pool = WorkerPool::new(2);
+        let finished = AtomicUsize::new(0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.run(vec![
+                Box::new(|| panic!("job failed")),
+                Box::new(|| {
+                    finished.fetch_add(1, Ordering::SeqCst);
+                }),
+            ])
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+        // The workers survive it
+        pool.run(vec![Box::new(|| {
+            finished.fetch_add(1, Ordering::SeqCst);
+        })]);
+        assert_eq!(finished.load(Ordering::SeqCst), 2);
+    }
+}